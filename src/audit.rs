@@ -0,0 +1,366 @@
+use std::error;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::exif::{ExifData, TAG_ORIENTATION};
+use crate::png::{PNGChunk, PNGFile, WriteOptions};
+use crate::report::{AuditReport, ReportIssue, Severity};
+
+const METERS_PER_INCH: f64 = 0.0254;
+
+#[derive(Debug, Clone)]
+pub struct InvalidPolicy {
+    message: String,
+}
+
+impl fmt::Display for InvalidPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid policy: {}", self.message)
+    }
+}
+
+impl error::Error for InvalidPolicy {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+// What a set of images is expected to look like, e.g. "all assets must be sRGB, 72 DPI and
+// orientation 1". Anything left unset isn't checked.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Policy {
+    // Required EXIF orientation. Files without EXIF data count as orientation 1.
+    pub orientation: Option<u16>,
+    // Required resolution in dots per inch, horizontally and vertically.
+    pub dpi: Option<u32>,
+    // Whether the file must be tagged as sRGB with an sRGB chunk.
+    pub srgb: bool,
+}
+
+impl Policy {
+    // Reads a policy file. Each line is "key = value" and # starts a comment:
+    //   orientation = 1
+    //   dpi = 72
+    //   color_space = srgb
+    pub fn parse(text: &str) -> Result<Policy, Box<dyn Error>> {
+        let mut policy = Policy::default();
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| InvalidPolicy {
+                message: format!("Expected key = value, found \"{}\".", line),
+            })?;
+            policy.set(key.trim(), value.trim())?;
+        }
+        Ok(policy)
+    }
+
+    // Sets one rule by its policy file key.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), Box<dyn Error>> {
+        let invalid = |message: String| InvalidPolicy { message };
+        match key {
+            "orientation" => match value.parse() {
+                Ok(orientation @ 1..=8) => self.orientation = Some(orientation),
+                _ => {
+                    return Err(
+                        invalid(format!("Orientation must be 1 - 8, not {}.", value)).into(),
+                    )
+                }
+            },
+            "dpi" => match value.parse() {
+                Ok(dpi) if dpi > 0 => self.dpi = Some(dpi),
+                _ => {
+                    return Err(
+                        invalid(format!("DPI must be a positive number, not {}.", value)).into(),
+                    )
+                }
+            },
+            "color_space" => match value.to_lowercase().as_str() {
+                "srgb" => self.srgb = true,
+                _ => return Err(invalid(format!("Unsupported color space: {}.", value)).into()),
+            },
+            _ => return Err(invalid(format!("Unknown key: {}.", key)).into()),
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.orientation.is_none() && self.dpi.is_none() && !self.srgb
+    }
+}
+
+// How a file says its colors should be interpreted. When several chunks are present, sRGB wins
+// over iCCP, which wins over gAMA and cHRM, as in the spec.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorSpace {
+    Srgb,
+    // An embedded ICC profile, with the name stored in the iCCP chunk.
+    Icc(String),
+    // gAMA and/or cHRM values without a profile.
+    Calibrated,
+    Unspecified,
+}
+
+impl ColorSpace {
+    pub fn of(png_file: &PNGFile) -> ColorSpace {
+        if png_file.first_chunk(b"sRGB").is_some() {
+            return ColorSpace::Srgb;
+        }
+        if let Some(chunk) = png_file.first_chunk(b"iCCP") {
+            let data = chunk.get_data();
+            let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+            return ColorSpace::Icc(String::from_utf8_lossy(&data[..end]).into_owned());
+        }
+        if png_file.first_chunk(b"gAMA").is_some() || png_file.first_chunk(b"cHRM").is_some() {
+            return ColorSpace::Calibrated;
+        }
+        ColorSpace::Unspecified
+    }
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorSpace::Srgb => write!(f, "sRGB"),
+            ColorSpace::Icc(name) => write!(f, "ICC profile \"{}\"", name),
+            ColorSpace::Calibrated => write!(f, "gAMA/cHRM"),
+            ColorSpace::Unspecified => write!(f, "unspecified"),
+        }
+    }
+}
+
+// The EXIF orientation of a file, 1 if it has no eXIf chunk or no orientation tag.
+pub fn orientation(png_file: &PNGFile) -> u16 {
+    exif(png_file)
+        .and_then(|exif| exif.orientation())
+        .unwrap_or(1)
+}
+
+// Horizontal and vertical resolution from the pHYs chunk, if it gives them in meters.
+pub fn dpi(png_file: &PNGFile) -> Option<(f64, f64)> {
+    let data = png_file.first_chunk(b"pHYs")?.get_data();
+    if data.len() != 9 || data[8] != 1 {
+        return None;
+    }
+    let x = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+    let y = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    Some((x as f64 * METERS_PER_INCH, y as f64 * METERS_PER_INCH))
+}
+
+// Checks one file against the policy. With fix set, violations that can be fixed without
+// changing how the image looks are fixed and the file is rewritten in place. Those issues are
+// then reported as info rather than errors. With backup set as well, the original is kept as
+// file.bak.
+pub fn audit(
+    file: &str,
+    policy: &Policy,
+    fix: bool,
+    backup: bool,
+) -> Result<AuditReport, Box<dyn Error>> {
+    let png_file = PNGFile::from_file(file)?;
+    let orientation = orientation(&png_file);
+    let dpi = dpi(&png_file);
+    let color_space = ColorSpace::of(&png_file);
+
+    let mut issues = Vec::new();
+    let mut fixed = png_file.to_chunks();
+    let mut image_fix = None;
+
+    if let Some(required) = policy.orientation {
+        if orientation != required {
+            // Turning the pixels upright is the only change that keeps the picture the same.
+            let fixable = required == 1;
+            issues.push((
+                issue(
+                    "orientation_mismatch",
+                    format!(
+                        "Orientation is {}, the policy requires {}.",
+                        orientation, required
+                    ),
+                    "eXIf",
+                ),
+                fixable,
+            ));
+            if fixable {
+                image_fix = Some(orientation);
+            }
+        }
+    }
+
+    if let Some(required) = policy.dpi {
+        let matches =
+            dpi.is_some_and(|(x, y)| x.round() as u32 == required && y.round() as u32 == required);
+        if !matches {
+            let found = match dpi {
+                Some((x, y)) if x.round() == y.round() => format!("{:.0} DPI", x),
+                Some((x, y)) => format!("{:.0}x{:.0} DPI", x, y),
+                None => "not set".to_string(),
+            };
+            issues.push((
+                issue(
+                    "dpi_mismatch",
+                    format!(
+                        "Resolution is {}, the policy requires {} DPI.",
+                        found, required
+                    ),
+                    "pHYs",
+                ),
+                true,
+            ));
+            let ppm = (required as f64 / METERS_PER_INCH).round() as u32;
+            let mut data = Vec::with_capacity(9);
+            data.extend_from_slice(&ppm.to_be_bytes());
+            data.extend_from_slice(&ppm.to_be_bytes());
+            data.push(1);
+            set_chunk(&mut fixed, PNGChunk::new(*b"pHYs", data));
+        }
+    }
+
+    if policy.srgb && color_space != ColorSpace::Srgb {
+        // Files that only lack the tag, or whose profile is sRGB anyway, can be retagged. Anything
+        // else would need its pixels converted.
+        let fixable = match &color_space {
+            ColorSpace::Unspecified => true,
+            ColorSpace::Icc(name) => name.to_lowercase().contains("srgb"),
+            ColorSpace::Calibrated => is_srgb_gamma(&png_file),
+            ColorSpace::Srgb => false,
+        };
+        issues.push((
+            issue(
+                "color_space_mismatch",
+                format!("Color space is {}, the policy requires sRGB.", color_space),
+                "sRGB",
+            ),
+            fixable,
+        ));
+        if fixable {
+            fixed.retain(|c| !matches!(c.get_chunk_type(), b"iCCP" | b"gAMA" | b"cHRM"));
+            // Rendering intent 0, perceptual.
+            set_chunk(&mut fixed, PNGChunk::new(*b"sRGB", vec![0]));
+        }
+    }
+
+    let any_fixable = issues.iter().any(|(_, fixable)| *fixable);
+    if fix && any_fixable {
+        let mut result = PNGFile::from_chunks(fixed)?;
+        if let Some(orientation) = image_fix {
+            let image = result.decode()?.apply_orientation(orientation);
+            result = result.with_image(&image)?;
+            result = set_orientation(&result, 1)?;
+        }
+        result.write_with_options(file, &WriteOptions::new().backup(backup))?;
+    }
+
+    let issues = issues
+        .into_iter()
+        .map(|(mut issue, fixable)| {
+            if fix && fixable {
+                issue.severity = Severity::Info;
+                issue.message.push_str(" Fixed.");
+            } else if !fixable {
+                issue
+                    .message
+                    .push_str(" This can't be fixed automatically.");
+            }
+            issue
+        })
+        .collect();
+    Ok(AuditReport::new(
+        file,
+        orientation,
+        dpi,
+        &color_space.to_string(),
+        issues,
+    ))
+}
+
+// Expands the given paths into the PNG files to audit. Directories are searched recursively
+// for files ending in .png, in name order. Files are returned as given.
+pub fn find_pngs(paths: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            collect_pngs(path, &mut files)?;
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+fn collect_pngs(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), Box<dyn Error>> {
+    let mut entries: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect();
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_pngs(&entry, files)?;
+        } else if entry
+            .extension()
+            .is_some_and(|e| e.to_string_lossy().to_lowercase() == "png")
+        {
+            files.push(entry);
+        }
+    }
+    Ok(())
+}
+
+fn issue(code: &str, message: String, chunk_type: &str) -> ReportIssue {
+    ReportIssue {
+        severity: Severity::Error,
+        code: code.to_string(),
+        message,
+        chunk_type: Some(chunk_type.to_string()),
+        offset: None,
+    }
+}
+
+fn exif(png_file: &PNGFile) -> Option<ExifData> {
+    ExifData::parse(png_file.first_chunk(b"eXIf")?.get_data()).ok()
+}
+
+// A gamma of 1/2.2 with no cHRM is as close to sRGB as gAMA alone can say.
+fn is_srgb_gamma(png_file: &PNGFile) -> bool {
+    if png_file.first_chunk(b"cHRM").is_some() {
+        return false;
+    }
+    match png_file.first_chunk(b"gAMA").map(|c| c.get_data()) {
+        Some([a, b, c, d]) => {
+            let gamma = u32::from_be_bytes([*a, *b, *c, *d]);
+            (45_000..=46_000).contains(&gamma)
+        }
+        _ => false,
+    }
+}
+
+// Replaces the chunk of the same type, or adds the chunk straight after IHDR, which is a valid
+// place for any of the chunks set here.
+fn set_chunk(chunks: &mut Vec<PNGChunk>, chunk: PNGChunk) {
+    match chunks
+        .iter()
+        .position(|c| c.get_chunk_type() == chunk.get_chunk_type())
+    {
+        Some(at) => chunks[at] = chunk,
+        None => chunks.insert(1, chunk),
+    }
+}
+
+fn set_orientation(png_file: &PNGFile, orientation: u16) -> Result<PNGFile, Box<dyn Error>> {
+    let mut chunks = png_file.to_chunks();
+    if let Some(at) = chunks.iter().position(|c| c.get_chunk_type() == b"eXIf") {
+        let mut exif = ExifData::parse(chunks[at].get_data())?;
+        exif.set_short(TAG_ORIENTATION, orientation)?;
+        chunks[at] = PNGChunk::new(*b"eXIf", exif.as_bytes().to_vec());
+    }
+    PNGFile::from_chunks(chunks)
+}
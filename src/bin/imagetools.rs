@@ -0,0 +1,501 @@
+use std::env;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use imagetools::audit;
+use imagetools::bmp::BMPFile;
+use imagetools::gif::GIFFile;
+use imagetools::i18n::tr;
+use imagetools::jpeg::{ChromaSubsampling, EncodeOptions, JPEGFile, PngConversionOptions};
+use imagetools::png::{DecodeOptions, PNGFile, ParseMode};
+use imagetools::rename;
+use imagetools::report::{
+    self, AnalyzeReport, BatchItem, ConformanceReport, DiffReport, InfoReport, ValidateReport,
+};
+use imagetools::tiff::TIFFFile;
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        usage(&args[0]);
+        process::exit(1);
+    }
+
+    match args[1].as_str() {
+        "info" | "validate" | "conformance" | "analyze" => {
+            report_command(&args[0], &args[1], &args[2..])
+        }
+        "diff" => diff_command(&args[0], &args[2..]),
+        "convert" => convert_command(&args[0], &args[2..]),
+        "audit" => audit_command(&args[0], &args[2..]),
+        "rename" => rename_command(&args[0], &args[2..]),
+        "serve" => serve_command(&args[0], &args[2..]),
+        _ => {
+            usage(&args[0]);
+            process::exit(1);
+        }
+    }
+}
+
+// Splits a flag such as --json out of the remaining arguments.
+fn take_flag(args: &[String], flag: &str) -> (bool, Vec<String>) {
+    let found = args.iter().any(|a| a == flag);
+    let rest = args.iter().filter(|a| *a != flag).cloned().collect();
+    (found, rest)
+}
+
+fn report_command(name: &str, command: &str, args: &[String]) {
+    let (json, args) = take_flag(args, "--json");
+    let (permissive, args) = take_flag(&args, "--permissive");
+    let (strict, files) = take_flag(&args, "--strict");
+    if files.is_empty()
+        || (permissive && command != "validate")
+        || (strict && (command == "validate" || command == "conformance"))
+    {
+        usage(name);
+        process::exit(1);
+    }
+    let mode = if permissive {
+        ParseMode::Permissive
+    } else {
+        ParseMode::Strict
+    };
+
+    let batch = report::batch(command, &files, |file| {
+        if command == "validate" {
+            return Ok(BatchItem::Validate(ValidateReport::from_file(file, mode)?));
+        }
+        if command == "conformance" {
+            return Ok(BatchItem::Conformance(ConformanceReport::from_file(file)?));
+        }
+        // --strict refuses files that break the spec instead of reading what's there.
+        let png_file = if strict {
+            PNGFile::from_file_with_mode(file, ParseMode::Strict)?.0
+        } else {
+            PNGFile::from_file(file)?
+        };
+        Ok(match command {
+            "info" => BatchItem::Info(InfoReport::from_png(file, &png_file)),
+            _ => BatchItem::Analyze(AnalyzeReport::from_image(file, &png_file.decode()?)),
+        })
+    });
+    let failed = batch.items.iter().any(|item| match item {
+        BatchItem::Failed { .. } => true,
+        BatchItem::Validate(report) => !report.valid,
+        BatchItem::Conformance(report) => !report.conformant,
+        _ => false,
+    });
+
+    if json {
+        // A single file gets its report on its own; several are wrapped in a batch report.
+        let output = match batch.items.as_slice() {
+            [BatchItem::Info(report)] => print_json(report),
+            [BatchItem::Validate(report)] => print_json(report),
+            [BatchItem::Conformance(report)] => print_json(report),
+            [BatchItem::Analyze(report)] => print_json(report),
+            _ => print_json(&batch),
+        };
+        output.unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(2);
+        });
+    } else {
+        for item in &batch.items {
+            println!("{}\n", item);
+        }
+    }
+
+    if failed {
+        process::exit(2);
+    }
+}
+
+fn diff_command(name: &str, args: &[String]) {
+    let (json, files) = take_flag(args, "--json");
+    if files.len() != 2 {
+        usage(name);
+        process::exit(1);
+    }
+
+    let load = |file: &str| {
+        PNGFile::from_file(file)
+            .and_then(|f| f.decode())
+            .unwrap_or_else(|err| {
+                eprintln!(
+                    "{}",
+                    tr(
+                        "could-not-load",
+                        &[("file", file), ("error", &err.to_string())]
+                    )
+                );
+                process::exit(2);
+            })
+    };
+    let left = load(&files[0]);
+    let right = load(&files[1]);
+    let diff = DiffReport::compare(&files[0], &left, &files[1], &right);
+
+    if json {
+        print_json(&diff).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(2);
+        });
+    } else {
+        println!("{}", diff);
+    }
+}
+
+fn convert_command(name: &str, args: &[String]) {
+    let mut exif = false;
+    let mut jpeg_options = EncodeOptions::default();
+    let mut files: Vec<String> = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--exif" => exif = true,
+            "--quality" => match iter
+                .next()
+                .and_then(|q| q.parse().ok())
+                .filter(|q| (1..=100).contains(q))
+            {
+                Some(q) => jpeg_options.quality = q,
+                None => {
+                    usage(name);
+                    process::exit(1);
+                }
+            },
+            "--subsampling" => match iter.next().map(|s| s.as_str()) {
+                Some("444") => jpeg_options.subsampling = ChromaSubsampling::Full,
+                Some("420") => jpeg_options.subsampling = ChromaSubsampling::Half,
+                _ => {
+                    usage(name);
+                    process::exit(1);
+                }
+            },
+            _ => files.push(arg.clone()),
+        }
+    }
+    if files.len() != 2 {
+        usage(name);
+        process::exit(1);
+    }
+
+    // The extensions pick the conversion.
+    let to_jpeg = has_extension(&files[1], &["jpg", "jpeg"]);
+    let to_bmp = has_extension(&files[1], &["bmp"]);
+    let from_gif = has_extension(&files[0], &["gif"]);
+    let from_bmp = has_extension(&files[0], &["bmp"]);
+    let from_tiff = has_extension(&files[0], &["tif", "tiff"]);
+    let could_not_load = |err: Box<dyn Error>| -> ! {
+        eprintln!(
+            "{}",
+            tr(
+                "could-not-load",
+                &[("file", &files[0]), ("error", &err.to_string())]
+            )
+        );
+        process::exit(2);
+    };
+    // JPEG files without a profile and BMP files are taken to be sRGB, so the pixels are
+    // converted to it.
+    let decode_options = DecodeOptions {
+        to_srgb: true,
+        ..DecodeOptions::default()
+    };
+    let written = if to_jpeg {
+        let jpeg_file = PNGFile::from_file(&files[0])
+            .and_then(|f| f.decode_with_options(&decode_options))
+            .and_then(|image| JPEGFile::from_image(&image, &jpeg_options))
+            .unwrap_or_else(|err| could_not_load(err));
+        jpeg_file.write(&files[1])
+    } else if to_bmp {
+        let bmp_file = PNGFile::from_file(&files[0])
+            .and_then(|f| f.decode_with_options(&decode_options))
+            .and_then(|image| BMPFile::from_image(&image))
+            .unwrap_or_else(|err| could_not_load(err));
+        bmp_file.write(&files[1])
+    } else if from_bmp {
+        let png_file = BMPFile::from_file(&files[0])
+            .and_then(|f| f.decode())
+            .and_then(|image| PNGFile::from_image(&image))
+            .unwrap_or_else(|err| could_not_load(err));
+        png_file.write(&files[1])
+    } else if from_tiff {
+        let png_file = TIFFFile::from_file(&files[0])
+            .and_then(|f| f.to_png())
+            .unwrap_or_else(|err| could_not_load(err));
+        png_file.write(&files[1])
+    } else if from_gif {
+        let png_file = GIFFile::from_file(&files[0])
+            .and_then(|f| f.to_png())
+            .unwrap_or_else(|err| could_not_load(err));
+        png_file.write(&files[1])
+    } else {
+        let options = PngConversionOptions { exif };
+        let png_file = JPEGFile::from_file(&files[0])
+            .and_then(|f| f.to_png(&options))
+            .unwrap_or_else(|err| could_not_load(err));
+        png_file.write(&files[1])
+    };
+    written.unwrap_or_else(|err| {
+        eprintln!(
+            "{}",
+            tr(
+                "could-not-write",
+                &[("file", &files[1]), ("error", &err.to_string())]
+            )
+        );
+        process::exit(2);
+    });
+}
+
+fn has_extension(file: &str, extensions: &[&str]) -> bool {
+    Path::new(file)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| extensions.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+#[cfg(feature = "serde")]
+fn print_json<T: serde::Serialize>(report: &T) -> Result<(), String> {
+    println!("{}", report::to_json(report).map_err(|e| e.to_string())?);
+    Ok(())
+}
+
+#[cfg(not(feature = "serde"))]
+fn print_json<T>(_report: &T) -> Result<(), String> {
+    Err(tr("json-not-included", &[]))
+}
+
+fn audit_command(name: &str, args: &[String]) {
+    let mut json = false;
+    let mut fix = false;
+    let mut backup = false;
+    let mut policy_file: Option<String> = None;
+    let mut rules: Vec<(&str, String)> = Vec::new();
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let key = match arg.as_str() {
+            "--json" => {
+                json = true;
+                continue;
+            }
+            "--fix" => {
+                fix = true;
+                continue;
+            }
+            "--backup" => {
+                backup = true;
+                continue;
+            }
+            "--policy" => None,
+            "--orientation" => Some("orientation"),
+            "--dpi" => Some("dpi"),
+            "--color-space" => Some("color_space"),
+            _ => {
+                paths.push(PathBuf::from(arg));
+                continue;
+            }
+        };
+        let value = iter.next().unwrap_or_else(|| {
+            usage(name);
+            process::exit(1);
+        });
+        match key {
+            Some(key) => rules.push((key, value.clone())),
+            None => policy_file = Some(value.clone()),
+        }
+    }
+
+    // Rules given on the command line override the ones in the policy file.
+    let mut policy = match &policy_file {
+        Some(file) => std::fs::read_to_string(file)
+            .map_err(|err| err.into())
+            .and_then(|text| audit::Policy::parse(&text))
+            .unwrap_or_else(|err| {
+                eprintln!(
+                    "{}",
+                    tr(
+                        "could-not-load",
+                        &[("file", file), ("error", &err.to_string())]
+                    )
+                );
+                process::exit(2);
+            }),
+        None => audit::Policy::default(),
+    };
+    for (key, value) in &rules {
+        policy.set(key, value).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        });
+    }
+    if paths.is_empty() || policy.is_empty() {
+        usage(name);
+        process::exit(1);
+    }
+
+    let files: Vec<String> = audit::find_pngs(&paths)
+        .unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(2);
+        })
+        .iter()
+        .map(|p| p.display().to_string())
+        .collect();
+    let batch = report::batch("audit", &files, |file| {
+        Ok(BatchItem::Audit(audit::audit(file, &policy, fix, backup)?))
+    });
+    let failed = batch.items.iter().any(|item| match item {
+        BatchItem::Failed { .. } => true,
+        BatchItem::Audit(report) => !report.compliant,
+        _ => false,
+    });
+
+    if json {
+        print_json(&batch).unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(2);
+        });
+    } else {
+        for item in &batch.items {
+            println!("{}\n", item);
+        }
+    }
+
+    if failed {
+        process::exit(2);
+    }
+}
+
+fn rename_command(name: &str, args: &[String]) {
+    let mut dry_run = false;
+    let mut template = rename::DEFAULT_TEMPLATE.to_string();
+    let mut files: Vec<PathBuf> = Vec::new();
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--dry-run" | "-n" => dry_run = true,
+            "--template" | "-t" => match iter.next() {
+                Some(t) => template = t.clone(),
+                None => {
+                    usage(name);
+                    process::exit(1);
+                }
+            },
+            _ => files.push(PathBuf::from(arg)),
+        }
+    }
+    if files.is_empty() {
+        usage(name);
+        process::exit(1);
+    }
+
+    let plans = rename::plan_renames(&files, &template).unwrap_or_else(|err| {
+        eprintln!(
+            "{}",
+            tr("could-not-plan-renames", &[("error", &err.to_string())])
+        );
+        process::exit(2);
+    });
+
+    for plan in &plans {
+        println!("{} -> {}", plan.from.display(), plan.to.display());
+    }
+
+    if !dry_run {
+        rename::apply_renames(&plans).unwrap_or_else(|err| {
+            eprintln!("{}", tr("could-not-rename", &[("error", &err.to_string())]));
+            process::exit(3);
+        });
+    }
+}
+
+#[cfg(feature = "serve")]
+fn serve_command(name: &str, args: &[String]) {
+    let mut address = String::from("127.0.0.1:8080");
+    let mut dir: Option<PathBuf> = None;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--address" | "-a" => match iter.next() {
+                Some(a) => address = a.clone(),
+                None => {
+                    usage(name);
+                    process::exit(1);
+                }
+            },
+            _ => dir = Some(PathBuf::from(arg)),
+        }
+    }
+    let dir = dir.unwrap_or_else(|| {
+        usage(name);
+        process::exit(1);
+    });
+
+    let dir_name = dir.display().to_string();
+    println!(
+        "{}",
+        tr("serving", &[("dir", &dir_name), ("address", &address)])
+    );
+    imagetools::serve::serve(&dir, &address).unwrap_or_else(|err| {
+        eprintln!(
+            "{}",
+            tr(
+                "could-not-serve",
+                &[("dir", &dir_name), ("error", &err.to_string())]
+            )
+        );
+        process::exit(2);
+    });
+}
+
+#[cfg(not(feature = "serve"))]
+fn serve_command(_name: &str, _args: &[String]) {
+    eprintln!("{}", tr("serve-not-included", &[]));
+    process::exit(1);
+}
+
+// The sections of the usage text, in the order they are printed. Each has an
+// imagetools-usage-<section> and an imagetools-usage-<section>-help message.
+const USAGE_SECTIONS: [&str; 14] = [
+    "info",
+    "validate",
+    "conformance",
+    "analyze",
+    "diff",
+    "convert-jpeg",
+    "convert-to-jpeg",
+    "convert-gif",
+    "convert-to-bmp",
+    "convert-bmp",
+    "convert-tiff",
+    "rename",
+    "audit",
+    "serve",
+];
+
+fn usage(name: &str) {
+    let args = [("program", name), ("template", rename::DEFAULT_TEMPLATE)];
+    println!("{}\n", tr("imagetools-usage", &args));
+    println!("{}", tr("imagetools-usage-commands", &args));
+    for section in USAGE_SECTIONS {
+        let command = tr(&format!("imagetools-usage-{}", section), &args);
+        // Options that don't fit on the first line are lined up after the command name.
+        let indent = command.find(' ').map_or(0, |end| end + 1);
+        for (i, line) in command.lines().enumerate() {
+            let padding = if i == 0 { 0 } else { indent };
+            println!("\t{:padding$}{}", "", line, padding = padding);
+        }
+        for line in tr(&format!("imagetools-usage-{}-help", section), &args).lines() {
+            println!("\t\t{}", line);
+        }
+    }
+    println!();
+}
@@ -1,7 +1,7 @@
 use std::env;
 use std::process;
 
-use imagetools::png::IHDRData;
+use imagetools::i18n::tr;
 use imagetools::png::PNGFile;
 
 fn main() {
@@ -15,12 +15,18 @@ fn main() {
     let out_file = &args[2];
 
     let png_file = PNGFile::from_file(in_file).unwrap_or_else(|err| {
-        eprintln!("Could not load {}: {}", in_file, err);
+        eprintln!(
+            "{}",
+            tr(
+                "could-not-load",
+                &[("file", in_file), ("error", &err.to_string())]
+            )
+        );
         process::exit(2);
     });
 
     // Debug - testing Display
-    let ihdr_data = IHDRData::from_chunk(png_file.get_ihdr_chunk());
+    let ihdr_data = png_file.get_ihdr_data();
     println!("IHDR:\n{}\n", ihdr_data);
 
     // Debug - testing Display
@@ -29,20 +35,24 @@ fn main() {
     }
 
     if let Some(last_modified) = png_file.get_last_modified() {
-        println!("Last modified: {}", last_modified);
+        println!(
+            "{}",
+            tr("last-modified", &[("time", &last_modified.to_string())])
+        );
     }
 
     png_file.write(out_file).unwrap_or_else(|err| {
-        eprintln!("Could not write {}: {}", out_file, err);
+        eprintln!(
+            "{}",
+            tr(
+                "could-not-write",
+                &[("file", out_file), ("error", &err.to_string())]
+            )
+        );
         process::exit(3);
     });
 }
 
 fn usage(name: &str) {
-    println!(
-        "usage: {} in_file out_file\n\
-         \tin_file\tThe name of the input file\n\
-         \tout_file\tThe name of the output file.\n",
-        name
-    )
+    println!("{}", tr("pngcopy-usage", &[("program", name)]))
 }
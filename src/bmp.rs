@@ -0,0 +1,353 @@
+// Windows bitmap files, limited to the uncompressed 24- and 32-bit variants most tools write.
+// A file is a 14-byte file header, an info header of one of several sizes, and the pixel rows,
+// each padded to a multiple of 4 bytes and stored bottom row first unless the height is
+// negative. 24-bit pixels are blue, green, red. 32-bit pixels are blue, green, red and an unused
+// byte, or laid out by bit masks, which is how files with an alpha channel store it.
+use std::convert::TryFrom;
+use std::error;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+
+use crate::image::{ColorType, Image};
+
+const FILE_HEADER_SIZE: usize = 14;
+// BITMAPCOREHEADER, BITMAPINFOHEADER and BITMAPV4HEADER.
+const CORE_HEADER_SIZE: u32 = 12;
+const INFO_HEADER_SIZE: u32 = 40;
+const V4_HEADER_SIZE: u32 = 108;
+
+// Compression methods.
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+// LCS_sRGB, the color space of a V4 header.
+const LCS_SRGB: u32 = 0x7352_4742;
+// 72 dpi, which is what most tools write when they don't know better.
+const DEFAULT_RESOLUTION: u32 = 2835;
+
+#[derive(Debug, Clone)]
+pub struct InvalidBMPFormat {
+    message: String,
+}
+
+impl InvalidBMPFormat {
+    pub(crate) fn new(message: &str) -> InvalidBMPFormat {
+        InvalidBMPFormat {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for InvalidBMPFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid BMP file: {}", self.message)
+    }
+}
+
+impl error::Error for InvalidBMPFormat {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BMPFile {
+    header: BMPHeader,
+    // The pixel rows as stored, padding included.
+    pixel_data: Vec<u8>,
+}
+
+// The parts of the info header this module uses. Files are written with a BITMAPINFOHEADER,
+// or a BITMAPV4HEADER when they have masks, whatever header they were read with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BMPHeader {
+    pub width: u32,
+    pub height: u32,
+    // Rows are stored top row first, which files mark with a negative height.
+    pub top_down: bool,
+    // 24 or 32.
+    pub bits_per_pixel: u16,
+    // Red, green, blue and alpha masks of 32-bit pixels stored with bit fields, with an alpha
+    // mask of 0 for files without alpha. None for pixels stored as plain blue, green, red.
+    pub masks: Option<[u32; 4]>,
+    // Pixels per meter, 0 when unknown.
+    pub horizontal_resolution: u32,
+    pub vertical_resolution: u32,
+}
+
+impl BMPFile {
+    pub fn from_file(filename: &str) -> Result<BMPFile, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        File::open(filename)?.read_to_end(&mut bytes)?;
+        BMPFile::from_bytes(&bytes)
+    }
+
+    // Parses a BMP held in memory. Anything after the pixel rows is ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Result<BMPFile, Box<dyn Error>> {
+        if bytes.get(..2) != Some(b"BM") {
+            return Err(InvalidBMPFormat::new("The file does not start with \"BM\".").into());
+        }
+        let u16_at = |at: usize| {
+            bytes
+                .get(at..at + 2)
+                .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        };
+        let u32_at = |at: usize| {
+            bytes
+                .get(at..at + 4)
+                .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        let cut_off = || InvalidBMPFormat::new("The headers are cut off.");
+        let pixel_offset = u32_at(10).ok_or_else(cut_off)? as usize;
+        let header_size = u32_at(FILE_HEADER_SIZE).ok_or_else(cut_off)?;
+        let info = FILE_HEADER_SIZE;
+
+        let (width, height, bits_per_pixel, compression) = if header_size == CORE_HEADER_SIZE {
+            let field = |at: usize| u16_at(info + at).ok_or_else(cut_off);
+            (
+                field(4)? as i32,
+                field(6)? as i16 as i32,
+                field(10)?,
+                BI_RGB,
+            )
+        } else if header_size >= INFO_HEADER_SIZE {
+            let field = |at: usize| u32_at(info + at).ok_or_else(cut_off);
+            let bits_per_pixel = u16_at(info + 14).ok_or_else(cut_off)?;
+            (
+                field(4)? as i32,
+                field(8)? as i32,
+                bits_per_pixel,
+                field(16)?,
+            )
+        } else {
+            return Err(InvalidBMPFormat::new("The info header has an unknown size.").into());
+        };
+        let resolution = |at: usize| {
+            if header_size >= INFO_HEADER_SIZE {
+                u32_at(info + at).unwrap_or(0)
+            } else {
+                0
+            }
+        };
+
+        // Masks follow a BITMAPINFOHEADER, and are part of every larger header.
+        let masks = match (bits_per_pixel, compression) {
+            (24, BI_RGB) | (32, BI_RGB) => None,
+            (32, BI_BITFIELDS) | (32, BI_ALPHABITFIELDS) => {
+                let count = if compression == BI_ALPHABITFIELDS || header_size >= 56 {
+                    4
+                } else {
+                    3
+                };
+                let mut masks = [0; 4];
+                for (i, mask) in masks.iter_mut().enumerate().take(count) {
+                    *mask = u32_at(info + 40 + i * 4).ok_or_else(cut_off)?;
+                }
+                Some(masks)
+            }
+            _ => {
+                return Err(InvalidBMPFormat::new(
+                    "Only uncompressed 24- and 32-bit BMPs are supported.",
+                )
+                .into())
+            }
+        };
+
+        if width <= 0 || height == 0 || height == i32::MIN {
+            return Err(InvalidBMPFormat::new("The image has no pixels.").into());
+        }
+        let header = BMPHeader {
+            width: width as u32,
+            height: height.unsigned_abs(),
+            top_down: height < 0,
+            bits_per_pixel,
+            masks,
+            horizontal_resolution: resolution(24),
+            vertical_resolution: resolution(28),
+        };
+        let size = header.row_size() * header.height as usize;
+        let pixel_data = bytes
+            .get(pixel_offset..)
+            .and_then(|data| data.get(..size))
+            .ok_or_else(|| InvalidBMPFormat::new("The pixel data is cut off."))?
+            .to_vec();
+        Ok(BMPFile { header, pixel_data })
+    }
+
+    // Stores an image as 24-bit pixels, or as 32-bit pixels with an alpha mask when the image
+    // has any transparency. Samples are reduced to 8 bits and palettes and gray resolved, since
+    // other tools rarely read the BMP variants that could keep them.
+    pub fn from_image(image: &Image) -> Result<BMPFile, Box<dyn Error>> {
+        if image.width() > i32::MAX as u32 || image.height() > i32::MAX as u32 {
+            return Err("The image is too large for a BMP file.".into());
+        }
+        let alpha = !image.is_fully_opaque();
+        let header = BMPHeader {
+            width: image.width(),
+            height: image.height(),
+            top_down: false,
+            bits_per_pixel: if alpha { 32 } else { 24 },
+            masks: if alpha {
+                Some([0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000])
+            } else {
+                None
+            },
+            horizontal_resolution: DEFAULT_RESOLUTION,
+            vertical_resolution: DEFAULT_RESOLUTION,
+        };
+
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let row_size = header.row_size();
+        let channels = image.channels();
+        let mut pixel_data = Vec::with_capacity(row_size * height);
+        for y in (0..height).rev() {
+            let row_start = pixel_data.len();
+            for x in 0..width {
+                let [r, g, b, a] = image
+                    .rgba_at((y * width + x) * channels)
+                    .map(|v| (v * 255.0).round() as u8);
+                pixel_data.extend_from_slice(&[b, g, r]);
+                if alpha {
+                    pixel_data.push(a);
+                }
+            }
+            pixel_data.resize(row_start + row_size, 0);
+        }
+        Ok(BMPFile { header, pixel_data })
+    }
+
+    pub fn header(&self) -> &BMPHeader {
+        &self.header
+    }
+
+    pub fn width(&self) -> u32 {
+        self.header.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.header.height
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.header.width, self.header.height)
+    }
+
+    // Decodes to 8-bit RGB, or RGBA when the file has an alpha mask.
+    pub fn decode(&self) -> Result<Image, Box<dyn Error>> {
+        let header = &self.header;
+        let alpha = header.masks.is_some_and(|m| m[3] != 0);
+        let color_type = if alpha {
+            ColorType::TruecolorAlpha
+        } else {
+            ColorType::Truecolor
+        };
+        let (width, height) = (header.width as usize, header.height as usize);
+        let bytes_per_pixel = header.bits_per_pixel as usize / 8;
+        let mut samples = Vec::with_capacity(width * height * color_type.channels());
+        for y in 0..height {
+            let stored_row = if header.top_down { y } else { height - 1 - y };
+            let row = &self.pixel_data[stored_row * header.row_size()..];
+            for pixel in row.chunks_exact(bytes_per_pixel).take(width) {
+                match header.masks {
+                    None => samples.extend_from_slice(&[
+                        pixel[2] as u16,
+                        pixel[1] as u16,
+                        pixel[0] as u16,
+                    ]),
+                    Some(masks) => {
+                        let value = u32::from_le_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                        let channels = if alpha { 4 } else { 3 };
+                        samples.extend(masks[..channels].iter().map(|m| masked(value, *m)));
+                    }
+                }
+            }
+        }
+        Image::from_samples(header.width, header.height, color_type, 8, samples)
+    }
+
+    pub fn write(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let mut file = File::create(filename)?;
+        self.write_to(&mut file)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        writer.write_all(&self.to_bytes()?)?;
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let header = &self.header;
+        let header_size = if header.masks.is_some() {
+            V4_HEADER_SIZE
+        } else {
+            INFO_HEADER_SIZE
+        };
+        let pixel_offset = FILE_HEADER_SIZE as u32 + header_size;
+        let file_size = u32::try_from(pixel_offset as usize + self.pixel_data.len())
+            .map_err(|_| "The image is too large for a BMP file.")?;
+        let height = if header.top_down {
+            -(header.height as i32)
+        } else {
+            header.height as i32
+        };
+
+        let mut bytes = Vec::with_capacity(file_size as usize);
+        bytes.extend_from_slice(b"BM");
+        bytes.extend_from_slice(&file_size.to_le_bytes());
+        // Two reserved fields.
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&pixel_offset.to_le_bytes());
+
+        bytes.extend_from_slice(&header_size.to_le_bytes());
+        bytes.extend_from_slice(&(header.width as i32).to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        // One plane.
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&header.bits_per_pixel.to_le_bytes());
+        let compression = if header.masks.is_some() {
+            BI_BITFIELDS
+        } else {
+            BI_RGB
+        };
+        bytes.extend_from_slice(&compression.to_le_bytes());
+        bytes.extend_from_slice(&(self.pixel_data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&header.horizontal_resolution.to_le_bytes());
+        bytes.extend_from_slice(&header.vertical_resolution.to_le_bytes());
+        // No color table, and every color is important.
+        bytes.extend_from_slice(&[0; 8]);
+        if let Some(masks) = header.masks {
+            for mask in masks {
+                bytes.extend_from_slice(&mask.to_le_bytes());
+            }
+            bytes.extend_from_slice(&LCS_SRGB.to_le_bytes());
+            // Endpoints and gamma, which sRGB doesn't use.
+            bytes.extend_from_slice(&[0; 48]);
+        }
+        bytes.extend_from_slice(&self.pixel_data);
+        Ok(bytes)
+    }
+}
+
+impl BMPHeader {
+    // Bytes per stored row, padding included.
+    pub fn row_size(&self) -> usize {
+        (self.width as usize * self.bits_per_pixel as usize).div_ceil(32) * 4
+    }
+}
+
+// Extracts the bits of a mask from a pixel and scales them to 8 bits.
+fn masked(value: u32, mask: u32) -> u16 {
+    if mask == 0 {
+        return 0;
+    }
+    let max = mask >> mask.trailing_zeros();
+    let bits = (value & mask) >> mask.trailing_zeros();
+    ((bits as u64 * 255 + max as u64 / 2) / max as u64) as u16
+}
@@ -0,0 +1,276 @@
+use std::convert::TryInto;
+use std::error;
+use std::error::Error;
+use std::fmt;
+
+// Tags this crate looks at directly.
+pub const TAG_ORIENTATION: u16 = 0x0112;
+pub const TAG_DATE_TIME: u16 = 0x0132;
+pub const TAG_EXIF_IFD: u16 = 0x8769;
+pub const TAG_DATE_TIME_ORIGINAL: u16 = 0x9003;
+
+#[derive(Debug, Clone)]
+pub struct InvalidExifData;
+
+impl fmt::Display for InvalidExifData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "The EXIF data is malformed.")
+    }
+}
+
+impl error::Error for InvalidExifData {
+    fn description(&self) -> &str {
+        "The EXIF data is malformed."
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExifValue {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    SignedLong(Vec<i32>),
+    SignedRational(Vec<(i32, i32)>),
+    Undefined(Vec<u8>),
+}
+
+#[derive(Debug, Clone)]
+pub struct ExifEntry {
+    pub tag: u16,
+    pub value: ExifValue,
+    // Where the value bytes live inside the EXIF block, so they can be patched later.
+    pub value_offset: usize,
+}
+
+// EXIF metadata as stored in a PNG eXIf chunk or a JPEG APP1 segment (minus the "Exif\0\0"
+// prefix): a TIFF header followed by image file directories. Entries from IFD0 and the Exif
+// sub-IFD are flattened into one list.
+#[derive(Debug, Clone)]
+pub struct ExifData {
+    raw: Vec<u8>,
+    little_endian: bool,
+    entries: Vec<ExifEntry>,
+}
+
+impl ExifData {
+    pub fn parse(data: &[u8]) -> Result<ExifData, Box<dyn Error>> {
+        if data.len() < 8 {
+            return Err(InvalidExifData.into());
+        }
+        let reader = TiffReader::new(data).ok_or(InvalidExifData)?;
+        let (mut entries, _) = reader.read_ifd(reader.read_u32(4)? as usize)?;
+        let exif_ifd = entries.iter().find(|e| e.tag == TAG_EXIF_IFD);
+        if let Some(ExifValue::Long(offset)) = exif_ifd.map(|e| &e.value) {
+            if let Some(offset) = offset.first() {
+                let (exif_entries, _) = reader.read_ifd(*offset as usize)?;
+                entries.extend(exif_entries);
+            }
+        }
+        Ok(ExifData {
+            raw: data.to_vec(),
+            little_endian: reader.little_endian,
+            entries,
+        })
+    }
+
+    pub fn entries(&self) -> &[ExifEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, tag: u16) -> Option<&ExifValue> {
+        self.entries.iter().find(|e| e.tag == tag).map(|e| &e.value)
+    }
+
+    pub fn get_ascii(&self, tag: u16) -> Option<&str> {
+        match self.get(tag) {
+            Some(ExifValue::Ascii(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn get_short(&self, tag: u16) -> Option<u16> {
+        match self.get(tag) {
+            Some(ExifValue::Short(v)) => v.first().copied(),
+            _ => None,
+        }
+    }
+
+    // DateTimeOriginal if present, otherwise DateTime. Formatted "YYYY:MM:DD HH:MM:SS".
+    pub fn date_time(&self) -> Option<&str> {
+        self.get_ascii(TAG_DATE_TIME_ORIGINAL)
+            .or_else(|| self.get_ascii(TAG_DATE_TIME))
+    }
+
+    pub fn orientation(&self) -> Option<u16> {
+        self.get_short(TAG_ORIENTATION)
+    }
+
+    // Overwrites a SHORT value in place. The tag must already be present, since adding entries
+    // would mean moving everything after the directory.
+    pub fn set_short(&mut self, tag: u16, value: u16) -> Result<(), Box<dyn Error>> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|e| e.tag == tag)
+            .ok_or("The EXIF tag is not present.")?;
+        match &mut entry.value {
+            ExifValue::Short(values) if !values.is_empty() => values[0] = value,
+            _ => return Err("The EXIF tag does not hold a SHORT value.".into()),
+        }
+
+        let bytes = if self.little_endian {
+            value.to_le_bytes()
+        } else {
+            value.to_be_bytes()
+        };
+        let at = entry.value_offset;
+        self.raw[at..at + 2].copy_from_slice(&bytes);
+        Ok(())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.raw
+    }
+}
+
+// Reads the TIFF structure EXIF data is stored in: a byte order mark, then image file
+// directories of tagged values, the same layout TIFF files use.
+pub(crate) struct TiffReader<'a> {
+    pub(crate) raw: &'a [u8],
+    pub(crate) little_endian: bool,
+}
+
+impl<'a> TiffReader<'a> {
+    // None unless the data starts with a TIFF header.
+    pub(crate) fn new(raw: &'a [u8]) -> Option<TiffReader<'a>> {
+        let little_endian = match raw.get(0..4)? {
+            b"II*\0" => true,
+            b"MM\0*" => false,
+            _ => return None,
+        };
+        Some(TiffReader { raw, little_endian })
+    }
+
+    // The entries of the directory at offset, and the offset of the next directory, which is 0
+    // after the last one.
+    pub(crate) fn read_ifd(
+        &self,
+        offset: usize,
+    ) -> Result<(Vec<ExifEntry>, usize), Box<dyn Error>> {
+        let count = self.read_u16(offset)? as usize;
+        let mut entries = Vec::with_capacity(count);
+        for i in 0..count {
+            let entry = offset + 2 + i * 12;
+            let tag = self.read_u16(entry)?;
+            let field_type = self.read_u16(entry + 2)?;
+            let n = self.read_u32(entry + 4)? as usize;
+
+            let size = match field_type {
+                1 | 2 | 6 | 7 => 1,
+                3 | 8 => 2,
+                4 | 9 => 4,
+                5 | 10 => 8,
+                // Unknown field types are skipped as the TIFF spec requires.
+                _ => continue,
+            };
+            let value_offset = if size * n <= 4 {
+                entry + 8
+            } else {
+                self.read_u32(entry + 8)? as usize
+            };
+            if value_offset + size * n > self.raw.len() {
+                return Err(InvalidExifData.into());
+            }
+
+            let value = self.read_value(field_type, value_offset, n)?;
+            entries.push(ExifEntry {
+                tag,
+                value,
+                value_offset,
+            });
+        }
+        // Some writers cut the data off right after the last entry.
+        let next = self.read_u32(offset + 2 + count * 12).unwrap_or(0) as usize;
+        Ok((entries, next))
+    }
+
+    fn read_value(
+        &self,
+        field_type: u16,
+        at: usize,
+        n: usize,
+    ) -> Result<ExifValue, Box<dyn Error>> {
+        let bytes = &self.raw[at..];
+        Ok(match field_type {
+            1 | 6 => ExifValue::Byte(bytes[..n].to_vec()),
+            2 => {
+                let text = &bytes[..n];
+                let end = text.iter().position(|b| *b == 0).unwrap_or(n);
+                ExifValue::Ascii(String::from_utf8_lossy(&text[..end]).into_owned())
+            }
+            3 | 8 => ExifValue::Short(
+                (0..n)
+                    .map(|i| self.read_u16(at + i * 2))
+                    .collect::<Result<_, _>>()?,
+            ),
+            4 => ExifValue::Long(
+                (0..n)
+                    .map(|i| self.read_u32(at + i * 4))
+                    .collect::<Result<_, _>>()?,
+            ),
+            9 => ExifValue::SignedLong(
+                (0..n)
+                    .map(|i| self.read_u32(at + i * 4).map(|v| v as i32))
+                    .collect::<Result<_, _>>()?,
+            ),
+            5 => ExifValue::Rational(
+                (0..n)
+                    .map(|i| Ok((self.read_u32(at + i * 8)?, self.read_u32(at + i * 8 + 4)?)))
+                    .collect::<Result<_, Box<dyn Error>>>()?,
+            ),
+            10 => ExifValue::SignedRational(
+                (0..n)
+                    .map(|i| {
+                        Ok((
+                            self.read_u32(at + i * 8)? as i32,
+                            self.read_u32(at + i * 8 + 4)? as i32,
+                        ))
+                    })
+                    .collect::<Result<_, Box<dyn Error>>>()?,
+            ),
+            _ => ExifValue::Undefined(bytes[..n].to_vec()),
+        })
+    }
+
+    pub(crate) fn read_u16(&self, at: usize) -> Result<u16, Box<dyn Error>> {
+        let bytes: [u8; 2] = self
+            .raw
+            .get(at..at + 2)
+            .ok_or(InvalidExifData)?
+            .try_into()?;
+        Ok(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    pub(crate) fn read_u32(&self, at: usize) -> Result<u32, Box<dyn Error>> {
+        let bytes: [u8; 4] = self
+            .raw
+            .get(at..at + 4)
+            .ok_or(InvalidExifData)?
+            .try_into()?;
+        Ok(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+}
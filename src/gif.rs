@@ -0,0 +1,419 @@
+// GIF files as their logical screen descriptor and a list of blocks, the way jpeg holds a JPEG
+// as a list of segments. Blocks are images, each with its LZW-compressed pixel data, and
+// extensions, of which graphic control extensions are parsed since they say how the image after
+// them is animated. Both GIF87a and GIF89a files are read.
+use std::error;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+
+mod convert;
+mod decode;
+
+pub use decode::GIFDecodeError;
+
+const IMAGE_SEPARATOR: u8 = 0x2C;
+const EXTENSION_INTRODUCER: u8 = 0x21;
+const TRAILER: u8 = 0x3B;
+
+// Extension labels.
+pub const PLAIN_TEXT: u8 = 0x01;
+pub const GRAPHIC_CONTROL: u8 = 0xF9;
+pub const COMMENT: u8 = 0xFE;
+pub const APPLICATION: u8 = 0xFF;
+
+#[derive(Debug, Clone)]
+pub struct InvalidGIFFormat {
+    message: String,
+}
+
+impl InvalidGIFFormat {
+    pub(crate) fn new(message: &str) -> InvalidGIFFormat {
+        InvalidGIFFormat {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for InvalidGIFFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid GIF file: {}", self.message)
+    }
+}
+
+impl error::Error for InvalidGIFFormat {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GIFFile {
+    // "87a" or "89a".
+    version: String,
+    screen: LogicalScreen,
+    // Every block between the screen descriptor and the trailer, in file order.
+    blocks: Vec<GIFBlock>,
+}
+
+// Logical screen descriptor, with the global color table that follows it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogicalScreen {
+    // The size of the canvas every image is drawn on.
+    pub width: u16,
+    pub height: u16,
+    // Bits per primary color of the original image, from 1 to 8. Decoders ignore it.
+    pub color_resolution: u8,
+    // Index into the global color table of the color outside every image. Browsers show the
+    // canvas transparent instead, and so does render_frames.
+    pub background_index: u8,
+    // 0, or the pixel aspect ratio as (value + 15) / 64.
+    pub pixel_aspect_ratio: u8,
+    pub global_color_table: Option<Vec<[u8; 3]>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GIFBlock {
+    GraphicControl(GraphicControl),
+    Image(ImageBlock),
+    // Any other extension: its label and data sub-blocks. An application extension's first
+    // sub-block is its identifier and authentication code, such as "NETSCAPE2.0".
+    Extension { label: u8, sub_blocks: Vec<Vec<u8>> },
+}
+
+// Graphic control extension, which applies to the image that follows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GraphicControl {
+    pub disposal: DisposalMethod,
+    // The viewer should wait for input before going on. Players ignore it.
+    pub user_input: bool,
+    // Pixels of this index are left out when drawing the image.
+    pub transparent_index: Option<u8>,
+    // How long the image is shown, in hundredths of a second.
+    pub delay: u16,
+}
+
+// What happens to an image's region once it has been shown, before the next image is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposalMethod {
+    // The encoder didn't say. Players leave the region as it is, and so does render_frames.
+    // Reserved values 4 - 7 are read as this too.
+    Unspecified,
+    // Leave it as it is.
+    Keep,
+    // Clear it to the background, which players take to mean transparent.
+    Background,
+    // Put back what was there before the image was drawn.
+    Previous,
+}
+
+// Image descriptor, local color table and image data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageBlock {
+    // The image's position and size on the logical screen.
+    pub left: u16,
+    pub top: u16,
+    pub width: u16,
+    pub height: u16,
+    // Rows are stored in four passes: every 8th row from 0, every 8th from 4, every 4th from 2
+    // and every 2nd from 1.
+    pub interlaced: bool,
+    // Used instead of the global color table when present.
+    pub local_color_table: Option<Vec<[u8; 3]>>,
+    // The LZW code size the data starts from, one less than its first code width.
+    pub min_code_size: u8,
+    // The LZW data with the sub-blocks it was split into joined back together.
+    pub data: Vec<u8>,
+}
+
+// An image together with the graphic control extension before it, if any.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GIFFrame {
+    pub control: Option<GraphicControl>,
+    pub image: ImageBlock,
+}
+
+impl GIFFile {
+    pub fn from_file(filename: &str) -> Result<GIFFile, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        File::open(filename)?.read_to_end(&mut bytes)?;
+        GIFFile::from_bytes(&bytes)
+    }
+
+    // Parses a GIF held in memory. Anything after the trailer is ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Result<GIFFile, Box<dyn Error>> {
+        let version = match bytes.get(..6) {
+            Some(b"GIF87a") => "87a",
+            Some(b"GIF89a") => "89a",
+            _ => {
+                return Err(
+                    InvalidGIFFormat::new("The file does not start with a GIF header.").into(),
+                )
+            }
+        };
+        let descriptor = bytes
+            .get(6..13)
+            .ok_or_else(|| InvalidGIFFormat::new("The logical screen descriptor is cut off."))?;
+        let flags = descriptor[4];
+        let mut at = 13;
+        let global_color_table = if flags & 0x80 != 0 {
+            Some(read_color_table(bytes, &mut at, flags & 0x07)?)
+        } else {
+            None
+        };
+        let screen = LogicalScreen {
+            width: u16::from_le_bytes([descriptor[0], descriptor[1]]),
+            height: u16::from_le_bytes([descriptor[2], descriptor[3]]),
+            color_resolution: ((flags >> 4) & 0x07) + 1,
+            background_index: descriptor[5],
+            pixel_aspect_ratio: descriptor[6],
+            global_color_table,
+        };
+
+        let mut blocks = Vec::new();
+        loop {
+            let introducer = *bytes
+                .get(at)
+                .ok_or_else(|| InvalidGIFFormat::new("The file ended before the trailer."))?;
+            at += 1;
+            match introducer {
+                TRAILER => break,
+                IMAGE_SEPARATOR => blocks.push(GIFBlock::Image(read_image(bytes, &mut at)?)),
+                EXTENSION_INTRODUCER => {
+                    let label = *bytes
+                        .get(at)
+                        .ok_or_else(|| InvalidGIFFormat::new("The file ended in an extension."))?;
+                    at += 1;
+                    let sub_blocks = read_sub_blocks(bytes, &mut at)?;
+                    blocks.push(match label {
+                        GRAPHIC_CONTROL => {
+                            GIFBlock::GraphicControl(GraphicControl::parse(&sub_blocks)?)
+                        }
+                        _ => GIFBlock::Extension { label, sub_blocks },
+                    });
+                }
+                _ => {
+                    return Err(InvalidGIFFormat::new(&format!(
+                        "Unknown block introducer 0x{:02X}.",
+                        introducer
+                    ))
+                    .into())
+                }
+            }
+        }
+        Ok(GIFFile {
+            version: version.to_string(),
+            screen,
+            blocks,
+        })
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn screen(&self) -> &LogicalScreen {
+        &self.screen
+    }
+
+    pub fn width(&self) -> u16 {
+        self.screen.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.screen.height
+    }
+
+    pub fn dimensions(&self) -> (u16, u16) {
+        (self.screen.width, self.screen.height)
+    }
+
+    pub fn blocks(&self) -> &[GIFBlock] {
+        &self.blocks
+    }
+
+    // Every image in order, each with the graphic control extension that came before it and
+    // after the previous image.
+    pub fn frames(&self) -> Vec<GIFFrame> {
+        let mut frames = Vec::new();
+        let mut control = None;
+        for block in &self.blocks {
+            match block {
+                GIFBlock::GraphicControl(c) => control = Some(*c),
+                GIFBlock::Image(image) => frames.push(GIFFrame {
+                    control: control.take(),
+                    image: image.clone(),
+                }),
+                GIFBlock::Extension { .. } => {}
+            }
+        }
+        frames
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.blocks
+            .iter()
+            .filter(|b| matches!(b, GIFBlock::Image(_)))
+            .count()
+            > 1
+    }
+
+    // How many times the animation repeats after it is first shown, from the NETSCAPE2.0
+    // application extension. 0 loops forever, and None means the file doesn't say, which
+    // players take as showing it once.
+    pub fn loop_count(&self) -> Option<u16> {
+        self.blocks.iter().find_map(|block| match block {
+            GIFBlock::Extension { label, sub_blocks } if *label == APPLICATION => {
+                match sub_blocks.as_slice() {
+                    [id, data, ..]
+                        if (id == b"NETSCAPE2.0" || id == b"ANIMEXTS1.0")
+                            && data.len() >= 3
+                            && data[0] == 1 =>
+                    {
+                        Some(u16::from_le_bytes([data[1], data[2]]))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+    }
+
+    // The text of every comment extension, in file order.
+    pub fn comments(&self) -> Vec<String> {
+        self.blocks
+            .iter()
+            .filter_map(|block| match block {
+                GIFBlock::Extension { label, sub_blocks } if *label == COMMENT => {
+                    Some(String::from_utf8_lossy(&sub_blocks.concat()).into_owned())
+                }
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl GraphicControl {
+    pub fn parse(sub_blocks: &[Vec<u8>]) -> Result<GraphicControl, InvalidGIFFormat> {
+        let data = match sub_blocks.first() {
+            Some(data) if data.len() >= 4 => data,
+            _ => {
+                return Err(InvalidGIFFormat::new(
+                    "A graphic control extension is too short.",
+                ))
+            }
+        };
+        let flags = data[0];
+        Ok(GraphicControl {
+            disposal: DisposalMethod::from_u8((flags >> 2) & 0x07),
+            user_input: flags & 0x02 != 0,
+            transparent_index: if flags & 0x01 != 0 {
+                Some(data[3])
+            } else {
+                None
+            },
+            delay: u16::from_le_bytes([data[1], data[2]]),
+        })
+    }
+}
+
+impl DisposalMethod {
+    pub fn from_u8(value: u8) -> DisposalMethod {
+        match value {
+            1 => DisposalMethod::Keep,
+            2 => DisposalMethod::Background,
+            3 => DisposalMethod::Previous,
+            _ => DisposalMethod::Unspecified,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            DisposalMethod::Unspecified => 0,
+            DisposalMethod::Keep => 1,
+            DisposalMethod::Background => 2,
+            DisposalMethod::Previous => 3,
+        }
+    }
+}
+
+impl ImageBlock {
+    // The color table the image's indices refer to.
+    pub fn color_table<'a>(&'a self, gif_file: &'a GIFFile) -> Option<&'a Vec<[u8; 3]>> {
+        self.local_color_table
+            .as_ref()
+            .or(gif_file.screen.global_color_table.as_ref())
+    }
+}
+
+// Reads a color table of 2^(size + 1) entries.
+fn read_color_table(
+    bytes: &[u8],
+    at: &mut usize,
+    size: u8,
+) -> Result<Vec<[u8; 3]>, InvalidGIFFormat> {
+    let length = 3 << (size + 1);
+    let table = bytes
+        .get(*at..*at + length)
+        .ok_or_else(|| InvalidGIFFormat::new("A color table is cut off."))?
+        .chunks_exact(3)
+        .map(|c| [c[0], c[1], c[2]])
+        .collect();
+    *at += length;
+    Ok(table)
+}
+
+// Reads data sub-blocks up to and including the empty one that ends them.
+fn read_sub_blocks(bytes: &[u8], at: &mut usize) -> Result<Vec<Vec<u8>>, InvalidGIFFormat> {
+    let mut sub_blocks = Vec::new();
+    loop {
+        let size = *bytes
+            .get(*at)
+            .ok_or_else(|| InvalidGIFFormat::new("The file ended in a block's data."))?
+            as usize;
+        *at += 1;
+        if size == 0 {
+            return Ok(sub_blocks);
+        }
+        let data = bytes
+            .get(*at..*at + size)
+            .ok_or_else(|| InvalidGIFFormat::new("The file ended in a block's data."))?;
+        sub_blocks.push(data.to_vec());
+        *at += size;
+    }
+}
+
+fn read_image(bytes: &[u8], at: &mut usize) -> Result<ImageBlock, InvalidGIFFormat> {
+    let descriptor = bytes
+        .get(*at..*at + 9)
+        .ok_or_else(|| InvalidGIFFormat::new("An image descriptor is cut off."))?;
+    let field = |i: usize| u16::from_le_bytes([descriptor[i], descriptor[i + 1]]);
+    let flags = descriptor[8];
+    let (left, top, width, height) = (field(0), field(2), field(4), field(6));
+    *at += 9;
+    let local_color_table = if flags & 0x80 != 0 {
+        Some(read_color_table(bytes, at, flags & 0x07)?)
+    } else {
+        None
+    };
+    let min_code_size = *bytes
+        .get(*at)
+        .ok_or_else(|| InvalidGIFFormat::new("The file ended before an image's data."))?;
+    *at += 1;
+    let data = read_sub_blocks(bytes, at)?.concat();
+    Ok(ImageBlock {
+        left,
+        top,
+        width,
+        height,
+        interlaced: flags & 0x40 != 0,
+        local_color_table,
+        min_code_size,
+        data,
+    })
+}
@@ -0,0 +1,192 @@
+// Converting GIF files to PNG. A GIF with one image becomes a still PNG and an animated one an
+// APNG, with the same frame delays and loop count.
+use std::error::Error;
+
+use super::{DisposalMethod, GIFFile};
+use crate::image::{ColorType, Image};
+use crate::png::{ApngBuilder, BlendOp, DisposeOp, PNGFile};
+
+impl GIFFile {
+    // Stills keep their palette when the image covers the whole canvas, and are RGBA otherwise.
+    // Each image of an animation becomes an RGBA frame over the same region, drawn and disposed
+    // of the same way, except for the first, which APNG needs to cover the canvas. Delays are
+    // copied as they are, although browsers stretch GIF delays under 2 hundredths of a second
+    // to 10 and APNG players don't.
+    pub fn to_png(&self) -> Result<PNGFile, Box<dyn Error>> {
+        let frames = self.frames();
+        if let [frame] = frames.as_slice() {
+            let image = &frame.image;
+            let covers_canvas = image.left == 0
+                && image.top == 0
+                && (image.width, image.height) == self.dimensions();
+            if covers_canvas {
+                let indexed = self.decode_frame(frame)?;
+                let colors = indexed.palette().map_or(0, |p| p.len());
+                if indexed.samples().iter().all(|i| (*i as usize) < colors) {
+                    return PNGFile::from_image(&indexed);
+                }
+            }
+        }
+        if frames.len() <= 1 {
+            return PNGFile::from_image(&self.decode()?);
+        }
+
+        // GIF counts the repeats after the first time through, and APNG every time through.
+        let num_plays = match self.loop_count() {
+            Some(0) => 0,
+            Some(repeats) => repeats as u32 + 1,
+            None => 1,
+        };
+        let (width, height) = (self.width() as u32, self.height() as u32);
+        let mut builder = ApngBuilder::new().with_loop_count(num_plays);
+        for (index, frame) in frames.iter().enumerate() {
+            let delay = frame.control.map_or(0, |c| c.delay);
+            let dispose_op = match frame.control.map(|c| c.disposal) {
+                Some(DisposalMethod::Background) => DisposeOp::Background,
+                Some(DisposalMethod::Previous) => DisposeOp::Previous,
+                _ => DisposeOp::None,
+            };
+            // The first image on the blank canvas. Disposing of the whole canvas afterwards
+            // does the same as disposing of the image's region, since the rest is blank.
+            if index == 0 {
+                let first = PNGFile::from_image(&self.decode()?)?;
+                builder = builder.with_frame_region(
+                    &first,
+                    0,
+                    0,
+                    delay,
+                    100,
+                    dispose_op,
+                    BlendOp::Source,
+                );
+                continue;
+            }
+
+            let image = &frame.image;
+            let (left, top) = (image.left as u32, image.top as u32);
+            let right = (left + image.width as u32).min(width);
+            let bottom = (top + image.height as u32).min(height);
+            // Transparent pixels leave the canvas as it is, the way blending over it does.
+            let (region, x, y) = if left < right && top < bottom {
+                let region = self
+                    .decode_frame(frame)?
+                    .expand_transparency()
+                    .convert_to(ColorType::TruecolorAlpha, 8)?
+                    .copy_region(0, 0, right - left, bottom - top);
+                (region, left, top)
+            } else {
+                // Nothing of the image lands on the canvas, so a transparent pixel stands in.
+                (Image::new(1, 1, ColorType::TruecolorAlpha, 8)?, 0, 0)
+            };
+            let region = PNGFile::from_image(&region)?;
+            builder =
+                builder.with_frame_region(&region, x, y, delay, 100, dispose_op, BlendOp::Over);
+        }
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gif::{GIFBlock, GraphicControl, ImageBlock, LogicalScreen, APPLICATION};
+
+    // LZW data for 4-bit indices that clears the table before every code, so every code is an
+    // index and 5 bits wide.
+    fn lzw(indices: &[u8]) -> Vec<u8> {
+        let mut codes: Vec<u8> = indices.iter().flat_map(|i| [16, *i]).collect();
+        codes.push(17);
+        let (mut bits, mut count, mut data) = (0u32, 0u32, Vec::new());
+        for code in codes {
+            bits |= (code as u32) << count;
+            count += 5;
+            while count >= 8 {
+                data.push(bits as u8);
+                bits >>= 8;
+                count -= 8;
+            }
+        }
+        if count > 0 {
+            data.push(bits as u8);
+        }
+        data
+    }
+
+    fn image(left: u16, width: u16, indices: &[u8]) -> GIFBlock {
+        GIFBlock::Image(ImageBlock {
+            left,
+            top: 0,
+            width,
+            height: 1,
+            interlaced: false,
+            local_color_table: None,
+            min_code_size: 4,
+            data: lzw(indices),
+        })
+    }
+
+    fn gif_file(blocks: Vec<GIFBlock>) -> GIFFile {
+        GIFFile {
+            version: "89a".to_string(),
+            screen: LogicalScreen {
+                width: 4,
+                height: 1,
+                color_resolution: 8,
+                background_index: 0,
+                pixel_aspect_ratio: 0,
+                global_color_table: Some((0..16).map(|i| [i * 16, 0, 0]).collect()),
+            },
+            blocks,
+        }
+    }
+
+    #[test]
+    fn still_keeps_palette() {
+        let png_file = gif_file(vec![image(0, 4, &[3, 1, 4, 1])]).to_png().unwrap();
+        let decoded = png_file.decode().unwrap();
+        assert_eq!(decoded.color_type(), ColorType::Indexed);
+        assert_eq!(decoded.samples(), [3, 1, 4, 1]);
+        assert_eq!(decoded.palette().unwrap()[4], [64, 0, 0]);
+        assert!(png_file.get_animation_control().unwrap().is_none());
+    }
+
+    #[test]
+    fn animation_becomes_apng() {
+        let control = GIFBlock::GraphicControl(GraphicControl {
+            disposal: DisposalMethod::Background,
+            user_input: false,
+            transparent_index: Some(0),
+            delay: 25,
+        });
+        let netscape = GIFBlock::Extension {
+            label: APPLICATION,
+            sub_blocks: vec![b"NETSCAPE2.0".to_vec(), vec![1, 2, 0]],
+        };
+        let gif_file = gif_file(vec![
+            netscape,
+            control.clone(),
+            image(0, 4, &[1, 2, 3, 4]),
+            control,
+            image(2, 2, &[0, 9]),
+        ]);
+        let png_file = gif_file.to_png().unwrap();
+
+        let animation_control = png_file.get_animation_control().unwrap().unwrap();
+        assert_eq!(animation_control.get_num_frames(), 2);
+        // Two repeats after the first time through.
+        assert_eq!(animation_control.get_num_plays(), 3);
+        let frames = png_file.frames().unwrap();
+        let second = frames[1].get_control();
+        assert_eq!(
+            (
+                second.get_x_offset(),
+                second.get_width(),
+                second.get_height()
+            ),
+            (2, 2, 1)
+        );
+        assert_eq!((second.get_delay_num(), second.get_delay_den()), (25, 100));
+        assert_eq!(second.get_dispose_op(), DisposeOp::Background);
+        assert_eq!(second.get_blend_op(), BlendOp::Over);
+    }
+}
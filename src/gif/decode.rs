@@ -0,0 +1,416 @@
+use std::error;
+use std::error::Error;
+use std::fmt;
+
+use super::{DisposalMethod, GIFFile, GIFFrame, GraphicControl};
+use crate::image::{ColorType, Image, Transparency};
+
+// LZW codes are at most 12 bits wide, so the code table never holds more entries than this.
+const MAX_CODES: usize = 4096;
+// The most pixels render keeps in memory: one canvas, or every canvas for render_frames. Each
+// pixel takes 8 bytes, and a screen descriptor alone can ask for 65535 x 65535 pixels.
+const MAX_CANVAS_PIXELS: usize = 1 << 26;
+const MAX_RENDERED_PIXELS: usize = 1 << 28;
+
+#[derive(Debug, Clone)]
+pub struct GIFDecodeError {
+    message: String,
+}
+
+impl GIFDecodeError {
+    pub(crate) fn new(message: &str) -> GIFDecodeError {
+        GIFDecodeError {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for GIFDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not decode GIF data: {}", self.message)
+    }
+}
+
+impl error::Error for GIFDecodeError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+impl GIFFile {
+    // The first image as it appears on the canvas, as 8-bit RGBA. Areas it doesn't cover are
+    // transparent.
+    pub fn decode(&self) -> Result<Image, Box<dyn Error>> {
+        self.render(1)?
+            .pop()
+            .ok_or_else(|| GIFDecodeError::new("The file has no images.").into())
+    }
+
+    // Plays the animation and returns what is on the canvas after each image is drawn, as 8-bit
+    // RGBA. Disposal methods are applied the way browsers apply them, starting from a
+    // transparent canvas rather than the background color. Animations whose canvases wouldn't
+    // fit in memory together are refused.
+    pub fn render_frames(&self) -> Result<Vec<Image>, Box<dyn Error>> {
+        self.render(usize::MAX)
+    }
+
+    // Decodes a frame's region on its own, as an 8-bit indexed image with the frame's color
+    // table as its palette and its transparent index, if any, in tRNS. Pixels the data runs out
+    // before are given the transparent index, or 0 without one.
+    pub fn decode_frame(&self, frame: &GIFFrame) -> Result<Image, Box<dyn Error>> {
+        let palette = frame
+            .image
+            .color_table(self)
+            .ok_or_else(|| GIFDecodeError::new("An image has no color table."))?;
+        let transparent_index = frame.control.and_then(|c| c.transparent_index);
+        let indices = self.indices(frame)?;
+        let samples = indices.iter().map(|i| *i as u16).collect();
+        let mut image = Image::from_samples(
+            frame.image.width as u32,
+            frame.image.height as u32,
+            ColorType::Indexed,
+            8,
+            samples,
+        )?;
+        image.set_palette(Some(palette.clone()));
+        if let Some(index) = transparent_index {
+            let mut alphas = vec![255; index as usize + 1];
+            alphas[index as usize] = 0;
+            image.set_transparency(Some(Transparency::Indexed(alphas)));
+        }
+        Ok(image)
+    }
+
+    fn render(&self, limit: usize) -> Result<Vec<Image>, Box<dyn Error>> {
+        let (width, height) = (self.screen.width as usize, self.screen.height as usize);
+        let frames = self.frames();
+        let rendered_frames = frames.len().min(limit);
+        if width * height > MAX_CANVAS_PIXELS
+            || width * height * rendered_frames > MAX_RENDERED_PIXELS
+        {
+            return Err(GIFDecodeError::new("The logical screen is too large to render.").into());
+        }
+        let mut canvas = Image::new(width as u32, height as u32, ColorType::TruecolorAlpha, 8)?;
+        let mut rendered = Vec::new();
+        for frame in frames.iter().take(limit) {
+            let image = &frame.image;
+            let control = frame.control.unwrap_or(GraphicControl {
+                disposal: DisposalMethod::Unspecified,
+                user_input: false,
+                transparent_index: None,
+                delay: 0,
+            });
+            let previous = match control.disposal {
+                DisposalMethod::Previous => Some(canvas.clone()),
+                _ => None,
+            };
+
+            // The part of the image that is on the canvas. Images may hang over its edges.
+            let (left, top) = (image.left as usize, image.top as usize);
+            let right = (left + image.width as usize).min(width);
+            let bottom = (top + image.height as usize).min(height);
+            if image.width > 0 && image.height > 0 {
+                let palette = image
+                    .color_table(self)
+                    .ok_or_else(|| GIFDecodeError::new("An image has no color table."))?;
+                let indices = self.indices(frame)?;
+                let samples = canvas.samples_mut();
+                for y in top..bottom {
+                    for x in left..right {
+                        let index = indices[(y - top) * image.width as usize + x - left];
+                        if Some(index) == control.transparent_index {
+                            continue;
+                        }
+                        let [r, g, b] = palette.get(index as usize).copied().unwrap_or([0, 0, 0]);
+                        let at = (y * width + x) * 4;
+                        samples[at..at + 4].copy_from_slice(&[r as u16, g as u16, b as u16, 255]);
+                    }
+                }
+            }
+            rendered.push(canvas.clone());
+
+            match control.disposal {
+                DisposalMethod::Unspecified | DisposalMethod::Keep => {}
+                DisposalMethod::Background => {
+                    // Images entirely off the canvas left nothing to clear.
+                    let samples = canvas.samples_mut();
+                    for y in top..bottom {
+                        if left < right {
+                            samples[(y * width + left) * 4..(y * width + right) * 4].fill(0);
+                        }
+                    }
+                }
+                DisposalMethod::Previous => {
+                    if let Some(previous) = previous {
+                        canvas = previous;
+                    }
+                }
+            }
+        }
+        Ok(rendered)
+    }
+
+    // The frame's color indices in row order, one per pixel.
+    fn indices(&self, frame: &GIFFrame) -> Result<Vec<u8>, GIFDecodeError> {
+        let image = &frame.image;
+        let (width, height) = (image.width as usize, image.height as usize);
+        let mut indices = decompress(image.min_code_size, &image.data, width * height)?;
+        let fill = frame.control.and_then(|c| c.transparent_index).unwrap_or(0);
+        indices.resize(width * height, fill);
+        if !image.interlaced || width == 0 {
+            return Ok(indices);
+        }
+
+        let mut rows = indices.chunks_exact(width);
+        let mut deinterlaced = vec![0; width * height];
+        for (start, step) in [(0, 8), (4, 8), (2, 4), (1, 2)] {
+            for y in (start..height).step_by(step) {
+                if let Some(row) = rows.next() {
+                    deinterlaced[y * width..(y + 1) * width].copy_from_slice(row);
+                }
+            }
+        }
+        Ok(deinterlaced)
+    }
+}
+
+// Expands LZW data into at most pixels indices. Codes are packed least significant bit first and
+// start min_code_size + 1 bits wide, growing a bit each time the table fills up to the next power
+// of two, up to 12 bits. Data that stops early, with or without an end code, gives fewer
+// indices.
+fn decompress(min_code_size: u8, data: &[u8], pixels: usize) -> Result<Vec<u8>, GIFDecodeError> {
+    if !(1..=8).contains(&min_code_size) {
+        return Err(GIFDecodeError::new("The LZW code size is out of range."));
+    }
+    let clear = 1usize << min_code_size;
+    let end = clear + 1;
+
+    // Each entry is an earlier entry plus one index, with the first index of the whole string
+    // kept alongside so it doesn't have to be looked up.
+    let mut prefix = [0u16; MAX_CODES];
+    let mut suffix = [0u8; MAX_CODES];
+    let mut first = [0u8; MAX_CODES];
+    for code in 0..clear {
+        suffix[code] = code as u8;
+        first[code] = code as u8;
+    }
+    let mut next = end + 1;
+    let mut width = min_code_size as u32 + 1;
+    let mut previous: Option<usize> = None;
+
+    let mut out = Vec::with_capacity(pixels);
+    let mut string = Vec::new();
+    let (mut bits, mut count, mut at) = (0u32, 0u32, 0usize);
+    while out.len() < pixels {
+        while count < width && at < data.len() {
+            bits |= (data[at] as u32) << count;
+            count += 8;
+            at += 1;
+        }
+        if count < width {
+            break;
+        }
+        let code = (bits & ((1 << width) - 1)) as usize;
+        bits >>= width;
+        count -= width;
+
+        if code == clear {
+            next = end + 1;
+            width = min_code_size as u32 + 1;
+            previous = None;
+            continue;
+        }
+        if code == end {
+            break;
+        }
+        let prior = match previous {
+            Some(prior) => prior,
+            None => {
+                if code >= clear {
+                    return Err(GIFDecodeError::new("The image data holds an invalid code."));
+                }
+                out.push(code as u8);
+                previous = Some(code);
+                continue;
+            }
+        };
+
+        // A code one past the table is the previous string followed by its own first index.
+        let (entry, tail) = if code < next {
+            (code, None)
+        } else if code == next {
+            (prior, Some(first[prior]))
+        } else {
+            return Err(GIFDecodeError::new("The image data holds an invalid code."));
+        };
+        string.clear();
+        let mut link = entry;
+        loop {
+            string.push(suffix[link]);
+            if link < clear {
+                break;
+            }
+            link = prefix[link] as usize;
+        }
+        out.extend(string.iter().rev());
+        out.extend(tail);
+
+        if next < MAX_CODES {
+            prefix[next] = prior as u16;
+            suffix[next] = first[entry];
+            first[next] = first[prior];
+            next += 1;
+            if next == 1 << width && width < 12 {
+                width += 1;
+            }
+        }
+        previous = Some(code);
+    }
+    out.truncate(pixels);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gif::{GIFBlock, ImageBlock, LogicalScreen};
+    use std::collections::HashMap;
+
+    // Packs codes least significant bit first, each at the width given with it.
+    fn pack(codes: &[(usize, u32)]) -> Vec<u8> {
+        let (mut bits, mut count, mut data) = (0u32, 0u32, Vec::new());
+        for (code, width) in codes {
+            bits |= (*code as u32) << count;
+            count += width;
+            while count >= 8 {
+                data.push(bits as u8);
+                bits >>= 8;
+                count -= 8;
+            }
+        }
+        if count > 0 {
+            data.push(bits as u8);
+        }
+        data
+    }
+
+    // A plain LZW encoder that keeps using a full table rather than clearing it. Returns the
+    // data and the widest code it wrote.
+    fn compress(min_code_size: u8, indices: &[u8]) -> (Vec<u8>, u32) {
+        let clear = 1usize << min_code_size;
+        let mut table: HashMap<(usize, u8), usize> = HashMap::new();
+        let mut next = clear + 2;
+        let mut width = min_code_size as u32 + 1;
+        let mut codes = vec![(clear, width)];
+        let mut current: Option<usize> = None;
+        for index in indices {
+            let string = match current {
+                None => {
+                    current = Some(*index as usize);
+                    continue;
+                }
+                Some(string) => string,
+            };
+            if let Some(code) = table.get(&(string, *index)) {
+                current = Some(*code);
+                continue;
+            }
+            codes.push((string, width));
+            if next < MAX_CODES {
+                table.insert((string, *index), next);
+                next += 1;
+                // The decoder adds each entry a code later, so it widens a code later too.
+                if next > 1 << width && width < 12 {
+                    width += 1;
+                }
+            }
+            current = Some(*index as usize);
+        }
+        if let Some(string) = current {
+            codes.push((string, width));
+            if next == 1 << width && width < 12 {
+                width += 1;
+            }
+        }
+        codes.push((clear + 1, width));
+        (pack(&codes), width)
+    }
+
+    fn gif_file(image: ImageBlock) -> GIFFile {
+        GIFFile {
+            version: "89a".to_string(),
+            screen: LogicalScreen {
+                width: image.width,
+                height: image.height,
+                color_resolution: 8,
+                background_index: 0,
+                pixel_aspect_ratio: 0,
+                global_color_table: Some((0..=255).map(|i| [i, i, i]).collect()),
+            },
+            blocks: vec![GIFBlock::Image(image)],
+        }
+    }
+
+    #[test]
+    fn clear_code() {
+        // Clear, 1, 1 (adding 6 = "1 1"), clear, 6, end: after the clear 6 is past the table.
+        let data = pack(&[(4, 3), (1, 3), (1, 3), (4, 3), (6, 3), (5, 3)]);
+        let err = decompress(2, &data, 10).unwrap_err();
+        assert!(err.to_string().contains("invalid code"));
+
+        // Clear, 1, 1, clear, 2, 6 (adding "2 2"), end.
+        let data = pack(&[(4, 3), (1, 3), (1, 3), (4, 3), (2, 3), (6, 3), (5, 3)]);
+        assert_eq!(decompress(2, &data, 10).unwrap(), [1, 1, 2, 2, 2]);
+    }
+
+    #[test]
+    fn code_one_past_table() {
+        // Clear, 1, then 6 before it is defined: "1" followed by its own first index.
+        let data = pack(&[(4, 3), (1, 3), (6, 3), (5, 3)]);
+        assert_eq!(decompress(2, &data, 10).unwrap(), [1, 1, 1]);
+    }
+
+    #[test]
+    fn codes_grow_to_12_bits() {
+        let mut state = 1u32;
+        let indices: Vec<u8> = (0..40_000)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 28) as u8
+            })
+            .collect();
+        let (data, width) = compress(4, &indices);
+        assert_eq!(width, 12);
+        assert_eq!(decompress(4, &data, indices.len()).unwrap(), indices);
+        // Short runs are cut off at the size of the image.
+        assert_eq!(decompress(4, &data, 100).unwrap(), indices[..100]);
+    }
+
+    #[test]
+    fn interlaced_rows() {
+        // Each row is filled with its own number, stored in the order of the four passes.
+        let order = [0, 8, 4, 2, 6, 1, 3, 5, 7, 9];
+        let stored: Vec<u8> = order.iter().flat_map(|row| [*row; 3]).collect();
+        let (data, _) = compress(8, &stored);
+        let gif_file = gif_file(ImageBlock {
+            left: 0,
+            top: 0,
+            width: 3,
+            height: 10,
+            interlaced: true,
+            local_color_table: None,
+            min_code_size: 8,
+            data,
+        });
+        let frame = &gif_file.frames()[0];
+        let expected: Vec<u16> = (0..10).flat_map(|row| [row; 3]).collect();
+        assert_eq!(gif_file.decode_frame(frame).unwrap().samples(), expected);
+
+        let rgba = gif_file.decode().unwrap();
+        assert_eq!(rgba.samples()[3 * 4 * 9..3 * 4 * 9 + 4], [9, 9, 9, 255]);
+    }
+}
@@ -0,0 +1,140 @@
+// Message catalogs for user-facing CLI strings.
+//
+// Catalogs are plain text files named after a language code (en.txt, es.txt, ...) holding one
+// `key = message` pair per line. Lines starting with # are comments, and \n, \t and \\ escapes
+// are recognized inside messages. Messages refer to their arguments by name, e.g. {file}.
+//
+// The catalogs in the locales directory of this repository are built in. Translators can try
+// out a new or updated catalog without rebuilding by pointing IMAGETOOLS_LOCALE_DIR at a
+// directory containing it; entries there take precedence over the built-in ones.
+//
+// The language is taken from IMAGETOOLS_LANG, then LC_ALL, LC_MESSAGES and LANG. Missing
+// messages fall back to English, and missing English messages to the key itself.
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+const FALLBACK_LANGUAGE: &str = "en";
+
+const BUILT_IN: [(&str, &str); 2] = [
+    ("en", include_str!("../locales/en.txt")),
+    ("es", include_str!("../locales/es.txt")),
+];
+
+pub struct Catalog {
+    messages: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+}
+
+impl Catalog {
+    pub fn load(language: &str) -> Catalog {
+        let mut messages = HashMap::new();
+        if let Some((_, text)) = BUILT_IN.iter().find(|(code, _)| *code == language) {
+            messages = parse(text);
+        }
+        let mut fallback = parse(BUILT_IN[0].1);
+
+        if let Ok(dir) = env::var("IMAGETOOLS_LOCALE_DIR") {
+            let dir = Path::new(&dir);
+            if let Ok(text) = fs::read_to_string(dir.join(format!("{}.txt", language))) {
+                messages.extend(parse(&text));
+            }
+            if let Ok(text) = fs::read_to_string(dir.join(format!("{}.txt", FALLBACK_LANGUAGE))) {
+                fallback.extend(parse(&text));
+            }
+        }
+        Catalog { messages, fallback }
+    }
+
+    // Looks up a message and fills in its {name} placeholders.
+    pub fn format(&self, key: &str, args: &[(&str, &str)]) -> String {
+        let template = self
+            .messages
+            .get(key)
+            .or_else(|| self.fallback.get(key))
+            .map(|m| m.as_str())
+            .unwrap_or(key);
+
+        // Placeholders are filled in a single pass so argument values are never reinterpreted.
+        // Braces that don't name an argument are kept as they are.
+        let mut message = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            message.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let value = rest
+                .find('}')
+                .and_then(|end| args.iter().find(|(name, _)| *name == &rest[1..end]))
+                .map(|(name, value)| (name.len() + 2, *value));
+            match value {
+                Some((len, value)) => {
+                    message.push_str(value);
+                    rest = &rest[len..];
+                }
+                None => {
+                    message.push('{');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        message.push_str(rest);
+        message
+    }
+}
+
+// The catalog for the current environment, loaded on first use.
+pub fn catalog() -> &'static Catalog {
+    static CATALOG: OnceLock<Catalog> = OnceLock::new();
+    CATALOG.get_or_init(|| Catalog::load(&current_language()))
+}
+
+// Shorthand for catalog().format().
+pub fn tr(key: &str, args: &[(&str, &str)]) -> String {
+    catalog().format(key, args)
+}
+
+// Language code from the environment, e.g. "es" for LANG=es_MX.UTF-8.
+pub fn current_language() -> String {
+    ["IMAGETOOLS_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| {
+            value
+                .split(&['_', '.', '@', '-'][..])
+                .next()
+                .unwrap_or("")
+                .to_lowercase()
+        })
+        .filter(|code| !code.is_empty() && code != "c" && code != "posix")
+        .unwrap_or_else(|| FALLBACK_LANGUAGE.to_string())
+}
+
+fn parse(text: &str) -> HashMap<String, String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| (key.trim().to_string(), unescape(value.trim())))
+        .collect()
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
@@ -0,0 +1,355 @@
+use std::error;
+use std::error::Error;
+use std::fmt;
+
+use crate::ops::LumaWeights;
+
+#[derive(Debug, Clone)]
+pub struct ImageError {
+    message: String,
+}
+
+impl ImageError {
+    pub fn new(message: &str) -> ImageError {
+        ImageError {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl error::Error for ImageError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+// Color types as defined in the PNG spec. Other formats decode into the closest match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Grayscale,
+    Truecolor,
+    Indexed,
+    GrayscaleAlpha,
+    TruecolorAlpha,
+}
+
+impl ColorType {
+    pub fn from_u8(value: u8) -> Option<ColorType> {
+        match value {
+            0 => Some(ColorType::Grayscale),
+            2 => Some(ColorType::Truecolor),
+            3 => Some(ColorType::Indexed),
+            4 => Some(ColorType::GrayscaleAlpha),
+            6 => Some(ColorType::TruecolorAlpha),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            ColorType::Grayscale => 0,
+            ColorType::Truecolor => 2,
+            ColorType::Indexed => 3,
+            ColorType::GrayscaleAlpha => 4,
+            ColorType::TruecolorAlpha => 6,
+        }
+    }
+
+    // Number of samples stored for each pixel.
+    pub fn channels(self) -> usize {
+        match self {
+            ColorType::Grayscale | ColorType::Indexed => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::Truecolor => 3,
+            ColorType::TruecolorAlpha => 4,
+        }
+    }
+
+    pub fn has_alpha_channel(self) -> bool {
+        self == ColorType::GrayscaleAlpha || self == ColorType::TruecolorAlpha
+    }
+
+    pub fn allows_bit_depth(self, bit_depth: u8) -> bool {
+        match self {
+            ColorType::Grayscale => [1, 2, 4, 8, 16].contains(&bit_depth),
+            ColorType::Indexed => [1, 2, 4, 8].contains(&bit_depth),
+            _ => bit_depth == 8 || bit_depth == 16,
+        }
+    }
+}
+
+// Contents of a tRNS chunk. Gray and truecolor images mark a single color as fully transparent,
+// while indexed images carry an alpha value per palette entry.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transparency {
+    Grayscale(u16),
+    Truecolor([u16; 3]),
+    Indexed(Vec<u8>),
+}
+
+// A decoded image. Samples are unpacked to one u16 per channel regardless of bit depth, so a
+// sample ranges from 0 to max_value(). Indexed images store palette indices as their samples.
+#[derive(Debug, Clone)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: u8,
+    palette: Option<Vec<[u8; 3]>>,
+    transparency: Option<Transparency>,
+    samples: Vec<u16>,
+}
+
+impl Image {
+    pub fn new(
+        width: u32,
+        height: u32,
+        color_type: ColorType,
+        bit_depth: u8,
+    ) -> Result<Image, Box<dyn Error>> {
+        let len = width as usize * height as usize * color_type.channels();
+        Image::from_samples(width, height, color_type, bit_depth, vec![0; len])
+    }
+
+    pub fn from_samples(
+        width: u32,
+        height: u32,
+        color_type: ColorType,
+        bit_depth: u8,
+        samples: Vec<u16>,
+    ) -> Result<Image, Box<dyn Error>> {
+        if width == 0 || height == 0 {
+            return Err(ImageError::new("Width and height must be non-zero numbers.").into());
+        }
+        if !color_type.allows_bit_depth(bit_depth) {
+            return Err(ImageError::new("Invalid bit depth specified for color type.").into());
+        }
+        if samples.len() != width as usize * height as usize * color_type.channels() {
+            return Err(
+                ImageError::new("Sample count does not match the image dimensions.").into(),
+            );
+        }
+
+        Ok(Image {
+            width,
+            height,
+            color_type,
+            bit_depth,
+            palette: None,
+            transparency: None,
+            samples,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn color_type(&self) -> ColorType {
+        self.color_type
+    }
+
+    pub fn bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+
+    pub fn channels(&self) -> usize {
+        self.color_type.channels()
+    }
+
+    // Largest value a sample can hold at this bit depth.
+    pub fn max_value(&self) -> u16 {
+        ((1u32 << self.bit_depth) - 1) as u16
+    }
+
+    pub fn palette(&self) -> Option<&Vec<[u8; 3]>> {
+        self.palette.as_ref()
+    }
+
+    pub fn set_palette(&mut self, palette: Option<Vec<[u8; 3]>>) {
+        self.palette = palette;
+    }
+
+    pub fn transparency(&self) -> Option<&Transparency> {
+        self.transparency.as_ref()
+    }
+
+    pub fn set_transparency(&mut self, transparency: Option<Transparency>) {
+        self.transparency = transparency;
+    }
+
+    pub fn samples(&self) -> &[u16] {
+        &self.samples
+    }
+
+    pub fn samples_mut(&mut self) -> &mut [u16] {
+        &mut self.samples
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> &[u16] {
+        let start = self.pixel_offset(x, y);
+        &self.samples[start..start + self.channels()]
+    }
+
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: &[u16]) {
+        let start = self.pixel_offset(x, y);
+        let channels = self.channels();
+        self.samples[start..start + channels].copy_from_slice(&pixel[..channels]);
+    }
+
+    fn pixel_offset(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width as usize + x as usize) * self.channels()
+    }
+
+    // Copies a rectangle out of the image. The caller is responsible for keeping it in bounds.
+    pub(crate) fn copy_region(&self, x: u32, y: u32, width: u32, height: u32) -> Image {
+        let channels = self.channels();
+        let mut samples = Vec::with_capacity(width as usize * height as usize * channels);
+        for row in y..y + height {
+            let start = self.pixel_offset(x, row);
+            samples.extend_from_slice(&self.samples[start..start + width as usize * channels]);
+        }
+        Image {
+            width,
+            height,
+            samples,
+            palette: self.palette.clone(),
+            transparency: self.transparency.clone(),
+            ..*self
+        }
+    }
+
+    // Returns the pixel at the given sample offset as RGBA normalized to 0.0 - 1.0, resolving
+    // palette indices and tRNS transparency.
+    pub(crate) fn rgba_at(&self, offset: usize) -> [f32; 4] {
+        let max = self.max_value() as f32;
+        let s = &self.samples[offset..offset + self.channels()];
+        let mut rgba = match self.color_type {
+            ColorType::Grayscale => {
+                let v = s[0] as f32 / max;
+                [v, v, v, 1.0]
+            }
+            ColorType::GrayscaleAlpha => {
+                let v = s[0] as f32 / max;
+                [v, v, v, s[1] as f32 / max]
+            }
+            ColorType::Truecolor => [s[0] as f32 / max, s[1] as f32 / max, s[2] as f32 / max, 1.0],
+            ColorType::TruecolorAlpha => [
+                s[0] as f32 / max,
+                s[1] as f32 / max,
+                s[2] as f32 / max,
+                s[3] as f32 / max,
+            ],
+            ColorType::Indexed => {
+                let entry = self
+                    .palette
+                    .as_ref()
+                    .and_then(|p| p.get(s[0] as usize))
+                    .copied()
+                    .unwrap_or([0, 0, 0]);
+                [
+                    entry[0] as f32 / 255.0,
+                    entry[1] as f32 / 255.0,
+                    entry[2] as f32 / 255.0,
+                    1.0,
+                ]
+            }
+        };
+
+        // Color-key transparency from a tRNS chunk overrides the otherwise opaque alpha.
+        match (&self.transparency, self.color_type) {
+            (Some(Transparency::Grayscale(key)), ColorType::Grayscale) if s[0] == *key => {
+                rgba[3] = 0.0;
+            }
+            (Some(Transparency::Truecolor(key)), ColorType::Truecolor) if s == key => {
+                rgba[3] = 0.0;
+            }
+            (Some(Transparency::Indexed(alphas)), ColorType::Indexed) => {
+                if let Some(alpha) = alphas.get(s[0] as usize) {
+                    rgba[3] = *alpha as f32 / 255.0;
+                }
+            }
+            _ => {}
+        }
+        rgba
+    }
+
+    // True when no pixel is even partly transparent, counting both alpha channels and tRNS
+    // color keys.
+    pub fn is_fully_opaque(&self) -> bool {
+        let channels = self.channels();
+        match (self.color_type, &self.transparency) {
+            (ColorType::GrayscaleAlpha, _) | (ColorType::TruecolorAlpha, _) => {
+                let max = self.max_value();
+                self.samples
+                    .iter()
+                    .skip(channels - 1)
+                    .step_by(channels)
+                    .all(|alpha| *alpha == max)
+            }
+            (ColorType::Grayscale, Some(Transparency::Grayscale(key))) => {
+                !self.samples.contains(key)
+            }
+            (ColorType::Truecolor, Some(Transparency::Truecolor(key))) => {
+                !self.samples.chunks_exact(3).any(|s| s == key)
+            }
+            (ColorType::Indexed, Some(Transparency::Indexed(alphas))) => self
+                .samples
+                .iter()
+                .all(|i| alphas.get(*i as usize).is_none_or(|alpha| *alpha == 255)),
+            _ => true,
+        }
+    }
+
+    // True for grayscale and gray+alpha images, whose first sample is already the luminance.
+    pub fn is_gray(&self) -> bool {
+        self.color_type == ColorType::Grayscale || self.color_type == ColorType::GrayscaleAlpha
+    }
+
+    // Luminance of every pixel on a 0.0 - 255.0 scale, using Rec. 601 weights.
+    pub(crate) fn luma_plane(&self) -> Vec<f32> {
+        let channels = self.channels();
+        if self.is_gray() {
+            let scale = 255.0 / self.max_value() as f32;
+            return self
+                .samples
+                .iter()
+                .step_by(channels)
+                .map(|v| *v as f32 * scale)
+                .collect();
+        }
+        (0..self.width as usize * self.height as usize)
+            .map(|i| {
+                let [r, g, b, _] = self.rgba_at(i * channels);
+                luma(r, g, b) * 255.0
+            })
+            .collect()
+    }
+}
+
+// Rec. 601 luminance of normalized RGB.
+pub(crate) fn luma(r: f32, g: f32, b: f32) -> f32 {
+    LumaWeights::Rec601.luma(r, g, b)
+}
+
+// Rescales a sample from one maximum value to another, rounding to the nearest value.
+pub(crate) fn scale_sample(value: u16, from_max: u16, to_max: u16) -> u16 {
+    if from_max == to_max {
+        return value;
+    }
+    ((value as u32 * to_max as u32 + from_max as u32 / 2) / from_max as u32) as u16
+}
@@ -0,0 +1,478 @@
+// JPEG files as a list of marker segments, the way png holds a PNG as a list of chunks. Each
+// segment is a 0xFF byte, a marker byte and, for most markers, a big-endian length followed by
+// the payload. Start of scan segments are followed by entropy-coded image data, which is kept
+// with the segment it belongs to, restart markers included.
+use std::convert::TryFrom;
+use std::error;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{Read, Write};
+
+mod convert;
+mod decode;
+mod encode;
+
+pub use convert::PngConversionOptions;
+pub use decode::JPEGDecodeError;
+pub use encode::{ChromaSubsampling, EncodeOptions};
+
+// Markers this crate looks at directly.
+pub const SOI: u8 = 0xD8;
+pub const EOI: u8 = 0xD9;
+pub const SOS: u8 = 0xDA;
+pub const DQT: u8 = 0xDB;
+pub const DRI: u8 = 0xDD;
+pub const DHT: u8 = 0xC4;
+pub const COM: u8 = 0xFE;
+pub const APP0: u8 = 0xE0;
+pub const APP1: u8 = 0xE1;
+pub const APP2: u8 = 0xE2;
+
+const EXIF_PREFIX: &[u8] = b"Exif\0\0";
+const XMP_PREFIX: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const ICC_PREFIX: &[u8] = b"ICC_PROFILE\0";
+
+#[derive(Debug, Clone)]
+pub struct InvalidJPEGFormat {
+    message: String,
+}
+
+impl InvalidJPEGFormat {
+    pub(crate) fn new(message: &str) -> InvalidJPEGFormat {
+        InvalidJPEGFormat {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for InvalidJPEGFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid JPEG file: {}", self.message)
+    }
+}
+
+impl error::Error for InvalidJPEGFormat {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+// Every segment between SOI and EOI, in file order. Neither SOI nor EOI is stored, since every
+// file has exactly one of each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JPEGFile {
+    segments: Vec<JPEGSegment>,
+}
+
+// Two segments are equal when their marker, payload and scan data are, wherever they were read
+// from.
+#[derive(Debug, Clone)]
+pub struct JPEGSegment {
+    marker: u8,
+    // The payload after the length field, empty for markers without one.
+    data: Vec<u8>,
+    // The entropy-coded data after an SOS segment, empty for every other marker.
+    scan_data: Vec<u8>,
+    // Byte offset of the segment's 0xFF byte in the file it was read from. None for segments
+    // made in memory.
+    offset: Option<u64>,
+}
+
+// SOFn segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameHeader {
+    // The SOF marker, which says how the image is coded: 0xC0 baseline, 0xC1 extended
+    // sequential, 0xC2 progressive and 0xC3 lossless, with 0xC9 - 0xCB their arithmetic coded
+    // versions.
+    pub marker: u8,
+    // Bits per sample, 8 for almost every file.
+    pub precision: u8,
+    pub height: u16,
+    pub width: u16,
+    pub components: Vec<FrameComponent>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameComponent {
+    pub id: u8,
+    // Sampling factors, from 1 to 4. A component sampled less than the largest factor has
+    // fewer samples than the image has pixels, such as the chroma of 4:2:0 files.
+    pub horizontal_sampling: u8,
+    pub vertical_sampling: u8,
+    // Which quantization table the component's coefficients use.
+    pub quantization_table: u8,
+}
+
+// SOS segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanHeader {
+    pub components: Vec<ScanComponent>,
+    // The range of coefficients, 0 - 63 in zigzag order, and the successive approximation bit
+    // positions the scan codes. Sequential files always use 0, 63, 0 and 0.
+    pub spectral_start: u8,
+    pub spectral_end: u8,
+    pub approximation_high: u8,
+    pub approximation_low: u8,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScanComponent {
+    pub id: u8,
+    pub dc_table: u8,
+    pub ac_table: u8,
+}
+
+impl JPEGFile {
+    pub fn from_file(filename: &str) -> Result<JPEGFile, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        File::open(filename)?.read_to_end(&mut bytes)?;
+        JPEGFile::from_bytes(&bytes)
+    }
+
+    // Parses a JPEG held in memory. Anything after EOI is ignored.
+    pub fn from_bytes(bytes: &[u8]) -> Result<JPEGFile, Box<dyn Error>> {
+        if bytes.get(..2) != Some(&[0xFF, SOI]) {
+            return Err(InvalidJPEGFormat::new("The file does not start with SOI.").into());
+        }
+        let mut segments = Vec::new();
+        let mut at = 2;
+        loop {
+            if bytes.get(at) != Some(&0xFF) {
+                let message = if at >= bytes.len() {
+                    "The file ended before EOI."
+                } else {
+                    "Expected a marker between segments."
+                };
+                return Err(InvalidJPEGFormat::new(message).into());
+            }
+            let offset = at;
+            // Any number of 0xFF fill bytes may come before a marker.
+            while bytes.get(at) == Some(&0xFF) {
+                at += 1;
+            }
+            let marker = *bytes
+                .get(at)
+                .ok_or_else(|| InvalidJPEGFormat::new("The file ended before EOI."))?;
+            at += 1;
+            if marker == EOI {
+                break;
+            }
+            let mut segment = JPEGSegment {
+                marker,
+                data: Vec::new(),
+                scan_data: Vec::new(),
+                offset: Some(offset as u64),
+            };
+            if has_length(marker) {
+                let length = match bytes.get(at..at + 2) {
+                    Some(b) => u16::from_be_bytes([b[0], b[1]]) as usize,
+                    None => {
+                        return Err(InvalidJPEGFormat::new("The file ended in a segment.").into())
+                    }
+                };
+                if length < 2 {
+                    return Err(InvalidJPEGFormat::new("A segment length is below 2.").into());
+                }
+                segment.data = bytes
+                    .get(at + 2..at + length)
+                    .ok_or_else(|| InvalidJPEGFormat::new("The file ended in a segment."))?
+                    .to_vec();
+                at += length;
+            }
+            if marker == SOS {
+                let end = scan_end(bytes, at);
+                segment.scan_data = bytes[at..end].to_vec();
+                at = end;
+            }
+            segments.push(segment);
+        }
+        Ok(JPEGFile { segments })
+    }
+
+    // Assembles a file from segments in file order. SOI and EOI are added when writing, so any
+    // in the list are dropped.
+    pub fn from_segments(segments: Vec<JPEGSegment>) -> JPEGFile {
+        JPEGFile {
+            segments: segments
+                .into_iter()
+                .filter(|s| s.marker != SOI && s.marker != EOI)
+                .collect(),
+        }
+    }
+
+    pub fn segments(&self) -> &[JPEGSegment] {
+        &self.segments
+    }
+
+    // The segments with the given marker, in file order.
+    pub fn segments_with_marker(&self, marker: u8) -> impl Iterator<Item = &JPEGSegment> {
+        self.segments.iter().filter(move |s| s.marker == marker)
+    }
+
+    pub fn first_segment(&self, marker: u8) -> Option<&JPEGSegment> {
+        self.segments_with_marker(marker).next()
+    }
+
+    // The header of the first frame. Files without one hold no image.
+    pub fn frame_header(&self) -> Result<FrameHeader, Box<dyn Error>> {
+        let segment = self
+            .segments
+            .iter()
+            .find(|s| is_sof(s.marker))
+            .ok_or_else(|| InvalidJPEGFormat::new("There is no SOF segment."))?;
+        FrameHeader::parse(segment)
+    }
+
+    pub fn width(&self) -> Result<u32, Box<dyn Error>> {
+        Ok(self.frame_header()?.width as u32)
+    }
+
+    pub fn height(&self) -> Result<u32, Box<dyn Error>> {
+        Ok(self.frame_header()?.height as u32)
+    }
+
+    // Width and height in pixels, from the frame header. A height of 0 means it is given by a
+    // DNL segment after the first scan, which this crate doesn't support.
+    pub fn dimensions(&self) -> Result<(u32, u32), Box<dyn Error>> {
+        let frame = self.frame_header()?;
+        Ok((frame.width as u32, frame.height as u32))
+    }
+
+    pub fn is_progressive(&self) -> bool {
+        self.segments
+            .iter()
+            .any(|s| matches!(s.marker, 0xC2 | 0xC6 | 0xCA | 0xCE))
+    }
+
+    // The EXIF data of the first APP1 segment holding any, without the "Exif\0\0" prefix, as
+    // ExifData::parse and a PNG eXIf chunk take it.
+    pub fn exif_data(&self) -> Option<&[u8]> {
+        self.segments_with_marker(APP1)
+            .find_map(|s| s.data.strip_prefix(EXIF_PREFIX))
+    }
+
+    // The XMP packet of the first APP1 segment holding one.
+    pub fn xmp(&self) -> Option<&[u8]> {
+        self.segments_with_marker(APP1)
+            .find_map(|s| s.data.strip_prefix(XMP_PREFIX))
+    }
+
+    // The embedded ICC profile. Profiles too big for one segment are split over several APP2
+    // segments, each numbered, and are put back together here.
+    pub fn icc_profile(&self) -> Option<Vec<u8>> {
+        let mut parts: Vec<(u8, &[u8])> = self
+            .segments_with_marker(APP2)
+            .filter_map(|s| s.data.strip_prefix(ICC_PREFIX))
+            .filter(|d| d.len() >= 2)
+            .map(|d| (d[0], &d[2..]))
+            .collect();
+        if parts.is_empty() {
+            return None;
+        }
+        parts.sort_by_key(|(number, _)| *number);
+        Some(parts.iter().flat_map(|(_, d)| d.iter().copied()).collect())
+    }
+
+    // The text of every COM segment, in file order.
+    pub fn comments(&self) -> Vec<String> {
+        self.segments_with_marker(COM)
+            .map(|s| String::from_utf8_lossy(&s.data).into_owned())
+            .collect()
+    }
+
+    pub fn write(&self, filename: &str) -> Result<(), Box<dyn Error>> {
+        let mut file = File::create(filename)?;
+        self.write_to(&mut file)
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Box<dyn Error>> {
+        writer.write_all(&self.to_bytes()?)?;
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut bytes = vec![0xFF, SOI];
+        for segment in &self.segments {
+            bytes.extend_from_slice(&[0xFF, segment.marker]);
+            if has_length(segment.marker) {
+                let length = u16::try_from(segment.data.len() + 2)
+                    .map_err(|_| InvalidJPEGFormat::new("A segment is too long to write."))?;
+                bytes.extend_from_slice(&length.to_be_bytes());
+                bytes.extend_from_slice(&segment.data);
+            }
+            bytes.extend_from_slice(&segment.scan_data);
+        }
+        bytes.extend_from_slice(&[0xFF, EOI]);
+        Ok(bytes)
+    }
+}
+
+impl JPEGSegment {
+    pub fn new(marker: u8, data: Vec<u8>) -> JPEGSegment {
+        JPEGSegment {
+            marker,
+            data,
+            scan_data: Vec::new(),
+            offset: None,
+        }
+    }
+
+    // An SOS segment followed by entropy-coded data.
+    pub fn new_scan(data: Vec<u8>, scan_data: Vec<u8>) -> JPEGSegment {
+        JPEGSegment {
+            marker: SOS,
+            data,
+            scan_data,
+            offset: None,
+        }
+    }
+
+    pub fn marker(&self) -> u8 {
+        self.marker
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn scan_data(&self) -> &[u8] {
+        &self.scan_data
+    }
+
+    pub fn offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    // The marker's name from the spec, such as "SOF0", "DHT" or "APP1".
+    pub fn name(&self) -> String {
+        match self.marker {
+            0xC4 => "DHT".to_string(),
+            0xC8 => "JPG".to_string(),
+            0xCC => "DAC".to_string(),
+            m @ 0xC0..=0xCF => format!("SOF{}", m - 0xC0),
+            m @ 0xD0..=0xD7 => format!("RST{}", m - 0xD0),
+            SOI => "SOI".to_string(),
+            EOI => "EOI".to_string(),
+            SOS => "SOS".to_string(),
+            DQT => "DQT".to_string(),
+            0xDC => "DNL".to_string(),
+            DRI => "DRI".to_string(),
+            m @ 0xE0..=0xEF => format!("APP{}", m - 0xE0),
+            COM => "COM".to_string(),
+            m => format!("0x{:02X}", m),
+        }
+    }
+}
+
+impl PartialEq for JPEGSegment {
+    fn eq(&self, other: &JPEGSegment) -> bool {
+        self.marker == other.marker && self.data == other.data && self.scan_data == other.scan_data
+    }
+}
+
+impl Eq for JPEGSegment {}
+
+impl fmt::Display for JPEGSegment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {} bytes", self.name(), self.data.len())?;
+        if !self.scan_data.is_empty() {
+            write!(f, " + {} bytes of scan data", self.scan_data.len())?;
+        }
+        Ok(())
+    }
+}
+
+impl FrameHeader {
+    pub fn parse(segment: &JPEGSegment) -> Result<FrameHeader, Box<dyn Error>> {
+        let data = &segment.data;
+        if !is_sof(segment.marker) {
+            return Err(InvalidJPEGFormat::new("The segment is not a frame header.").into());
+        }
+        let count = *data
+            .get(5)
+            .ok_or_else(|| InvalidJPEGFormat::new("The frame header is too short."))?
+            as usize;
+        if count == 0 || data.len() < 6 + count * 3 {
+            return Err(InvalidJPEGFormat::new("The frame header is too short.").into());
+        }
+        let components = data[6..6 + count * 3]
+            .chunks_exact(3)
+            .map(|c| FrameComponent {
+                id: c[0],
+                horizontal_sampling: c[1] >> 4,
+                vertical_sampling: c[1] & 0x0F,
+                quantization_table: c[2],
+            })
+            .collect();
+        Ok(FrameHeader {
+            marker: segment.marker,
+            precision: data[0],
+            height: u16::from_be_bytes([data[1], data[2]]),
+            width: u16::from_be_bytes([data[3], data[4]]),
+            components,
+        })
+    }
+}
+
+impl ScanHeader {
+    pub fn parse(segment: &JPEGSegment) -> Result<ScanHeader, Box<dyn Error>> {
+        let data = &segment.data;
+        if segment.marker != SOS {
+            return Err(InvalidJPEGFormat::new("The segment is not a scan header.").into());
+        }
+        let count = *data
+            .first()
+            .ok_or_else(|| InvalidJPEGFormat::new("The scan header is too short."))?
+            as usize;
+        if count == 0 || data.len() < 4 + count * 2 {
+            return Err(InvalidJPEGFormat::new("The scan header is too short.").into());
+        }
+        let components = data[1..1 + count * 2]
+            .chunks_exact(2)
+            .map(|c| ScanComponent {
+                id: c[0],
+                dc_table: c[1] >> 4,
+                ac_table: c[1] & 0x0F,
+            })
+            .collect();
+        let rest = &data[1 + count * 2..];
+        Ok(ScanHeader {
+            components,
+            spectral_start: rest[0],
+            spectral_end: rest[1],
+            approximation_high: rest[2] >> 4,
+            approximation_low: rest[2] & 0x0F,
+        })
+    }
+}
+
+// Whether the marker is a start of frame. 0xC4, 0xC8 and 0xCC fall in the same range but
+// mean something else.
+pub(crate) fn is_sof(marker: u8) -> bool {
+    matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC)
+}
+
+// Whether a length and payload follow the marker. SOI, EOI, the restart markers and TEM stand
+// alone.
+fn has_length(marker: u8) -> bool {
+    !matches!(marker, SOI | EOI | 0xD0..=0xD7 | 0x01)
+}
+
+// Where the entropy-coded data starting at start ends: at the first marker other than a
+// restart marker. 0xFF bytes in the data itself are followed by a 0x00 stuffing byte.
+fn scan_end(bytes: &[u8], start: usize) -> usize {
+    let mut at = start;
+    while at + 1 < bytes.len() {
+        if bytes[at] == 0xFF && !matches!(bytes[at + 1], 0x00 | 0xD0..=0xD7) {
+            // A run of fill bytes before the marker belongs to the marker.
+            return at;
+        }
+        at += 1;
+    }
+    bytes.len()
+}
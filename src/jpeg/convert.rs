@@ -0,0 +1,61 @@
+// Converting JPEG files to PNG. The pixels are decoded and encoded again without loss, and the
+// color space travels with them: an embedded ICC profile that fits the decoded pixels becomes an
+// iCCP chunk, and anything else is marked sRGB, which is what viewers assume for untagged JPEGs.
+use std::error::Error;
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+
+use super::JPEGFile;
+use crate::exif::ExifData;
+use crate::image::ColorType;
+use crate::png::{PNGChunk, PNGFile, PNGFileBuilder};
+
+// Name given to the profile in the iCCP chunk. JPEG files don't name their profiles.
+const ICC_PROFILE_NAME: &[u8] = b"ICC Profile";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PngConversionOptions {
+    // Copies the EXIF data into an eXIf chunk. EXIF often holds the camera's serial number and
+    // where the photo was taken, so it is left out unless asked for. Without it the EXIF
+    // orientation is applied to the pixels instead, so the picture still shows the right way up.
+    pub exif: bool,
+}
+
+impl JPEGFile {
+    pub fn to_png(&self, options: &PngConversionOptions) -> Result<PNGFile, Box<dyn Error>> {
+        let mut image = self.decode()?;
+        let exif = self.exif_data();
+        if !options.exif {
+            let orientation = exif
+                .and_then(|data| ExifData::parse(data).ok())
+                .and_then(|exif| exif.orientation());
+            if let Some(orientation) = orientation {
+                image = image.apply_orientation(orientation);
+            }
+        }
+
+        let mut builder = PNGFileBuilder::new(image.width(), image.height(), image.color_type())
+            .with_image(&image);
+        // CMYK profiles describe the colors before they were turned into RGB, so they can't be
+        // kept.
+        let color_space: &[u8] = match image.color_type() {
+            ColorType::Grayscale => b"GRAY",
+            _ => b"RGB ",
+        };
+        builder = match self.icc_profile() {
+            Some(profile) if profile.get(16..20) == Some(color_space) => {
+                let mut data = ICC_PROFILE_NAME.to_vec();
+                // The name's terminator, then the compression method.
+                data.extend_from_slice(&[0, 0]);
+                data.extend(compress_to_vec_zlib(&profile, 9));
+                builder.with_chunk(PNGChunk::new(*b"iCCP", data))
+            }
+            // Perceptual rendering intent.
+            _ => builder.with_chunk(PNGChunk::new(*b"sRGB", vec![0])),
+        };
+        if let (true, Some(exif)) = (options.exif, exif) {
+            builder = builder.with_chunk(PNGChunk::new(*b"eXIf", exif.to_vec()));
+        }
+        builder.build()
+    }
+}
@@ -0,0 +1,714 @@
+use std::error;
+use std::error::Error;
+use std::fmt;
+
+use super::{FrameHeader, JPEGFile, ScanHeader, APP0, DHT, DQT, DRI, SOS};
+use crate::image::{ColorType, Image};
+
+// Natural, row by row, position of each coefficient in zigzag order, the order they are stored.
+pub(crate) const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+const APP14: u8 = APP0 + 14;
+
+// Largest frame decode will allocate planes for, 16384 x 16384 pixels, as for PNG. SOF allows
+// 65535 x 65535, which a file of a few hundred bytes can claim.
+const MAX_IMAGE_PIXELS: u64 = 1 << 28;
+
+// The largest magnitude categories 8-bit samples produce. Anything bigger comes from a corrupt
+// Huffman table.
+const MAX_DC_SIZE: u8 = 11;
+const MAX_AC_SIZE: u8 = 10;
+
+#[derive(Debug, Clone)]
+pub struct JPEGDecodeError {
+    message: String,
+}
+
+impl JPEGDecodeError {
+    pub(crate) fn new(message: &str) -> JPEGDecodeError {
+        JPEGDecodeError {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for JPEGDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not decode JPEG data: {}", self.message)
+    }
+}
+
+impl error::Error for JPEGDecodeError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+// A Huffman table in the form the spec's decoding procedure uses: for each code length, the
+// largest code of that length and where its codes start among the values.
+#[derive(Debug, Clone)]
+struct HuffmanTable {
+    max_code: [i32; 17],
+    min_code: [i32; 17],
+    first_value: [usize; 17],
+    values: Vec<u8>,
+}
+
+impl HuffmanTable {
+    fn new(counts: &[u8], values: &[u8]) -> HuffmanTable {
+        let mut table = HuffmanTable {
+            max_code: [-1; 17],
+            min_code: [0; 17],
+            first_value: [0; 17],
+            values: values.to_vec(),
+        };
+        let (mut code, mut index) = (0i32, 0usize);
+        for length in 1..=16 {
+            let count = counts[length - 1] as usize;
+            if count > 0 {
+                table.first_value[length] = index;
+                table.min_code[length] = code;
+                code += count as i32;
+                index += count;
+                table.max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        table
+    }
+}
+
+// Reads the entropy-coded data of a scan a bit at a time, skipping the 0x00 stuffed after every
+// 0xFF byte. At a marker it yields zero bits until restart moves past it.
+struct BitReader<'a> {
+    data: &'a [u8],
+    at: usize,
+    bits: u32,
+    count: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data,
+            at: 0,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    fn bit(&mut self) -> u32 {
+        if self.count == 0 {
+            let byte = match self.data.get(self.at) {
+                Some(0xFF) if self.data.get(self.at + 1) == Some(&0x00) => {
+                    self.at += 2;
+                    0xFF
+                }
+                // A marker, or the end of the data.
+                Some(0xFF) | None => 0,
+                Some(byte) => {
+                    self.at += 1;
+                    *byte
+                }
+            };
+            self.bits = byte as u32;
+            self.count = 8;
+        }
+        self.count -= 1;
+        (self.bits >> self.count) & 1
+    }
+
+    fn bits(&mut self, count: u8) -> u32 {
+        (0..count).fold(0, |value, _| (value << 1) | self.bit())
+    }
+
+    fn decode(&mut self, table: &HuffmanTable) -> Result<u8, JPEGDecodeError> {
+        let mut code = 0i32;
+        for length in 1..=16 {
+            code = (code << 1) | self.bit() as i32;
+            if code <= table.max_code[length] {
+                let index = table.first_value[length] + (code - table.min_code[length]) as usize;
+                return table
+                    .values
+                    .get(index)
+                    .copied()
+                    .ok_or_else(|| JPEGDecodeError::new("A Huffman table is incomplete."));
+            }
+        }
+        Err(JPEGDecodeError::new(
+            "The scan data holds an invalid Huffman code.",
+        ))
+    }
+
+    // A value coded in count bits, where values with the top bit clear are negative.
+    fn signed(&mut self, count: u8) -> i32 {
+        if count == 0 {
+            return 0;
+        }
+        let value = self.bits(count) as i32;
+        if value < 1 << (count - 1) {
+            value - (1 << count) + 1
+        } else {
+            value
+        }
+    }
+
+    // Drops the bits left in the current byte and moves past the restart marker that follows.
+    fn restart(&mut self) {
+        self.count = 0;
+        while self.at < self.data.len() && self.data[self.at] != 0xFF {
+            self.at += 1;
+        }
+        while self.data.get(self.at) == Some(&0xFF) {
+            self.at += 1;
+        }
+        if matches!(self.data.get(self.at), Some(0xD0..=0xD7)) {
+            self.at += 1;
+        }
+    }
+}
+
+// One component's decoded samples, padded out to whole MCUs.
+struct Plane {
+    id: u8,
+    horizontal_sampling: usize,
+    vertical_sampling: usize,
+    quantization_table: usize,
+    // Width of the plane in blocks.
+    blocks_across: usize,
+    samples: Vec<u8>,
+}
+
+impl JPEGFile {
+    // Decodes a baseline or extended sequential, Huffman coded file with 8-bit samples, which
+    // covers what cameras and most encoders write. Gray files decode to 8-bit grayscale and
+    // color ones to 8-bit truecolor. Progressive, arithmetic coded, lossless and 12-bit files
+    // are rejected.
+    pub fn decode(&self) -> Result<Image, Box<dyn Error>> {
+        let frame = self.frame_header()?;
+        match frame.marker {
+            0xC0 | 0xC1 => {}
+            0xC2 | 0xC6 => {
+                return Err(JPEGDecodeError::new("Progressive JPEGs are not supported.").into())
+            }
+            _ => {
+                return Err(JPEGDecodeError::new(
+                    "Only Huffman coded sequential JPEGs are supported.",
+                )
+                .into())
+            }
+        }
+        if frame.precision != 8 {
+            return Err(JPEGDecodeError::new("Only 8-bit samples are supported.").into());
+        }
+        if frame.width == 0 || frame.height == 0 {
+            return Err(JPEGDecodeError::new("The frame has no pixels.").into());
+        }
+        if frame.width as u64 * frame.height as u64 > MAX_IMAGE_PIXELS {
+            return Err(JPEGDecodeError::new("The frame is too large to decode.").into());
+        }
+        if !matches!(frame.components.len(), 1 | 3 | 4) {
+            return Err(JPEGDecodeError::new("Only 1, 3 and 4 components are supported.").into());
+        }
+
+        let max_h = frame
+            .components
+            .iter()
+            .map(|c| c.horizontal_sampling)
+            .max()
+            .unwrap_or(1);
+        let max_v = frame
+            .components
+            .iter()
+            .map(|c| c.vertical_sampling)
+            .max()
+            .unwrap_or(1);
+        if frame.components.iter().any(|c| {
+            !(1..=4).contains(&c.horizontal_sampling) || !(1..=4).contains(&c.vertical_sampling)
+        }) {
+            return Err(JPEGDecodeError::new("A sampling factor is out of range.").into());
+        }
+        let (max_h, max_v) = (max_h as usize, max_v as usize);
+        let mcus_across = (frame.width as usize).div_ceil(8 * max_h);
+        let mcus_down = (frame.height as usize).div_ceil(8 * max_v);
+        let mut planes: Vec<Plane> = frame
+            .components
+            .iter()
+            .map(|c| {
+                let (h, v) = (c.horizontal_sampling as usize, c.vertical_sampling as usize);
+                let blocks_across = mcus_across * h;
+                let blocks_down = mcus_down * v;
+                Plane {
+                    id: c.id,
+                    horizontal_sampling: h,
+                    vertical_sampling: v,
+                    quantization_table: c.quantization_table as usize,
+                    blocks_across,
+                    samples: vec![0; blocks_across * blocks_down * 64],
+                }
+            })
+            .collect();
+
+        // Tables may be redefined between scans, so segments are handled in file order.
+        let mut quantization: [Option<[u16; 64]>; 4] = [None; 4];
+        let mut dc_tables: [Option<HuffmanTable>; 4] = Default::default();
+        let mut ac_tables: [Option<HuffmanTable>; 4] = Default::default();
+        let mut restart_interval = 0;
+        for segment in self.segments() {
+            match segment.marker {
+                DQT => read_quantization_tables(segment.data(), &mut quantization)?,
+                DHT => read_huffman_tables(segment.data(), &mut dc_tables, &mut ac_tables)?,
+                DRI => {
+                    let data = segment.data();
+                    if data.len() < 2 {
+                        return Err(JPEGDecodeError::new("The DRI segment is too short.").into());
+                    }
+                    restart_interval = u16::from_be_bytes([data[0], data[1]]) as usize;
+                }
+                SOS => {
+                    let scan = ScanHeader::parse(segment)?;
+                    let context = ScanContext {
+                        frame: &frame,
+                        quantization: &quantization,
+                        dc_tables: &dc_tables,
+                        ac_tables: &ac_tables,
+                        restart_interval,
+                        mcus: (mcus_across, mcus_down),
+                        max_sampling: (max_h, max_v),
+                    };
+                    decode_scan(&context, &scan, segment.scan_data(), &mut planes)?;
+                }
+                _ => {}
+            }
+        }
+
+        self.assemble(&frame, &planes, (max_h, max_v))
+    }
+
+    // Upsamples the planes to full size and converts them to gray or RGB.
+    fn assemble(
+        &self,
+        frame: &FrameHeader,
+        planes: &[Plane],
+        (max_h, max_v): (usize, usize),
+    ) -> Result<Image, Box<dyn Error>> {
+        let (width, height) = (frame.width as usize, frame.height as usize);
+        let sample = |plane: &Plane, x: usize, y: usize| {
+            let px = x * plane.horizontal_sampling / max_h;
+            let py = y * plane.vertical_sampling / max_v;
+            plane.samples[py * plane.blocks_across * 8 + px] as f32
+        };
+        // The Adobe APP14 segment says whether color components were transformed to YCbCr.
+        let adobe_transform = self
+            .segments_with_marker(APP14)
+            .find(|s| s.data().starts_with(b"Adobe") && s.data().len() >= 12)
+            .map(|s| s.data()[11]);
+
+        let clamp = |v: f32| v.round().clamp(0.0, 255.0) as u16;
+        let ycc_to_rgb = |y: f32, cb: f32, cr: f32| {
+            [
+                y + 1.402 * (cr - 128.0),
+                y - 0.344_136 * (cb - 128.0) - 0.714_136 * (cr - 128.0),
+                y + 1.772 * (cb - 128.0),
+            ]
+        };
+        let mut samples = Vec::with_capacity(width * height * 3);
+        if planes.len() == 1 {
+            for y in 0..height {
+                for x in 0..width {
+                    samples.push(sample(&planes[0], x, y) as u16);
+                }
+            }
+            return Image::from_samples(
+                width as u32,
+                height as u32,
+                ColorType::Grayscale,
+                8,
+                samples,
+            );
+        }
+
+        let ids: Vec<u8> = planes.iter().map(|p| p.id).collect();
+        let is_rgb = match adobe_transform {
+            Some(transform) => transform == 0,
+            None => ids == b"RGB",
+        };
+        for y in 0..height {
+            for x in 0..width {
+                let values: Vec<f32> = planes.iter().map(|p| sample(p, x, y)).collect();
+                let rgb = if planes.len() == 3 {
+                    if is_rgb {
+                        [values[0], values[1], values[2]]
+                    } else {
+                        ycc_to_rgb(values[0], values[1], values[2])
+                    }
+                } else {
+                    // Four components are CMYK, or YCCK when the Adobe transform is 2, stored
+                    // inverted the way Adobe's software writes them.
+                    let cmy = if adobe_transform == Some(2) {
+                        let [r, g, b] = ycc_to_rgb(values[0], values[1], values[2]);
+                        [255.0 - r, 255.0 - g, 255.0 - b]
+                    } else {
+                        [values[0], values[1], values[2]]
+                    };
+                    let k = values[3] / 255.0;
+                    [cmy[0] * k, cmy[1] * k, cmy[2] * k]
+                };
+                samples.extend(rgb.iter().map(|v| clamp(*v)));
+            }
+        }
+        Image::from_samples(
+            width as u32,
+            height as u32,
+            ColorType::Truecolor,
+            8,
+            samples,
+        )
+    }
+}
+
+struct ScanContext<'a> {
+    frame: &'a FrameHeader,
+    quantization: &'a [Option<[u16; 64]>; 4],
+    dc_tables: &'a [Option<HuffmanTable>; 4],
+    ac_tables: &'a [Option<HuffmanTable>; 4],
+    restart_interval: usize,
+    mcus: (usize, usize),
+    max_sampling: (usize, usize),
+}
+
+// A scan component resolved to what decoding its blocks needs.
+struct ScanPlane<'a> {
+    plane: usize,
+    dc_table: &'a HuffmanTable,
+    ac_table: &'a HuffmanTable,
+    quantization: &'a [u16; 64],
+    predictor: i32,
+}
+
+fn decode_scan(
+    context: &ScanContext,
+    scan: &ScanHeader,
+    data: &[u8],
+    planes: &mut [Plane],
+) -> Result<(), Box<dyn Error>> {
+    let mut components = Vec::with_capacity(scan.components.len());
+    for component in &scan.components {
+        let plane = planes
+            .iter()
+            .position(|p| p.id == component.id)
+            .ok_or_else(|| JPEGDecodeError::new("A scan uses a component not in the frame."))?;
+        let quantization = context
+            .quantization
+            .get(planes[plane].quantization_table)
+            .and_then(|q| q.as_ref())
+            .ok_or_else(|| {
+                JPEGDecodeError::new("A component uses an undefined quantization table.")
+            })?;
+        components.push(ScanPlane {
+            plane,
+            dc_table: huffman_table(context.dc_tables, component.dc_table)?,
+            ac_table: huffman_table(context.ac_tables, component.ac_table)?,
+            quantization,
+            predictor: 0,
+        });
+    }
+
+    let mut reader = BitReader::new(data);
+    let mut decoded = 0;
+    let mut next_mcu = |reader: &mut BitReader, components: &mut [ScanPlane]| {
+        if context.restart_interval > 0 && decoded > 0 && decoded % context.restart_interval == 0 {
+            reader.restart();
+            for component in components.iter_mut() {
+                component.predictor = 0;
+            }
+        }
+        decoded += 1;
+    };
+
+    if components.len() == 1 {
+        // A single component scan codes its blocks one by one, covering only the component's
+        // own size rather than whole MCUs.
+        let component = &mut components[0];
+        let plane = &planes[component.plane];
+        let (max_h, max_v) = context.max_sampling;
+        let width = (context.frame.width as usize * plane.horizontal_sampling).div_ceil(max_h);
+        let height = (context.frame.height as usize * plane.vertical_sampling).div_ceil(max_v);
+        for by in 0..height.div_ceil(8) {
+            for bx in 0..width.div_ceil(8) {
+                next_mcu(&mut reader, std::slice::from_mut(component));
+                decode_block(&mut reader, component, &mut planes[component.plane], bx, by)?;
+            }
+        }
+        return Ok(());
+    }
+
+    let (mcus_across, mcus_down) = context.mcus;
+    for my in 0..mcus_down {
+        for mx in 0..mcus_across {
+            next_mcu(&mut reader, &mut components);
+            for component in components.iter_mut() {
+                let plane = &mut planes[component.plane];
+                let (h, v) = (plane.horizontal_sampling, plane.vertical_sampling);
+                for by in 0..v {
+                    for bx in 0..h {
+                        decode_block(&mut reader, component, plane, mx * h + bx, my * v + by)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn huffman_table(
+    tables: &[Option<HuffmanTable>; 4],
+    id: u8,
+) -> Result<&HuffmanTable, JPEGDecodeError> {
+    tables
+        .get(id as usize)
+        .and_then(|t| t.as_ref())
+        .ok_or_else(|| JPEGDecodeError::new("A scan uses an undefined Huffman table."))
+}
+
+// Decodes one 8x8 block and writes its samples into the plane at block column bx, row by.
+fn decode_block(
+    reader: &mut BitReader,
+    component: &mut ScanPlane,
+    plane: &mut Plane,
+    bx: usize,
+    by: usize,
+) -> Result<(), JPEGDecodeError> {
+    let mut coefficients = [0f32; 64];
+    let size = reader.decode(component.dc_table)?;
+    if size > MAX_DC_SIZE {
+        return Err(JPEGDecodeError::new("A DC difference is out of range."));
+    }
+    component.predictor = component.predictor.wrapping_add(reader.signed(size));
+    coefficients[0] = component.predictor as f32 * component.quantization[0] as f32;
+    let mut k = 1;
+    while k < 64 {
+        let symbol = reader.decode(component.ac_table)?;
+        let (run, size) = ((symbol >> 4) as usize, symbol & 0x0F);
+        if size == 0 {
+            if run == 15 {
+                k += 16;
+                continue;
+            }
+            break;
+        }
+        if size > MAX_AC_SIZE {
+            return Err(JPEGDecodeError::new("An AC coefficient is out of range."));
+        }
+        k += run;
+        if k > 63 {
+            return Err(JPEGDecodeError::new(
+                "A block has more than 64 coefficients.",
+            ));
+        }
+        coefficients[ZIGZAG[k]] = reader.signed(size) as f32 * component.quantization[k] as f32;
+        k += 1;
+    }
+
+    let pixels = inverse_dct(&coefficients);
+    let stride = plane.blocks_across * 8;
+    let origin = by * 8 * stride + bx * 8;
+    if origin + 7 * stride + 8 > plane.samples.len() {
+        return Ok(());
+    }
+    for y in 0..8 {
+        for x in 0..8 {
+            plane.samples[origin + y * stride + x] =
+                (pixels[y * 8 + x] + 128.0).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+    Ok(())
+}
+
+// cos((2x + 1)u pi / 16) scaled by C(u) / 2, where C(0) is 1 / sqrt(2) and 1 otherwise, indexed
+// by x * 8 + u.
+pub(crate) fn dct_basis() -> [f32; 64] {
+    let mut basis = [0f32; 64];
+    for x in 0..8 {
+        for u in 0..8 {
+            let scale = if u == 0 {
+                std::f32::consts::FRAC_1_SQRT_2
+            } else {
+                1.0
+            };
+            let angle = (2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0;
+            basis[x * 8 + u] = scale * angle.cos() / 2.0;
+        }
+    }
+    basis
+}
+
+// Turns coefficients in natural order into samples centered on 0, as rows then columns.
+pub(crate) fn inverse_dct(coefficients: &[f32; 64]) -> [f32; 64] {
+    let basis = dct_basis();
+    let mut rows = [0f32; 64];
+    for v in 0..8 {
+        for x in 0..8 {
+            rows[v * 8 + x] = (0..8)
+                .map(|u| coefficients[v * 8 + u] * basis[x * 8 + u])
+                .sum();
+        }
+    }
+    let mut out = [0f32; 64];
+    for y in 0..8 {
+        for x in 0..8 {
+            out[y * 8 + x] = (0..8).map(|v| rows[v * 8 + x] * basis[y * 8 + v]).sum();
+        }
+    }
+    out
+}
+
+fn read_quantization_tables(
+    mut data: &[u8],
+    tables: &mut [Option<[u16; 64]>; 4],
+) -> Result<(), JPEGDecodeError> {
+    while !data.is_empty() {
+        let (precision, id) = (data[0] >> 4, (data[0] & 0x0F) as usize);
+        let size = if precision == 0 { 64 } else { 128 };
+        if id > 3 || data.len() < 1 + size {
+            return Err(JPEGDecodeError::new("A DQT segment is invalid."));
+        }
+        let mut table = [0u16; 64];
+        for (k, value) in table.iter_mut().enumerate() {
+            *value = if precision == 0 {
+                data[1 + k] as u16
+            } else {
+                u16::from_be_bytes([data[1 + 2 * k], data[2 + 2 * k]])
+            };
+        }
+        tables[id] = Some(table);
+        data = &data[1 + size..];
+    }
+    Ok(())
+}
+
+fn read_huffman_tables(
+    mut data: &[u8],
+    dc_tables: &mut [Option<HuffmanTable>; 4],
+    ac_tables: &mut [Option<HuffmanTable>; 4],
+) -> Result<(), JPEGDecodeError> {
+    while !data.is_empty() {
+        let invalid = || JPEGDecodeError::new("A DHT segment is invalid.");
+        let (class, id) = (data[0] >> 4, (data[0] & 0x0F) as usize);
+        let counts = data.get(1..17).ok_or_else(invalid)?;
+        let total: usize = counts.iter().map(|c| *c as usize).sum();
+        let values = data.get(17..17 + total).ok_or_else(invalid)?;
+        if id > 3 || class > 1 {
+            return Err(invalid());
+        }
+        let table = Some(HuffmanTable::new(counts, values));
+        if class == 0 {
+            dc_tables[id] = table;
+        } else {
+            ac_tables[id] = table;
+        }
+        data = &data[17 + total..];
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::{EncodeOptions, JPEGSegment};
+
+    // Baseline frame header, which the encoder writes.
+    const SOF0: u8 = 0xC0;
+
+    // A smooth color gradient, which JPEG reproduces closely.
+    fn gradient(width: u32, height: u32) -> Image {
+        let mut samples = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                samples.push((x * 255 / (width - 1)) as u16);
+                samples.push((y * 255 / (height - 1)) as u16);
+                samples.push(128);
+            }
+        }
+        Image::from_samples(width, height, ColorType::Truecolor, 8, samples).unwrap()
+    }
+
+    fn encoded(image: &Image) -> JPEGFile {
+        let options = EncodeOptions {
+            quality: 95,
+            ..EncodeOptions::default()
+        };
+        JPEGFile::from_image(image, &options).unwrap()
+    }
+
+    // The file with each segment that has the marker passed through edit.
+    fn edited(file: &JPEGFile, marker: u8, edit: impl Fn(&mut Vec<u8>)) -> JPEGFile {
+        let segments = file
+            .segments()
+            .iter()
+            .map(|segment| {
+                let mut segment = segment.clone();
+                if segment.marker == marker {
+                    edit(&mut segment.data);
+                }
+                segment
+            })
+            .collect::<Vec<JPEGSegment>>();
+        JPEGFile::from_segments(segments)
+    }
+
+    fn message(err: &(dyn Error + 'static)) -> String {
+        err.downcast_ref::<JPEGDecodeError>().unwrap().to_string()
+    }
+
+    #[test]
+    fn baseline_round_trip() {
+        let image = gradient(37, 21);
+        let bytes = encoded(&image).to_bytes().unwrap();
+        let decoded = JPEGFile::from_bytes(&bytes).unwrap().decode().unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (37, 21));
+        assert_eq!(decoded.color_type(), ColorType::Truecolor);
+        assert!(image.psnr(&decoded).unwrap() > 35.0);
+
+        let gray = image.convert(ColorType::Grayscale).unwrap();
+        let decoded = encoded(&gray).decode().unwrap();
+        assert_eq!(decoded.color_type(), ColorType::Grayscale);
+        assert!(gray.psnr(&decoded).unwrap() > 35.0);
+    }
+
+    #[test]
+    fn oversized_dc_category() {
+        // Every DC code in every table now decodes to category 40, which would shift a 32-bit
+        // value out of range.
+        let corrupt = edited(&encoded(&gradient(16, 16)), DHT, |data| {
+            let mut at = 0;
+            while at < data.len() {
+                let total: usize = data[at + 1..at + 17].iter().map(|c| *c as usize).sum();
+                if data[at] >> 4 == 0 {
+                    data[at + 17..at + 17 + total].fill(40);
+                }
+                at += 17 + total;
+            }
+        });
+        let err = corrupt.decode().unwrap_err();
+        assert!(message(&*err).contains("DC difference"));
+    }
+
+    #[test]
+    fn oversized_frame() {
+        let huge = edited(&encoded(&gradient(16, 16)), SOF0, |data| {
+            data[1..5].copy_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF]);
+        });
+        assert_eq!(huge.dimensions().unwrap(), (65535, 65535));
+        let err = huge.decode().unwrap_err();
+        assert!(message(&*err).contains("too large"));
+    }
+}
@@ -0,0 +1,529 @@
+// A baseline JPEG encoder, for photos that would be unreasonably large as PNG. It writes a JFIF
+// file with the example quantization and Huffman tables from Annex K of the spec, the same ones
+// libjpeg starts from, so quality settings behave the way users of other tools expect.
+use std::error::Error;
+
+use super::decode::{dct_basis, ZIGZAG};
+use super::{JPEGFile, JPEGSegment, APP0, DHT, DQT};
+use crate::image::Image;
+
+// Quantization tables for quality 50, in natural order.
+const LUMA_QUANTIZATION: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113,
+    92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99,
+];
+const CHROMA_QUANTIZATION: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+// Huffman tables as stored in a DHT segment: the number of codes of each length from 1 to 16,
+// then the values in code order.
+const LUMA_DC_COUNTS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const CHROMA_DC_COUNTS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+const LUMA_AC_COUNTS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7D];
+const LUMA_AC_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xA1, 0x08, 0x23, 0x42, 0xB1, 0xC1, 0x15, 0x52, 0xD1, 0xF0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0A, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2A, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7,
+    0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3, 0xC4, 0xC5,
+    0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA, 0xE1, 0xE2,
+    0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+];
+const CHROMA_AC_COUNTS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const CHROMA_AC_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xA1, 0xB1, 0xC1, 0x09, 0x23, 0x33, 0x52, 0xF0,
+    0x15, 0x62, 0x72, 0xD1, 0x0A, 0x16, 0x24, 0x34, 0xE1, 0x25, 0xF1, 0x17, 0x18, 0x19, 0x1A, 0x26,
+    0x27, 0x28, 0x29, 0x2A, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5,
+    0xA6, 0xA7, 0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3,
+    0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA,
+    0xE2, 0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+];
+
+// Baseline sequential frames.
+const SOF0: u8 = 0xC0;
+
+// How much of the color detail is kept. The eye is far less sensitive to it than to
+// brightness, so halving it loses little and saves a lot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChromaSubsampling {
+    // 4:4:4, color at full resolution. Best for sharp colored edges such as text or diagrams.
+    Full,
+    // 4:2:0, color at half the resolution both ways.
+    #[default]
+    Half,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    // 1 to 100, with the same meaning as in libjpeg. Above 95 files grow quickly for little gain.
+    pub quality: u8,
+    pub subsampling: ChromaSubsampling,
+    // JPEG has no transparency, so transparent pixels are blended over this color.
+    pub background: [u8; 3],
+}
+
+impl Default for EncodeOptions {
+    fn default() -> EncodeOptions {
+        EncodeOptions {
+            quality: 85,
+            subsampling: ChromaSubsampling::default(),
+            background: [255, 255, 255],
+        }
+    }
+}
+
+// One component being encoded: its samples, padded by repeating the last row and column out
+// to whole MCUs, and the tables it uses.
+struct Component {
+    samples: Vec<f32>,
+    width: usize,
+    sampling: usize,
+    quantization: [u16; 64],
+    dc_codes: [(u16, u8); 256],
+    ac_codes: [(u16, u8); 256],
+    previous_dc: i32,
+}
+
+impl Component {
+    // Copies out block x, y centered on 0.
+    fn block(&self, x: usize, y: usize) -> [f32; 64] {
+        let mut block = [0f32; 64];
+        for (i, value) in block.iter_mut().enumerate() {
+            *value = self.samples[(y * 8 + i / 8) * self.width + x * 8 + i % 8] - 128.0;
+        }
+        block
+    }
+}
+
+// Writes entropy-coded data, stuffing a 0x00 after every 0xFF byte so it can't be mistaken for
+// a marker.
+struct BitWriter {
+    data: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u16, count: u8) {
+        for i in (0..count).rev() {
+            self.bits = (self.bits << 1) | ((value >> i) & 1) as u32;
+            self.count += 1;
+            if self.count == 8 {
+                self.data.push(self.bits as u8);
+                if self.bits == 0xFF {
+                    self.data.push(0x00);
+                }
+                self.bits = 0;
+                self.count = 0;
+            }
+        }
+    }
+
+    // Pads the last byte with 1 bits, as the spec asks.
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.write(0xFF, (8 - self.count) as u8);
+        }
+        self.data
+    }
+}
+
+impl JPEGFile {
+    // Encodes an image as a baseline JFIF file. Gray images become single-component files and
+    // everything else YCbCr. Samples are reduced to 8 bits and palettes resolved; the file is
+    // untagged, which viewers read as sRGB.
+    pub fn from_image(image: &Image, options: &EncodeOptions) -> Result<JPEGFile, Box<dyn Error>> {
+        if !(1..=100).contains(&options.quality) {
+            return Err("The quality must be from 1 to 100.".into());
+        }
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        if width == 0 || height == 0 || width > 65535 || height > 65535 {
+            return Err("JPEG images must be 1 to 65535 pixels wide and high.".into());
+        }
+
+        let gray = image.is_gray();
+        let background = options.background.map(|v| v as f32 / 255.0);
+        let count = if gray { 1 } else { 3 };
+        let mut planes = vec![Vec::with_capacity(width * height); count];
+        let channels = image.channels();
+        for i in 0..width * height {
+            let [r, g, b, a] = image.rgba_at(i * channels);
+            let over = |v: f32, back: f32| (v * a + back * (1.0 - a)) * 255.0;
+            let [r, g, b] = [
+                over(r, background[0]),
+                over(g, background[1]),
+                over(b, background[2]),
+            ];
+            // YCbCr as JFIF defines it, from full-range RGB. Gray files only have the Y.
+            planes[0].push(0.299 * r + 0.587 * g + 0.114 * b);
+            if !gray {
+                planes[1].push(-0.168_736 * r - 0.331_264 * g + 0.5 * b + 128.0);
+                planes[2].push(0.5 * r - 0.418_688 * g - 0.081_312 * b + 128.0);
+            }
+        }
+
+        let sampling = match options.subsampling {
+            ChromaSubsampling::Half if !gray => 2,
+            _ => 1,
+        };
+        let mcus_across = width.div_ceil(8 * sampling);
+        let mcus_down = height.div_ceil(8 * sampling);
+        let luma_quantization = scale_quantization(&LUMA_QUANTIZATION, options.quality);
+        let chroma_quantization = scale_quantization(&CHROMA_QUANTIZATION, options.quality);
+        let mut components: Vec<Component> = planes
+            .into_iter()
+            .enumerate()
+            .map(|(index, plane)| {
+                let (plane, plane_width, plane_height, component_sampling) = if index == 0 {
+                    (plane, width, height, sampling)
+                } else {
+                    let (w, h) = (width.div_ceil(sampling), height.div_ceil(sampling));
+                    (downsample(&plane, width, height, sampling), w, h, 1)
+                };
+                let padded_width = mcus_across * component_sampling * 8;
+                let padded_height = mcus_down * component_sampling * 8;
+                let mut samples = Vec::with_capacity(padded_width * padded_height);
+                for y in 0..padded_height {
+                    let row = &plane[y.min(plane_height - 1) * plane_width..][..plane_width];
+                    samples.extend((0..padded_width).map(|x| row[x.min(plane_width - 1)]));
+                }
+                let (quantization, dc, ac) = if index == 0 {
+                    (
+                        luma_quantization,
+                        (&LUMA_DC_COUNTS, &DC_VALUES[..]),
+                        (&LUMA_AC_COUNTS, &LUMA_AC_VALUES[..]),
+                    )
+                } else {
+                    (
+                        chroma_quantization,
+                        (&CHROMA_DC_COUNTS, &DC_VALUES[..]),
+                        (&CHROMA_AC_COUNTS, &CHROMA_AC_VALUES[..]),
+                    )
+                };
+                Component {
+                    samples,
+                    width: padded_width,
+                    sampling: component_sampling,
+                    quantization,
+                    dc_codes: huffman_codes(dc.0, dc.1),
+                    ac_codes: huffman_codes(ac.0, ac.1),
+                    previous_dc: 0,
+                }
+            })
+            .collect();
+
+        // Every component in one interleaved scan, MCU by MCU.
+        let basis = dct_basis();
+        let mut writer = BitWriter {
+            data: Vec::new(),
+            bits: 0,
+            count: 0,
+        };
+        for mcu_y in 0..mcus_down {
+            for mcu_x in 0..mcus_across {
+                for component in components.iter_mut() {
+                    let s = component.sampling;
+                    for block_y in mcu_y * s..(mcu_y + 1) * s {
+                        for block_x in mcu_x * s..(mcu_x + 1) * s {
+                            let block = component.block(block_x, block_y);
+                            encode_block(&mut writer, component, &forward_dct(&block, &basis));
+                        }
+                    }
+                }
+            }
+        }
+        let scan_data = writer.finish();
+
+        // JFIF 1.01 with a 1:1 pixel aspect ratio and no thumbnail.
+        let mut segments = vec![JPEGSegment::new(
+            APP0,
+            b"JFIF\0\x01\x01\x00\x00\x01\x00\x01\x00\x00".to_vec(),
+        )];
+        let mut dqt = Vec::new();
+        for (id, table) in [luma_quantization, chroma_quantization]
+            .iter()
+            .take(count.min(2))
+            .enumerate()
+        {
+            dqt.push(id as u8);
+            dqt.extend(ZIGZAG.iter().map(|k| table[*k] as u8));
+        }
+        segments.push(JPEGSegment::new(DQT, dqt));
+
+        let mut sof = vec![8];
+        sof.extend_from_slice(&(height as u16).to_be_bytes());
+        sof.extend_from_slice(&(width as u16).to_be_bytes());
+        sof.push(count as u8);
+        for (index, component) in components.iter().enumerate() {
+            let s = component.sampling as u8;
+            sof.extend_from_slice(&[index as u8 + 1, s << 4 | s, index.min(1) as u8]);
+        }
+        segments.push(JPEGSegment::new(SOF0, sof));
+
+        let mut dht = Vec::new();
+        let mut tables = vec![
+            (0x00, &LUMA_DC_COUNTS, &DC_VALUES[..]),
+            (0x10, &LUMA_AC_COUNTS, &LUMA_AC_VALUES[..]),
+        ];
+        if !gray {
+            tables.push((0x01, &CHROMA_DC_COUNTS, &DC_VALUES[..]));
+            tables.push((0x11, &CHROMA_AC_COUNTS, &CHROMA_AC_VALUES[..]));
+        }
+        for (class_and_id, counts, values) in tables {
+            dht.push(class_and_id);
+            dht.extend_from_slice(counts);
+            dht.extend_from_slice(values);
+        }
+        segments.push(JPEGSegment::new(DHT, dht));
+
+        let mut sos = vec![count as u8];
+        for index in 0..count {
+            let table = index.min(1) as u8;
+            sos.extend_from_slice(&[index as u8 + 1, table << 4 | table]);
+        }
+        // Spectral selection and successive approximation, which baseline files don't use.
+        sos.extend_from_slice(&[0, 63, 0]);
+        segments.push(JPEGSegment::new_scan(sos, scan_data));
+
+        Ok(JPEGFile::from_segments(segments))
+    }
+}
+
+// Scales a quality 50 table the way libjpeg does, keeping every entry within 1 to 255 so it fits
+// an 8-bit DQT table.
+fn scale_quantization(table: &[u16; 64], quality: u8) -> [u16; 64] {
+    let quality = quality as u32;
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    };
+    table.map(|v| ((v as u32 * scale + 50) / 100).clamp(1, 255) as u16)
+}
+
+// The code and its length for each value of a table.
+fn huffman_codes(counts: &[u8; 16], values: &[u8]) -> [(u16, u8); 256] {
+    let mut codes = [(0, 0); 256];
+    let (mut code, mut values) = (0u16, values.iter());
+    for (length, count) in counts.iter().enumerate() {
+        for value in values.by_ref().take(*count as usize) {
+            codes[*value as usize] = (code, length as u8 + 1);
+            code += 1;
+        }
+        code <<= 1;
+    }
+    codes
+}
+
+// Averages each factor by factor square of samples, or what's left of one at the edges.
+fn downsample(plane: &[f32], width: usize, height: usize, factor: usize) -> Vec<f32> {
+    if factor == 1 {
+        return plane.to_vec();
+    }
+    let (out_width, out_height) = (width.div_ceil(factor), height.div_ceil(factor));
+    let mut out = Vec::with_capacity(out_width * out_height);
+    for y in 0..out_height {
+        for x in 0..out_width {
+            let rows = y * factor..((y + 1) * factor).min(height);
+            let columns = x * factor..((x + 1) * factor).min(width);
+            let count = (rows.len() * columns.len()) as f32;
+            let sum: f32 = rows
+                .flat_map(|row| plane[row * width..][columns.clone()].iter())
+                .sum();
+            out.push(sum / count);
+        }
+    }
+    out
+}
+
+// Turns samples centered on 0 into coefficients in natural order, as rows then columns.
+fn forward_dct(samples: &[f32; 64], basis: &[f32; 64]) -> [f32; 64] {
+    let mut rows = [0f32; 64];
+    for y in 0..8 {
+        for u in 0..8 {
+            rows[y * 8 + u] = (0..8).map(|x| samples[y * 8 + x] * basis[x * 8 + u]).sum();
+        }
+    }
+    let mut out = [0f32; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            out[v * 8 + u] = (0..8).map(|y| rows[y * 8 + u] * basis[y * 8 + v]).sum();
+        }
+    }
+    out
+}
+
+// Quantizes a block and writes its DC difference and run-length coded AC coefficients.
+fn encode_block(writer: &mut BitWriter, component: &mut Component, coefficients: &[f32; 64]) {
+    let mut quantized = [0i32; 64];
+    for (k, value) in quantized.iter_mut().enumerate() {
+        let natural = ZIGZAG[k];
+        let q = component.quantization[natural] as f32;
+        // Keeps DC differences within 11 bits and AC values within 10, as far as baseline
+        // Huffman tables reach.
+        *value = ((coefficients[natural] / q).round() as i32).clamp(-1023, 1023);
+    }
+
+    let difference = quantized[0] - component.previous_dc;
+    component.previous_dc = quantized[0];
+    let (size, bits) = magnitude(difference);
+    let (code, length) = component.dc_codes[size as usize];
+    writer.write(code, length);
+    writer.write(bits, size);
+
+    let mut run = 0;
+    for value in quantized[1..].iter().copied() {
+        if value == 0 {
+            run += 1;
+            continue;
+        }
+        // Runs of 16 zeros have a code of their own.
+        while run >= 16 {
+            let (code, length) = component.ac_codes[0xF0];
+            writer.write(code, length);
+            run -= 16;
+        }
+        let (size, bits) = magnitude(value);
+        let (code, length) = component.ac_codes[(run << 4 | size) as usize];
+        writer.write(code, length);
+        writer.write(bits, size);
+        run = 0;
+    }
+    if run > 0 {
+        // End of block.
+        let (code, length) = component.ac_codes[0x00];
+        writer.write(code, length);
+    }
+}
+
+// The number of bits a value needs, and those bits as JPEG stores them: negative values are one
+// less than their two's complement.
+fn magnitude(value: i32) -> (u8, u16) {
+    let size = (32 - value.unsigned_abs().leading_zeros()) as u8;
+    let bits = if value < 0 {
+        value + (1 << size) - 1
+    } else {
+        value
+    };
+    (size, bits as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::ColorType;
+
+    // Smooth color ramps with a sharp-edged square in the middle, at a size that leaves partial
+    // MCUs on the right and bottom.
+    fn test_image() -> Image {
+        let (width, height) = (45u32, 29u32);
+        let mut samples = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let inside = (15..30).contains(&x) && (10..20).contains(&y);
+                let [r, g, b] = if inside {
+                    [220, 40, 40]
+                } else {
+                    [x * 255 / width, y * 255 / height, 100]
+                };
+                samples.extend_from_slice(&[r as u16, g as u16, b as u16]);
+            }
+        }
+        Image::from_samples(width, height, ColorType::Truecolor, 8, samples).unwrap()
+    }
+
+    fn round_trip(image: &Image, options: &EncodeOptions) -> (usize, Image) {
+        let bytes = JPEGFile::from_image(image, options)
+            .and_then(|file| file.to_bytes())
+            .unwrap();
+        let decoded = JPEGFile::from_bytes(&bytes).unwrap().decode().unwrap();
+        assert_eq!(
+            (decoded.width(), decoded.height()),
+            (image.width(), image.height())
+        );
+        (bytes.len(), decoded)
+    }
+
+    #[test]
+    fn encode_decode_round_trip() {
+        let image = test_image();
+        for subsampling in [ChromaSubsampling::Full, ChromaSubsampling::Half] {
+            let options = |quality| EncodeOptions {
+                quality,
+                subsampling,
+                ..EncodeOptions::default()
+            };
+            let (high_size, high) = round_trip(&image, &options(95));
+            let (low_size, low) = round_trip(&image, &options(20));
+            let high_psnr = image.psnr(&high).unwrap();
+            let low_psnr = image.psnr(&low).unwrap();
+            // Halving the color resolution blurs the red square's edges.
+            let bound = match subsampling {
+                ChromaSubsampling::Full => 40.0,
+                ChromaSubsampling::Half => 28.0,
+            };
+            assert!(high_psnr > bound, "{:?}: {}", subsampling, high_psnr);
+            assert!(low_psnr > 22.0, "{:?}: {}", subsampling, low_psnr);
+            assert!(high_psnr > low_psnr && high_size > low_size);
+        }
+
+        let gray = image.convert(ColorType::Grayscale).unwrap();
+        let (_, decoded) = round_trip(&gray, &EncodeOptions::default());
+        assert_eq!(decoded.color_type(), ColorType::Grayscale);
+        assert!(gray.psnr(&decoded).unwrap() > 35.0);
+    }
+
+    #[test]
+    fn transparency_over_background() {
+        let image =
+            Image::from_samples(8, 8, ColorType::GrayscaleAlpha, 8, [0, 0].repeat(64)).unwrap();
+        let options = EncodeOptions {
+            background: [200, 200, 200],
+            ..EncodeOptions::default()
+        };
+        let (_, decoded) = round_trip(&image, &options);
+        assert!(decoded
+            .samples()
+            .iter()
+            .all(|v| (*v as i32 - 200).abs() <= 2));
+    }
+
+    #[test]
+    fn quality_out_of_range() {
+        let image = test_image();
+        for quality in [0, 101] {
+            let options = EncodeOptions {
+                quality,
+                ..EncodeOptions::default()
+            };
+            let err = JPEGFile::from_image(&image, &options).err().unwrap();
+            assert_eq!(err.to_string(), "The quality must be from 1 to 100.");
+        }
+    }
+
+    #[test]
+    fn too_wide() {
+        let image = Image::from_samples(65536, 1, ColorType::Grayscale, 8, vec![0; 65536]).unwrap();
+        let err = JPEGFile::from_image(&image, &EncodeOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "JPEG images must be 1 to 65535 pixels wide and high."
+        );
+    }
+}
@@ -0,0 +1,171 @@
+use std::error::Error;
+
+use crate::image::{ColorType, Image, ImageError};
+use crate::png::PNGFile;
+
+// Blank space in pixels around the edge of a page, plus the gap between neighbouring cells.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Margins {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+    pub gutter: u32,
+}
+
+impl Margins {
+    pub fn uniform(size: u32) -> Margins {
+        Margins {
+            top: size,
+            right: size,
+            bottom: size,
+            left: size,
+            gutter: size,
+        }
+    }
+}
+
+// Lays the input PNGs out rows x cols to a page on white pages of paper_size_px pixels, scaling
+// each to fit its cell while keeping its aspect ratio. Returns one PNG per page, in input order.
+pub fn n_up(
+    inputs: &[PNGFile],
+    paper_size_px: (u32, u32),
+    rows: u32,
+    cols: u32,
+    margins: Margins,
+) -> Result<Vec<PNGFile>, Box<dyn Error>> {
+    let images = inputs
+        .iter()
+        .map(|f| f.decode())
+        .collect::<Result<Vec<Image>, Box<dyn Error>>>()?;
+
+    n_up_images(&images, paper_size_px, rows, cols, margins)?
+        .iter()
+        .map(PNGFile::from_image)
+        .collect()
+}
+
+// Same as n_up, but works on already decoded images and returns the pages undecoded.
+pub fn n_up_images(
+    images: &[Image],
+    paper_size_px: (u32, u32),
+    rows: u32,
+    cols: u32,
+    margins: Margins,
+) -> Result<Vec<Image>, Box<dyn Error>> {
+    if rows == 0 || cols == 0 {
+        return Err(ImageError::new("A page needs at least one row and one column.").into());
+    }
+
+    let (page_width, page_height) = paper_size_px;
+    // The margins and gutters across one side of the page, or None if they overflow.
+    let used = |before: u32, after: u32, cells: u32| {
+        margins
+            .gutter
+            .checked_mul(cells - 1)
+            .and_then(|gutters| gutters.checked_add(before))
+            .and_then(|used| used.checked_add(after))
+    };
+    let (used_width, used_height) = match (
+        used(margins.left, margins.right, cols),
+        used(margins.top, margins.bottom, rows),
+    ) {
+        (Some(width), Some(height)) if width < page_width && height < page_height => {
+            (width, height)
+        }
+        _ => return Err(ImageError::new("The margins leave no room on the page.").into()),
+    };
+    let cell_width = (page_width - used_width) / cols;
+    let cell_height = (page_height - used_height) / rows;
+    if cell_width == 0 || cell_height == 0 {
+        return Err(ImageError::new("The page is too small for that many cells.").into());
+    }
+
+    let page_size = (page_width as usize)
+        .checked_mul(page_height as usize)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .ok_or_else(|| ImageError::new("The page is too large."))?;
+    let per_page = (rows as usize)
+        .checked_mul(cols as usize)
+        .ok_or_else(|| ImageError::new("The page is too small for that many cells."))?;
+    let mut pages = Vec::new();
+    for page_images in images.chunks(per_page) {
+        let mut page = Image::from_samples(
+            page_width,
+            page_height,
+            ColorType::Truecolor,
+            8,
+            vec![255; page_size],
+        )?;
+
+        for (i, image) in page_images.iter().enumerate() {
+            let row = i as u32 / cols;
+            let col = i as u32 % cols;
+            let cell_x = margins.left + col * (cell_width + margins.gutter);
+            let cell_y = margins.top + row * (cell_height + margins.gutter);
+            place_in_cell(&mut page, image, cell_x, cell_y, cell_width, cell_height);
+        }
+        pages.push(page);
+    }
+    Ok(pages)
+}
+
+// Scales the image to fit the cell, centers it and blends it over the white page.
+fn place_in_cell(page: &mut Image, image: &Image, x: u32, y: u32, width: u32, height: u32) {
+    let scale = (width as f64 / image.width() as f64).min(height as f64 / image.height() as f64);
+    let fitted_width = ((image.width() as f64 * scale).round() as u32).clamp(1, width);
+    let fitted_height = ((image.height() as f64 * scale).round() as u32).clamp(1, height);
+    let offset_x = x + (width - fitted_width) / 2;
+    let offset_y = y + (height - fitted_height) / 2;
+
+    for dy in 0..fitted_height {
+        let sy = ((dy as u64 * image.height() as u64) / fitted_height as u64) as u32;
+        for dx in 0..fitted_width {
+            let sx = ((dx as u64 * image.width() as u64) / fitted_width as u64) as u32;
+            let offset = (sy as usize * image.width() as usize + sx as usize) * image.channels();
+            let [r, g, b, a] = image.rgba_at(offset);
+            let blend = |c: f32| (((c * a) + (1.0 - a)) * 255.0).round() as u16;
+            page.set_pixel(
+                offset_x + dx,
+                offset_y + dy,
+                &[blend(r), blend(g), blend(b)],
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(result: Result<Vec<Image>, Box<dyn Error>>) -> String {
+        result.err().unwrap().to_string()
+    }
+
+    #[test]
+    fn two_up() {
+        let image = Image::from_samples(2, 1, ColorType::Grayscale, 8, vec![0, 0]).unwrap();
+        let images = vec![image.clone(), image.clone(), image];
+        let pages = n_up_images(&images, (30, 20), 1, 2, Margins::uniform(2)).unwrap();
+        assert_eq!(pages.len(), 2);
+        assert_eq!((pages[0].width(), pages[0].height()), (30, 20));
+    }
+
+    #[test]
+    fn overflowing_margins() {
+        let huge = Margins {
+            gutter: u32::MAX / 2,
+            ..Margins::default()
+        };
+        let err = message(n_up_images(&[], (100, 100), 1, 3, huge));
+        assert!(err.contains("no room"));
+
+        let huge = Margins {
+            left: u32::MAX,
+            right: 1,
+            ..Margins::default()
+        };
+        let err = message(n_up_images(&[], (100, 100), 1, 1, huge));
+        assert!(err.contains("no room"));
+    }
+}
@@ -0,0 +1,50 @@
+// Pixel operations on decoded images. Each submodule adds methods to Image.
+mod adjust;
+mod alpha;
+mod blur;
+mod channels;
+mod convert;
+mod crop;
+mod diff;
+mod digest;
+mod dither;
+mod document;
+mod draw;
+mod edges;
+mod font;
+mod generate;
+mod grayscale;
+mod histogram;
+mod levels;
+mod montage;
+mod noise;
+mod orientation;
+mod overlay;
+mod pad;
+mod quality;
+mod quantize;
+mod replace;
+mod resize;
+mod rotate;
+mod sharpen;
+mod steganalysis;
+mod stego;
+mod stylize;
+mod threshold;
+mod trim;
+mod watermark;
+
+pub use convert::ConvertOptions;
+pub use diff::{DiffOptions, DiffStats};
+pub use dither::Dither;
+pub use font::BitmapFont;
+pub use document::DocumentBounds;
+pub use draw::Paint;
+pub use grayscale::LumaWeights;
+pub use histogram::Histogram;
+pub use montage::MontageOptions;
+pub use noise::NoiseKind;
+pub use quantize::QuantizeOptions;
+pub use resize::Filter;
+pub use stego::LsbOptions;
+pub use watermark::{Anchor, WatermarkOptions};
@@ -0,0 +1,90 @@
+// Tone adjustments, such as fixing scans that came out too dark. Each maps every color value
+// through the same curve, on a 0.0 - 1.0 scale so that 16-bit images keep their precision, and
+// clamps the result. Alpha is left alone. Indexed images have their palette adjusted instead of
+// their pixels, and tRNS color keys are moved along with the colors they mark.
+use crate::image::{ColorType, Image, Transparency};
+
+impl Image {
+    // Adds offset, from -1.0 to 1.0 of the full range, to every color value.
+    pub fn adjust_brightness(&self, offset: f32) -> Image {
+        self.map_colors(|v| v + offset)
+    }
+
+    // Scales the distance of every color value from the middle gray by factor. Above 1.0
+    // increases contrast, below decreases it, and 0.0 turns everything gray.
+    pub fn adjust_contrast(&self, factor: f32) -> Image {
+        self.map_colors(|v| (v - 0.5) * factor + 0.5)
+    }
+
+    // Applies a gamma curve. Above 1.0 brightens the shadows and midtones while keeping black
+    // and white, below 1.0 darkens them.
+    pub fn adjust_gamma(&self, gamma: f32) -> Image {
+        if gamma <= 0.0 {
+            return self.clone();
+        }
+        self.map_colors(|v| v.powf(1.0 / gamma))
+    }
+
+    // Multiplies every color value by 2 to the power of stops, like changing a camera's exposure.
+    // Values pushed past white are clipped.
+    pub fn adjust_exposure(&self, stops: f32) -> Image {
+        let factor = 2f32.powf(stops);
+        self.map_colors(|v| v * factor)
+    }
+
+    // Turns every color into its negative. Inverting twice gives back the original image.
+    pub fn invert(&self) -> Image {
+        self.map_colors(|v| 1.0 - v)
+    }
+
+    // Maps every color value through curve, which takes and returns values from 0.0 to 1.0, and
+    // leaves alpha alone.
+    pub(crate) fn map_colors<F: Fn(f32) -> f32>(&self, curve: F) -> Image {
+        let mut image = self.clone();
+        if self.color_type() == ColorType::Indexed {
+            if let Some(palette) = self.palette() {
+                let map = |v: u8| (curve(v as f32 / 255.0).clamp(0.0, 1.0) * 255.0).round() as u8;
+                let palette = palette
+                    .iter()
+                    .map(|e| [map(e[0]), map(e[1]), map(e[2])])
+                    .collect();
+                image.set_palette(Some(palette));
+            }
+            return image;
+        }
+
+        // Every possible value goes through the curve once.
+        let max = self.max_value();
+        let table: Vec<u16> = (0..=max)
+            .map(|v| {
+                let mapped = curve(v as f32 / max as f32).clamp(0.0, 1.0);
+                (mapped * max as f32).round() as u16
+            })
+            .collect();
+        let channels = self.channels();
+        let color_channels = if self.color_type().has_alpha_channel() {
+            channels - 1
+        } else {
+            channels
+        };
+        for pixel in image.samples_mut().chunks_exact_mut(channels) {
+            for sample in pixel[..color_channels].iter_mut() {
+                *sample = table[*sample as usize];
+            }
+        }
+
+        let transparency = match self.transparency() {
+            Some(Transparency::Grayscale(key)) => {
+                Some(Transparency::Grayscale(table[(*key).min(max) as usize]))
+            }
+            Some(Transparency::Truecolor(key)) => Some(Transparency::Truecolor([
+                table[key[0].min(max) as usize],
+                table[key[1].min(max) as usize],
+                table[key[2].min(max) as usize],
+            ])),
+            other => other.cloned(),
+        };
+        image.set_transparency(transparency);
+        image
+    }
+}
@@ -0,0 +1,90 @@
+use crate::image::{scale_sample, ColorType, Image, Transparency};
+
+impl Image {
+    // Converts color-key transparency from a tRNS chunk into a real alpha channel. Gray images
+    // become gray+alpha, truecolor images become RGBA, and indexed images are expanded through
+    // their palette into 8-bit RGBA. Images without tRNS data are returned unchanged.
+    pub fn expand_transparency(&self) -> Image {
+        let transparency = match self.transparency() {
+            Some(t) => t,
+            None => return self.clone(),
+        };
+
+        let pixel_count = self.width() as usize * self.height() as usize;
+        let (color_type, bit_depth, samples) = match (self.color_type(), transparency) {
+            (ColorType::Grayscale, Transparency::Grayscale(key)) => {
+                // Gray+alpha only allows 8 and 16 bit samples, so low bit depths get promoted.
+                let bit_depth = self.bit_depth().max(8);
+                let out_max = ((1u32 << bit_depth) - 1) as u16;
+                let mut samples = Vec::with_capacity(pixel_count * 2);
+                for v in self.samples() {
+                    samples.push(scale_sample(*v, self.max_value(), out_max));
+                    samples.push(if v == key { 0 } else { out_max });
+                }
+                (ColorType::GrayscaleAlpha, bit_depth, samples)
+            }
+            (ColorType::Truecolor, Transparency::Truecolor(key)) => {
+                let max = self.max_value();
+                let mut samples = Vec::with_capacity(pixel_count * 4);
+                for rgb in self.samples().chunks_exact(3) {
+                    samples.extend_from_slice(rgb);
+                    samples.push(if rgb == key { 0 } else { max });
+                }
+                (ColorType::TruecolorAlpha, self.bit_depth(), samples)
+            }
+            (ColorType::Indexed, Transparency::Indexed(_)) => {
+                let mut samples = Vec::with_capacity(pixel_count * 4);
+                for i in 0..pixel_count {
+                    let rgba = self.rgba_at(i);
+                    samples.extend(rgba.iter().map(|c| (c * 255.0).round() as u16));
+                }
+                (ColorType::TruecolorAlpha, 8, samples)
+            }
+            // The tRNS data doesn't match the color type, so there is nothing usable to expand.
+            _ => return self.clone(),
+        };
+
+        Image::from_samples(self.width(), self.height(), color_type, bit_depth, samples)
+            .expect("Expanded image has the same dimensions as its source")
+    }
+
+    // Multiplies the color samples of every pixel by its alpha, as GPU uploads and many
+    // compositors expect. tRNS transparency is expanded first, and images without any alpha are
+    // returned unchanged. Nothing records that the samples are premultiplied, so the result
+    // should be turned back with to_straight before it is saved as a PNG.
+    pub fn to_premultiplied(&self) -> Image {
+        self.map_alpha(|value, alpha, max| {
+            ((value as u32 * alpha as u32 + max as u32 / 2) / max as u32) as u16
+        })
+    }
+
+    // Undoes to_premultiplied, dividing the color samples of every pixel by its alpha. Fully
+    // transparent pixels come out black, as their color is lost once premultiplied.
+    pub fn to_straight(&self) -> Image {
+        self.map_alpha(|value, alpha, max| {
+            if alpha == 0 {
+                0
+            } else {
+                ((value as u32 * max as u32 + alpha as u32 / 2) / alpha as u32).min(max as u32)
+                    as u16
+            }
+        })
+    }
+
+    // Replaces every color sample with f(sample, alpha, max_value).
+    fn map_alpha<F: Fn(u16, u16, u16) -> u16>(&self, f: F) -> Image {
+        let mut image = self.expand_transparency();
+        if !image.color_type().has_alpha_channel() {
+            return image;
+        }
+        let channels = image.channels();
+        let max = image.max_value();
+        for pixel in image.samples_mut().chunks_exact_mut(channels) {
+            let alpha = pixel[channels - 1];
+            for sample in pixel[..channels - 1].iter_mut() {
+                *sample = f(*sample, alpha, max);
+            }
+        }
+        image
+    }
+}
@@ -0,0 +1,121 @@
+// Blurs, such as for hiding names and faces in screenshots. Both are done as a horizontal pass
+// followed by a vertical one, and treat pixels past the edges as copies of the edge pixels, so the
+// borders don't darken. Images are converted the way resize converts them.
+use std::error::Error;
+
+use crate::image::Image;
+
+impl Image {
+    // Gaussian blur with the given standard deviation in pixels. The kernel reaches three standard
+    // deviations either side, beyond which its weights are negligible.
+    pub fn gaussian_blur(&self, sigma: f32) -> Result<Image, Box<dyn Error>> {
+        let source = self.for_interpolation()?;
+        if sigma <= 0.0 {
+            return Ok(source);
+        }
+        let kernel = gaussian_kernel(sigma);
+        let values = source.premultiplied_samples();
+        let blurred = convolve(
+            &values,
+            source.width() as usize,
+            source.height() as usize,
+            source.channels(),
+            &kernel,
+        );
+        source.unpremultiply(source.width(), source.height(), &blurred)
+    }
+
+    // Averages every pixel with the ones up to radius pixels away in both directions. Coarser
+    // than a Gaussian blur but takes the same time whatever the radius.
+    pub fn box_blur(&self, radius: u32) -> Result<Image, Box<dyn Error>> {
+        let source = self.for_interpolation()?;
+        if radius == 0 {
+            return Ok(source);
+        }
+        let (width, height) = (source.width() as usize, source.height() as usize);
+        let channels = source.channels();
+        let radius = radius as usize;
+        let mut values = source.premultiplied_samples();
+        let mut line = Vec::new();
+        for y in 0..height {
+            line.clear();
+            line.extend((0..width).map(|x| (y * width + x) * channels));
+            box_blur_line(&mut values, &line, channels, radius);
+        }
+        for x in 0..width {
+            line.clear();
+            line.extend((0..height).map(|y| (y * width + x) * channels));
+            box_blur_line(&mut values, &line, channels, radius);
+        }
+        source.unpremultiply(source.width(), source.height(), &values)
+    }
+}
+
+// Normalized weights from -radius to radius.
+pub(crate) fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let radius = (sigma * 3.0).ceil() as i32;
+    let kernel: Vec<f32> = (-radius..=radius)
+        .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let sum: f32 = kernel.iter().sum();
+    kernel.iter().map(|k| k / sum).collect()
+}
+
+// Convolves interleaved samples with a symmetric kernel horizontally, then vertically.
+pub(crate) fn convolve(
+    values: &[f32],
+    width: usize,
+    height: usize,
+    channels: usize,
+    kernel: &[f32],
+) -> Vec<f32> {
+    let radius = kernel.len() as isize / 2;
+    let clamp = |v: isize, len: usize| v.clamp(0, len as isize - 1) as usize;
+    let mut rows = vec![0.0f32; values.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let out = (y * width + x) * channels;
+            for (k, weight) in kernel.iter().enumerate() {
+                let sx = clamp(x as isize + k as isize - radius, width);
+                let at = (y * width + sx) * channels;
+                for c in 0..channels {
+                    rows[out + c] += values[at + c] * weight;
+                }
+            }
+        }
+    }
+    let mut out = vec![0.0f32; values.len()];
+    for y in 0..height {
+        for (k, weight) in kernel.iter().enumerate() {
+            let sy = clamp(y as isize + k as isize - radius, height);
+            let (from, to) = (sy * width * channels, y * width * channels);
+            for i in 0..width * channels {
+                out[to + i] += rows[from + i] * weight;
+            }
+        }
+    }
+    out
+}
+
+// Replaces the pixels at the given offsets, which make up one row or column, with the mean of
+// the 2 * radius + 1 pixels around them, keeping a running sum.
+fn box_blur_line(values: &mut [f32], line: &[usize], channels: usize, radius: usize) {
+    let len = line.len();
+    let window = (2 * radius + 1) as f32;
+    let original: Vec<f32> = line
+        .iter()
+        .flat_map(|offset| values[*offset..*offset + channels].iter().copied())
+        .collect();
+    let sample = |i: isize, c: usize| {
+        let index = i.clamp(0, len as isize - 1) as usize;
+        original[index * channels + c]
+    };
+    for c in 0..channels {
+        let radius = radius as isize;
+        let mut sum: f32 = (-radius..=radius).map(|i| sample(i, c)).sum();
+        for i in 0..len as isize {
+            values[line[i as usize] + c] = sum / window;
+            sum += sample(i + radius + 1, c) - sample(i - radius, c);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// Splitting images into one grayscale image per channel and putting them back together, such as
+// for looking at an alpha mask on its own or packing unrelated maps into the channels of a game
+// texture.
+use std::error::Error;
+
+use crate::image::{scale_sample, ColorType, Image};
+
+impl Image {
+    // One grayscale image per channel, in the order the channels are stored: R, G, B and A for
+    // color images, gray and A for gray ones. The image is converted the way resize converts it
+    // first, so tRNS transparency comes out as an alpha channel and indexed images as R, G and B.
+    pub fn split(&self) -> Result<Vec<Image>, Box<dyn Error>> {
+        let source = self.for_interpolation()?;
+        let channels = source.channels();
+        (0..channels)
+            .map(|c| {
+                let samples = source
+                    .samples()
+                    .iter()
+                    .skip(c)
+                    .step_by(channels)
+                    .copied()
+                    .collect();
+                Image::from_samples(
+                    source.width(),
+                    source.height(),
+                    ColorType::Grayscale,
+                    source.bit_depth(),
+                    samples,
+                )
+            })
+            .collect()
+    }
+
+    // Puts grayscale images of the same size together as the channels of one image: one gives a
+    // grayscale image, two gray+alpha, three truecolor and four RGBA. Channels with different bit
+    // depths are scaled to the highest of them, and to at least 8 bits when there is more than
+    // one, as PNG requires.
+    pub fn merge(channels: &[Image]) -> Result<Image, Box<dyn Error>> {
+        let color_type = match channels.len() {
+            1 => ColorType::Grayscale,
+            2 => ColorType::GrayscaleAlpha,
+            3 => ColorType::Truecolor,
+            4 => ColorType::TruecolorAlpha,
+            _ => return Err("Merging takes one to four channels.".into()),
+        };
+        let (width, height) = (channels[0].width(), channels[0].height());
+        for channel in channels {
+            if channel.color_type() != ColorType::Grayscale {
+                return Err("Channels to merge must be grayscale images.".into());
+            }
+            if channel.width() != width || channel.height() != height {
+                return Err("Channels to merge must all have the same size.".into());
+            }
+        }
+
+        let mut bit_depth = channels.iter().map(|c| c.bit_depth()).max().unwrap_or(8);
+        if channels.len() > 1 {
+            bit_depth = bit_depth.max(8);
+        }
+        let max = ((1u32 << bit_depth) - 1) as u16;
+        let pixel_count = width as usize * height as usize;
+        let mut samples = Vec::with_capacity(pixel_count * channels.len());
+        for i in 0..pixel_count {
+            for channel in channels {
+                samples.push(scale_sample(channel.samples()[i], channel.max_value(), max));
+            }
+        }
+        Image::from_samples(width, height, color_type, bit_depth, samples)
+    }
+}
@@ -0,0 +1,448 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+
+use super::dither::{floyd_steinberg, Dither};
+use super::quantize::QuantizeOptions;
+use crate::image::{luma, scale_sample, ColorType, Image, ImageError, Transparency};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConvertOptions {
+    pub dither: Dither,
+}
+
+impl Image {
+    // Converts to another color type, picking the bit depth automatically: the current depth is
+    // kept when the new color type allows it, low depths are promoted to 8 and 16-bit images
+    // become 8-bit when converted to indexed. Converting to indexed builds a palette from the
+    // colors in use and fails if there are more than 256 of them.
+    pub fn convert(&self, color_type: ColorType) -> Result<Image, Box<dyn Error>> {
+        let bit_depth = if color_type == ColorType::Indexed && self.color_type() != color_type {
+            indexed_bit_depth(self.count_colors(256))
+        } else if color_type.allows_bit_depth(self.bit_depth()) {
+            self.bit_depth()
+        } else if self.bit_depth() < 8 {
+            8
+        } else {
+            8.min(self.bit_depth())
+        };
+        self.convert_to(color_type, bit_depth)
+    }
+
+    // Converts to the given color type and bit depth.
+    pub fn convert_to(
+        &self,
+        color_type: ColorType,
+        bit_depth: u8,
+    ) -> Result<Image, Box<dyn Error>> {
+        self.convert_with_options(color_type, bit_depth, &ConvertOptions::default())
+    }
+
+    // Same as convert_to, with control over dithering when the bit depth goes down. With dithering
+    // on, converting to indexed no longer fails on images with too many colors: the palette is
+    // built by quantizing to as many colors as the bit depth allows, and the rest are dithered.
+    pub fn convert_with_options(
+        &self,
+        color_type: ColorType,
+        bit_depth: u8,
+        options: &ConvertOptions,
+    ) -> Result<Image, Box<dyn Error>> {
+        if !color_type.allows_bit_depth(bit_depth) {
+            return Err(ImageError::new("Invalid bit depth specified for color type.").into());
+        }
+        if color_type == self.color_type() && bit_depth == self.bit_depth() {
+            return Ok(self.clone());
+        }
+        if color_type == ColorType::Indexed {
+            if options.dither != Dither::None && self.color_type() != ColorType::Indexed {
+                let quantized = self.quantize_with_options(&QuantizeOptions {
+                    max_colors: 1 << bit_depth,
+                    dither: options.dither,
+                })?;
+                return quantized.convert_to(ColorType::Indexed, bit_depth);
+            }
+            return self.to_indexed(bit_depth);
+        }
+        let reduces_depth = bit_depth < self.bit_depth();
+        if self.is_gray() && !(reduces_depth && options.dither != Dither::None) {
+            return self.convert_from_gray(color_type, bit_depth);
+        }
+
+        let max = ((1u32 << bit_depth) - 1) as f32;
+        let pixel_count = self.width() as usize * self.height() as usize;
+        let channels = color_type.channels();
+        let mut values = Vec::with_capacity(pixel_count * channels);
+        for i in 0..pixel_count {
+            let [r, g, b, a] = self.rgba_at(i * self.channels());
+            match color_type {
+                ColorType::Grayscale => values.push(luma(r, g, b) * max),
+                ColorType::GrayscaleAlpha => {
+                    values.extend_from_slice(&[luma(r, g, b) * max, a * max])
+                }
+                ColorType::Truecolor => values.extend_from_slice(&[r * max, g * max, b * max]),
+                ColorType::TruecolorAlpha => {
+                    values.extend_from_slice(&[r * max, g * max, b * max, a * max])
+                }
+                ColorType::Indexed => unreachable!(),
+            }
+        }
+
+        let round = |v: &mut [f32]| {
+            for c in v.iter_mut() {
+                *c = c.round().clamp(0.0, max);
+            }
+        };
+        match options.dither {
+            Dither::None => round(&mut values),
+            Dither::FloydSteinberg => floyd_steinberg(
+                &mut values,
+                self.width() as usize,
+                self.height() as usize,
+                channels,
+                round,
+            ),
+        }
+        let mut samples: Vec<u16> = values.iter().map(|v| *v as u16).collect();
+
+        // Color-key transparency carries over between gray and truecolor images without alpha.
+        let transparency = match (self.transparency(), color_type) {
+            (Some(Transparency::Truecolor(key)), ColorType::Grayscale) => {
+                Some(self.gray_key(key, &mut samples, bit_depth)?)
+            }
+            _ => self.converted_transparency(color_type, bit_depth),
+        };
+        let mut image =
+            Image::from_samples(self.width(), self.height(), color_type, bit_depth, samples)?;
+        image.set_transparency(transparency);
+        Ok(image)
+    }
+
+    // Gray and gray+alpha sources only need their samples rescaled and copied into place, which
+    // is exact and skips the round trip through normalized RGBA.
+    fn convert_from_gray(
+        &self,
+        color_type: ColorType,
+        bit_depth: u8,
+    ) -> Result<Image, Box<dyn Error>> {
+        let to_max = ((1u32 << bit_depth) - 1) as u16;
+        let scale = |v: u16| scale_sample(v, self.max_value(), to_max);
+        let key = match self.transparency() {
+            Some(Transparency::Grayscale(key)) => Some(*key),
+            _ => None,
+        };
+
+        let pixel_count = self.width() as usize * self.height() as usize;
+        let mut samples = Vec::with_capacity(pixel_count * color_type.channels());
+        for pixel in self.samples().chunks_exact(self.channels()) {
+            let gray = scale(pixel[0]);
+            let alpha = match (pixel.get(1), key) {
+                (Some(alpha), _) => scale(*alpha),
+                (None, Some(key)) if pixel[0] == key => 0,
+                _ => to_max,
+            };
+            match color_type {
+                ColorType::Grayscale => samples.push(gray),
+                ColorType::GrayscaleAlpha => samples.extend_from_slice(&[gray, alpha]),
+                ColorType::Truecolor => samples.extend_from_slice(&[gray; 3]),
+                ColorType::TruecolorAlpha => samples.extend_from_slice(&[gray, gray, gray, alpha]),
+                ColorType::Indexed => unreachable!(),
+            }
+        }
+
+        let mut image =
+            Image::from_samples(self.width(), self.height(), color_type, bit_depth, samples)?;
+        image.set_transparency(self.converted_transparency(color_type, bit_depth));
+        Ok(image)
+    }
+
+    fn to_indexed(&self, bit_depth: u8) -> Result<Image, Box<dyn Error>> {
+        let capacity = 1usize << bit_depth;
+
+        if self.color_type() == ColorType::Indexed {
+            let palette_len = self.palette().map_or(0, |p| p.len());
+            if palette_len > capacity {
+                return Err(ImageError::new("The palette does not fit in that bit depth.").into());
+            }
+            let mut image = Image::from_samples(
+                self.width(),
+                self.height(),
+                ColorType::Indexed,
+                bit_depth,
+                self.samples().to_vec(),
+            )?;
+            image.set_palette(self.palette().cloned());
+            image.set_transparency(self.transparency().cloned());
+            return Ok(image);
+        }
+
+        let mut lookup: HashMap<[u8; 4], u16> = HashMap::new();
+        let mut colors: Vec<[u8; 4]> = Vec::new();
+        let mut samples = Vec::with_capacity(self.width() as usize * self.height() as usize);
+        for color in self.rgba8_pixels() {
+            let index = *lookup.entry(color).or_insert_with(|| {
+                colors.push(color);
+                (colors.len() - 1) as u16
+            });
+            if colors.len() > capacity {
+                return Err(ImageError::new(
+                    "The image has too many colors for a palette of that bit depth.",
+                )
+                .into());
+            }
+            samples.push(index);
+        }
+
+        let mut image = Image::from_samples(
+            self.width(),
+            self.height(),
+            ColorType::Indexed,
+            bit_depth,
+            samples,
+        )?;
+        image.set_palette(Some(colors.iter().map(|c| [c[0], c[1], c[2]]).collect()));
+        image.set_transparency(palette_transparency(&colors));
+        Ok(image)
+    }
+
+    // Number of distinct colors, counting no further than limit + 1.
+    pub(crate) fn count_colors(&self, limit: usize) -> usize {
+        let mut seen = HashSet::new();
+        let pixel_count = self.width() as usize * self.height() as usize;
+        for i in 0..pixel_count {
+            let offset = i * self.channels();
+            seen.insert(self.samples()[offset..offset + self.channels()].to_vec());
+            if seen.len() > limit {
+                break;
+            }
+        }
+        seen.len()
+    }
+
+    // Turns a truecolor image's transparent color into a gray key for its converted samples. The
+    // key's own gray is used unless an opaque pixel converted to the same value, in which case the
+    // transparent pixels are moved to the nearest gray no opaque pixel uses.
+    fn gray_key(
+        &self,
+        key: &[u16; 3],
+        samples: &mut [u16],
+        bit_depth: u8,
+    ) -> Result<Transparency, Box<dyn Error>> {
+        let to_max = (1u32 << bit_depth) - 1;
+        let is_key: Vec<bool> = self.samples().chunks_exact(3).map(|p| p == key).collect();
+        let mut used = vec![false; to_max as usize + 1];
+        for (sample, transparent) in samples.iter().zip(&is_key) {
+            if !transparent {
+                used[*sample as usize] = true;
+            }
+        }
+
+        let max = self.max_value() as f32;
+        let wanted = luma(
+            key[0] as f32 / max,
+            key[1] as f32 / max,
+            key[2] as f32 / max,
+        );
+        let wanted = (wanted * to_max as f32).round() as i64;
+        let gray = (0..=to_max as u16)
+            .filter(|v| !used[*v as usize])
+            .min_by_key(|v| (*v as i64 - wanted).abs())
+            .ok_or_else(|| {
+                ImageError::new(
+                    "Every gray value is in use, so the transparent color can't be kept as a key.",
+                )
+            })?;
+        for (sample, transparent) in samples.iter_mut().zip(&is_key) {
+            if *transparent {
+                *sample = gray;
+            }
+        }
+        Ok(Transparency::Grayscale(gray))
+    }
+
+    fn converted_transparency(&self, color_type: ColorType, bit_depth: u8) -> Option<Transparency> {
+        let to_max = ((1u32 << bit_depth) - 1) as u16;
+        let scale = |v: u16| scale_sample(v, self.max_value(), to_max);
+        match (self.transparency()?, color_type) {
+            (Transparency::Grayscale(v), ColorType::Grayscale) => {
+                Some(Transparency::Grayscale(scale(*v)))
+            }
+            (Transparency::Grayscale(v), ColorType::Truecolor) => {
+                Some(Transparency::Truecolor([scale(*v); 3]))
+            }
+            (Transparency::Truecolor(rgb), ColorType::Truecolor) => {
+                Some(Transparency::Truecolor([
+                    scale(rgb[0]),
+                    scale(rgb[1]),
+                    scale(rgb[2]),
+                ]))
+            }
+            _ => None,
+        }
+    }
+}
+
+pub(crate) fn indexed_bit_depth(colors: usize) -> u8 {
+    match colors {
+        0..=2 => 1,
+        3..=4 => 2,
+        5..=16 => 4,
+        _ => 8,
+    }
+}
+
+// Builds tRNS data for a palette, trimmed after the last entry that isn't fully opaque. Returns
+// None when every entry is opaque.
+pub(crate) fn palette_transparency(colors: &[[u8; 4]]) -> Option<Transparency> {
+    let last = colors.iter().rposition(|c| c[3] != 255)?;
+    Some(Transparency::Indexed(
+        colors[..=last].iter().map(|c| c[3]).collect(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray_alpha(bit_depth: u8, samples: Vec<u16>) -> Image {
+        let width = samples.len() as u32 / 2;
+        Image::from_samples(width, 1, ColorType::GrayscaleAlpha, bit_depth, samples).unwrap()
+    }
+
+    #[test]
+    fn gray_alpha_to_truecolor_alpha() {
+        let image = gray_alpha(8, vec![0, 255, 77, 128, 255, 0]);
+        let rgba = image.convert(ColorType::TruecolorAlpha).unwrap();
+        assert_eq!(rgba.bit_depth(), 8);
+        assert_eq!(
+            rgba.samples(),
+            [0, 0, 0, 255, 77, 77, 77, 128, 255, 255, 255, 0]
+        );
+
+        let image = gray_alpha(16, vec![0x1234, 0xFFFF, 0xABCD, 0x0001]);
+        let rgba = image.convert(ColorType::TruecolorAlpha).unwrap();
+        assert_eq!(rgba.bit_depth(), 16);
+        assert_eq!(
+            rgba.samples(),
+            [0x1234, 0x1234, 0x1234, 0xFFFF, 0xABCD, 0xABCD, 0xABCD, 0x0001]
+        );
+    }
+
+    #[test]
+    fn gray_alpha_round_trips_through_truecolor_alpha() {
+        for bit_depth in [8, 16] {
+            let max = ((1u32 << bit_depth) - 1) as u16;
+            let image = gray_alpha(bit_depth, vec![0, max, max / 3, max / 2, max, 0, 1, 1]);
+            let back = image
+                .convert(ColorType::TruecolorAlpha)
+                .and_then(|rgba| rgba.convert(ColorType::GrayscaleAlpha))
+                .unwrap();
+            assert_eq!(back.color_type(), ColorType::GrayscaleAlpha);
+            assert_eq!(back.bit_depth(), bit_depth);
+            assert_eq!(back.samples(), image.samples());
+        }
+    }
+
+    #[test]
+    fn gray_alpha_bit_depths() {
+        let image = gray_alpha(8, vec![0, 255, 0x12, 0x80]);
+        let deep = image.convert_to(ColorType::GrayscaleAlpha, 16).unwrap();
+        assert_eq!(deep.samples(), [0, 0xFFFF, 0x1212, 0x8080]);
+        let back = deep.convert_to(ColorType::GrayscaleAlpha, 8).unwrap();
+        assert_eq!(back.samples(), image.samples());
+
+        let image = gray_alpha(16, vec![0x1234, 0x7F7F, 0xFFFF, 0x0080]);
+        // convert keeps 16 bits, convert_to rounds to the nearest 8-bit value.
+        assert_eq!(
+            image
+                .convert(ColorType::GrayscaleAlpha)
+                .unwrap()
+                .bit_depth(),
+            16
+        );
+        let shallow = image.convert_to(ColorType::GrayscaleAlpha, 8).unwrap();
+        assert_eq!(shallow.samples(), [0x12, 0x7F, 0xFF, 0x00]);
+    }
+
+    #[test]
+    fn gray_alpha_drops_and_gains_alpha() {
+        let image = gray_alpha(16, vec![0x1234, 0x0000, 0xABCD, 0xFFFF]);
+        let gray = image.convert(ColorType::Grayscale).unwrap();
+        assert_eq!(gray.samples(), [0x1234, 0xABCD]);
+        let again = gray.convert(ColorType::GrayscaleAlpha).unwrap();
+        assert_eq!(again.samples(), [0x1234, 0xFFFF, 0xABCD, 0xFFFF]);
+    }
+
+    #[test]
+    fn gray_key_becomes_alpha() {
+        let mut gray = Image::from_samples(3, 1, ColorType::Grayscale, 8, vec![5, 9, 5]).unwrap();
+        gray.set_transparency(Some(Transparency::Grayscale(5)));
+        let image = gray.convert(ColorType::GrayscaleAlpha).unwrap();
+        assert_eq!(image.samples(), [5, 0, 9, 255, 5, 0]);
+        assert!(image.transparency().is_none());
+    }
+
+    fn truecolor(samples: Vec<u16>, key: [u16; 3]) -> Image {
+        let width = samples.len() as u32 / 3;
+        let mut image = Image::from_samples(width, 1, ColorType::Truecolor, 8, samples).unwrap();
+        image.set_transparency(Some(Transparency::Truecolor(key)));
+        image
+    }
+
+    #[test]
+    fn truecolor_key_becomes_gray_key() {
+        let image = truecolor(vec![0, 255, 0, 40, 40, 40, 0, 255, 0], [0, 255, 0]);
+        let gray = image.convert(ColorType::Grayscale).unwrap();
+        // Rec. 601 luma of pure green.
+        assert_eq!(gray.samples(), [150, 40, 150]);
+        assert_eq!(gray.transparency(), Some(&Transparency::Grayscale(150)));
+    }
+
+    #[test]
+    fn truecolor_key_moves_off_opaque_gray() {
+        // The opaque gray pixel has the same luma as the transparent green ones.
+        let image = truecolor(vec![0, 255, 0, 150, 150, 150, 0, 255, 0], [0, 255, 0]);
+        let gray = image.convert(ColorType::Grayscale).unwrap();
+        assert_eq!(gray.samples(), [149, 150, 149]);
+        assert_eq!(gray.transparency(), Some(&Transparency::Grayscale(149)));
+
+        let with_alpha = image.convert(ColorType::GrayscaleAlpha).unwrap();
+        assert_eq!(with_alpha.samples(), [150, 0, 150, 255, 150, 0]);
+    }
+
+    #[test]
+    fn dithered_indexed() {
+        let samples = (0..300u16)
+            .flat_map(|i| [i % 256, i / 2 % 256, 0])
+            .collect();
+        let image = Image::from_samples(300, 1, ColorType::Truecolor, 8, samples).unwrap();
+        assert!(image.convert_to(ColorType::Indexed, 4).is_err());
+
+        let options = ConvertOptions {
+            dither: Dither::FloydSteinberg,
+        };
+        let indexed = image
+            .convert_with_options(ColorType::Indexed, 4, &options)
+            .unwrap();
+        assert_eq!(indexed.bit_depth(), 4);
+        assert!(indexed.palette().unwrap().len() <= 16);
+        let expected = image.quantize_with_options(&QuantizeOptions {
+            max_colors: 16,
+            dither: Dither::FloydSteinberg,
+        });
+        assert_eq!(indexed.samples(), expected.unwrap().samples());
+    }
+
+    #[test]
+    fn truecolor_alpha_to_gray_alpha() {
+        let rgba = Image::from_samples(
+            2,
+            1,
+            ColorType::TruecolorAlpha,
+            8,
+            vec![255, 0, 0, 200, 0, 0, 255, 10],
+        )
+        .unwrap();
+        let image = rgba.convert(ColorType::GrayscaleAlpha).unwrap();
+        // Rec. 601 luma of pure red and pure blue; alpha carries straight over.
+        assert_eq!(image.samples(), [76, 200, 29, 10]);
+    }
+}
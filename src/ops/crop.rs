@@ -0,0 +1,28 @@
+use std::error::Error;
+
+use crate::image::{Image, ImageError};
+
+impl Image {
+    // Cuts out the rectangle with its top left corner at x, y. The rectangle must be non-empty
+    // and lie inside the image.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Image, Box<dyn Error>> {
+        if width == 0 || height == 0 {
+            return Err(ImageError::new("Width and height must be non-zero numbers.").into());
+        }
+        let fits =
+            |start: u32, len: u32, max: u32| start.checked_add(len).is_some_and(|end| end <= max);
+        if !fits(x, width, self.width()) || !fits(y, height, self.height()) {
+            return Err(ImageError::new(&format!(
+                "The rectangle {}x{} at {}, {} doesn't fit in the {}x{} image.",
+                width,
+                height,
+                x,
+                y,
+                self.width(),
+                self.height()
+            ))
+            .into());
+        }
+        Ok(self.copy_region(x, y, width, height))
+    }
+}
@@ -0,0 +1,123 @@
+// Comparing two renderings pixel by pixel, for visual regression tests. The diff image shows the
+// first image faded to light gray with every changed pixel drawn in red, so changes are easy to
+// find and can be placed in context. Colors are compared after resolving palettes and
+// transparency, so images of different color types and bit depths can be compared, and a change
+// in a fully transparent pixel's color doesn't count.
+use std::error::Error;
+
+use crate::image::{luma, ColorType, Image, ImageError};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffOptions {
+    // The largest difference, from 0.0 to 1.0, that still counts as unchanged. 0.0 counts every
+    // difference; a little more ignores rounding and dithering noise.
+    pub threshold: f32,
+    // Factor differences are multiplied by before they are drawn. A changed pixel is drawn pale
+    // red for a small difference and pure red for a difference of 1.0; raising this makes small
+    // differences show up as strongly as large ones.
+    pub amplify: f32,
+}
+
+impl Default for DiffOptions {
+    fn default() -> DiffOptions {
+        DiffOptions {
+            threshold: 0.0,
+            amplify: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffStats {
+    pub changed_pixels: u64,
+    pub total_pixels: u64,
+    // The largest and mean difference over all pixels, from 0.0 to 1.0. A pixel's difference is
+    // the largest difference in any of its channels.
+    pub max_difference: f32,
+    pub mean_difference: f32,
+    // The smallest rectangle holding every changed pixel, as x, y, width and height, or None when
+    // nothing changed.
+    pub changed_region: Option<(u32, u32, u32, u32)>,
+}
+
+impl DiffStats {
+    // Share of the pixels that changed, from 0.0 to 1.0.
+    pub fn changed_fraction(&self) -> f64 {
+        self.changed_pixels as f64 / self.total_pixels as f64
+    }
+}
+
+impl Image {
+    // Compares the image with another of the same size. Returns an 8-bit RGB diff image and
+    // statistics about the changes.
+    pub fn diff(
+        &self,
+        other: &Image,
+        options: &DiffOptions,
+    ) -> Result<(Image, DiffStats), Box<dyn Error>> {
+        if (self.width(), self.height()) != (other.width(), other.height()) {
+            return Err(ImageError::new("The images must be the same size.").into());
+        }
+        let (width, height) = (self.width(), self.height());
+        let mut samples = Vec::with_capacity(width as usize * height as usize * 3);
+        let mut changed_pixels = 0u64;
+        let mut max_difference = 0.0f32;
+        let mut total_difference = 0.0f64;
+        let (mut left, mut top, mut right, mut bottom) = (width, height, 0, 0);
+
+        for y in 0..height {
+            for x in 0..width {
+                let i = y as usize * width as usize + x as usize;
+                let a = premultiplied(self.rgba_at(i * self.channels()));
+                let b = premultiplied(other.rgba_at(i * other.channels()));
+                let difference = a
+                    .iter()
+                    .zip(b.iter())
+                    .map(|(a, b)| (a - b).abs())
+                    .fold(0.0, f32::max);
+                max_difference = max_difference.max(difference);
+                total_difference += difference as f64;
+
+                if difference > options.threshold {
+                    changed_pixels += 1;
+                    left = left.min(x);
+                    top = top.min(y);
+                    right = right.max(x + 1);
+                    bottom = bottom.max(y + 1);
+                    let fade = (1.0 - (difference * options.amplify).clamp(0.0, 1.0)) * 0.75;
+                    let other_channels = (fade * 255.0).round() as u16;
+                    samples.extend_from_slice(&[255, other_channels, other_channels]);
+                } else {
+                    // Composited over white and pulled three quarters of the way to white.
+                    let [r, g, b, alpha] = a;
+                    let gray = luma(r, g, b) + (1.0 - alpha);
+                    let faded = (0.75 + gray.clamp(0.0, 1.0) * 0.25) * 255.0;
+                    let faded = faded.round() as u16;
+                    samples.extend_from_slice(&[faded, faded, faded]);
+                }
+            }
+        }
+
+        let total_pixels = width as u64 * height as u64;
+        let changed_region = if changed_pixels > 0 {
+            Some((left, top, right - left, bottom - top))
+        } else {
+            None
+        };
+        let stats = DiffStats {
+            changed_pixels,
+            total_pixels,
+            max_difference,
+            mean_difference: (total_difference / total_pixels as f64) as f32,
+            changed_region,
+        };
+        let image = Image::from_samples(width, height, ColorType::Truecolor, 8, samples)?;
+        Ok((image, stats))
+    }
+}
+
+// Color channels multiplied by alpha, so that the color of a transparent pixel doesn't matter.
+fn premultiplied(rgba: [f32; 4]) -> [f32; 4] {
+    let [r, g, b, a] = rgba;
+    [r * a, g * a, b * a, a]
+}
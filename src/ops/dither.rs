@@ -0,0 +1,52 @@
+// How quantization error is handled when an operation reduces color depth or palette size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Dither {
+    // Each pixel is replaced by its closest available color.
+    #[default]
+    None,
+    // Error diffusion, which trades banding in gradients for fine noise.
+    FloydSteinberg,
+}
+
+// Runs Floyd-Steinberg error diffusion over interleaved pixel values. The quantize callback
+// replaces a pixel's values in place with the closest available color; the difference is then
+// spread to the unprocessed neighbours with weights 7/16 (right), 3/16 (below left), 5/16 (below)
+// and 1/16 (below right).
+pub(crate) fn floyd_steinberg<F>(
+    values: &mut [f32],
+    width: usize,
+    height: usize,
+    channels: usize,
+    mut quantize: F,
+) where
+    F: FnMut(&mut [f32]),
+{
+    let mut error = vec![0f32; channels];
+    for y in 0..height {
+        for x in 0..width {
+            let offset = (y * width + x) * channels;
+            let pixel = &mut values[offset..offset + channels];
+            error.copy_from_slice(pixel);
+            quantize(pixel);
+            for (e, v) in error.iter_mut().zip(pixel.iter()) {
+                *e -= v;
+            }
+
+            let mut spread = |dx: isize, dy: usize, weight: f32| {
+                let nx = x as isize + dx;
+                let ny = y + dy;
+                if nx < 0 || nx as usize >= width || ny >= height {
+                    return;
+                }
+                let at = (ny * width + nx as usize) * channels;
+                for c in 0..channels {
+                    values[at + c] += error[c] * weight;
+                }
+            };
+            spread(1, 0, 7.0 / 16.0);
+            spread(-1, 1, 3.0 / 16.0);
+            spread(0, 1, 5.0 / 16.0);
+            spread(1, 1, 1.0 / 16.0);
+        }
+    }
+}
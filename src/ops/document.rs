@@ -0,0 +1,125 @@
+use crate::image::Image;
+
+// Fraction of a row or column that has to differ from the background for it to count as part of
+// the document.
+const CONTENT_FRACTION: f32 = 0.5;
+const INITIAL_CONTENT_FRACTION: f32 = 0.25;
+
+// Rectangle found by detect_document_bounds. Confidence runs from 0.0 to 1.0 and describes how
+// cleanly the region separates from the surrounding background.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DocumentBounds {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub confidence: f32,
+}
+
+impl Image {
+    // Finds the dominant rectangular content region of a scan, such as a page lying on a dark
+    // flatbed lid. The background level is estimated from the outermost pixels, so both dark and
+    // light surrounds work. Returns None when nothing stands out from the background.
+    pub fn detect_document_bounds(&self) -> Option<DocumentBounds> {
+        let luma = self.luma_plane();
+        let width = self.width() as usize;
+        let height = self.height() as usize;
+
+        let mut border = Vec::with_capacity(2 * (width + height));
+        for x in 0..width {
+            border.push(luma[x]);
+            border.push(luma[(height - 1) * width + x]);
+        }
+        for y in 0..height {
+            border.push(luma[y * width]);
+            border.push(luma[y * width + width - 1]);
+        }
+        let background = border.iter().sum::<f32>() / border.len() as f32;
+        let deviation = (border.iter().map(|l| (l - background).powi(2)).sum::<f32>()
+            / border.len() as f32)
+            .sqrt();
+        // Anything further from the background than its own noise counts as content.
+        let tolerance = (deviation * 3.0).max(16.0);
+        let content: Vec<bool> = luma
+            .iter()
+            .map(|l| (l - background).abs() > tolerance)
+            .collect();
+
+        // Rows are first judged across the full width, where a page covering only part of the
+        // scan has to be accepted with a low fraction. Columns are then judged within those rows
+        // and the rows refined within the columns, both with the stricter fraction.
+        let row_fractions = |left: usize, right: usize| -> Vec<f32> {
+            (0..height)
+                .map(|y| {
+                    content[y * width + left..y * width + right]
+                        .iter()
+                        .filter(|c| **c)
+                        .count()
+                })
+                .map(|count| count as f32 / (right - left) as f32)
+                .collect()
+        };
+        let (top, bottom) = longest_run(&row_fractions(0, width), INITIAL_CONTENT_FRACTION)?;
+        let col_fractions: Vec<f32> = (0..width)
+            .map(|x| (top..bottom).filter(|y| content[y * width + x]).count())
+            .map(|count| count as f32 / (bottom - top) as f32)
+            .collect();
+        let (left, right) = longest_run(&col_fractions, CONTENT_FRACTION)?;
+        let (top, bottom) = longest_run(&row_fractions(left, right), CONTENT_FRACTION)?;
+
+        let mut inside_content = 0usize;
+        let mut outside_background = 0usize;
+        for y in 0..height {
+            for x in 0..width {
+                let inside = y >= top && y < bottom && x >= left && x < right;
+                if inside && content[y * width + x] {
+                    inside_content += 1;
+                } else if !inside && !content[y * width + x] {
+                    outside_background += 1;
+                }
+            }
+        }
+        let inside_total = (bottom - top) * (right - left);
+        let outside_total = width * height - inside_total;
+        let inside_score = inside_content as f32 / inside_total as f32;
+        // A region covering the whole image has no surround to compare against, so it can only
+        // be half trusted.
+        let outside_score = if outside_total == 0 {
+            0.5
+        } else {
+            outside_background as f32 / outside_total as f32
+        };
+
+        Some(DocumentBounds {
+            x: left as u32,
+            y: top as u32,
+            width: (right - left) as u32,
+            height: (bottom - top) as u32,
+            confidence: inside_score * outside_score,
+        })
+    }
+
+    // Detects the document region and crops the image to it.
+    pub fn crop_to_document(&self) -> Option<(Image, DocumentBounds)> {
+        let bounds = self.detect_document_bounds()?;
+        let cropped = self.copy_region(bounds.x, bounds.y, bounds.width, bounds.height);
+        Some((cropped, bounds))
+    }
+}
+
+// Start and end (exclusive) of the longest stretch of lines that are mostly content.
+fn longest_run(fractions: &[f32], minimum: f32) -> Option<(usize, usize)> {
+    let mut best: Option<(usize, usize)> = None;
+    let mut start = None;
+    // The extra step past the end closes a run that reaches the last line.
+    for i in 0..=fractions.len() {
+        if i < fractions.len() && fractions[i] >= minimum {
+            start.get_or_insert(i);
+        } else if let Some(s) = start.take() {
+            if best.is_none_or(|(bs, be)| i - s > be - bs) {
+                best = Some((s, i));
+            }
+        }
+    }
+    best
+}
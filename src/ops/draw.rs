@@ -0,0 +1,254 @@
+// Drawing shapes onto an image in place, such as boxes and arrows marking up a screenshot.
+// Shapes are one pixel wide, coordinates may be negative or past the edges, and whatever falls
+// outside the image is cut off. An image that can't hold a color is converted the way rotate
+// converts it for that color before the shape is drawn.
+use std::error::Error;
+
+use crate::image::{ColorType, Image};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Paint {
+    // 8-bit RGBA.
+    pub color: [u8; 4],
+    // Blends the color over the pixels by its alpha. Otherwise the pixels are set to the color,
+    // alpha included, which can punch transparent holes in an image.
+    pub blend: bool,
+}
+
+impl Default for Paint {
+    fn default() -> Paint {
+        Paint {
+            color: [0, 0, 0, 255],
+            blend: true,
+        }
+    }
+}
+
+impl Image {
+    // A straight line from x0, y0 to x1, y1, both ends included.
+    pub fn draw_line(
+        &mut self,
+        x0: i64,
+        y0: i64,
+        x1: i64,
+        y1: i64,
+        paint: &Paint,
+    ) -> Result<(), Box<dyn Error>> {
+        // Bresenham's algorithm, stepping along the longer axis.
+        let (dx, dy) = ((x1 - x0).abs(), -(y1 - y0).abs());
+        let (step_x, step_y) = ((x1 - x0).signum(), (y1 - y0).signum());
+        let (mut x, mut y) = (x0, y0);
+        let mut error = dx + dy;
+        let mut points = Vec::new();
+        loop {
+            points.push((x, y));
+            if x == x1 && y == y1 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+        self.paint_points(&mut points, paint)
+    }
+
+    // The outline of the rectangle with its top left corner at x, y.
+    pub fn draw_rect(
+        &mut self,
+        x: i64,
+        y: i64,
+        width: u32,
+        height: u32,
+        paint: &Paint,
+    ) -> Result<(), Box<dyn Error>> {
+        if width == 0 || height == 0 {
+            return Ok(());
+        }
+        let (right, bottom) = (x + width as i64 - 1, y + height as i64 - 1);
+        let mut points = Vec::new();
+        for px in x..=right {
+            points.push((px, y));
+            points.push((px, bottom));
+        }
+        for py in y..=bottom {
+            points.push((x, py));
+            points.push((right, py));
+        }
+        self.paint_points(&mut points, paint)
+    }
+
+    pub fn fill_rect(
+        &mut self,
+        x: i64,
+        y: i64,
+        width: u32,
+        height: u32,
+        paint: &Paint,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut spans = (y..y + height as i64)
+            .map(|py| (py, x, x + width as i64 - 1))
+            .collect();
+        self.paint_spans(&mut spans, paint)
+    }
+
+    // The outline of the circle around cx, cy.
+    pub fn draw_circle(
+        &mut self,
+        cx: i64,
+        cy: i64,
+        radius: u32,
+        paint: &Paint,
+    ) -> Result<(), Box<dyn Error>> {
+        self.draw_ellipse(cx, cy, radius, radius, paint)
+    }
+
+    pub fn fill_circle(
+        &mut self,
+        cx: i64,
+        cy: i64,
+        radius: u32,
+        paint: &Paint,
+    ) -> Result<(), Box<dyn Error>> {
+        self.fill_ellipse(cx, cy, radius, radius, paint)
+    }
+
+    // The outline of the ellipse around cx, cy with the given horizontal and vertical radii.
+    pub fn draw_ellipse(
+        &mut self,
+        cx: i64,
+        cy: i64,
+        radius_x: u32,
+        radius_y: u32,
+        paint: &Paint,
+    ) -> Result<(), Box<dyn Error>> {
+        // Stepping along both axes leaves no gaps where the outline runs steeply.
+        let (rx, ry) = (radius_x as i64, radius_y as i64);
+        let mut points = Vec::new();
+        for x in -rx..=rx {
+            let y = half_chord(x, rx, ry);
+            points.push((cx + x, cy - y));
+            points.push((cx + x, cy + y));
+        }
+        for y in -ry..=ry {
+            let x = half_chord(y, ry, rx);
+            points.push((cx - x, cy + y));
+            points.push((cx + x, cy + y));
+        }
+        self.paint_points(&mut points, paint)
+    }
+
+    pub fn fill_ellipse(
+        &mut self,
+        cx: i64,
+        cy: i64,
+        radius_x: u32,
+        radius_y: u32,
+        paint: &Paint,
+    ) -> Result<(), Box<dyn Error>> {
+        let (rx, ry) = (radius_x as i64, radius_y as i64);
+        let mut spans = (-ry..=ry)
+            .map(|y| {
+                let x = half_chord(y, ry, rx);
+                (cy + y, cx - x, cx + x)
+            })
+            .collect();
+        self.paint_spans(&mut spans, paint)
+    }
+
+    // Paints every point once, even where the list repeats it, so that blending doesn't darken
+    // the corners of shapes.
+    fn paint_points(
+        &mut self,
+        points: &mut Vec<(i64, i64)>,
+        paint: &Paint,
+    ) -> Result<(), Box<dyn Error>> {
+        points.sort_unstable_by_key(|(x, y)| (*y, *x));
+        points.dedup();
+        let mut spans = points.iter().map(|(x, y)| (*y, *x, *x)).collect();
+        self.paint_spans(&mut spans, paint)
+    }
+
+    // Paints rows of pixels, given as y, first x and last x.
+    fn paint_spans(
+        &mut self,
+        spans: &mut Vec<(i64, i64, i64)>,
+        paint: &Paint,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.can_hold(paint.color) {
+            *self = self.for_fill(paint.color)?;
+        }
+        let fill = self.fill_samples(paint.color);
+        let color = paint.color.map(|v| v as f32 / 255.0);
+        let (width, height) = (self.width() as i64, self.height() as i64);
+        let channels = self.channels();
+        for (y, first, last) in spans.drain(..) {
+            if y < 0 || y >= height {
+                continue;
+            }
+            for x in first.max(0)..=last.min(width - 1) {
+                let at = (y * width + x) as usize * channels;
+                if paint.blend {
+                    self.blend_at(at, color);
+                } else {
+                    self.samples_mut()[at..at + channels].copy_from_slice(&fill);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Paints single pixels, given as x, y and how much of the pixel is covered from 0.0 to 1.0.
+    // Blending scales the paint's alpha by the coverage; otherwise pixels at least half covered
+    // are set.
+    pub(crate) fn paint_coverage(
+        &mut self,
+        pixels: &[(i64, i64, f32)],
+        paint: &Paint,
+    ) -> Result<(), Box<dyn Error>> {
+        if !self.can_hold(paint.color) {
+            *self = self.for_fill(paint.color)?;
+        }
+        let fill = self.fill_samples(paint.color);
+        let [r, g, b, a] = paint.color.map(|v| v as f32 / 255.0);
+        let (width, height) = (self.width() as i64, self.height() as i64);
+        let channels = self.channels();
+        for (x, y, coverage) in pixels.iter().copied() {
+            if x < 0 || y < 0 || x >= width || y >= height {
+                continue;
+            }
+            let at = (y * width + x) as usize * channels;
+            if paint.blend {
+                self.blend_at(at, [r, g, b, a * coverage]);
+            } else if coverage >= 0.5 {
+                self.samples_mut()[at..at + channels].copy_from_slice(&fill);
+            }
+        }
+        Ok(())
+    }
+
+    // Whether the image is already what for_fill would turn it into for color.
+    fn can_hold(&self, color: [u8; 4]) -> bool {
+        let gray_color = color[0] == color[1] && color[1] == color[2];
+        self.transparency().is_none()
+            && self.color_type() != ColorType::Indexed
+            && self.bit_depth() >= 8
+            && (gray_color || !self.is_gray())
+            && (color[3] == 255 || self.color_type().has_alpha_channel())
+    }
+}
+
+// Half the length of the chord through an ellipse at offset along one axis, with radius along
+// that axis and other along the other one.
+fn half_chord(offset: i64, radius: i64, other: i64) -> i64 {
+    if radius == 0 {
+        return other;
+    }
+    let t = offset as f64 / radius as f64;
+    (other as f64 * (1.0 - t * t).max(0.0).sqrt()).round() as i64
+}
@@ -0,0 +1,42 @@
+use crate::image::{ColorType, Image};
+
+impl Image {
+    // Sobel edge detection. Returns an 8-bit grayscale image of how steeply the luminance changes
+    // at each pixel, black where it is flat and brighter the sharper the edge. A step straight
+    // from black to white comes out white; diagonal steps that steep are clipped. Pixels past the
+    // borders count as copies of the edge pixels, so the borders of the image aren't edges.
+    pub fn sobel_edges(&self) -> Image {
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        let luma = self.luma_plane();
+        let at = |x: isize, y: isize| {
+            let x = x.clamp(0, width as isize - 1) as usize;
+            let y = y.clamp(0, height as isize - 1) as usize;
+            luma[y * width + x]
+        };
+
+        let mut samples = Vec::with_capacity(width * height);
+        for y in 0..height as isize {
+            for x in 0..width as isize {
+                let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2.0 * at(x - 1, y)
+                    - at(x - 1, y + 1);
+                let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                    - at(x - 1, y - 1)
+                    - 2.0 * at(x, y - 1)
+                    - at(x + 1, y - 1);
+                // The kernels weigh each side 4 times, so a full step is 4 * 255.
+                let magnitude = (gx * gx + gy * gy).sqrt() / 4.0;
+                samples.push(magnitude.round().min(255.0) as u16);
+            }
+        }
+        Image::from_samples(
+            self.width(),
+            self.height(),
+            ColorType::Grayscale,
+            8,
+            samples,
+        )
+        .expect("Edge image has the same size as its source")
+    }
+}
@@ -0,0 +1,262 @@
+// Stamping text onto images with bitmap fonts, such as for burning timestamps and labels into
+// generated images. A built-in 5x7 pixel font covers printable ASCII, and fonts can also be cut
+// from a glyph atlas image.
+use std::convert::TryFrom;
+use std::error::Error;
+
+use super::draw::Paint;
+use crate::image::{luma, Image};
+
+// The rows of the built-in glyphs for ' ' to '~', top to bottom, with the leftmost pixel in bit
+// 4 of each row.
+const BUILTIN_GLYPHS: [[u8; 7]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+    [0x0a, 0x0a, 0x0a, 0x00, 0x00, 0x00, 0x00],
+    [0x0a, 0x0a, 0x1f, 0x0a, 0x1f, 0x0a, 0x0a],
+    [0x04, 0x0f, 0x14, 0x0e, 0x05, 0x1e, 0x04],
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03],
+    [0x0c, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0d],
+    [0x0c, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+    [0x00, 0x04, 0x15, 0x0e, 0x15, 0x04, 0x00],
+    [0x00, 0x04, 0x04, 0x1f, 0x04, 0x04, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x0c, 0x04, 0x08],
+    [0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0c, 0x0c],
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+    [0x0e, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0e],
+    [0x04, 0x0c, 0x04, 0x04, 0x04, 0x04, 0x0e],
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1f],
+    [0x1f, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0e],
+    [0x02, 0x06, 0x0a, 0x12, 0x1f, 0x02, 0x02],
+    [0x1f, 0x10, 0x1e, 0x01, 0x01, 0x11, 0x0e],
+    [0x06, 0x08, 0x10, 0x1e, 0x11, 0x11, 0x0e],
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+    [0x0e, 0x11, 0x11, 0x0e, 0x11, 0x11, 0x0e],
+    [0x0e, 0x11, 0x11, 0x0f, 0x01, 0x02, 0x0c],
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x0c, 0x00],
+    [0x00, 0x0c, 0x0c, 0x00, 0x0c, 0x04, 0x08],
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02],
+    [0x00, 0x00, 0x1f, 0x00, 0x1f, 0x00, 0x00],
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08],
+    [0x0e, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    [0x0e, 0x11, 0x01, 0x0d, 0x15, 0x15, 0x0e],
+    [0x0e, 0x11, 0x11, 0x11, 0x1f, 0x11, 0x11],
+    [0x1e, 0x11, 0x11, 0x1e, 0x11, 0x11, 0x1e],
+    [0x0e, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0e],
+    [0x1c, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1c],
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x1f],
+    [0x1f, 0x10, 0x10, 0x1e, 0x10, 0x10, 0x10],
+    [0x0e, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0f],
+    [0x11, 0x11, 0x11, 0x1f, 0x11, 0x11, 0x11],
+    [0x0e, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0c],
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1f],
+    [0x11, 0x1b, 0x15, 0x15, 0x11, 0x11, 0x11],
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+    [0x0e, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+    [0x1e, 0x11, 0x11, 0x1e, 0x10, 0x10, 0x10],
+    [0x0e, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0d],
+    [0x1e, 0x11, 0x11, 0x1e, 0x14, 0x12, 0x11],
+    [0x0f, 0x10, 0x10, 0x0e, 0x01, 0x01, 0x1e],
+    [0x1f, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0e],
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0a, 0x04],
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0a],
+    [0x11, 0x11, 0x0a, 0x04, 0x0a, 0x11, 0x11],
+    [0x11, 0x11, 0x11, 0x0a, 0x04, 0x04, 0x04],
+    [0x1f, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1f],
+    [0x0e, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0e],
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00],
+    [0x0e, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0e],
+    [0x04, 0x0a, 0x11, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1f],
+    [0x08, 0x04, 0x02, 0x00, 0x00, 0x00, 0x00],
+    [0x00, 0x00, 0x0e, 0x01, 0x0f, 0x11, 0x0f],
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x1e],
+    [0x00, 0x00, 0x0e, 0x10, 0x10, 0x11, 0x0e],
+    [0x01, 0x01, 0x0d, 0x13, 0x11, 0x11, 0x0f],
+    [0x00, 0x00, 0x0e, 0x11, 0x1f, 0x10, 0x0e],
+    [0x06, 0x09, 0x08, 0x1c, 0x08, 0x08, 0x08],
+    [0x00, 0x0f, 0x11, 0x11, 0x0f, 0x01, 0x0e],
+    [0x10, 0x10, 0x16, 0x19, 0x11, 0x11, 0x11],
+    [0x04, 0x00, 0x0c, 0x04, 0x04, 0x04, 0x0e],
+    [0x02, 0x00, 0x06, 0x02, 0x02, 0x12, 0x0c],
+    [0x10, 0x10, 0x12, 0x14, 0x18, 0x14, 0x12],
+    [0x0c, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0e],
+    [0x00, 0x00, 0x1a, 0x15, 0x15, 0x11, 0x11],
+    [0x00, 0x00, 0x16, 0x19, 0x11, 0x11, 0x11],
+    [0x00, 0x00, 0x0e, 0x11, 0x11, 0x11, 0x0e],
+    [0x00, 0x00, 0x1e, 0x11, 0x1e, 0x10, 0x10],
+    [0x00, 0x00, 0x0d, 0x13, 0x0f, 0x01, 0x01],
+    [0x00, 0x00, 0x16, 0x19, 0x10, 0x10, 0x10],
+    [0x00, 0x00, 0x0e, 0x10, 0x0e, 0x01, 0x1e],
+    [0x08, 0x08, 0x1c, 0x08, 0x08, 0x09, 0x06],
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x13, 0x0d],
+    [0x00, 0x00, 0x11, 0x11, 0x11, 0x0a, 0x04],
+    [0x00, 0x00, 0x11, 0x11, 0x15, 0x15, 0x0a],
+    [0x00, 0x00, 0x11, 0x0a, 0x04, 0x0a, 0x11],
+    [0x00, 0x00, 0x11, 0x11, 0x0f, 0x01, 0x0e],
+    [0x00, 0x00, 0x1f, 0x02, 0x04, 0x08, 0x1f],
+    [0x02, 0x04, 0x04, 0x08, 0x04, 0x04, 0x02],
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+    [0x08, 0x04, 0x04, 0x02, 0x04, 0x04, 0x08],
+    [0x00, 0x00, 0x08, 0x15, 0x02, 0x00, 0x00],
+];
+
+#[derive(Debug, Clone)]
+pub struct BitmapFont {
+    // Size of the cell every glyph is drawn in, including the space around it.
+    cell_width: u32,
+    cell_height: u32,
+    // The character of the first glyph. The rest follow in code point order.
+    first: u32,
+    // How much each pixel of each glyph's cell is covered, from 0.0 to 1.0, row by row.
+    glyphs: Vec<Vec<f32>>,
+}
+
+impl Default for BitmapFont {
+    fn default() -> BitmapFont {
+        BitmapFont::builtin()
+    }
+}
+
+impl BitmapFont {
+    // The built-in 5x7 pixel font, in 6x8 pixel cells so that letters and lines stay apart.
+    pub fn builtin() -> BitmapFont {
+        let glyphs = BUILTIN_GLYPHS
+            .iter()
+            .map(|rows| {
+                let mut cell = vec![0.0; 6 * 8];
+                for (y, row) in rows.iter().enumerate() {
+                    for x in 0..5 {
+                        if row & (0x10 >> x) != 0 {
+                            cell[y * 6 + x] = 1.0;
+                        }
+                    }
+                }
+                cell
+            })
+            .collect();
+        BitmapFont {
+            cell_width: 6,
+            cell_height: 8,
+            first: ' ' as u32,
+            glyphs,
+        }
+    }
+
+    // Cuts a font from an atlas of equally sized cells holding the glyphs of consecutive
+    // characters from first, left to right and then top to bottom. Glyphs are light on a dark or
+    // transparent background: how much a pixel is covered is its luminance times its alpha, so
+    // antialiased atlases keep their smooth edges. Cells should include the space wanted between
+    // letters and lines.
+    pub fn from_atlas(
+        atlas: &Image,
+        cell_width: u32,
+        cell_height: u32,
+        first: char,
+    ) -> Result<BitmapFont, Box<dyn Error>> {
+        if cell_width == 0 || cell_height == 0 {
+            return Err("Glyph cells must be at least one pixel wide and tall.".into());
+        }
+        let columns = atlas.width() / cell_width;
+        let rows = atlas.height() / cell_height;
+        if columns == 0 || rows == 0 {
+            return Err("The atlas is smaller than one glyph cell.".into());
+        }
+        let mut glyphs = Vec::with_capacity((columns * rows) as usize);
+        for row in 0..rows {
+            for column in 0..columns {
+                let mut cell = Vec::with_capacity((cell_width * cell_height) as usize);
+                for y in row * cell_height..(row + 1) * cell_height {
+                    for x in column * cell_width..(column + 1) * cell_width {
+                        let at =
+                            (y as usize * atlas.width() as usize + x as usize) * atlas.channels();
+                        let [r, g, b, a] = atlas.rgba_at(at);
+                        cell.push(luma(r, g, b) * a);
+                    }
+                }
+                glyphs.push(cell);
+            }
+        }
+        Ok(BitmapFont {
+            cell_width,
+            cell_height,
+            first: first as u32,
+            glyphs,
+        })
+    }
+
+    // Width and height text takes up when drawn at the given scale, with lines split at '\n'.
+    pub fn text_size(&self, text: &str, scale: u32) -> (u32, u32) {
+        let columns = text.lines().map(|l| l.chars().count()).max().unwrap_or(0);
+        let lines = text.lines().count();
+        let size = |count: usize, cell: u32| {
+            u32::try_from(count)
+                .unwrap_or(u32::MAX)
+                .saturating_mul(cell)
+                .saturating_mul(scale.max(1))
+        };
+        (
+            size(columns, self.cell_width),
+            size(lines, self.cell_height),
+        )
+    }
+
+    // The glyph for c, falling back to the one for '?'.
+    fn glyph(&self, c: char) -> Option<&Vec<f32>> {
+        let find = |c: char| {
+            (c as u32)
+                .checked_sub(self.first)
+                .and_then(|i| self.glyphs.get(i as usize))
+        };
+        find(c).or_else(|| find('?'))
+    }
+}
+
+impl Image {
+    // Draws text with its top left corner at x, y, with every pixel of the font drawn as a
+    // square of scale by scale pixels. Lines are split at '\n', and characters the font has no
+    // glyph for are drawn as '?', or left blank if it has no '?' either. The image is converted
+    // for the paint's color the way the drawing methods convert it.
+    pub fn draw_text(
+        &mut self,
+        text: &str,
+        x: i64,
+        y: i64,
+        font: &BitmapFont,
+        scale: u32,
+        paint: &Paint,
+    ) -> Result<(), Box<dyn Error>> {
+        let scale = scale.max(1) as i64;
+        let (cell_width, cell_height) = (font.cell_width as i64, font.cell_height as i64);
+        let mut pixels = Vec::new();
+        for (row, line) in text.lines().enumerate() {
+            let top = y + row as i64 * cell_height * scale;
+            for (column, c) in line.chars().enumerate() {
+                let left = x + column as i64 * cell_width * scale;
+                let glyph = match font.glyph(c) {
+                    Some(glyph) => glyph,
+                    None => continue,
+                };
+                for (i, coverage) in glyph.iter().enumerate() {
+                    if *coverage == 0.0 {
+                        continue;
+                    }
+                    let gx = left + (i as i64 % cell_width) * scale;
+                    let gy = top + (i as i64 / cell_width) * scale;
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            pixels.push((gx + dx, gy + dy, *coverage));
+                        }
+                    }
+                }
+            }
+        }
+        self.paint_coverage(&pixels, paint)
+    }
+}
@@ -0,0 +1,97 @@
+// Images made from scratch, such as placeholders and test fixtures. Colors are 8-bit RGBA and the
+// images are 8-bit, using the smallest color type that holds the colors given.
+use std::error::Error;
+
+use crate::image::{ColorType, Image};
+
+impl Image {
+    // An image filled with color.
+    pub fn solid(width: u32, height: u32, color: [u8; 4]) -> Result<Image, Box<dyn Error>> {
+        let color_type = color_type_for(&[color]);
+        let pixel = pixel_for(color_type, color);
+        let samples = pixel
+            .iter()
+            .copied()
+            .cycle()
+            .take(width as usize * height as usize * pixel.len())
+            .collect();
+        Image::from_samples(width, height, color_type, 8, samples)
+    }
+
+    // An image that blends from one color to the other in a straight line. The angle is in
+    // degrees clockwise, with 0 going from left to right and 90 from top to bottom, and the
+    // blend runs between the corners the line meets first and last, so both colors show in
+    // full there. Blending is done with premultiplied alpha, so fading a color out to
+    // transparency doesn't pass through the transparent color.
+    pub fn linear_gradient(
+        width: u32,
+        height: u32,
+        from: [u8; 4],
+        to: [u8; 4],
+        angle: f32,
+    ) -> Result<Image, Box<dyn Error>> {
+        let color_type = color_type_for(&[from, to]);
+        let premultiply = |color: [u8; 4]| {
+            let alpha = color[3] as f32 / 255.0;
+            [
+                color[0] as f32 * alpha,
+                color[1] as f32 * alpha,
+                color[2] as f32 * alpha,
+                alpha,
+            ]
+        };
+        let (from, to) = (premultiply(from), premultiply(to));
+
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (w, h) = (width as f32, height as f32);
+        let extent = (w * cos.abs() + h * sin.abs()).max(f32::EPSILON);
+        let mut samples =
+            Vec::with_capacity(width as usize * height as usize * color_type.channels());
+        for y in 0..height {
+            for x in 0..width {
+                let dx = x as f32 + 0.5 - w / 2.0;
+                let dy = y as f32 + 0.5 - h / 2.0;
+                let t = ((dx * cos + dy * sin) / extent + 0.5).clamp(0.0, 1.0);
+                let mix = |c: usize| from[c] + (to[c] - from[c]) * t;
+                let alpha = mix(3);
+                let color = |c: usize| {
+                    if alpha == 0.0 {
+                        0
+                    } else {
+                        (mix(c) / alpha).round().clamp(0.0, 255.0) as u8
+                    }
+                };
+                let pixel = [color(0), color(1), color(2), (alpha * 255.0).round() as u8];
+                samples.extend(pixel_for(color_type, pixel));
+            }
+        }
+        Image::from_samples(width, height, color_type, 8, samples)
+    }
+}
+
+// Gray when every color is gray, with alpha when any isn't opaque.
+fn color_type_for(colors: &[[u8; 4]]) -> ColorType {
+    let gray = colors.iter().all(|c| c[0] == c[1] && c[1] == c[2]);
+    let opaque = colors.iter().all(|c| c[3] == 255);
+    match (gray, opaque) {
+        (true, true) => ColorType::Grayscale,
+        (true, false) => ColorType::GrayscaleAlpha,
+        (false, true) => ColorType::Truecolor,
+        (false, false) => ColorType::TruecolorAlpha,
+    }
+}
+
+fn pixel_for(color_type: ColorType, color: [u8; 4]) -> Vec<u16> {
+    let [r, g, b, a] = [
+        color[0] as u16,
+        color[1] as u16,
+        color[2] as u16,
+        color[3] as u16,
+    ];
+    match color_type {
+        ColorType::Grayscale => vec![r],
+        ColorType::GrayscaleAlpha => vec![r, a],
+        ColorType::Truecolor => vec![r, g, b],
+        _ => vec![r, g, b, a],
+    }
+}
@@ -0,0 +1,77 @@
+use std::error::Error;
+
+use crate::image::{ColorType, Image};
+use crate::png::PNGFile;
+
+// Luminance weights for the red, green and blue channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LumaWeights {
+    // SDTV weights (0.299, 0.587, 0.114), the traditional choice for scanned documents.
+    #[default]
+    Rec601,
+    // HDTV and sRGB weights (0.2126, 0.7152, 0.0722).
+    Rec709,
+}
+
+impl LumaWeights {
+    pub fn luma(self, r: f32, g: f32, b: f32) -> f32 {
+        match self {
+            LumaWeights::Rec601 => 0.299 * r + 0.587 * g + 0.114 * b,
+            LumaWeights::Rec709 => 0.2126 * r + 0.7152 * g + 0.0722 * b,
+        }
+    }
+}
+
+impl Image {
+    // Converts to grayscale with the given luminance weights. Images with an alpha channel
+    // become gray+alpha, everything else plain grayscale. The bit depth is kept, except that
+    // indexed and sub-byte images with alpha come out at 8 bits.
+    pub fn to_grayscale(&self, weights: LumaWeights) -> Image {
+        // Gray images are already in the right form, apart from a tRNS key becoming alpha.
+        if self.is_gray() {
+            return self.expand_transparency();
+        }
+
+        let has_alpha = self.color_type().has_alpha_channel() || self.transparency().is_some();
+        let color_type = if has_alpha {
+            ColorType::GrayscaleAlpha
+        } else {
+            ColorType::Grayscale
+        };
+        let bit_depth = if self.color_type() == ColorType::Indexed {
+            8
+        } else if color_type.allows_bit_depth(self.bit_depth()) {
+            self.bit_depth()
+        } else {
+            8
+        };
+
+        let max = ((1u32 << bit_depth) - 1) as f32;
+        let pixel_count = self.width() as usize * self.height() as usize;
+        let mut samples = Vec::with_capacity(pixel_count * color_type.channels());
+        for i in 0..pixel_count {
+            let [r, g, b, a] = self.rgba_at(i * self.channels());
+            samples.push((weights.luma(r, g, b) * max).round() as u16);
+            if has_alpha {
+                samples.push((a * max).round() as u16);
+            }
+        }
+
+        Image::from_samples(self.width(), self.height(), color_type, bit_depth, samples)
+            .expect("Grayscale image has the same dimensions as its source")
+    }
+}
+
+impl PNGFile {
+    // Decodes the file, converts it to grayscale and re-encodes it as PNG color type 0. Any
+    // alpha is dropped, since color type 0 has none.
+    pub fn to_grayscale_png(&self, weights: LumaWeights) -> Result<PNGFile, Box<dyn Error>> {
+        let gray = self.decode()?.to_grayscale(weights);
+        let gray = if gray.color_type() == ColorType::Grayscale {
+            gray
+        } else {
+            gray.convert(ColorType::Grayscale)?
+        };
+        PNGFile::from_image(&gray)
+    }
+}
@@ -0,0 +1,44 @@
+use crate::image::{scale_sample, ColorType, Image};
+
+// Counts of each sample value, per channel in the image's own channel order, and of each
+// luminance level.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    // One list of counts per channel, indexed by sample value. 16-bit images get 65536 bins and
+    // everything else 256. Samples of bit depths below 8 are scaled to 0 - 255 first, so that a
+    // 1-bit image's pixels land in bins 0 and 255. For indexed images the only channel holds
+    // palette indices.
+    pub channels: Vec<Vec<u64>>,
+    // 256 bins of Rec. 601 luminance, from the resolved colors whatever the color type.
+    pub luma: Vec<u64>,
+}
+
+impl Image {
+    pub fn histogram(&self) -> Histogram {
+        let channels = self.channels();
+        let bins = if self.bit_depth() == 16 { 1 << 16 } else { 256 };
+        let scale = self.bit_depth() < 8 && self.color_type() != ColorType::Indexed;
+        let max = self.max_value();
+
+        let mut counts = vec![vec![0u64; bins]; channels];
+        for pixel in self.samples().chunks_exact(channels) {
+            for (channel, sample) in counts.iter_mut().zip(pixel.iter()) {
+                let value = if scale {
+                    scale_sample(*sample, max, 255)
+                } else {
+                    *sample
+                };
+                channel[value as usize] += 1;
+            }
+        }
+
+        let mut luma = vec![0u64; 256];
+        for value in self.luma_plane() {
+            luma[(value.round() as usize).min(255)] += 1;
+        }
+        Histogram {
+            channels: counts,
+            luma,
+        }
+    }
+}
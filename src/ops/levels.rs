@@ -0,0 +1,59 @@
+// Automatic tone corrections for low-contrast images, such as faded scans. Both build a curve
+// from the luminance histogram and apply it to every color channel the way the tone adjustments
+// do, so colors keep their hue apart from rounding.
+use crate::image::Image;
+
+impl Image {
+    // Spreads the luminance levels out so that each is about equally common, which brings out
+    // detail in images crowded into a narrow range of tones.
+    pub fn equalize(&self) -> Image {
+        let luma = self.histogram().luma;
+        let total: u64 = luma.iter().sum();
+        let mut cdf = Vec::with_capacity(luma.len());
+        let mut sum = 0;
+        for count in &luma {
+            sum += count;
+            cdf.push(sum);
+        }
+        // The darkest level present maps to black.
+        let first = cdf.iter().copied().find(|c| *c > 0).unwrap_or(0);
+        if total == first {
+            return self.clone();
+        }
+        let table: Vec<f32> = cdf
+            .iter()
+            .map(|c| c.saturating_sub(first) as f32 / (total - first) as f32)
+            .collect();
+        self.map_colors(|v| {
+            let at = v * 255.0;
+            let i = (at.floor() as usize).min(254);
+            let t = at - i as f32;
+            table[i] * (1.0 - t) + table[i + 1] * t
+        })
+    }
+
+    // Stretches the luminance range to fill black to white, ignoring the given fraction of the
+    // darkest and of the brightest pixels, such as 0.005, so that a few specks don't stop the
+    // stretch.
+    pub fn auto_levels(&self, clip: f32) -> Image {
+        let luma = self.histogram().luma;
+        let total: u64 = luma.iter().sum();
+        let cutoff = (total as f32 * clip.clamp(0.0, 0.5)) as u64;
+        let level_at = |levels: &mut dyn Iterator<Item = (usize, &u64)>| {
+            let mut sum = 0;
+            for (level, count) in levels {
+                sum += count;
+                if sum > cutoff {
+                    return level;
+                }
+            }
+            0
+        };
+        let low = level_at(&mut luma.iter().enumerate()) as f32 / 255.0;
+        let high = level_at(&mut luma.iter().enumerate().rev()) as f32 / 255.0;
+        if high <= low {
+            return self.clone();
+        }
+        self.map_colors(|v| (v - low) / (high - low))
+    }
+}
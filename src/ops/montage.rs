@@ -0,0 +1,88 @@
+use std::convert::TryFrom;
+use std::error::Error;
+
+use crate::image::{ColorType, Image, ImageError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MontageOptions {
+    // Images per row. None makes the grid as close to square as it can.
+    pub columns: Option<u32>,
+    // Size of each cell. None makes the cells as wide and as tall as the largest image.
+    pub cell_width: Option<u32>,
+    pub cell_height: Option<u32>,
+    // Pixels between cells and around the edge of the sheet.
+    pub spacing: u32,
+    // 8-bit RGBA color behind the images; [0, 0, 0, 0] leaves it transparent.
+    pub background: [u8; 4],
+}
+
+impl Default for MontageOptions {
+    fn default() -> MontageOptions {
+        MontageOptions {
+            columns: None,
+            cell_width: None,
+            cell_height: None,
+            spacing: 4,
+            background: [255, 255, 255, 255],
+        }
+    }
+}
+
+impl Image {
+    // Lays images out left to right and top to bottom in a grid, as a contact sheet. Images larger
+    // than a cell are shrunk to fit it with thumbnail, and each is centered in its cell. The sheet
+    // is RGB, or RGBA when the background isn't opaque, and 16-bit when any image is.
+    pub fn montage(images: &[Image], options: &MontageOptions) -> Result<Image, Box<dyn Error>> {
+        if images.is_empty() {
+            return Err(ImageError::new("A montage needs at least one image.").into());
+        }
+        let count = images.len() as u32;
+        let columns = options
+            .columns
+            .unwrap_or_else(|| (count as f64).sqrt().ceil() as u32)
+            .clamp(1, count);
+        let rows = count.div_ceil(columns);
+        let cell_width = options
+            .cell_width
+            .unwrap_or_else(|| images.iter().map(|i| i.width()).max().unwrap_or(1));
+        let cell_height = options
+            .cell_height
+            .unwrap_or_else(|| images.iter().map(|i| i.height()).max().unwrap_or(1));
+        if cell_width == 0 || cell_height == 0 {
+            return Err(ImageError::new("Width and height must be non-zero numbers.").into());
+        }
+
+        let spacing = options.spacing as u64;
+        let size = |cells: u32, cell: u32| -> Result<u32, Box<dyn Error>> {
+            let size = cells as u64 * (cell as u64 + spacing) + spacing;
+            u32::try_from(size).map_err(|_| "The montage is too large.".into())
+        };
+        let (width, height) = (size(columns, cell_width)?, size(rows, cell_height)?);
+        let color_type = if options.background[3] == 255 {
+            ColorType::Truecolor
+        } else {
+            ColorType::TruecolorAlpha
+        };
+        let bit_depth = if images.iter().any(|i| i.bit_depth() == 16) {
+            16
+        } else {
+            8
+        };
+
+        let mut sheet = Image::new(width, height, color_type, bit_depth)?;
+        let fill = sheet.fill_samples(options.background);
+        for (sample, value) in sheet.samples_mut().iter_mut().zip(fill.iter().cycle()) {
+            *sample = *value;
+        }
+        for (i, image) in images.iter().enumerate() {
+            let image = image.thumbnail(cell_width, cell_height)?;
+            let (column, row) = (i as u32 % columns, i as u32 / columns);
+            let cell_x = spacing + column as u64 * (cell_width as u64 + spacing);
+            let cell_y = spacing + row as u64 * (cell_height as u64 + spacing);
+            let x = cell_x + (cell_width - image.width()) as u64 / 2;
+            let y = cell_y + (cell_height - image.height()) as u64 / 2;
+            sheet.draw_over(&image, x as i64, y as i64, 1.0);
+        }
+        Ok(sheet)
+    }
+}
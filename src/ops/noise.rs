@@ -0,0 +1,97 @@
+// Adding noise, such as for making test data for denoisers and compression, and taking it out
+// again, such as for cleaning up scans before re-encoding them. Images are converted the way
+// resize converts them, and alpha is left alone.
+use std::error::Error;
+
+use super::stego::SplitMix64;
+use crate::image::Image;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NoiseKind {
+    // Normally distributed changes to every sample, like sensor noise. Amount is the standard
+    // deviation, from 0.0 to 1.0 of the full range.
+    #[default]
+    Gaussian,
+    // Changes spread evenly between -amount and amount of the full range.
+    Uniform,
+    // Pixels turned black or white at random. Amount is the fraction of pixels changed.
+    SaltAndPepper,
+}
+
+impl Image {
+    // Adds random noise of the given kind. The same seed always gives the same noise, so test
+    // images can be made again exactly.
+    pub fn add_noise(
+        &self,
+        amount: f32,
+        kind: NoiseKind,
+        seed: u64,
+    ) -> Result<Image, Box<dyn Error>> {
+        let mut image = self.for_interpolation()?;
+        let mut rng = SplitMix64::new(seed);
+        let mut unit = || (rng.next() >> 40) as f32 / (1u64 << 24) as f32;
+        let max = image.max_value() as f32;
+        let channels = image.channels();
+        let color_channels = if image.color_type().has_alpha_channel() {
+            channels - 1
+        } else {
+            channels
+        };
+
+        for pixel in image.samples_mut().chunks_exact_mut(channels) {
+            let colors = &mut pixel[..color_channels];
+            if kind == NoiseKind::SaltAndPepper {
+                if unit() < amount {
+                    let value = if unit() < 0.5 { 0 } else { max as u16 };
+                    colors.iter_mut().for_each(|s| *s = value);
+                }
+                continue;
+            }
+            for sample in colors.iter_mut() {
+                let offset = match kind {
+                    // Box-Muller transform, using 1 - u so the logarithm never sees 0.
+                    NoiseKind::Gaussian => {
+                        let (u, v) = (1.0 - unit(), unit());
+                        (-2.0 * u.ln()).sqrt() * (2.0 * std::f32::consts::PI * v).cos() * amount
+                    }
+                    _ => (unit() * 2.0 - 1.0) * amount,
+                };
+                *sample = (*sample as f32 + offset * max).round().clamp(0.0, max) as u16;
+            }
+        }
+        Ok(image)
+    }
+
+    // Replaces every sample with the median of the samples in the square reaching radius pixels
+    // around it, which removes specks and salt-and-pepper noise while keeping edges sharp.
+    // Pixels past the edges count as copies of the edge pixels.
+    pub fn median_filter(&self, radius: u32) -> Result<Image, Box<dyn Error>> {
+        let source = self.for_interpolation()?;
+        if radius == 0 {
+            return Ok(source);
+        }
+        let (width, height) = (source.width() as i64, source.height() as i64);
+        let channels = source.channels();
+        let radius = radius as i64;
+        let mut image = source.clone();
+        let mut window = Vec::with_capacity(((2 * radius + 1) * (2 * radius + 1)) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                for c in 0..channels {
+                    window.clear();
+                    for wy in y - radius..=y + radius {
+                        let row = wy.clamp(0, height - 1) * width;
+                        for wx in x - radius..=x + radius {
+                            let at = (row + wx.clamp(0, width - 1)) as usize * channels + c;
+                            window.push(source.samples()[at]);
+                        }
+                    }
+                    let middle = window.len() / 2;
+                    let median = *window.select_nth_unstable(middle).1;
+                    image.samples_mut()[(y * width + x) as usize * channels + c] = median;
+                }
+            }
+        }
+        Ok(image)
+    }
+}
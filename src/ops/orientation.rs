@@ -0,0 +1,95 @@
+use crate::image::Image;
+
+impl Image {
+    // Rotates a quarter turn clockwise, swapping the width and height.
+    pub fn rotate90(&self) -> Image {
+        self.apply_orientation(6)
+    }
+
+    pub fn rotate180(&self) -> Image {
+        self.apply_orientation(3)
+    }
+
+    // Rotates a quarter turn counterclockwise, swapping the width and height.
+    pub fn rotate270(&self) -> Image {
+        self.apply_orientation(8)
+    }
+
+    // Mirrors left to right.
+    pub fn flip_horizontal(&self) -> Image {
+        let mut image = self.clone();
+        let channels = self.channels();
+        let row_len = self.width() as usize * channels;
+        for row in image.samples_mut().chunks_exact_mut(row_len) {
+            // Reversing the row reverses the samples within each pixel too, which reversing each
+            // pixel again undoes.
+            row.reverse();
+            for pixel in row.chunks_exact_mut(channels) {
+                pixel.reverse();
+            }
+        }
+        image
+    }
+
+    // Mirrors top to bottom.
+    pub fn flip_vertical(&self) -> Image {
+        let mut image = self.clone();
+        let row_len = self.width() as usize * self.channels();
+        let height = self.height() as usize;
+        let samples = image.samples_mut();
+        for y in 0..height / 2 {
+            let (top, bottom) = samples.split_at_mut((height - 1 - y) * row_len);
+            top[y * row_len..(y + 1) * row_len].swap_with_slice(&mut bottom[..row_len]);
+        }
+        image
+    }
+
+    // Returns the image as it should be displayed given an EXIF orientation value (1 - 8), so
+    // that the result has orientation 1. Values 5 to 8 swap the width and height. Unknown values
+    // leave the image unchanged.
+    pub fn apply_orientation(&self, orientation: u16) -> Image {
+        let (w, h) = (self.width() as usize, self.height() as usize);
+        // Maps a pixel in the output to the pixel in this image that lands there.
+        let source: fn(usize, usize, usize, usize) -> (usize, usize) = match orientation {
+            // Mirrored horizontally
+            2 => |x, y, w, _| (w - 1 - x, y),
+            // Rotated 180 degrees
+            3 => |x, y, w, h| (w - 1 - x, h - 1 - y),
+            // Mirrored vertically
+            4 => |x, y, _, h| (x, h - 1 - y),
+            // Mirrored along the top-left to bottom-right diagonal
+            5 => |x, y, _, _| (y, x),
+            // Needs rotating 90 degrees clockwise
+            6 => |x, y, _, h| (y, h - 1 - x),
+            // Mirrored along the top-right to bottom-left diagonal
+            7 => |x, y, w, h| (w - 1 - y, h - 1 - x),
+            // Needs rotating 90 degrees counterclockwise
+            8 => |x, y, w, _| (w - 1 - y, x),
+            _ => return self.clone(),
+        };
+        let (out_w, out_h) = if orientation >= 5 { (h, w) } else { (w, h) };
+
+        let channels = self.channels();
+        let samples = self.samples();
+        let mut out = Vec::with_capacity(samples.len());
+        for y in 0..out_h {
+            for x in 0..out_w {
+                let (sx, sy) = source(x, y, w, h);
+                let at = (sy * w + sx) * channels;
+                out.extend_from_slice(&samples[at..at + channels]);
+            }
+        }
+
+        let mut image = Image::from_samples(
+            out_w as u32,
+            out_h as u32,
+            self.color_type(),
+            self.bit_depth(),
+            out,
+        )
+        .expect("Reoriented image has the same number of pixels as its source");
+        image.set_palette(self.palette().cloned());
+        image.set_transparency(self.transparency().cloned());
+        image
+    }
+}
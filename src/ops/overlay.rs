@@ -0,0 +1,123 @@
+use std::error::Error;
+
+use crate::image::{luma, ColorType, Image};
+
+impl Image {
+    // Draws other on top of the image with its top left corner at x, y, blending with the usual
+    // source-over rule: where other is opaque it covers the image and where it is partly
+    // transparent the image shows through. Parts of other that fall outside the image are cut
+    // off, so x and y may be negative.
+    //
+    // The result has the image's size. Blending makes new colors, so it is converted the way
+    // resize converts images, and a gray image becomes truecolor when other isn't gray.
+    pub fn overlay(&self, other: &Image, x: i64, y: i64) -> Result<Image, Box<dyn Error>> {
+        let mut image = self.overlay_base(other)?;
+        image.draw_over(other, x, y, 1.0);
+        Ok(image)
+    }
+
+    // The image converted so that other can be blended into it.
+    pub(crate) fn overlay_base(&self, other: &Image) -> Result<Image, Box<dyn Error>> {
+        let base = self.for_interpolation()?;
+        match (base.color_type(), other.is_gray()) {
+            (ColorType::Grayscale, false) => {
+                base.convert_to(ColorType::Truecolor, base.bit_depth())
+            }
+            (ColorType::GrayscaleAlpha, false) => {
+                base.convert_to(ColorType::TruecolorAlpha, base.bit_depth())
+            }
+            _ => Ok(base),
+        }
+    }
+
+    // Blends other into the image in place, with its alpha multiplied by opacity. The image must
+    // come from overlay_base.
+    pub(crate) fn draw_over(&mut self, other: &Image, x: i64, y: i64, opacity: f32) {
+        let left = x.max(0);
+        let top = y.max(0);
+        let right = (x + other.width() as i64).min(self.width() as i64);
+        let bottom = (y + other.height() as i64).min(self.height() as i64);
+
+        for by in top..bottom {
+            for bx in left..right {
+                let (ox, oy) = ((bx - x) as usize, (by - y) as usize);
+                let [r, g, b, a] =
+                    other.rgba_at((oy * other.width() as usize + ox) * other.channels());
+                let at = (by as usize * self.width() as usize + bx as usize) * self.channels();
+                self.blend_at(at, [r, g, b, a * opacity]);
+            }
+        }
+    }
+
+    // Blends an RGBA color, normalized to 0.0 - 1.0, over the pixel at the given sample offset.
+    // The image must come from overlay_base or be able to hold the color.
+    pub(crate) fn blend_at(&mut self, at: usize, color: [f32; 4]) {
+        let [sr, sg, sb, sa] = color;
+        if sa == 0.0 {
+            return;
+        }
+        let channels = self.channels();
+        let max = self.max_value() as f32;
+        let [dr, dg, db, da] = self.rgba_at(at);
+        let out_alpha = sa + da * (1.0 - sa);
+        let blend = |s: f32, d: f32| (s * sa + d * da * (1.0 - sa)) / out_alpha;
+        let (r, g, b) = (blend(sr, dr), blend(sg, dg), blend(sb, db));
+
+        let mut pixel = if self.is_gray() {
+            vec![luma(r, g, b)]
+        } else {
+            vec![r, g, b]
+        };
+        if self.color_type().has_alpha_channel() {
+            pixel.push(out_alpha);
+        }
+        let samples = &mut self.samples_mut()[at..at + channels];
+        for (sample, value) in samples.iter_mut().zip(pixel) {
+            *sample = (value * max).round().clamp(0.0, max) as u16;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gray_alpha_over_gray_alpha() {
+        for bit_depth in [8, 16] {
+            let max = ((1u32 << bit_depth) - 1) as u16;
+            let base = Image::from_samples(
+                2,
+                1,
+                ColorType::GrayscaleAlpha,
+                bit_depth,
+                vec![0, max, 0, 0],
+            )
+            .unwrap();
+            let top = Image::from_samples(1, 1, ColorType::GrayscaleAlpha, bit_depth, vec![max, 0])
+                .unwrap();
+            let covered = base.overlay(&top, 0, 0).unwrap();
+            assert_eq!(covered.color_type(), ColorType::GrayscaleAlpha);
+            assert_eq!(covered.bit_depth(), bit_depth);
+            // A fully transparent top pixel leaves the base alone.
+            assert_eq!(covered.samples(), base.samples());
+
+            let top =
+                Image::from_samples(1, 1, ColorType::GrayscaleAlpha, bit_depth, vec![max, max])
+                    .unwrap();
+            let covered = base.overlay(&top, 1, 0).unwrap();
+            assert_eq!(covered.samples(), [0, max, max, max]);
+        }
+    }
+
+    #[test]
+    fn gray_alpha_under_color_becomes_truecolor_alpha() {
+        let base =
+            Image::from_samples(1, 1, ColorType::GrayscaleAlpha, 16, vec![0, 0xFFFF]).unwrap();
+        let red = Image::from_samples(1, 1, ColorType::Truecolor, 8, vec![255, 0, 0]).unwrap();
+        let covered = base.overlay(&red, 0, 0).unwrap();
+        assert_eq!(covered.color_type(), ColorType::TruecolorAlpha);
+        assert_eq!(covered.bit_depth(), 16);
+        assert_eq!(covered.samples(), [0xFFFF, 0, 0, 0xFFFF]);
+    }
+}
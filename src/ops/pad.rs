@@ -0,0 +1,50 @@
+use std::error::Error;
+
+use crate::image::Image;
+
+impl Image {
+    // Extends the canvas by the given number of pixels on each side, filling the new area with
+    // fill, an 8-bit RGBA color; [0, 0, 0, 0] leaves it transparent. The image is converted the
+    // way rotate converts it.
+    pub fn pad(
+        &self,
+        left: u32,
+        top: u32,
+        right: u32,
+        bottom: u32,
+        fill: [u8; 4],
+    ) -> Result<Image, Box<dyn Error>> {
+        let source = self.for_fill(fill)?;
+        let width = source
+            .width()
+            .checked_add(left)
+            .and_then(|w| w.checked_add(right))
+            .ok_or("The padded image is too wide.")?;
+        let height = source
+            .height()
+            .checked_add(top)
+            .and_then(|h| h.checked_add(bottom))
+            .ok_or("The padded image is too tall.")?;
+
+        let fill = source.fill_samples(fill);
+        let samples = fill
+            .iter()
+            .copied()
+            .cycle()
+            .take(width as usize * height as usize * fill.len())
+            .collect();
+        let mut image = Image::from_samples(
+            width,
+            height,
+            source.color_type(),
+            source.bit_depth(),
+            samples,
+        )?;
+        let row_len = source.width() as usize * source.channels();
+        for (y, row) in source.samples().chunks_exact(row_len).enumerate() {
+            let at = ((y + top as usize) * width as usize + left as usize) * source.channels();
+            image.samples_mut()[at..at + row_len].copy_from_slice(row);
+        }
+        Ok(image)
+    }
+}
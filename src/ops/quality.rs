@@ -0,0 +1,129 @@
+// Measures of how far a lossy version of an image, such as one reduced to a palette or converted
+// to a lower bit depth, is from the original. MSE and PSNR count raw sample errors; SSIM compares
+// local structure, which follows what people notice more closely.
+//
+// Colors are compared after resolving palettes and transparency, so images of different color
+// types and bit depths can be compared. Alpha is compared as well when either image has any.
+use std::error::Error;
+
+use crate::image::{Image, ImageError};
+
+// Standard deviation and radius of the Gaussian window SSIM averages over, as in the paper by
+// Wang, Bovik, Sheikh and Simoncelli.
+const SSIM_SIGMA: f64 = 1.5;
+const SSIM_RADIUS: usize = 5;
+
+// Constants that keep SSIM stable where the means or variances are close to zero.
+const SSIM_C1: f64 = (0.01 * 255.0) * (0.01 * 255.0);
+const SSIM_C2: f64 = (0.03 * 255.0) * (0.03 * 255.0);
+
+impl Image {
+    // Mean squared error over all color samples, on a 0 - 255 scale whatever the bit depth. 0
+    // means the images are identical.
+    pub fn mse(&self, other: &Image) -> Result<f64, Box<dyn Error>> {
+        check_same_size(self, other)?;
+        let compare_alpha = !self.is_fully_opaque() || !other.is_fully_opaque();
+        let channels = if compare_alpha { 4 } else { 3 };
+        let mut total = 0.0f64;
+        for i in 0..self.width() as usize * self.height() as usize {
+            let a = self.rgba_at(i * self.channels());
+            let b = other.rgba_at(i * other.channels());
+            for c in 0..channels {
+                let error = (a[c] - b[c]) as f64 * 255.0;
+                total += error * error;
+            }
+        }
+        Ok(total / (self.width() as f64 * self.height() as f64 * channels as f64))
+    }
+
+    // Peak signal-to-noise ratio in decibels. Higher is closer: above 40 dB differences are hard
+    // to see and below 30 dB they usually show. Identical images give infinity.
+    pub fn psnr(&self, other: &Image) -> Result<f64, Box<dyn Error>> {
+        let mse = self.mse(other)?;
+        if mse == 0.0 {
+            return Ok(f64::INFINITY);
+        }
+        Ok(10.0 * (255.0 * 255.0 / mse).log10())
+    }
+
+    // Mean structural similarity of the luminance, from -1 to 1, where 1 means identical.
+    // Ignores alpha.
+    pub fn ssim(&self, other: &Image) -> Result<f64, Box<dyn Error>> {
+        check_same_size(self, other)?;
+        let (width, height) = (self.width() as usize, self.height() as usize);
+        let x: Vec<f64> = self.luma_plane().iter().map(|v| *v as f64).collect();
+        let y: Vec<f64> = other.luma_plane().iter().map(|v| *v as f64).collect();
+        let products = |a: &[f64], b: &[f64]| -> Vec<f64> {
+            a.iter().zip(b.iter()).map(|(a, b)| a * b).collect()
+        };
+
+        let kernel = gaussian_kernel();
+        let mean_x = gaussian_blur(&x, width, height, &kernel);
+        let mean_y = gaussian_blur(&y, width, height, &kernel);
+        let mean_xx = gaussian_blur(&products(&x, &x), width, height, &kernel);
+        let mean_yy = gaussian_blur(&products(&y, &y), width, height, &kernel);
+        let mean_xy = gaussian_blur(&products(&x, &y), width, height, &kernel);
+
+        let mut total = 0.0;
+        for i in 0..width * height {
+            let (mx, my) = (mean_x[i], mean_y[i]);
+            let variance_x = mean_xx[i] - mx * mx;
+            let variance_y = mean_yy[i] - my * my;
+            let covariance = mean_xy[i] - mx * my;
+            total += ((2.0 * mx * my + SSIM_C1) * (2.0 * covariance + SSIM_C2))
+                / ((mx * mx + my * my + SSIM_C1) * (variance_x + variance_y + SSIM_C2));
+        }
+        Ok(total / (width * height) as f64)
+    }
+}
+
+fn check_same_size(a: &Image, b: &Image) -> Result<(), ImageError> {
+    if (a.width(), a.height()) != (b.width(), b.height()) {
+        return Err(ImageError::new("The images must be the same size."));
+    }
+    Ok(())
+}
+
+fn gaussian_kernel() -> Vec<f64> {
+    let kernel: Vec<f64> = (0..=2 * SSIM_RADIUS)
+        .map(|i| {
+            let d = i as f64 - SSIM_RADIUS as f64;
+            (-d * d / (2.0 * SSIM_SIGMA * SSIM_SIGMA)).exp()
+        })
+        .collect();
+    let sum: f64 = kernel.iter().sum();
+    kernel.iter().map(|k| k / sum).collect()
+}
+
+// Blurs a plane with a separable kernel, repeating the edge values past the borders.
+fn gaussian_blur(plane: &[f64], width: usize, height: usize, kernel: &[f64]) -> Vec<f64> {
+    let radius = kernel.len() / 2;
+    let clamp = |v: isize, len: usize| v.clamp(0, len as isize - 1) as usize;
+    let mut rows = vec![0.0; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            rows[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, weight)| {
+                    let sx = clamp(x as isize + k as isize - radius as isize, width);
+                    plane[y * width + sx] * weight
+                })
+                .sum();
+        }
+    }
+    let mut out = vec![0.0; plane.len()];
+    for y in 0..height {
+        for x in 0..width {
+            out[y * width + x] = kernel
+                .iter()
+                .enumerate()
+                .map(|(k, weight)| {
+                    let sy = clamp(y as isize + k as isize - radius as isize, height);
+                    rows[sy * width + x] * weight
+                })
+                .sum();
+        }
+    }
+    out
+}
@@ -0,0 +1,288 @@
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+
+use super::convert::{indexed_bit_depth, palette_transparency};
+use super::dither::{floyd_steinberg, Dither};
+use crate::image::{ColorType, Image, ImageError};
+
+#[derive(Debug, Clone, Copy)]
+pub struct QuantizeOptions {
+    // Palette size, from 1 to 256.
+    pub max_colors: usize,
+    pub dither: Dither,
+}
+
+impl Default for QuantizeOptions {
+    fn default() -> QuantizeOptions {
+        QuantizeOptions {
+            max_colors: 256,
+            dither: Dither::None,
+        }
+    }
+}
+
+// A box of colors in RGBA space used by median cut, holding each color with its pixel count.
+struct ColorBox {
+    colors: Vec<([u8; 4], u32)>,
+}
+
+impl ColorBox {
+    fn ranges(&self) -> [u8; 4] {
+        let mut min = [255u8; 4];
+        let mut max = [0u8; 4];
+        for (color, _) in &self.colors {
+            for c in 0..4 {
+                min[c] = min[c].min(color[c]);
+                max[c] = max[c].max(color[c]);
+            }
+        }
+        [
+            max[0] - min[0],
+            max[1] - min[1],
+            max[2] - min[2],
+            max[3] - min[3],
+        ]
+    }
+
+    fn widest_channel(&self) -> (usize, u8) {
+        let ranges = self.ranges();
+        let channel = (0..4).max_by_key(|c| ranges[*c]).unwrap();
+        (channel, ranges[channel])
+    }
+
+    fn pixel_count(&self) -> u64 {
+        self.colors.iter().map(|(_, n)| *n as u64).sum()
+    }
+
+    // Splits at the weighted median of the widest channel.
+    fn split(mut self) -> (ColorBox, ColorBox) {
+        let (channel, _) = self.widest_channel();
+        self.colors.sort_by_key(|(color, _)| color[channel]);
+
+        let half = self.pixel_count() / 2;
+        let mut running = 0u64;
+        let mut at = 1;
+        for (i, (_, n)) in self.colors.iter().enumerate() {
+            running += *n as u64;
+            if running >= half {
+                at = (i + 1).clamp(1, self.colors.len() - 1);
+                break;
+            }
+        }
+        let upper = self.colors.split_off(at);
+        (self, ColorBox { colors: upper })
+    }
+
+    fn average(&self) -> [u8; 4] {
+        let total = self.pixel_count().max(1);
+        let mut sums = [0u64; 4];
+        for (color, n) in &self.colors {
+            for c in 0..4 {
+                sums[c] += color[c] as u64 * *n as u64;
+            }
+        }
+        [
+            ((sums[0] + total / 2) / total) as u8,
+            ((sums[1] + total / 2) / total) as u8,
+            ((sums[2] + total / 2) / total) as u8,
+            ((sums[3] + total / 2) / total) as u8,
+        ]
+    }
+}
+
+impl Image {
+    // Reduces the image to at most max_colors (up to 256) using median cut and returns it as an
+    // indexed image with a PLTE palette and tRNS alpha, ready to be written as color type 3.
+    pub fn quantize(&self, max_colors: usize) -> Result<Image, Box<dyn Error>> {
+        self.quantize_with_options(&QuantizeOptions {
+            max_colors,
+            ..QuantizeOptions::default()
+        })
+    }
+
+    pub fn quantize_with_options(
+        &self,
+        options: &QuantizeOptions,
+    ) -> Result<Image, Box<dyn Error>> {
+        let max_colors = options.max_colors;
+        if max_colors == 0 || max_colors > 256 {
+            return Err(ImageError::new("A palette holds between 1 and 256 colors.").into());
+        }
+
+        let pixels = self.rgba8_pixels();
+        // Ordered, so the same image always gets the same palette.
+        let mut histogram: BTreeMap<[u8; 4], u32> = BTreeMap::new();
+        for pixel in &pixels {
+            *histogram.entry(*pixel).or_insert(0) += 1;
+        }
+
+        let palette = if histogram.len() <= max_colors {
+            histogram.keys().copied().collect()
+        } else {
+            median_cut(histogram.into_iter().collect(), max_colors)
+        };
+        let palette = order_palette(palette);
+
+        let samples = match options.dither {
+            Dither::None => {
+                let mut nearest: HashMap<[u8; 4], u16> = HashMap::new();
+                pixels
+                    .iter()
+                    .map(|pixel| {
+                        *nearest
+                            .entry(*pixel)
+                            .or_insert_with(|| nearest_index(&palette, pixel))
+                    })
+                    .collect()
+            }
+            Dither::FloydSteinberg => {
+                let mut values: Vec<f32> = pixels
+                    .iter()
+                    .flat_map(|p| p.iter().map(|c| *c as f32))
+                    .collect();
+                let mut samples = Vec::with_capacity(pixels.len());
+                floyd_steinberg(
+                    &mut values,
+                    self.width() as usize,
+                    self.height() as usize,
+                    4,
+                    |pixel| {
+                        let color = [
+                            pixel[0].round().clamp(0.0, 255.0) as u8,
+                            pixel[1].round().clamp(0.0, 255.0) as u8,
+                            pixel[2].round().clamp(0.0, 255.0) as u8,
+                            pixel[3].round().clamp(0.0, 255.0) as u8,
+                        ];
+                        let index = nearest_index(&palette, &color);
+                        for (v, c) in pixel.iter_mut().zip(palette[index as usize].iter()) {
+                            *v = *c as f32;
+                        }
+                        samples.push(index);
+                    },
+                );
+                samples
+            }
+        };
+
+        self.indexed_from_palette(samples, &palette)
+    }
+
+    pub(crate) fn indexed_from_palette(
+        &self,
+        samples: Vec<u16>,
+        palette: &[[u8; 4]],
+    ) -> Result<Image, Box<dyn Error>> {
+        let mut image = Image::from_samples(
+            self.width(),
+            self.height(),
+            ColorType::Indexed,
+            indexed_bit_depth(palette.len()),
+            samples,
+        )?;
+        image.set_palette(Some(palette.iter().map(|c| [c[0], c[1], c[2]]).collect()));
+        image.set_transparency(palette_transparency(palette));
+        Ok(image)
+    }
+
+    // Every pixel as 8-bit RGBA.
+    pub(crate) fn rgba8_pixels(&self) -> Vec<[u8; 4]> {
+        let pixel_count = self.width() as usize * self.height() as usize;
+        (0..pixel_count)
+            .map(|i| {
+                let rgba = self.rgba_at(i * self.channels());
+                [
+                    (rgba[0] * 255.0).round() as u8,
+                    (rgba[1] * 255.0).round() as u8,
+                    (rgba[2] * 255.0).round() as u8,
+                    (rgba[3] * 255.0).round() as u8,
+                ]
+            })
+            .collect()
+    }
+}
+
+fn median_cut(colors: Vec<([u8; 4], u32)>, max_colors: usize) -> Vec<[u8; 4]> {
+    let mut boxes = vec![ColorBox { colors }];
+    while boxes.len() < max_colors {
+        // Split the box with the widest spread, weighted by how many pixels it covers.
+        let candidate = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.colors.len() > 1)
+            .max_by_key(|(_, b)| b.widest_channel().1 as u64 * b.pixel_count())
+            .map(|(i, _)| i);
+        let index = match candidate {
+            Some(i) => i,
+            None => break,
+        };
+        let (a, b) = boxes.swap_remove(index).split();
+        boxes.push(a);
+        boxes.push(b);
+    }
+    boxes.iter().map(ColorBox::average).collect()
+}
+
+// Puts translucent entries first so the tRNS chunk can stop after the last of them. Ties are
+// broken by the color itself, which also brings duplicates together for dedup.
+fn order_palette(mut palette: Vec<[u8; 4]>) -> Vec<[u8; 4]> {
+    palette.sort_by_key(|c| (c[3] == 255, c[3], *c));
+    palette.dedup();
+    palette
+}
+
+pub(crate) fn nearest_index(palette: &[[u8; 4]], color: &[u8; 4]) -> u16 {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| {
+            (0..4)
+                .map(|c| {
+                    let d = entry[c] as i32 - color[c] as i32;
+                    (d * d) as u32
+                })
+                .sum::<u32>()
+        })
+        .map(|(i, _)| i as u16)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 64 x 64 pixels of smoothly varying, partly translucent color: more than any palette holds.
+    fn colorful() -> Image {
+        let mut samples = Vec::new();
+        for y in 0..64u16 {
+            for x in 0..64u16 {
+                samples.extend_from_slice(&[x * 4, y * 4, (x + y) * 2, 255 - (x % 4) * 40]);
+            }
+        }
+        Image::from_samples(64, 64, ColorType::TruecolorAlpha, 8, samples).unwrap()
+    }
+
+    #[test]
+    fn same_palette_every_time() {
+        let image = colorful();
+        let first = image.quantize(16).unwrap();
+        for _ in 0..10 {
+            let again = image.quantize(16).unwrap();
+            assert_eq!(again.palette(), first.palette());
+            assert_eq!(again.transparency(), first.transparency());
+            assert_eq!(again.samples(), first.samples());
+        }
+    }
+
+    #[test]
+    fn palette_has_no_duplicates() {
+        let palette = order_palette(vec![
+            [1, 2, 3, 255],
+            [9, 9, 9, 0],
+            [1, 2, 3, 255],
+            [4, 5, 6, 255],
+            [9, 9, 9, 0],
+            [1, 2, 3, 255],
+        ]);
+        assert_eq!(palette, [[9, 9, 9, 0], [1, 2, 3, 255], [4, 5, 6, 255]]);
+    }
+}
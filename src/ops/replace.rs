@@ -0,0 +1,43 @@
+use std::error::Error;
+
+use crate::image::Image;
+
+impl Image {
+    // Replaces every pixel whose color is within tolerance of target with replacement, an 8-bit
+    // RGBA color. Tolerance is the distance between the RGB colors on a 0 - 255 scale, so 0.0
+    // only replaces the exact color and about 442.0 replaces everything. Alpha is not compared,
+    // but fully transparent pixels are left alone. The image is converted the way rotate
+    // converts it with replacement as the fill.
+    pub fn replace_color(
+        &self,
+        target: [u8; 3],
+        replacement: [u8; 4],
+        tolerance: f32,
+    ) -> Result<Image, Box<dyn Error>> {
+        let mut image = self.for_fill(replacement)?;
+        let fill = image.fill_samples(replacement);
+        let channels = image.channels();
+        let target = target.map(|v| v as f32);
+        for i in 0..image.width() as usize * image.height() as usize {
+            let at = i * channels;
+            let [r, g, b, a] = image.rgba_at(at);
+            if a == 0.0 {
+                continue;
+            }
+            let distance = ((r * 255.0 - target[0]).powi(2)
+                + (g * 255.0 - target[1]).powi(2)
+                + (b * 255.0 - target[2]).powi(2))
+            .sqrt();
+            if distance <= tolerance {
+                image.samples_mut()[at..at + channels].copy_from_slice(&fill);
+            }
+        }
+        Ok(image)
+    }
+
+    // Makes every pixel within tolerance of key transparent, such as the green background of a
+    // sprite rendered for a green screen. Tolerance works as in replace_color.
+    pub fn chroma_key(&self, key: [u8; 3], tolerance: f32) -> Result<Image, Box<dyn Error>> {
+        self.replace_color(key, [0, 0, 0, 0], tolerance)
+    }
+}
@@ -0,0 +1,362 @@
+use std::error::Error;
+use std::f32::consts::PI;
+
+use crate::image::{ColorType, Image, ImageError};
+
+// How new pixels are computed from the old ones. Each filter is sharper and slower than the one
+// before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Filter {
+    // Copies the closest pixel. Keeps hard edges, which suits pixel art, and is the only filter
+    // that keeps indexed images indexed.
+    Nearest,
+    Bilinear,
+    // Catmull-Rom.
+    #[default]
+    Bicubic,
+    Lanczos3,
+    // Averages the source pixels each new pixel covers, weighted by how much of each it covers.
+    // When enlarging it copies pixels like Nearest except where a new pixel straddles the edge
+    // between two old ones, which get mixed. The natural choice for shrinking a lot, as
+    // thumbnails do.
+    Area,
+}
+
+impl Filter {
+    // How far, in source pixels at scale 1, the kernel reaches on either side of a pixel.
+    fn support(self) -> f32 {
+        match self {
+            Filter::Nearest | Filter::Area => 0.5,
+            Filter::Bilinear => 1.0,
+            Filter::Bicubic => 2.0,
+            Filter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Filter::Nearest | Filter::Area => {
+                if x < 0.5 {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Bilinear => (1.0 - x).max(0.0),
+            Filter::Bicubic => {
+                // Keys' cubic with a = -0.5.
+                if x < 1.0 {
+                    1.5 * x * x * x - 2.5 * x * x + 1.0
+                } else if x < 2.0 {
+                    -0.5 * x * x * x + 2.5 * x * x - 4.0 * x + 2.0
+                } else {
+                    0.0
+                }
+            }
+            Filter::Lanczos3 => {
+                if x < 3.0 {
+                    sinc(x) * sinc(x / 3.0)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+impl Image {
+    // Scales the image to the given size. When shrinking, every filter but Nearest averages over
+    // all the source pixels that fall into each new one, so fine detail doesn't turn into
+    // aliasing. Colors with alpha are mixed weighted by their alpha, so transparent pixels don't
+    // bleed their color into the edges of opaque ones.
+    //
+    // Nearest keeps the color type and bit depth. The other filters mix colors, so indexed images
+    // are expanded to 8-bit RGB first, gray images below 8 bits are promoted to 8 bits and tRNS
+    // transparency becomes an alpha channel.
+    pub fn resize(&self, width: u32, height: u32, filter: Filter) -> Result<Image, Box<dyn Error>> {
+        if width == 0 || height == 0 {
+            return Err(ImageError::new("Width and height must be non-zero numbers.").into());
+        }
+        if filter == Filter::Nearest {
+            return Ok(self.resize_nearest(width, height));
+        }
+        let source = self.for_interpolation()?;
+
+        let channels = source.channels();
+        let values = source.premultiplied_samples();
+
+        let (src_width, src_height) = (source.width() as usize, source.height() as usize);
+        let (dst_width, dst_height) = (width as usize, height as usize);
+        let columns = resample_weights(src_width, dst_width, filter);
+        let rows = resample_weights(src_height, dst_height, filter);
+
+        let mut horizontal = vec![0.0f32; dst_width * src_height * channels];
+        for y in 0..src_height {
+            for (x, (start, weights)) in columns.iter().enumerate() {
+                let out = (y * dst_width + x) * channels;
+                for (i, weight) in weights.iter().enumerate() {
+                    let at = (y * src_width + start + i) * channels;
+                    for c in 0..channels {
+                        horizontal[out + c] += values[at + c] * weight;
+                    }
+                }
+            }
+        }
+        let mut resized = vec![0.0f32; dst_width * dst_height * channels];
+        for (y, (start, weights)) in rows.iter().enumerate() {
+            for (i, weight) in weights.iter().enumerate() {
+                let from = (start + i) * dst_width * channels;
+                let to = y * dst_width * channels;
+                for x in 0..dst_width * channels {
+                    resized[to + x] += horizontal[from + x] * weight;
+                }
+            }
+        }
+
+        source.unpremultiply(width, height, &resized)
+    }
+
+    // Shrinks the image with the Area filter to fit within max_width by max_height, keeping its
+    // aspect ratio. Images that already fit are returned unchanged, never enlarged.
+    pub fn thumbnail(&self, max_width: u32, max_height: u32) -> Result<Image, Box<dyn Error>> {
+        if max_width == 0 || max_height == 0 {
+            return Err(ImageError::new("Width and height must be non-zero numbers.").into());
+        }
+        if self.width() <= max_width && self.height() <= max_height {
+            return Ok(self.clone());
+        }
+        let scale =
+            (max_width as f64 / self.width() as f64).min(max_height as f64 / self.height() as f64);
+        let fit = |size: u32, max: u32| ((size as f64 * scale).round() as u32).clamp(1, max);
+        self.resize(
+            fit(self.width(), max_width),
+            fit(self.height(), max_height),
+            Filter::Area,
+        )
+    }
+
+    // The image in a form whose samples can be mixed: indexed images expanded to 8-bit RGB, gray
+    // images below 8 bits promoted to 8 bits and tRNS transparency turned into an alpha channel.
+    pub(crate) fn for_interpolation(&self) -> Result<Image, Box<dyn Error>> {
+        let image = self.expand_transparency();
+        if image.color_type() == ColorType::Indexed {
+            image.convert_to(ColorType::Truecolor, 8)
+        } else if image.bit_depth() < 8 {
+            image.convert_to(image.color_type(), 8)
+        } else {
+            Ok(image)
+        }
+    }
+
+    // The samples as floats, with the colors multiplied by alpha when there is an alpha channel,
+    // so that mixing them doesn't let the color of transparent pixels show.
+    pub(crate) fn premultiplied_samples(&self) -> Vec<f32> {
+        let channels = self.channels();
+        let max = self.max_value() as f32;
+        let mut values: Vec<f32> = self.samples().iter().map(|s| *s as f32).collect();
+        if self.color_type().has_alpha_channel() {
+            for pixel in values.chunks_exact_mut(channels) {
+                let alpha = pixel[channels - 1] / max;
+                for c in pixel[..channels - 1].iter_mut() {
+                    *c *= alpha;
+                }
+            }
+        }
+        values
+    }
+
+    // Builds an image with this one's color type and bit depth from values laid out like
+    // premultiplied_samples returns them.
+    pub(crate) fn unpremultiply(
+        &self,
+        width: u32,
+        height: u32,
+        values: &[f32],
+    ) -> Result<Image, Box<dyn Error>> {
+        let channels = self.channels();
+        let has_alpha = self.color_type().has_alpha_channel();
+        let max = self.max_value() as f32;
+        let mut samples = Vec::with_capacity(values.len());
+        for pixel in values.chunks_exact(channels) {
+            let alpha = if has_alpha {
+                pixel[channels - 1].clamp(0.0, max)
+            } else {
+                max
+            };
+            for (c, value) in pixel.iter().enumerate() {
+                let value = if has_alpha && c < channels - 1 {
+                    if alpha == 0.0 {
+                        0.0
+                    } else {
+                        value * max / alpha
+                    }
+                } else {
+                    *value
+                };
+                samples.push(value.round().clamp(0.0, max) as u16);
+            }
+        }
+        Image::from_samples(width, height, self.color_type(), self.bit_depth(), samples)
+    }
+
+    fn resize_nearest(&self, width: u32, height: u32) -> Image {
+        let channels = self.channels();
+        let mut samples = Vec::with_capacity(width as usize * height as usize * channels);
+        for y in 0..height {
+            let sy = ((y as u64 * 2 + 1) * self.height() as u64 / (height as u64 * 2)) as u32;
+            for x in 0..width {
+                let sx = ((x as u64 * 2 + 1) * self.width() as u64 / (width as u64 * 2)) as u32;
+                samples.extend_from_slice(self.pixel(sx, sy));
+            }
+        }
+        let mut image =
+            Image::from_samples(width, height, self.color_type(), self.bit_depth(), samples)
+                .expect("Resized image has as many samples as its size needs");
+        image.set_palette(self.palette().cloned());
+        image.set_transparency(self.transparency().cloned());
+        image
+    }
+}
+
+// For each destination pixel, the first source pixel it draws from and the normalized weights of
+// that pixel and the ones after it.
+fn resample_weights(src_len: usize, dst_len: usize, filter: Filter) -> Vec<(usize, Vec<f32>)> {
+    if filter == Filter::Area {
+        return coverage_weights(src_len, dst_len);
+    }
+    let scale = src_len as f32 / dst_len as f32;
+    // Shrinking stretches the kernel over every source pixel that lands in the destination pixel.
+    let filter_scale = scale.max(1.0);
+    let support = filter.support() * filter_scale;
+    (0..dst_len)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let start = ((center - support).floor().max(0.0) as usize).min(src_len - 1);
+            let end = ((center + support).ceil() as usize).clamp(start + 1, src_len);
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| filter.weight((j as f32 + 0.5 - center) / filter_scale))
+                .collect();
+            // The nearest source pixel is always within half a pixel of the center, where
+            // every kernel is well above zero.
+            let total: f32 = weights.iter().sum();
+            weights.iter_mut().for_each(|w| *w /= total);
+            (start, weights)
+        })
+        .collect()
+}
+
+// Weights for the Area filter: the part of each source pixel that the destination pixel covers.
+fn coverage_weights(src_len: usize, dst_len: usize) -> Vec<(usize, Vec<f32>)> {
+    let scale = src_len as f32 / dst_len as f32;
+    (0..dst_len)
+        .map(|i| {
+            let left = i as f32 * scale;
+            let right = left + scale;
+            let start = (left.floor() as usize).min(src_len - 1);
+            let end = (right.ceil() as usize).clamp(start + 1, src_len);
+            let mut weights: Vec<f32> = (start..end)
+                .map(|j| (right.min(j as f32 + 1.0) - left.max(j as f32)).max(0.0))
+                .collect();
+            let total: f32 = weights.iter().sum();
+            weights.iter_mut().for_each(|w| *w /= total);
+            (start, weights)
+        })
+        .collect()
+}
+
+fn sinc(x: f32) -> f32 {
+    if x == 0.0 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILTERS: [Filter; 5] = [
+        Filter::Nearest,
+        Filter::Bilinear,
+        Filter::Bicubic,
+        Filter::Lanczos3,
+        Filter::Area,
+    ];
+
+    // A 2x1 image whose left pixel is opaque white and whose right pixel is transparent black.
+    fn white_beside_clear(bit_depth: u8) -> Image {
+        let max = ((1u32 << bit_depth) - 1) as u16;
+        Image::from_samples(
+            2,
+            1,
+            ColorType::GrayscaleAlpha,
+            bit_depth,
+            vec![max, max, 0, 0],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn gray_alpha_stays_gray_alpha() {
+        for bit_depth in [8, 16] {
+            let image = white_beside_clear(bit_depth);
+            for filter in FILTERS {
+                let resized = image.resize(5, 3, filter).unwrap();
+                assert_eq!((resized.width(), resized.height()), (5, 3));
+                assert_eq!(
+                    resized.color_type(),
+                    ColorType::GrayscaleAlpha,
+                    "{:?}",
+                    filter
+                );
+                assert_eq!(resized.bit_depth(), bit_depth, "{:?}", filter);
+                assert_eq!(resized.samples().len(), 5 * 3 * 2);
+            }
+        }
+    }
+
+    #[test]
+    fn gray_alpha_transparent_color_doesnt_bleed() {
+        for bit_depth in [8, 16] {
+            let max = ((1u32 << bit_depth) - 1) as u16;
+            let resized = white_beside_clear(bit_depth)
+                .resize(1, 1, Filter::Area)
+                .unwrap();
+            // Half covered, and what color there is comes from the white pixel alone.
+            assert_eq!(resized.samples(), [max, max / 2 + 1]);
+        }
+    }
+
+    #[test]
+    fn area_coverage() {
+        let gray = |samples: Vec<u16>| {
+            Image::from_samples(samples.len() as u32, 1, ColorType::Grayscale, 8, samples).unwrap()
+        };
+        // Each new pixel covers one and a half old ones.
+        let shrunk = gray(vec![0, 90, 255]).resize(2, 1, Filter::Area).unwrap();
+        assert_eq!(shrunk.samples(), [30, 200]);
+        // Only the middle pixel straddles the edge.
+        let enlarged = gray(vec![0, 255]).resize(3, 1, Filter::Area).unwrap();
+        assert_eq!(enlarged.samples(), [0, 128, 255]);
+    }
+
+    #[test]
+    fn gray_alpha_nearest_copies_pixels() {
+        let image = white_beside_clear(16);
+        let resized = image.resize(4, 2, Filter::Nearest).unwrap();
+        let row = [0xFFFF, 0xFFFF, 0xFFFF, 0xFFFF, 0, 0, 0, 0];
+        assert_eq!(resized.samples(), [row, row].concat());
+    }
+
+    #[test]
+    fn gray_alpha_thumbnail() {
+        let image =
+            Image::from_samples(4, 2, ColorType::GrayscaleAlpha, 16, [1000, 40000].repeat(8))
+                .unwrap();
+        let thumbnail = image.thumbnail(2, 2).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (2, 1));
+        assert_eq!(thumbnail.samples(), [1000, 40000, 1000, 40000]);
+    }
+}
@@ -0,0 +1,105 @@
+use std::error::Error;
+
+use crate::image::{scale_sample, ColorType, Image};
+
+impl Image {
+    // Rotates clockwise by any angle, in degrees, with bilinear resampling. The canvas grows to
+    // hold the whole rotated image and the corners it uncovers are filled with fill, an 8-bit
+    // RGBA color; [0, 0, 0, 0] leaves them transparent. Multiples of 90 degrees are better done
+    // with rotate90 and friends, which don't resample.
+    //
+    // The result is converted the way resize converts images, and also gets an alpha channel
+    // when fill isn't opaque and turns to truecolor when a gray image gets a colored fill.
+    pub fn rotate(&self, degrees: f32, fill: [u8; 4]) -> Result<Image, Box<dyn Error>> {
+        let source = self.for_fill(fill)?;
+
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let (src_width, src_height) = (source.width() as f32, source.height() as f32);
+        // The small tolerance keeps rounding errors from adding a row or column at right angles.
+        let out_size = |size: f32| ((size - 1e-3).ceil() as u32).max(1);
+        let width = out_size(src_width * cos.abs() + src_height * sin.abs());
+        let height = out_size(src_width * sin.abs() + src_height * cos.abs());
+
+        let channels = source.channels();
+        let max = source.max_value() as f32;
+        let mut fill: Vec<f32> = source
+            .fill_samples(fill)
+            .iter()
+            .map(|s| *s as f32)
+            .collect();
+        if source.color_type().has_alpha_channel() {
+            let alpha = fill[channels - 1] / max;
+            for c in fill[..channels - 1].iter_mut() {
+                *c *= alpha;
+            }
+        }
+        let values = source.premultiplied_samples();
+        let sample = |x: i64, y: i64| -> &[f32] {
+            if x < 0 || y < 0 || x >= source.width() as i64 || y >= source.height() as i64 {
+                &fill
+            } else {
+                let at = (y as usize * source.width() as usize + x as usize) * channels;
+                &values[at..at + channels]
+            }
+        };
+
+        let mut rotated = Vec::with_capacity(width as usize * height as usize * channels);
+        for y in 0..height {
+            for x in 0..width {
+                // Turns the center of the output pixel back into the source image.
+                let dx = x as f32 + 0.5 - width as f32 / 2.0;
+                let dy = y as f32 + 0.5 - height as f32 / 2.0;
+                let sx = cos * dx + sin * dy + src_width / 2.0 - 0.5;
+                let sy = -sin * dx + cos * dy + src_height / 2.0 - 0.5;
+                let (x0, y0) = (sx.floor(), sy.floor());
+                let (fx, fy) = (sx - x0, sy - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let (top_left, top_right) = (sample(x0, y0), sample(x0 + 1, y0));
+                let (bottom_left, bottom_right) = (sample(x0, y0 + 1), sample(x0 + 1, y0 + 1));
+                for c in 0..channels {
+                    let top = top_left[c] * (1.0 - fx) + top_right[c] * fx;
+                    let bottom = bottom_left[c] * (1.0 - fx) + bottom_right[c] * fx;
+                    rotated.push(top * (1.0 - fy) + bottom * fy);
+                }
+            }
+        }
+        source.unpremultiply(width, height, &rotated)
+    }
+
+    // The image converted the way resize converts images, and to a color type that can hold the
+    // 8-bit RGBA color fill: with an alpha channel when fill isn't opaque and truecolor when it
+    // isn't gray.
+    pub(crate) fn for_fill(&self, fill: [u8; 4]) -> Result<Image, Box<dyn Error>> {
+        let source = self.for_interpolation()?;
+        let gray_fill = fill[0] == fill[1] && fill[1] == fill[2];
+        let color_type = match (source.color_type(), gray_fill, fill[3] == 255) {
+            (ColorType::Grayscale, true, true) => ColorType::Grayscale,
+            (ColorType::Grayscale, true, false) | (ColorType::GrayscaleAlpha, true, _) => {
+                ColorType::GrayscaleAlpha
+            }
+            (ColorType::Grayscale, false, true) | (ColorType::Truecolor, _, true) => {
+                ColorType::Truecolor
+            }
+            _ => ColorType::TruecolorAlpha,
+        };
+        source.convert_to(color_type, source.bit_depth())
+    }
+
+    // The 8-bit RGBA color fill as a pixel of an image from for_fill.
+    pub(crate) fn fill_samples(&self, fill: [u8; 4]) -> Vec<u16> {
+        let max = self.max_value();
+        let scale = |v: u8| scale_sample(v as u16, 255, max);
+        let (r, g, b, a) = (
+            scale(fill[0]),
+            scale(fill[1]),
+            scale(fill[2]),
+            scale(fill[3]),
+        );
+        match self.color_type() {
+            ColorType::Grayscale => vec![r],
+            ColorType::GrayscaleAlpha => vec![r, a],
+            ColorType::Truecolor => vec![r, g, b],
+            _ => vec![r, g, b, a],
+        }
+    }
+}
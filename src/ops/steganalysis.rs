@@ -0,0 +1,191 @@
+// Statistical tests for payloads hidden in the least significant bits of the samples, such as the
+// ones embed_lsb writes. Both look at the color samples only and need 8 or 16-bit samples to mean
+// anything: in indexed and low bit depth images the lowest bit is part of the picture itself.
+use crate::image::Image;
+
+// Pairs of values expected to occur fewer times than this are left out of the chi-square test,
+// which isn't reliable for rare values.
+const MIN_EXPECTED: f64 = 5.0;
+
+impl Image {
+    // The chi-square attack of Westfeld and Pfitzmann. Replacing the lowest bits with payload
+    // makes each pair of values differing only in that bit, such as 100 and 101, about equally
+    // common, where in ordinary pictures they aren't. Returns the probability, from 0 to 1, that
+    // the counts are that even because of embedding. It only notices payloads spread over most
+    // of the image; sample_pair_rate also finds short ones. Very noisy pictures can come out
+    // high without a payload.
+    pub fn lsb_chi_square(&self) -> f64 {
+        chi_square_probability(&self.color_samples())
+    }
+
+    // Sample pair analysis (Dumitrescu, Wu and Wang). Looks at how neighbouring samples of the
+    // same channel relate before and after flipping their lowest bits, and estimates the share
+    // of samples, from 0 to 1, that carry payload. Ordinary pictures come out close to 0.
+    pub fn sample_pair_rate(&self) -> f64 {
+        let channels = self.channels();
+        let color_channels = self.color_channel_count();
+        let row = self.width() as usize * channels;
+        let samples = self.samples();
+
+        let (mut x, mut y, mut z, mut w) = (0u64, 0u64, 0u64, 0u64);
+        for line in samples.chunks_exact(row.max(1)) {
+            for i in 0..row.saturating_sub(channels) {
+                if i % channels >= color_channels {
+                    continue;
+                }
+                let (u, v) = (line[i], line[i + channels]);
+                if u == v {
+                    z += 1;
+                } else if (v % 2 == 0) == (u < v) {
+                    x += 1;
+                } else {
+                    y += 1;
+                    if u >> 1 == v >> 1 {
+                        w += 1;
+                    }
+                }
+            }
+        }
+
+        let total = (x + y + z) as f64;
+        let (x, y, z, w) = (x as f64, y as f64, z as f64, w as f64);
+        let a = (w + z) / 2.0;
+        let b = 2.0 * x - total;
+        let c = y - x;
+        let rate = if a == 0.0 {
+            if b == 0.0 {
+                0.0
+            } else {
+                -c / b
+            }
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            // Near full embedding the estimate is noisy enough to miss the axis; the vertex is
+            // the closest the parabola gets.
+            if discriminant < 0.0 {
+                -b / (2.0 * a)
+            } else {
+                let root = discriminant.sqrt();
+                ((-b + root) / (2.0 * a)).min((-b - root) / (2.0 * a))
+            }
+        };
+        if rate.is_finite() {
+            rate.clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    // Every sample except alpha, in image order.
+    fn color_samples(&self) -> Vec<u16> {
+        let channels = self.channels();
+        let color_channels = self.color_channel_count();
+        self.samples()
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i % channels < color_channels)
+            .map(|(_, s)| *s)
+            .collect()
+    }
+
+    fn color_channel_count(&self) -> usize {
+        if self.color_type().has_alpha_channel() {
+            self.channels() - 1
+        } else {
+            self.channels()
+        }
+    }
+}
+
+// The p-value of the chi-square statistic comparing the counts of each pair of values 2k and
+// 2k + 1 with their mean. It is close to 1 when the counts are as even as embedding makes them.
+fn chi_square_probability(samples: &[u16]) -> f64 {
+    let mut histogram = vec![0u64; 1 << 16];
+    for sample in samples {
+        histogram[*sample as usize] += 1;
+    }
+    let mut statistic = 0.0;
+    let mut categories = 0usize;
+    for pair in histogram.chunks_exact(2) {
+        let expected = (pair[0] + pair[1]) as f64 / 2.0;
+        if expected < MIN_EXPECTED {
+            continue;
+        }
+        let difference = pair[0] as f64 - expected;
+        statistic += difference * difference / expected;
+        categories += 1;
+    }
+    if categories < 2 {
+        return 0.0;
+    }
+    upper_gamma_regularized((categories - 1) as f64 / 2.0, statistic / 2.0)
+}
+
+// Q(a, x), the upper regularized incomplete gamma function, which is the chi-square survival
+// function for a = degrees of freedom / 2 and x = statistic / 2. Numerical Recipes' series and
+// continued fraction.
+fn upper_gamma_regularized(a: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-12;
+    const TINY: f64 = 1e-300;
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let prefix = (-x + a * x.ln() - ln_gamma(a)).exp();
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut n = a;
+        for _ in 0..1000 {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+        return (1.0 - sum * prefix).clamp(0.0, 1.0);
+    }
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / TINY;
+    let mut d = 1.0 / b;
+    let mut h = d;
+    for i in 1..1000 {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = b + an / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        h *= delta;
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    (prefix * h).clamp(0.0, 1.0)
+}
+
+// Lanczos approximation of ln(Γ(x)) for x > 0.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+    let mut y = x;
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000000000190015;
+    for coefficient in COEFFICIENTS.iter() {
+        y += 1.0;
+        series += coefficient / y;
+    }
+    -tmp + (2.5066282746310005 * series / x).ln()
+}
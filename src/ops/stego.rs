@@ -0,0 +1,193 @@
+// Hiding a payload in the least significant bits of the samples, for watermarking and provenance
+// experiments. Changing the lowest bit of an 8-bit sample changes it by at most 1 in 255, which
+// nobody can see, and PNG compression is lossless, so the payload survives saving the image.
+// Anything that changes the pixels, such as resizing or converting to a lower bit depth, destroys
+// it.
+//
+// The payload is stored as its length, a 32-bit big-endian number, followed by its bytes, most
+// significant bit first. Alpha samples are left alone, since changing them in fully transparent
+// areas would show up in tools that reveal hidden pixels.
+//
+// With a password, the samples are visited in an order shuffled by a generator seeded from it, so
+// the payload is spread over the whole image instead of filling it from the top. This is
+// obfuscation, not encryption: encrypt the payload first if it must stay secret.
+use std::error::Error;
+
+use crate::image::{ColorType, Image, ImageError};
+
+// Bytes of the length stored before the payload.
+const LENGTH_BYTES: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsbOptions {
+    // How many of the lowest bits of each sample hold payload, from 1 to 4. More bits hold more
+    // data and are easier to see and to detect.
+    pub bits_per_sample: u8,
+    pub password: Option<String>,
+}
+
+impl Default for LsbOptions {
+    fn default() -> LsbOptions {
+        LsbOptions {
+            bits_per_sample: 1,
+            password: None,
+        }
+    }
+}
+
+impl Image {
+    // The largest payload, in bytes, that embed_lsb can hide in the image.
+    pub fn lsb_capacity(&self, options: &LsbOptions) -> usize {
+        let bits = self.payload_samples().len() * options.bits_per_sample as usize;
+        (bits / 8).saturating_sub(LENGTH_BYTES)
+    }
+
+    // Returns a copy of the image with payload hidden in it. Indexed images and bit depths below
+    // 8 can't be used, since changing a palette index or a low-depth sample changes the color
+    // visibly; convert them first.
+    pub fn embed_lsb(&self, payload: &[u8], options: &LsbOptions) -> Result<Image, Box<dyn Error>> {
+        self.check_lsb(options)?;
+        let capacity = self.lsb_capacity(options);
+        if payload.len() > capacity || payload.len() > u32::MAX as usize {
+            return Err(format!(
+                "The payload is {} bytes but the image can only hide {}.",
+                payload.len(),
+                capacity
+            )
+            .into());
+        }
+
+        let mut data = (payload.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(payload);
+        let bits_per_sample = options.bits_per_sample as usize;
+        let mask = (1u16 << bits_per_sample) - 1;
+        let slots = self.lsb_order(options);
+
+        let mut image = self.clone();
+        let samples = image.samples_mut();
+        let mut bits = data
+            .iter()
+            .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1));
+        for index in slots {
+            let mut value = 0u16;
+            let mut taken = 0;
+            for _ in 0..bits_per_sample {
+                match bits.next() {
+                    Some(bit) => {
+                        value = (value << 1) | bit as u16;
+                        taken += 1;
+                    }
+                    None => break,
+                }
+            }
+            if taken == 0 {
+                break;
+            }
+            // A partly filled last sample keeps its remaining low bits.
+            let shift = bits_per_sample - taken;
+            let taken_mask = mask & !((1u16 << shift) - 1);
+            samples[index] = (samples[index] & !taken_mask) | (value << shift);
+        }
+        Ok(image)
+    }
+
+    // Recovers a payload hidden by embed_lsb with the same options. Images without a payload,
+    // or read with the wrong options, usually fail with an error about the length; otherwise
+    // the result is garbage.
+    pub fn extract_lsb(&self, options: &LsbOptions) -> Result<Vec<u8>, Box<dyn Error>> {
+        self.check_lsb(options)?;
+        let bits_per_sample = options.bits_per_sample as usize;
+        let samples = self.samples();
+        let mut bits = self.lsb_order(options).into_iter().flat_map(|index| {
+            let value = samples[index];
+            (0..bits_per_sample)
+                .rev()
+                .map(move |i| ((value >> i) & 1) as u8)
+        });
+        let mut next_byte =
+            || (0..8).try_fold(0u8, |byte, _| bits.next().map(|bit| (byte << 1) | bit));
+
+        let mut length = [0u8; LENGTH_BYTES];
+        for byte in length.iter_mut() {
+            *byte = next_byte().ok_or("The image is too small to hold a payload.")?;
+        }
+        let length = u32::from_be_bytes(length) as usize;
+        if length > self.lsb_capacity(options) {
+            return Err(ImageError::new(
+                "No payload was found. The image may not hold one, or the password or bits per \
+                 sample may be wrong.",
+            )
+            .into());
+        }
+        (0..length)
+            .map(|_| next_byte().ok_or_else(|| "The payload is cut short.".into()))
+            .collect()
+    }
+
+    fn check_lsb(&self, options: &LsbOptions) -> Result<(), Box<dyn Error>> {
+        if !(1..=4).contains(&options.bits_per_sample) {
+            return Err(ImageError::new("Bits per sample must be from 1 to 4.").into());
+        }
+        if self.color_type() == ColorType::Indexed || self.bit_depth() < 8 {
+            return Err(ImageError::new(
+                "Payloads can only be hidden in 8 and 16-bit grayscale or truecolor images.",
+            )
+            .into());
+        }
+        Ok(())
+    }
+
+    // Indices of the samples that can hold payload: every color sample, skipping alpha.
+    fn payload_samples(&self) -> Vec<usize> {
+        let channels = self.channels();
+        let color_channels = if self.color_type().has_alpha_channel() {
+            channels - 1
+        } else {
+            channels
+        };
+        (0..self.samples().len())
+            .filter(|i| i % channels < color_channels)
+            .collect()
+    }
+
+    // The payload samples in the order bits are written to them.
+    fn lsb_order(&self, options: &LsbOptions) -> Vec<usize> {
+        let mut order = self.payload_samples();
+        if let Some(password) = &options.password {
+            let mut rng = SplitMix64::new(fnv1a(password.as_bytes()));
+            // Fisher-Yates shuffle.
+            for i in (1..order.len()).rev() {
+                let j = (rng.next() % (i as u64 + 1)) as usize;
+                order.swap(i, j);
+            }
+        }
+        order
+    }
+}
+
+// 64-bit FNV-1a, to turn a password into a seed.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+// A small, fast generator with good statistical quality. Its output for a seed never changes,
+// which the shuffle relies on to find the payload again.
+pub(crate) struct SplitMix64 {
+    state: u64,
+}
+
+impl SplitMix64 {
+    pub(crate) fn new(seed: u64) -> SplitMix64 {
+        SplitMix64 { state: seed }
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
@@ -0,0 +1,41 @@
+use std::error::Error;
+
+use crate::image::{ColorType, Image};
+
+impl Image {
+    // Cuts every color channel down to the given number of evenly spaced levels, for a flat,
+    // poster-like look. Levels below 2 are treated as 2. Alpha is left alone and indexed images
+    // have their palette posterized, as with the tone adjustments.
+    pub fn posterize(&self, levels: u32) -> Image {
+        let steps = (levels.max(2) - 1) as f32;
+        self.map_colors(|v| (v * steps).round() / steps)
+    }
+
+    // Gives the image the brown tint of an old photograph. The result is truecolor, with an
+    // alpha channel if the image had any transparency, and is converted the way resize
+    // converts images.
+    pub fn sepia(&self) -> Result<Image, Box<dyn Error>> {
+        let source = self.for_interpolation()?;
+        let color_type = if source.color_type().has_alpha_channel() {
+            ColorType::TruecolorAlpha
+        } else {
+            ColorType::Truecolor
+        };
+        let mut image = source.convert_to(color_type, source.bit_depth())?;
+        let channels = image.channels();
+        let max = image.max_value() as f32;
+        for i in 0..image.width() as usize * image.height() as usize {
+            let at = i * channels;
+            let [r, g, b, _] = image.rgba_at(at);
+            let tinted = [
+                0.393 * r + 0.769 * g + 0.189 * b,
+                0.349 * r + 0.686 * g + 0.168 * b,
+                0.272 * r + 0.534 * g + 0.131 * b,
+            ];
+            for (sample, value) in image.samples_mut()[at..at + 3].iter_mut().zip(tinted) {
+                *sample = (value.min(1.0) * max).round() as u16;
+            }
+        }
+        Ok(image)
+    }
+}
@@ -0,0 +1,108 @@
+use crate::image::{ColorType, Image};
+
+impl Image {
+    // Binarizes the image at a fixed luminance level (0 - 255). Pixels brighter than the level
+    // become white. The result is a 1-bit grayscale image.
+    pub fn threshold(&self, level: u8) -> Image {
+        let luma = self.luma_plane();
+        let samples = luma.iter().map(|l| (*l > level as f32) as u16).collect();
+        self.binary_image(samples)
+    }
+
+    // Picks the threshold that minimizes the variance within the dark and light classes of the
+    // luminance histogram (Otsu's method) and binarizes with it.
+    pub fn threshold_otsu(&self) -> Image {
+        self.threshold(self.otsu_level())
+    }
+
+    pub fn otsu_level(&self) -> u8 {
+        let luma = self.luma_plane();
+        let mut histogram = [0u64; 256];
+        for l in &luma {
+            histogram[l.round().min(255.0) as usize] += 1;
+        }
+
+        let total = luma.len() as f64;
+        let weighted_total: f64 = histogram
+            .iter()
+            .enumerate()
+            .map(|(i, count)| i as f64 * *count as f64)
+            .sum();
+
+        let mut background_weight = 0.0;
+        let mut background_sum = 0.0;
+        let mut best_variance = -1.0;
+        let mut best_level = 0u8;
+
+        for (level, count) in histogram.iter().enumerate() {
+            background_weight += *count as f64;
+            if background_weight == 0.0 {
+                continue;
+            }
+            let foreground_weight = total - background_weight;
+            if foreground_weight == 0.0 {
+                break;
+            }
+
+            background_sum += level as f64 * *count as f64;
+            let background_mean = background_sum / background_weight;
+            let foreground_mean = (weighted_total - background_sum) / foreground_weight;
+            let between_variance =
+                background_weight * foreground_weight * (background_mean - foreground_mean).powi(2);
+
+            if between_variance > best_variance {
+                best_variance = between_variance;
+                best_level = level as u8;
+            }
+        }
+        best_level
+    }
+
+    // Binarizes each pixel against the mean luminance of the block x block window around it,
+    // minus c. This copes with uneven lighting across a scan where a single level can't.
+    pub fn adaptive_threshold(&self, block: u32, c: f32) -> Image {
+        let luma = self.luma_plane();
+        let width = self.width() as usize;
+        let height = self.height() as usize;
+        let radius = (block.max(1) / 2) as usize;
+
+        // Summed-area table with a zero row and column so window sums are constant time.
+        let mut integral = vec![0f64; (width + 1) * (height + 1)];
+        for y in 0..height {
+            let mut row_sum = 0.0;
+            for x in 0..width {
+                row_sum += luma[y * width + x] as f64;
+                integral[(y + 1) * (width + 1) + x + 1] =
+                    integral[y * (width + 1) + x + 1] + row_sum;
+            }
+        }
+
+        let mut samples = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let top = y.saturating_sub(radius);
+            let bottom = (y + radius + 1).min(height);
+            for x in 0..width {
+                let left = x.saturating_sub(radius);
+                let right = (x + radius + 1).min(width);
+                let sum = integral[bottom * (width + 1) + right]
+                    - integral[top * (width + 1) + right]
+                    - integral[bottom * (width + 1) + left]
+                    + integral[top * (width + 1) + left];
+                let mean = sum / ((bottom - top) * (right - left)) as f64;
+                samples.push((luma[y * width + x] as f64 > mean - c as f64) as u16);
+            }
+        }
+        self.binary_image(samples)
+    }
+
+    fn binary_image(&self, samples: Vec<u16>) -> Image {
+        Image::from_samples(
+            self.width(),
+            self.height(),
+            ColorType::Grayscale,
+            1,
+            samples,
+        )
+        .expect("Binary image has the same dimensions as its source")
+    }
+}
@@ -0,0 +1,39 @@
+use crate::image::Image;
+
+impl Image {
+    // Crops away a uniform border, such as the empty space around a sprite. When the top left
+    // pixel is fully transparent the border is every fully transparent pixel, whatever its color;
+    // otherwise it is every pixel exactly the color of the top left one. Returns the cropped image
+    // and the rectangle it was cut from, as x, y, width and height, which is the whole image when
+    // there is no border. Returns None when the image is nothing but border.
+    pub fn trim(&self) -> Option<(Image, (u32, u32, u32, u32))> {
+        let channels = self.channels();
+        let corner = self.pixel(0, 0).to_vec();
+        let transparent = self.rgba_at(0)[3] == 0.0;
+        let is_border = |x: u32, y: u32| {
+            if transparent {
+                let at = (y as usize * self.width() as usize + x as usize) * channels;
+                self.rgba_at(at)[3] == 0.0
+            } else {
+                self.pixel(x, y) == &corner[..]
+            }
+        };
+
+        let (mut left, mut top, mut right, mut bottom) = (self.width(), self.height(), 0, 0);
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                if !is_border(x, y) {
+                    left = left.min(x);
+                    top = top.min(y);
+                    right = right.max(x + 1);
+                    bottom = bottom.max(y + 1);
+                }
+            }
+        }
+        if right == 0 {
+            return None;
+        }
+        let rect = (left, top, right - left, bottom - top);
+        Some((self.copy_region(rect.0, rect.1, rect.2, rect.3), rect))
+    }
+}
@@ -0,0 +1,102 @@
+use std::error::Error;
+
+use crate::image::Image;
+
+// Where a watermark goes, as one of the nine points where the edges and middle lines of the
+// image meet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Anchor {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    #[default]
+    BottomRight,
+}
+
+impl Anchor {
+    // Where something of the given size goes inside the given area, kept margin away from the
+    // edges it touches.
+    fn place(self, area: (u32, u32), size: (u32, u32), margin: u32) -> (i64, i64) {
+        let along = |area: u32, size: u32, step: u8| -> i64 {
+            let (area, size, margin) = (area as i64, size as i64, margin as i64);
+            match step {
+                0 => margin,
+                1 => (area - size) / 2,
+                _ => area - size - margin,
+            }
+        };
+        let (column, row) = match self {
+            Anchor::TopLeft => (0, 0),
+            Anchor::Top => (1, 0),
+            Anchor::TopRight => (2, 0),
+            Anchor::Left => (0, 1),
+            Anchor::Center => (1, 1),
+            Anchor::Right => (2, 1),
+            Anchor::BottomLeft => (0, 2),
+            Anchor::Bottom => (1, 2),
+            Anchor::BottomRight => (2, 2),
+        };
+        (along(area.0, size.0, column), along(area.1, size.1, row))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatermarkOptions {
+    pub position: Anchor,
+    // From 0.0, invisible, to 1.0, drawn as it is.
+    pub opacity: f32,
+    // Repeats the watermark over the whole image, in a grid lined up with the one at position.
+    pub tile: bool,
+    // Pixels between the watermark and the edges it is anchored to, and between tiles.
+    pub margin: u32,
+}
+
+impl Default for WatermarkOptions {
+    fn default() -> WatermarkOptions {
+        WatermarkOptions {
+            position: Anchor::BottomRight,
+            opacity: 0.5,
+            tile: false,
+            margin: 0,
+        }
+    }
+}
+
+impl Image {
+    // Draws watermark over the image, converting it the way overlay does.
+    pub fn watermark(
+        &self,
+        watermark: &Image,
+        options: &WatermarkOptions,
+    ) -> Result<Image, Box<dyn Error>> {
+        let mut image = self.overlay_base(watermark)?;
+        let opacity = options.opacity.clamp(0.0, 1.0);
+        let (width, height) = (watermark.width(), watermark.height());
+        let (x, y) = options.position.place(
+            (image.width(), image.height()),
+            (width, height),
+            options.margin,
+        );
+        if !options.tile {
+            image.draw_over(watermark, x, y, opacity);
+            return Ok(image);
+        }
+
+        // Starts from the first tile of the grid that reaches into the image.
+        let step_x = width as i64 + options.margin as i64;
+        let step_y = height as i64 + options.margin as i64;
+        let first_x = x - (x + width as i64).div_euclid(step_x) * step_x;
+        let first_y = y - (y + height as i64).div_euclid(step_y) * step_y;
+        for tile_y in (first_y..image.height() as i64).step_by(step_y as usize) {
+            for tile_x in (first_x..image.width() as i64).step_by(step_x as usize) {
+                image.draw_over(watermark, tile_x, tile_y, opacity);
+            }
+        }
+        Ok(image)
+    }
+}
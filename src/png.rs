@@ -1,12 +1,112 @@
-use std::convert::TryInto;
-use std::error::Error;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::error::Error;
+#[cfg(feature = "std")]
 use std::fs::File;
-use std::io::Read;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::io::{BufReader, Read, Seek};
+#[cfg(feature = "std")]
 use std::io::Write;
-use std::str;
-
-use std::error;
-use std::fmt;
+#[cfg(feature = "std")]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use core::error;
+use core::fmt;
+use core::iter;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+#[cfg(feature = "std")]
+mod apng;
+#[cfg(feature = "std")]
+mod apng_optimize;
+#[cfg(feature = "async")]
+mod async_io;
+#[cfg(feature = "std")]
+mod builder;
+#[cfg(feature = "std")]
+mod canonical;
+#[cfg(feature = "std")]
+mod codec;
+pub(crate) mod crc;
+#[cfg(feature = "std")]
+mod decode;
+#[cfg(feature = "std")]
+mod encode;
+#[cfg(feature = "std")]
+mod icc;
+#[cfg(feature = "mmap")]
+mod mapped;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod parser;
+#[cfg(feature = "std")]
+mod patch;
+mod polyglot;
+mod recover;
+mod repair;
+mod rules;
+#[cfg(feature = "std")]
+mod scanline;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "std")]
+mod simd;
+#[cfg(feature = "std")]
+mod steganalysis;
+mod strip;
+#[cfg(feature = "std")]
+mod text;
+mod trailing;
+#[cfg(feature = "std")]
+mod transform;
+mod warnings;
+#[cfg(feature = "std")]
+mod write;
+
+#[cfg(feature = "std")]
+pub use apng::{AnimationControl, BlendOp, DisposeOp, Frame, FrameControl, InvalidAPNG};
+#[cfg(feature = "std")]
+pub use builder::{ApngBuilder, PNGFileBuilder};
+#[cfg(feature = "std")]
+pub use codec::ChunkCodec;
+#[cfg(feature = "std")]
+pub use decode::{DecodeOptions, GammaCorrection, PNGDecodeError};
+#[cfg(feature = "std")]
+pub use encode::FilterStrategy;
+#[cfg(feature = "mmap")]
+pub use mapped::{MappedChunk, MappedPNGFile};
+pub use parser::{LimitExceeded, PNGParseEvent, PNGParser, ParseOptions, ParseReport};
+#[cfg(feature = "std")]
+pub use patch::{patch_chunk, patch_text, patch_time};
+pub use polyglot::{find_polyglots, PolyglotFinding, PolyglotKind};
+pub use recover::{RecoveryOptions, RecoveryReport};
+pub use repair::CrcRepair;
+pub use rules::{ParseMode, SpecViolation};
+#[cfg(feature = "std")]
+pub use steganalysis::{SteganalysisReport, SuspiciousChunk};
+pub use strip::StripLevel;
+#[cfg(feature = "std")]
+pub use text::{InvalidTextChunk, TextData};
+pub use trailing::{png_end, trailing_data};
+#[cfg(feature = "std")]
+pub use trailing::{extract_trailing_data, find_trailing_data, remove_trailing_data};
+pub use warnings::{Warning, Warnings};
+#[cfg(feature = "std")]
+pub use write::{MetadataPolicy, WriteOptions};
+#[cfg(feature = "std")]
+pub use scanline::ScanlineDecoder;
+
+use trailing::trailing_data_warning;
+
+#[cfg(feature = "std")]
+const READ_BUFFER_SIZE: usize = 64 * 1024;
 
 const PNG_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
 
@@ -29,20 +129,135 @@ impl error::Error for InvalidPNGFormat {
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct InvalidIHDRData {
+    message: String,
+}
+
+impl fmt::Display for InvalidIHDRData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid IHDR data: {}", self.message)
+    }
+}
+
+impl error::Error for InvalidIHDRData {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TruncatedPNGFile {
+    message: String,
+}
+
+impl TruncatedPNGFile {
+    fn new(field: &str) -> TruncatedPNGFile {
+        TruncatedPNGFile {
+            message: format!("The file ended while reading the {}.", field),
+        }
+    }
+}
+
+impl fmt::Display for TruncatedPNGFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Truncated PNG file: {}", self.message)
+    }
+}
+
+impl error::Error for TruncatedPNGFile {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+// Feeds a stream to a PNGParser until IEND or the end of the data, returning the chunks in file
+// order along with the parser so the caller can decide what an early end means.
+#[cfg(feature = "std")]
+fn read_chunks<R: Read>(
+    mut reader: R,
+    options: &ParseOptions,
+) -> Result<(Vec<PNGChunk>, PNGParser), Box<dyn Error>> {
+    let mut parser = PNGParser::with_options(*options);
+    let mut chunks = Vec::new();
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+    loop {
+        // Never ask for more than the parser needs, so nothing past IEND is consumed.
+        let wanted = parser.bytes_needed().min(buffer.len());
+        if wanted == 0 {
+            break;
+        }
+        let read = match reader.read(&mut buffer[..wanted]) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        for event in parser.feed(&buffer[..read])? {
+            if let PNGParseEvent::ChunkParsed(chunk) = event {
+                chunks.push(chunk);
+            }
+        }
+    }
+    Ok((chunks, parser))
+}
+
+// read_chunks for data that is already in memory. It's fed to the parser a chunk at a time, so
+// only the chunk being parsed is copied into its buffer.
+fn parse_chunks(
+    mut bytes: &[u8],
+    options: &ParseOptions,
+) -> Result<(Vec<PNGChunk>, PNGParser), Box<dyn Error>> {
+    let mut parser = PNGParser::with_options(*options);
+    let mut chunks = Vec::new();
+    loop {
+        let wanted = parser.bytes_needed().min(bytes.len());
+        if wanted == 0 {
+            break;
+        }
+        for event in parser.feed(&bytes[..wanted])? {
+            if let PNGParseEvent::ChunkParsed(chunk) = event {
+                chunks.push(chunk);
+            }
+        }
+        bytes = &bytes[wanted..];
+    }
+    Ok((chunks, parser))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PNGFile {
     ihdr_chunk: PNGChunk,
+    // Parsed from ihdr_chunk whenever a file is made, so every file has a valid header.
+    ihdr: IHDRData,
     time_chunk: Option<PNGChunk>,
     chunks: Vec<PNGChunk>,
 }
 
+// Two chunks are equal when their type and data are. The CRC follows from those for any chunk
+// that was read intact, and where a chunk came from doesn't make it a different chunk.
+#[derive(Debug, Clone)]
 pub struct PNGChunk {
     length: u32,
     chunk_type: [u8; 4],
     data: Vec<u8>,
     crc: [u8; 4],
+    // Where the chunk was in the file it was read from. None for chunks made in memory.
+    offset: Option<u64>,
+    index: Option<usize>,
 }
 
 // IHDR chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct IHDRData {
     width: u32,
     height: u32,
@@ -54,6 +269,8 @@ pub struct IHDRData {
 }
 
 // tIME chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
 pub struct TimeData {
     year: u16,
     month: u8,
@@ -65,76 +282,190 @@ pub struct TimeData {
 
 impl PNGFile {
 
+    #[cfg(feature = "std")]
     pub fn from_file(filename: &str) -> Result<PNGFile, Box<dyn Error>> {
-        let mut header: [u8; 8] = [0; 8];
-        let mut file = File::open(filename)?;
-        file.read(&mut header)?;
+        let file = File::open(filename)?;
+        PNGFile::from_reader(BufReader::new(file))
+    }
+
+    // Reads a PNG from any byte stream, such as a pipe or socket. Reading stops at IEND, so
+    // anything after it is left in the reader. Unbuffered readers should be wrapped in a
+    // BufReader first. Spec violations are tolerated as they are by from_file_with_mode in
+    // permissive mode, but the data must not end before IEND.
+    #[cfg(feature = "std")]
+    pub fn from_reader<R: Read>(reader: R) -> Result<PNGFile, Box<dyn Error>> {
+        let (chunks, parser) = read_chunks(reader, &ParseOptions::default())?;
+        if !parser.is_done() {
+            return Err(TruncatedPNGFile::new(&parser.awaiting()).into());
+        }
+        PNGFile::from_chunks(chunks)
+    }
+
+    // Parses a PNG held in memory, the way from_reader parses a stream. Anything after IEND is
+    // ignored. This is the loader to use without std.
+    pub fn from_bytes(bytes: &[u8]) -> Result<PNGFile, Box<dyn Error>> {
+        let (chunks, parser) = parse_chunks(bytes, &ParseOptions::default())?;
+        if !parser.is_done() {
+            return Err(TruncatedPNGFile::new(&parser.awaiting()).into());
+        }
+        PNGFile::from_chunks(chunks)
+    }
 
-        // All PNG files must have the same header by definition.
-        if !header.iter().zip(PNG_HEADER.iter()).all(|(a, b)| a == b) {
-            return Err(InvalidPNGFormat.into());
+    // Like from_reader_with_options, for a PNG held in memory. Data after IEND is reported as a
+    // warning.
+    pub fn from_bytes_with_options(
+        bytes: &[u8],
+        options: &ParseOptions,
+    ) -> Result<(PNGFile, ParseReport), Box<dyn Error>> {
+        let (chunks, mut parser) = parse_chunks(bytes, options)?;
+        parser.finish()?;
+        let trailing = if parser.is_done() {
+            trailing_data(bytes).map_or(0, |t| t.len())
+        } else {
+            0
+        };
+        let png_file = PNGFile::from_chunks(chunks)?;
+        let mut report = parser.into_report();
+        if trailing > 0 {
+            let end = bytes.len() - trailing;
+            report
+                .warnings
+                .push(trailing_data_warning(end as u64, trailing as u64));
         }
+        Ok((png_file, report))
+    }
+
+    // Reads and parses a whole file in the given mode, returning a report of what was found
+    // alongside it. In permissive mode its warnings include the spec violations let through.
+    #[cfg(feature = "std")]
+    pub fn from_file_with_mode(
+        filename: &str,
+        mode: ParseMode,
+    ) -> Result<(PNGFile, ParseReport), Box<dyn Error>> {
+        let options = ParseOptions {
+            mode,
+            ..ParseOptions::default()
+        };
+        PNGFile::from_file_with_options(filename, &options)
+    }
+
+    // Like from_file_with_mode, for any byte stream.
+    #[cfg(feature = "std")]
+    pub fn from_reader_with_mode<R: Read>(
+        reader: R,
+        mode: ParseMode,
+    ) -> Result<(PNGFile, ParseReport), Box<dyn Error>> {
+        let options = ParseOptions {
+            mode,
+            ..ParseOptions::default()
+        };
+        PNGFile::from_reader_with_options(reader, &options)
+    }
+
+    // Reads and parses a whole file with the given mode and limits, returning a report of what
+    // was found alongside it. Use this for files from untrusted sources. Data after IEND is
+    // reported as a warning.
+    #[cfg(feature = "std")]
+    pub fn from_file_with_options(
+        filename: &str,
+        options: &ParseOptions,
+    ) -> Result<(PNGFile, ParseReport), Box<dyn Error>> {
+        let file = File::open(filename)?;
+        let length = file.metadata()?.len();
+        let mut reader = BufReader::new(file);
+        let (png_file, mut report) = PNGFile::from_reader_with_options(&mut reader, options)?;
+        // Reading only stops before the end of the file at IEND.
+        let end = reader.stream_position()?;
+        if end < length {
+            report
+                .warnings
+                .push(trailing_data_warning(end, length - end));
+        }
+        Ok((png_file, report))
+    }
+
+    // Like from_file_with_options, for any byte stream. In permissive mode data that ends early
+    // is only a warning, and the file holds the chunks that were complete.
+    #[cfg(feature = "std")]
+    pub fn from_reader_with_options<R: Read>(
+        reader: R,
+        options: &ParseOptions,
+    ) -> Result<(PNGFile, ParseReport), Box<dyn Error>> {
+        let (chunks, mut parser) = read_chunks(reader, options)?;
+        parser.finish()?;
+        let png_file = PNGFile::from_chunks(chunks)?;
+        Ok((png_file, parser.into_report()))
+    }
 
+    // Assembles a file from chunks in file order, such as the ones produced by PNGParser. The
+    // chunks must include a valid IHDR. Should there be more than one IHDR or tIME, the first is
+    // used.
+    pub fn from_chunks(all_chunks: Vec<PNGChunk>) -> Result<PNGFile, Box<dyn Error>> {
         let mut ihdr_chunk: Option<PNGChunk> = None;
         let mut time_chunk: Option<PNGChunk> = None;
         let mut chunks: Vec<PNGChunk> = Vec::new();
-        let mut found_iend = false;
-
-        while !found_iend {
-            let mut length: [u8; 4] = [0; 4];
-            file.read(&mut length).unwrap();
-            let length: u32 = u32::from_be_bytes(length);
-
-            let mut chunk_type: [u8; 4] = [0; 4];
-            file.read(&mut chunk_type).unwrap();
-
-            let mut data: Vec<u8> = vec![0u8; length as usize];
-            file.read(data.as_mut_slice()).unwrap();
-
-            let mut crc: [u8; 4] = [0; 4];
-            file.read(&mut crc).unwrap();
-
-            let chunk = PNGChunk {
-                length,
-                chunk_type,
-                data,
-                crc,
-            };
-            let chunk_type_str = str::from_utf8(&chunk_type).unwrap();
-
-            if chunk_type_str == "IHDR" {
-                ihdr_chunk = Some(chunk);
-                continue;
-            } else if chunk_type_str == "tIME" {
-                time_chunk = Some(chunk);
-                continue;
-            }
 
-            if chunk_type_str == "IEND" {
-                found_iend = true;
+        for chunk in all_chunks {
+            match &chunk.chunk_type {
+                b"IHDR" if ihdr_chunk.is_none() => ihdr_chunk = Some(chunk),
+                b"tIME" if time_chunk.is_none() => time_chunk = Some(chunk),
+                b"IHDR" | b"tIME" => {}
+                _ => chunks.push(chunk),
             }
-
-            chunks.push(chunk);
         }
 
-        if let Some(ihdr) = ihdr_chunk {
-            return Ok( PNGFile{ ihdr_chunk: ihdr, time_chunk, chunks } );
-        }
-        Err(InvalidPNGFormat.into())
+        let ihdr_chunk = ihdr_chunk.ok_or(InvalidPNGFormat)?;
+        Ok(PNGFile {
+            ihdr: IHDRData::parse(&ihdr_chunk)?,
+            ihdr_chunk,
+            time_chunk,
+            chunks,
+        })
     }
 
     pub fn get_ihdr_chunk(&self) -> &PNGChunk {
-        // TODO - A caller would be more likely to care about the IHDR data, not the chunk. Change
-        // this to return an IHDRData chunk. For now this won't be a struct that affects the file
-        // itself, but that's probably a good future step.
         &self.ihdr_chunk
     }
 
+    pub fn get_ihdr_data(&self) -> &IHDRData {
+        &self.ihdr
+    }
+
+    pub fn width(&self) -> u32 {
+        self.dimensions().0
+    }
+
+    pub fn height(&self) -> u32 {
+        self.dimensions().1
+    }
+
+    // Whether the format can carry transparency, through an alpha channel or a tRNS chunk. It
+    // says nothing about whether any pixel is actually transparent; see is_fully_opaque. The
+    // color type comes from the IHDR parsed when the file was made, so this can't panic.
+    pub fn has_alpha(&self) -> bool {
+        matches!(self.ihdr.color_type, 4 | 6) || self.first_chunk(b"tRNS").is_some()
+    }
+
+    // Whether the file is an APNG, which is marked by an acTL chunk. Decoders that don't know
+    // APNG show the default image as a still.
+    pub fn is_animated(&self) -> bool {
+        self.first_chunk(b"acTL").is_some()
+    }
+
+    // Width and height in pixels, from IHDR.
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.ihdr.width, self.ihdr.height)
+    }
+
+    // Replaces the tIME chunk outright rather than editing it.
+    pub fn set_last_modified(&mut self, time: &TimeData) {
+        self.time_chunk = Some(time.to_chunk());
+    }
+
+    // None when there's no tIME chunk, or when it isn't the 7 bytes the spec gives it.
     pub fn get_last_modified(&self) -> Option<TimeData> {
-        // TODO Add a set_last_modified - unlike other chunks, the existing data for last time
-        // modified should be entirely replaced with a new TimeData, not edited.
-        if let Some(chunk) = &self.time_chunk {
-            let year = u16::from_be_bytes(chunk.data[0..2].try_into().unwrap());
+        if let Some(chunk) = self.time_chunk.as_ref().filter(|chunk| chunk.data.len() == 7) {
+            let year = u16::from_be_bytes([chunk.data[0], chunk.data[1]]);
             let month = chunk.data[2];
             let day = chunk.data[3];
             let hour = chunk.data[4];
@@ -157,41 +488,173 @@ impl PNGFile {
         &self.chunks
     }
 
+    // Every chunk in the order write_to writes them, starting with IHDR. Unlike get_chunks this
+    // includes IHDR and tIME.
+    pub fn iter_chunks(&self) -> impl Iterator<Item = &PNGChunk> {
+        iter::once(&self.ihdr_chunk)
+            .chain(self.time_chunk.iter())
+            .chain(self.chunks.iter())
+    }
+
+    // The chunks of the given type, in file order.
+    pub fn chunks_of_type(&self, chunk_type: &[u8; 4]) -> impl Iterator<Item = &PNGChunk> {
+        let chunk_type = *chunk_type;
+        self.iter_chunks()
+            .filter(move |c| c.chunk_type == chunk_type)
+    }
+
+    pub fn first_chunk(&self, chunk_type: &[u8; 4]) -> Option<&PNGChunk> {
+        self.chunks_of_type(chunk_type).next()
+    }
+
+    // Copies of every chunk in the order write_to writes them, starting with IHDR. Handy for
+    // editing the list and rebuilding the file with from_chunks.
+    pub fn to_chunks(&self) -> Vec<PNGChunk> {
+        let mut chunks = Vec::with_capacity(self.chunks.len() + 2);
+        chunks.push(self.ihdr_chunk.clone());
+        chunks.extend(self.time_chunk.clone());
+        chunks.extend(self.chunks.iter().cloned());
+        chunks
+    }
+
+    // Writes the file through a temporary file that replaces filename once it's complete, so
+    // the target is never left half written. See WriteOptions::backup to keep the old file.
+    #[cfg(feature = "std")]
     pub fn write(&self, filename: &str) -> Result<(), Box<dyn Error>> {
-        let mut buffer = File::create(filename).unwrap();
-        buffer.write(&PNG_HEADER)?;
+        write::write_atomically(filename, false, |out| self.write_to(out))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn write_to<W: Write>(&self, buffer: &mut W) -> Result<(), Box<dyn Error>> {
+        buffer.write_all(&PNG_HEADER)?;
 
-        &self.ihdr_chunk.write_to_file(&mut buffer)?;
+        self.ihdr_chunk.write_to_file(buffer)?;
         // TODO Update to use a current timestamp since the file is being written out.
         // The spec allows the time chunk to come in this order, but it may be valuable in the
         // future to preserve the original ordering if there is one.
         if let Some(time_chunk) = &self.time_chunk {
-            time_chunk.write_to_file(&mut buffer)?;
+            time_chunk.write_to_file(buffer)?;
         }
 
         for chunk in &self.chunks {
-            &chunk.write_to_file(&mut buffer)?;
+            chunk.write_to_file(buffer)?;
         }
 
         Ok(())
     }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut bytes = PNG_HEADER.to_vec();
+        self.ihdr_chunk.append_to(&mut bytes);
+        if let Some(time_chunk) = &self.time_chunk {
+            time_chunk.append_to(&mut bytes);
+        }
+        for chunk in &self.chunks {
+            chunk.append_to(&mut bytes);
+        }
+        Ok(bytes)
+    }
 }
 
 impl PNGChunk {
-    fn write_to_file(&self, open_file: &mut File) -> Result<(), Box<dyn Error>> {
-        open_file.write(&self.length.to_be_bytes())?;
-        open_file.write(&self.chunk_type)?;
-        open_file.write(&self.data)?;
-        open_file.write(&self.crc)?;
+    pub fn new(chunk_type: [u8; 4], data: Vec<u8>) -> PNGChunk {
+        let crc = crc::chunk_crc(&chunk_type, &data).to_be_bytes();
+        PNGChunk {
+            length: data.len() as u32,
+            chunk_type,
+            data,
+            crc,
+            offset: None,
+            index: None,
+        }
+    }
+
+    pub fn get_chunk_type(&self) -> &[u8; 4] {
+        &self.chunk_type
+    }
+
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn get_crc(&self) -> u32 {
+        u32::from_be_bytes(self.crc)
+    }
+
+    // Whether the stored CRC matches the chunk's type and data. Chunks read in permissive mode
+    // keep whatever CRC the file had.
+    pub fn has_valid_crc(&self) -> bool {
+        crc::chunk_crc(&self.chunk_type, &self.data) == self.get_crc()
+    }
+
+    // Byte offset of the chunk's length field from the start of the file it was read from.
+    pub fn get_offset(&self) -> Option<u64> {
+        self.offset
+    }
+
+    // Position of the chunk among all the chunks of the file it was read from, IHDR being 0.
+    pub fn get_index(&self) -> Option<usize> {
+        self.index
+    }
+
+    // The case of each letter of the chunk type is a flag, uppercase meaning the bit is clear.
+
+    // Critical chunks are needed to show the image, so decoders must reject files with critical
+    // chunks they don't know. Everything else is ancillary.
+    pub fn is_critical(&self) -> bool {
+        self.chunk_type[0].is_ascii_uppercase()
+    }
+
+    pub fn is_ancillary(&self) -> bool {
+        !self.is_critical()
+    }
+
+    // Private chunks are defined by an application rather than by the spec or its registry.
+    pub fn is_private(&self) -> bool {
+        self.chunk_type[1].is_ascii_lowercase()
+    }
+
+    // Clear in every valid chunk type.
+    pub fn is_reserved_bit_set(&self) -> bool {
+        self.chunk_type[2].is_ascii_lowercase()
+    }
+
+    // Whether an editor that doesn't know the chunk may keep it after changing critical chunks,
+    // such as the image data. Unsafe-to-copy chunks depend on those and must be dropped instead.
+    pub fn is_safe_to_copy(&self) -> bool {
+        self.chunk_type[3].is_ascii_lowercase()
+    }
+
+    #[cfg(feature = "std")]
+    fn write_to_file<W: Write>(&self, open_file: &mut W) -> Result<(), Box<dyn Error>> {
+        open_file.write_all(&self.length.to_be_bytes())?;
+        open_file.write_all(&self.chunk_type)?;
+        open_file.write_all(&self.data)?;
+        open_file.write_all(&self.crc)?;
 
         Ok(())
     }
+
+    fn append_to(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.length.to_be_bytes());
+        bytes.extend_from_slice(&self.chunk_type);
+        bytes.extend_from_slice(&self.data);
+        bytes.extend_from_slice(&self.crc);
+    }
+}
+
+impl PartialEq for PNGChunk {
+    fn eq(&self, other: &PNGChunk) -> bool {
+        self.chunk_type == other.chunk_type && self.data == other.data
+    }
 }
 
+impl Eq for PNGChunk {}
+
 impl fmt::Display for PNGChunk {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let length = &self.length;
-        let chunk_type = str::from_utf8(&self.chunk_type).unwrap();
+        let chunk_type = String::from_utf8_lossy(&self.chunk_type);
         let crc = u32::from_be_bytes(self.crc);
 
         let data: Vec<String> = self.data.iter().map(|b| format!("{:02X}", b)).collect();
@@ -209,10 +672,22 @@ impl fmt::Display for PNGChunk {
 }
 
 impl IHDRData {
+    // Kept for existing callers. Panics when the chunk isn't a valid IHDR.
+    #[deprecated(note = "use IHDRData::parse, or PNGFile::get_ihdr_data for a file's header")]
     pub fn from_chunk(chunk: &PNGChunk) -> IHDRData {
-        if str::from_utf8(&chunk.chunk_type).unwrap() != "IHDR" {
-            // TODO Change this to return a Result
-            panic!("Not an IHDR chunk!");
+        IHDRData::parse(chunk).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    pub fn parse(chunk: &PNGChunk) -> Result<IHDRData, InvalidIHDRData> {
+        let invalid = |message: &str| InvalidIHDRData {
+            message: message.to_string(),
+        };
+
+        if &chunk.chunk_type != b"IHDR" {
+            return Err(invalid("Not an IHDR chunk!"));
+        }
+        if chunk.data.len() != 13 {
+            return Err(invalid("The IHDR chunk must be 13 bytes long."));
         }
 
         let width: u32 = u32::from_be_bytes(chunk.data[0..4].try_into().unwrap());
@@ -224,61 +699,60 @@ impl IHDRData {
         let interlace_method = chunk.data[12];
 
         if width == 0 || height == 0 {
-            // TODO Create error type for invalid IHDR data and return
-            panic!("Width and height must be non-zero numbers.");
+            return Err(invalid("Width and height must be non-zero numbers."));
         }
 
-        // TODO There's probably a cleaner way to do this. Find one or remove this comment.
-        if bit_depth != 1 && bit_depth != 2 && bit_depth != 4 && bit_depth != 8 && bit_depth != 16 {
-            // TODO Create error type for invalid IHDR data and return
-            panic!("Invalid bit depth specified. Valid values are 1, 2, 4, 8, and 16.");
+        if ![1, 2, 4, 8, 16].contains(&bit_depth) {
+            return Err(invalid(
+                "Invalid bit depth specified. Valid values are 1, 2, 4, 8, and 16.",
+            ));
         }
 
-        // TODO There's probably a cleaner way to do this. Find one or remove this comment.
-        if color_type != 0
-            && color_type != 2
-            && color_type != 3
-            && color_type != 4
-            && color_type != 6
-        {
-            // TODO Create error type for invalid IHDR data and return
-            panic!("Invalid color type specified. Valid values are 0, 2, 3, 4, and 6.");
+        if ![0, 2, 3, 4, 6].contains(&color_type) {
+            return Err(invalid(
+                "Invalid color type specified. Valid values are 0, 2, 3, 4, and 6.",
+            ));
         }
 
         if (color_type == 2 || color_type == 4 || color_type == 6)
             && (bit_depth != 8 && bit_depth != 16)
         {
-            // TODO Create error type for invalid IHDR data and return
-            panic!("Invalid bit depth specified for color type. Valid values are 8 and 16.");
+            return Err(invalid(
+                "Invalid bit depth specified for color type. Valid values are 8 and 16.",
+            ));
         } else if color_type == 3 && bit_depth == 16 {
-            // TODO Create error type for invalid IHDR data and return
-            panic!("Invalid bit depth specified for color type. Valid values are 1, 2, 4, and 8.");
+            return Err(invalid(
+                "Invalid bit depth specified for color type. Valid values are 1, 2, 4, and 8.",
+            ));
         }
         // Color type 0 allows all valid bit depths, so no check needed.
 
         if compression_method != 0 {
             // TODO While not defined in the ISO spec, this may still be valid. Needs more
-            // research, but for now we'll reject it. Needs an IHDR error type if we don't allow
-            // it.
-            panic!("Unsupported compression method specified. The only valid value is 0.");
+            // research, but for now we'll reject it.
+            return Err(invalid(
+                "Unsupported compression method specified. The only valid value is 0.",
+            ));
         }
 
         if filter_method != 0 {
             // TODO While not defined in the ISO spec, this may still be valid. Needs more
-            // research, but for now we'll reject it. Needs an IHDR error type if we don't allow
-            // it.
-            panic!("Unsupported filter method specified. The only valid value is 0.");
+            // research, but for now we'll reject it.
+            return Err(invalid(
+                "Unsupported filter method specified. The only valid value is 0.",
+            ));
         }
 
         if interlace_method > 1 {
             // TODO While not defined in the ISO spec, this may still be valid. Needs more
-            // research, but for now we'll reject it. Needs an IHDR error type if we don't allow
-            // it.
-            panic!("Unsupported interlace method specified. Valid values are 0 and 1.");
+            // research, but for now we'll reject it.
+            return Err(invalid(
+                "Unsupported interlace method specified. Valid values are 0 and 1.",
+            ));
         }
 
         // All validation passed.
-        IHDRData {
+        Ok(IHDRData {
             width,
             height,
             bit_depth,
@@ -286,7 +760,29 @@ impl IHDRData {
             compression_method,
             filter_method,
             interlace_method,
-        }
+        })
+    }
+}
+
+impl IHDRData {
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get_bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+
+    pub fn get_color_type(&self) -> u8 {
+        self.color_type
+    }
+
+    pub fn get_interlace_method(&self) -> u8 {
+        self.interlace_method
     }
 }
 
@@ -312,6 +808,85 @@ impl fmt::Display for IHDRData {
     }
 }
 
+impl TimeData {
+    pub fn new(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> TimeData {
+        TimeData {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        }
+    }
+
+    // Converts seconds since the Unix epoch to a UTC date and time.
+    pub fn from_unix_seconds(seconds: u64) -> TimeData {
+        let days = (seconds / 86400) as i64;
+        let time_of_day = seconds % 86400;
+
+        // Days to civil date, from Howard Hinnant's date algorithms.
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        TimeData {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (time_of_day / 3600) as u8,
+            minute: (time_of_day % 3600 / 60) as u8,
+            second: (time_of_day % 60) as u8,
+        }
+    }
+
+    // The current UTC time, as the spec asks tIME to hold.
+    #[cfg(feature = "std")]
+    pub fn now() -> TimeData {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        TimeData::from_unix_seconds(seconds)
+    }
+
+    pub fn to_chunk(&self) -> PNGChunk {
+        let mut data = self.year.to_be_bytes().to_vec();
+        data.extend_from_slice(&[self.month, self.day, self.hour, self.minute, self.second]);
+        PNGChunk::new(*b"tIME", data)
+    }
+
+    pub fn get_year(&self) -> u16 {
+        self.year
+    }
+
+    pub fn get_month(&self) -> u8 {
+        self.month
+    }
+
+    pub fn get_day(&self) -> u8 {
+        self.day
+    }
+
+    pub fn get_hour(&self) -> u8 {
+        self.hour
+    }
+
+    pub fn get_minute(&self) -> u8 {
+        self.minute
+    }
+
+    pub fn get_second(&self) -> u8 {
+        self.second
+    }
+}
+
 impl fmt::Display for TimeData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -0,0 +1,633 @@
+use std::convert::TryInto;
+use std::error;
+use std::error::Error;
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use super::{IHDRData, PNGChunk, PNGFile};
+use crate::image::{ColorType, Image};
+
+#[derive(Debug, Clone)]
+pub struct InvalidAPNG {
+    message: String,
+}
+
+impl InvalidAPNG {
+    fn new(message: &str) -> InvalidAPNG {
+        InvalidAPNG {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for InvalidAPNG {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid APNG: {}", self.message)
+    }
+}
+
+impl error::Error for InvalidAPNG {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+// acTL chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct AnimationControl {
+    num_frames: u32,
+    num_plays: u32,
+}
+
+// What happens to a frame's region once it has been shown, before the next frame is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum DisposeOp {
+    // Leave it as it is.
+    None,
+    // Clear it to transparent black.
+    Background,
+    // Put back what was there before the frame was drawn.
+    Previous,
+}
+
+// How a frame is drawn onto the canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub enum BlendOp {
+    // Replace the region, alpha included.
+    Source,
+    // Alpha composite the frame over the region.
+    Over,
+}
+
+// fcTL chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FrameControl {
+    pub(crate) sequence_number: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) x_offset: u32,
+    pub(crate) y_offset: u32,
+    pub(crate) delay_num: u16,
+    pub(crate) delay_den: u16,
+    pub(crate) dispose_op: DisposeOp,
+    pub(crate) blend_op: BlendOp,
+}
+
+// One frame of an animation: its fcTL and its image data. The data is a zlib stream like the
+// one the IDAT chunks of a still image hold, covering only the frame's region, with the
+// sequence numbers of the fdAT chunks it came from removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    control: FrameControl,
+    data: Vec<u8>,
+    default_image: bool,
+}
+
+impl AnimationControl {
+    // A num_plays of 0 loops forever.
+    pub fn new(num_frames: u32, num_plays: u32) -> AnimationControl {
+        AnimationControl {
+            num_frames,
+            num_plays,
+        }
+    }
+
+    pub fn parse(chunk: &PNGChunk) -> Result<AnimationControl, InvalidAPNG> {
+        if &chunk.chunk_type != b"acTL" {
+            return Err(InvalidAPNG::new("Not an acTL chunk."));
+        }
+        if chunk.data.len() != 8 {
+            return Err(InvalidAPNG::new("The acTL chunk must be 8 bytes long."));
+        }
+        let num_frames = u32::from_be_bytes(chunk.data[0..4].try_into().unwrap());
+        let num_plays = u32::from_be_bytes(chunk.data[4..8].try_into().unwrap());
+        if num_frames == 0 {
+            return Err(InvalidAPNG::new(
+                "An animation must have at least one frame.",
+            ));
+        }
+        Ok(AnimationControl {
+            num_frames,
+            num_plays,
+        })
+    }
+
+    pub fn to_chunk(&self) -> PNGChunk {
+        let mut data = Vec::with_capacity(8);
+        data.extend_from_slice(&self.num_frames.to_be_bytes());
+        data.extend_from_slice(&self.num_plays.to_be_bytes());
+        PNGChunk::new(*b"acTL", data)
+    }
+
+    pub fn get_num_frames(&self) -> u32 {
+        self.num_frames
+    }
+
+    // How many times the animation plays, 0 meaning forever.
+    pub fn get_num_plays(&self) -> u32 {
+        self.num_plays
+    }
+}
+
+impl DisposeOp {
+    pub fn from_u8(value: u8) -> Option<DisposeOp> {
+        match value {
+            0 => Some(DisposeOp::None),
+            1 => Some(DisposeOp::Background),
+            2 => Some(DisposeOp::Previous),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            DisposeOp::None => 0,
+            DisposeOp::Background => 1,
+            DisposeOp::Previous => 2,
+        }
+    }
+}
+
+impl BlendOp {
+    pub fn from_u8(value: u8) -> Option<BlendOp> {
+        match value {
+            0 => Some(BlendOp::Source),
+            1 => Some(BlendOp::Over),
+            _ => None,
+        }
+    }
+
+    pub fn to_u8(self) -> u8 {
+        match self {
+            BlendOp::Source => 0,
+            BlendOp::Over => 1,
+        }
+    }
+}
+
+impl FrameControl {
+    pub fn parse(chunk: &PNGChunk) -> Result<FrameControl, InvalidAPNG> {
+        if &chunk.chunk_type != b"fcTL" {
+            return Err(InvalidAPNG::new("Not an fcTL chunk."));
+        }
+        let data = &chunk.data;
+        if data.len() != 26 {
+            return Err(InvalidAPNG::new("The fcTL chunk must be 26 bytes long."));
+        }
+        let u32_at = |at: usize| u32::from_be_bytes(data[at..at + 4].try_into().unwrap());
+        let u16_at = |at: usize| u16::from_be_bytes(data[at..at + 2].try_into().unwrap());
+
+        let control = FrameControl {
+            sequence_number: u32_at(0),
+            width: u32_at(4),
+            height: u32_at(8),
+            x_offset: u32_at(12),
+            y_offset: u32_at(16),
+            delay_num: u16_at(20),
+            delay_den: u16_at(22),
+            dispose_op: DisposeOp::from_u8(data[24])
+                .ok_or_else(|| InvalidAPNG::new("Unknown dispose op."))?,
+            blend_op: BlendOp::from_u8(data[25])
+                .ok_or_else(|| InvalidAPNG::new("Unknown blend op."))?,
+        };
+        if control.width == 0 || control.height == 0 {
+            return Err(InvalidAPNG::new("Frames must be at least 1x1."));
+        }
+        Ok(control)
+    }
+
+    pub fn to_chunk(&self) -> PNGChunk {
+        let mut data = Vec::with_capacity(26);
+        data.extend_from_slice(&self.sequence_number.to_be_bytes());
+        data.extend_from_slice(&self.width.to_be_bytes());
+        data.extend_from_slice(&self.height.to_be_bytes());
+        data.extend_from_slice(&self.x_offset.to_be_bytes());
+        data.extend_from_slice(&self.y_offset.to_be_bytes());
+        data.extend_from_slice(&self.delay_num.to_be_bytes());
+        data.extend_from_slice(&self.delay_den.to_be_bytes());
+        data.push(self.dispose_op.to_u8());
+        data.push(self.blend_op.to_u8());
+        PNGChunk::new(*b"fcTL", data)
+    }
+
+    // Position of the chunk among the fcTL and fdAT chunks, which are numbered together from 0.
+    pub fn get_sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get_x_offset(&self) -> u32 {
+        self.x_offset
+    }
+
+    pub fn get_y_offset(&self) -> u32 {
+        self.y_offset
+    }
+
+    pub fn get_delay_num(&self) -> u16 {
+        self.delay_num
+    }
+
+    pub fn get_delay_den(&self) -> u16 {
+        self.delay_den
+    }
+
+    // How long the frame is shown, in seconds. A denominator of 0 stands for 100.
+    pub fn get_delay(&self) -> f64 {
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            self.delay_den
+        };
+        self.delay_num as f64 / den as f64
+    }
+
+    pub fn get_dispose_op(&self) -> DisposeOp {
+        self.dispose_op
+    }
+
+    pub fn get_blend_op(&self) -> BlendOp {
+        self.blend_op
+    }
+}
+
+impl Frame {
+    pub fn get_control(&self) -> &FrameControl {
+        &self.control
+    }
+
+    pub fn get_data(&self) -> &[u8] {
+        &self.data
+    }
+
+    // Whether the frame is the image in the IDAT chunks, which decoders that don't know APNG
+    // show on its own.
+    pub fn is_default_image(&self) -> bool {
+        self.default_image
+    }
+}
+
+impl PNGFile {
+    // The acTL chunk, or None for a still image.
+    pub fn get_animation_control(&self) -> Result<Option<AnimationControl>, InvalidAPNG> {
+        self.first_chunk(b"acTL")
+            .map(AnimationControl::parse)
+            .transpose()
+    }
+
+    // Every frame of the animation in order, or none for a still image. The fcTL and fdAT
+    // chunks are checked against each other and against the acTL chunk: sequence numbers must
+    // count up from 0 without gaps, every frame must fit on the canvas and have data, and there
+    // must be as many frames as acTL says.
+    pub fn frames(&self) -> Result<Vec<Frame>, InvalidAPNG> {
+        let animation_control = match self.get_animation_control()? {
+            Some(animation_control) => animation_control,
+            None => return Ok(Vec::new()),
+        };
+        let ihdr = &self.ihdr;
+
+        let mut frames: Vec<Frame> = Vec::new();
+        let mut next_sequence_number = 0;
+        let mut check_sequence_number = |sequence_number: u32| {
+            if sequence_number != next_sequence_number {
+                return Err(InvalidAPNG::new(&format!(
+                    "Expected sequence number {} but found {}.",
+                    next_sequence_number, sequence_number
+                )));
+            }
+            next_sequence_number += 1;
+            Ok(())
+        };
+        let mut seen_idat = false;
+
+        for chunk in &self.chunks {
+            match &chunk.chunk_type {
+                b"fcTL" => {
+                    let control = FrameControl::parse(chunk)?;
+                    check_sequence_number(control.sequence_number)?;
+                    if control.x_offset as u64 + control.width as u64 > ihdr.width as u64
+                        || control.y_offset as u64 + control.height as u64 > ihdr.height as u64
+                    {
+                        return Err(InvalidAPNG::new(&format!(
+                            "Frame {} doesn't fit on the canvas.",
+                            frames.len()
+                        )));
+                    }
+                    if !seen_idat {
+                        if !frames.is_empty() {
+                            return Err(InvalidAPNG::new(
+                                "Only one fcTL chunk may come before the image data.",
+                            ));
+                        }
+                        if control.x_offset != 0
+                            || control.y_offset != 0
+                            || control.width != ihdr.width
+                            || control.height != ihdr.height
+                        {
+                            return Err(InvalidAPNG::new(
+                                "A frame using the default image must cover the whole canvas.",
+                            ));
+                        }
+                    }
+                    if frames.last().is_some_and(|f| f.data.is_empty()) {
+                        return Err(InvalidAPNG::new(&format!(
+                            "Frame {} has no data.",
+                            frames.len() - 1
+                        )));
+                    }
+                    frames.push(Frame {
+                        control,
+                        data: Vec::new(),
+                        default_image: !seen_idat,
+                    });
+                }
+                b"IDAT" => {
+                    seen_idat = true;
+                    if let Some(frame) = frames.last_mut().filter(|f| f.default_image) {
+                        frame.data.extend_from_slice(&chunk.data);
+                    }
+                }
+                b"fdAT" => {
+                    if chunk.data.len() < 4 {
+                        return Err(InvalidAPNG::new("An fdAT chunk is too short."));
+                    }
+                    check_sequence_number(u32::from_be_bytes(
+                        chunk.data[0..4].try_into().unwrap(),
+                    ))?;
+                    match frames.last_mut() {
+                        Some(frame) if !frame.default_image => {
+                            frame.data.extend_from_slice(&chunk.data[4..])
+                        }
+                        _ => {
+                            return Err(InvalidAPNG::new(
+                                "An fdAT chunk doesn't follow an fcTL chunk.",
+                            ))
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if frames.last().is_some_and(|f| f.data.is_empty()) {
+            return Err(InvalidAPNG::new(&format!(
+                "Frame {} has no data.",
+                frames.len() - 1
+            )));
+        }
+        if frames.len() as u64 != animation_control.num_frames as u64 {
+            return Err(InvalidAPNG::new(&format!(
+                "The acTL chunk says there are {} frames, but there are {}.",
+                animation_control.num_frames,
+                frames.len()
+            )));
+        }
+        Ok(frames)
+    }
+
+    // Retiming only rewrites the acTL and fcTL chunks; the frame data is left alone. Those
+    // chunks keep their length, so patch_chunk can also write them straight into a file.
+
+    // Sets how many times the animation plays, 0 meaning forever.
+    pub fn set_loop_count(&mut self, num_plays: u32) -> Result<(), InvalidAPNG> {
+        let chunk = self
+            .chunks
+            .iter_mut()
+            .find(|c| &c.chunk_type == b"acTL")
+            .ok_or_else(|| InvalidAPNG::new("The file is not animated."))?;
+        let control = AnimationControl::parse(chunk)?;
+        *chunk = AnimationControl {
+            num_plays,
+            ..control
+        }
+        .to_chunk();
+        Ok(())
+    }
+
+    // Sets how long one frame is shown, in delay_num / delay_den seconds.
+    pub fn set_frame_delay(
+        &mut self,
+        frame: usize,
+        delay_num: u16,
+        delay_den: u16,
+    ) -> Result<(), InvalidAPNG> {
+        let chunk = self
+            .chunks
+            .iter_mut()
+            .filter(|c| &c.chunk_type == b"fcTL")
+            .nth(frame)
+            .ok_or_else(|| InvalidAPNG::new(&format!("There is no frame {}.", frame)))?;
+        set_delay(chunk, delay_num, delay_den)
+    }
+
+    // Sets the same delay for every frame.
+    pub fn set_frame_delays(&mut self, delay_num: u16, delay_den: u16) -> Result<(), InvalidAPNG> {
+        for chunk in self.chunks.iter_mut().filter(|c| &c.chunk_type == b"fcTL") {
+            set_delay(chunk, delay_num, delay_den)?;
+        }
+        Ok(())
+    }
+}
+
+// The chunks holding an animation's image data, in order: the default image's IDAT chunks when
+// it isn't part of the animation, then every frame's fcTL followed by its data. The first frame's
+// data goes in IDAT chunks when there is no separate default image and in fdAT chunks otherwise,
+// one chunk per piece of data. Sequence numbers are assigned here, so the ones in the frame
+// controls don't matter.
+pub(crate) fn frame_chunks(
+    default_image: Option<Vec<Vec<u8>>>,
+    frames: Vec<(FrameControl, Vec<Vec<u8>>)>,
+) -> Vec<PNGChunk> {
+    let idat = |data: Vec<u8>| PNGChunk::new(*b"IDAT", data);
+    let separate_default = default_image.is_some();
+    let mut chunks: Vec<PNGChunk> = default_image.into_iter().flatten().map(idat).collect();
+
+    let mut sequence_number = 0;
+    for (index, (control, data)) in frames.into_iter().enumerate() {
+        chunks.push(
+            FrameControl {
+                sequence_number,
+                ..control
+            }
+            .to_chunk(),
+        );
+        sequence_number += 1;
+
+        if index == 0 && !separate_default {
+            chunks.extend(data.into_iter().map(idat));
+            continue;
+        }
+        for piece in data {
+            let mut fdat = Vec::with_capacity(piece.len() + 4);
+            fdat.extend_from_slice(&sequence_number.to_be_bytes());
+            fdat.extend_from_slice(&piece);
+            chunks.push(PNGChunk::new(*b"fdAT", fdat));
+            sequence_number += 1;
+        }
+    }
+    chunks
+}
+
+fn set_delay(chunk: &mut PNGChunk, delay_num: u16, delay_den: u16) -> Result<(), InvalidAPNG> {
+    let control = FrameControl::parse(chunk)?;
+    *chunk = FrameControl {
+        delay_num,
+        delay_den,
+        ..control
+    }
+    .to_chunk();
+    Ok(())
+}
+
+impl PNGFile {
+    // Plays the animation and returns what is on the canvas after each frame is drawn, as a
+    // still PNG file per frame. Dispose and blend ops are applied the way players apply them, so
+    // each file shows the frame as it appears on screen rather than just its own region. The
+    // files are RGBA, 16-bit when the animation is and 8-bit otherwise.
+    pub fn extract_frames(&self) -> Result<Vec<PNGFile>, Box<dyn Error>> {
+        self.render_frames()?
+            .iter()
+            .map(PNGFile::from_image)
+            .collect()
+    }
+
+    // Same as extract_frames, returning the decoded canvases.
+    pub fn render_frames(&self) -> Result<Vec<Image>, Box<dyn Error>> {
+        let frames = self.frames()?;
+        let ihdr = &self.ihdr;
+        let bit_depth = if ihdr.bit_depth == 16 { 16 } else { 8 };
+        // The canvas starts out transparent black.
+        let mut canvas = Image::new(
+            ihdr.width,
+            ihdr.height,
+            ColorType::TruecolorAlpha,
+            bit_depth,
+        )?;
+
+        let mut rendered = Vec::with_capacity(frames.len());
+        for frame in &frames {
+            let control = &frame.control;
+            let image = self
+                .decode_frame(frame)?
+                .expand_transparency()
+                .convert_to(ColorType::TruecolorAlpha, bit_depth)?;
+            let previous = match control.dispose_op {
+                // Before the first frame that is the blank canvas, so disposing it clears the
+                // region as the spec asks.
+                DisposeOp::Previous => Some(canvas.copy_region(
+                    control.x_offset,
+                    control.y_offset,
+                    control.width,
+                    control.height,
+                )),
+                _ => None,
+            };
+
+            draw(&mut canvas, &image, control);
+            rendered.push(canvas.clone());
+
+            match control.dispose_op {
+                DisposeOp::None => {}
+                DisposeOp::Background => clear(&mut canvas, control),
+                DisposeOp::Previous => {
+                    if let Some(previous) = previous {
+                        draw_region(&mut canvas, &previous, control.x_offset, control.y_offset);
+                    }
+                }
+            }
+        }
+        Ok(rendered)
+    }
+
+    // Decodes a frame's region on its own, as a file with the animation's color type, bit
+    // depth, palette and transparency.
+    fn decode_frame(&self, frame: &Frame) -> Result<Image, Box<dyn Error>> {
+        let mut ihdr = self.ihdr_chunk.data.clone();
+        ihdr[0..4].copy_from_slice(&frame.control.width.to_be_bytes());
+        ihdr[4..8].copy_from_slice(&frame.control.height.to_be_bytes());
+
+        let mut chunks: Vec<PNGChunk> = self
+            .chunks
+            .iter()
+            .filter(|c| &c.chunk_type == b"PLTE" || &c.chunk_type == b"tRNS")
+            .cloned()
+            .collect();
+        chunks.push(PNGChunk::new(*b"IDAT", frame.data.clone()));
+        chunks.push(PNGChunk::new(*b"IEND", Vec::new()));
+
+        let ihdr_chunk = PNGChunk::new(*b"IHDR", ihdr);
+        PNGFile {
+            ihdr: IHDRData::parse(&ihdr_chunk)?,
+            ihdr_chunk,
+            time_chunk: None,
+            chunks,
+        }
+        .decode()
+    }
+}
+
+// Draws a decoded frame onto the canvas at its offset, using its blend op. Both images are RGBA
+// with the same bit depth.
+fn draw(canvas: &mut Image, image: &Image, control: &FrameControl) {
+    if control.blend_op == BlendOp::Source {
+        draw_region(canvas, image, control.x_offset, control.y_offset);
+        return;
+    }
+
+    let max = canvas.max_value() as f32;
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            let (cx, cy) = (control.x_offset + x, control.y_offset + y);
+            let source = image.pixel(x, y);
+            let dest = canvas.pixel(cx, cy);
+            let source_alpha = source[3] as f32 / max;
+            let dest_alpha = dest[3] as f32 / max * (1.0 - source_alpha);
+            let alpha = source_alpha + dest_alpha;
+            let mut pixel = [0u16; 4];
+            if alpha > 0.0 {
+                for c in 0..3 {
+                    let value =
+                        (source[c] as f32 * source_alpha + dest[c] as f32 * dest_alpha) / alpha;
+                    pixel[c] = value.round() as u16;
+                }
+                pixel[3] = (alpha * max).round() as u16;
+            }
+            canvas.set_pixel(cx, cy, &pixel);
+        }
+    }
+}
+
+// Copies an image onto the canvas at the given position, replacing what was there.
+fn draw_region(canvas: &mut Image, image: &Image, x_offset: u32, y_offset: u32) {
+    for y in 0..image.height() {
+        for x in 0..image.width() {
+            canvas.set_pixel(x_offset + x, y_offset + y, image.pixel(x, y));
+        }
+    }
+}
+
+// Clears a frame's region to transparent black.
+fn clear(canvas: &mut Image, control: &FrameControl) {
+    for y in control.y_offset..control.y_offset + control.height {
+        for x in control.x_offset..control.x_offset + control.width {
+            canvas.set_pixel(x, y, &[0; 4]);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::error::Error;
+
+use super::apng::{frame_chunks, AnimationControl, BlendOp, DisposeOp, FrameControl};
+use super::encode::{compress_image, FilterStrategy, DEFAULT_COMPRESSION_LEVEL};
+use super::rules::AFTER_PLTE;
+use super::write::copyable_after_edit;
+use super::{PNGChunk, PNGFile};
+use crate::image::{ColorType, Image};
+
+// Chunks the optimized file can't carry over, because it is RGBA and its animation chunks are
+// written from scratch.
+const DROPPED_CHUNKS: [&[u8; 4]; 5] = [b"acTL", b"fcTL", b"fdAT", b"sBIT", b"PLTE"];
+
+impl PNGFile {
+    // Shrinks an animation by storing, for each frame after the first, only the rectangle that
+    // changed since the frame before it. Design tools often export every frame in full even when
+    // only a small part of the picture moves.
+    //
+    // The animation is played to get the picture after every frame, and each frame is then
+    // stored as the bounding box of the pixels that differ from the previous picture, drawn over
+    // it without disposal. When every changed pixel is opaque the frame is blended over the
+    // previous picture, and the unchanged pixels inside the box become transparent, which
+    // compresses better. Otherwise the box replaces what was there.
+    //
+    // The result is RGBA like the pictures extract_frames returns and looks the same frame for
+    // frame. If it isn't smaller than the original, a copy of the original is returned instead.
+    pub fn optimize_animation(&self) -> Result<PNGFile, Box<dyn Error>> {
+        let animation_control = self
+            .get_animation_control()?
+            .ok_or("The file is not animated.")?;
+        let frames = self.frames()?;
+        let canvases = self.render_frames()?;
+        let (width, height) = self.dimensions();
+        let bit_depth = canvases[0].bit_depth();
+        let compress = |image: &Image| {
+            vec![compress_image(
+                image,
+                DEFAULT_COMPRESSION_LEVEL,
+                FilterStrategy::default(),
+                false,
+            )]
+        };
+
+        let mut optimized = Vec::with_capacity(frames.len());
+        for (index, frame) in frames.iter().enumerate() {
+            let control = FrameControl {
+                dispose_op: DisposeOp::None,
+                ..*frame.get_control()
+            };
+            if index == 0 {
+                // The first frame may be the default image, which has to cover the canvas.
+                let control = FrameControl {
+                    width,
+                    height,
+                    x_offset: 0,
+                    y_offset: 0,
+                    blend_op: BlendOp::Source,
+                    ..control
+                };
+                optimized.push((control, compress(&canvases[0])));
+                continue;
+            }
+
+            let (region, blend_op, x, y) = changed_region(&canvases[index - 1], &canvases[index]);
+            let control = FrameControl {
+                width: region.width(),
+                height: region.height(),
+                x_offset: x,
+                y_offset: y,
+                blend_op,
+                ..control
+            };
+            optimized.push((control, compress(&region)));
+        }
+
+        // A default image that isn't part of the animation is kept, converted to RGBA.
+        let default_image = if frames[0].is_default_image() {
+            None
+        } else {
+            let image = self
+                .decode()?
+                .expand_transparency()
+                .convert_to(ColorType::TruecolorAlpha, bit_depth)?;
+            Some(compress(&image))
+        };
+
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[bit_depth, ColorType::TruecolorAlpha.to_u8(), 0, 0, 0]);
+
+        let mut chunks = vec![
+            PNGChunk::new(*b"IHDR", ihdr),
+            AnimationControl::new(frames.len() as u32, animation_control.get_num_plays())
+                .to_chunk(),
+        ];
+        chunks.extend(self.time_chunk.clone());
+        // The rest of the metadata goes before the image data, where every ancillary chunk that
+        // doesn't depend on the palette is allowed.
+        chunks.extend(
+            self.chunks
+                .iter()
+                .filter(|c| c.is_ancillary() && copyable_after_edit(c))
+                .filter(|c| {
+                    !DROPPED_CHUNKS.contains(&&c.chunk_type) && !AFTER_PLTE.contains(&&c.chunk_type)
+                })
+                .cloned(),
+        );
+        chunks.extend(frame_chunks(default_image, optimized));
+        chunks.push(PNGChunk::new(*b"IEND", Vec::new()));
+
+        let png_file = PNGFile::from_chunks(chunks)?;
+        if png_file.to_bytes()?.len() >= self.to_bytes()?.len() {
+            return Ok(self.clone());
+        }
+        Ok(png_file)
+    }
+}
+
+// The smallest rectangle holding every pixel that differs between two pictures, as an image to
+// draw at the returned position with the returned blend op. Identical pictures still need a
+// frame, so they get a single unchanged pixel.
+fn changed_region(previous: &Image, current: &Image) -> (Image, BlendOp, u32, u32) {
+    let mut bounds: Option<(u32, u32, u32, u32)> = None;
+    let mut all_opaque = true;
+    let max = current.max_value();
+    for y in 0..current.height() {
+        for x in 0..current.width() {
+            let pixel = current.pixel(x, y);
+            if pixel == previous.pixel(x, y) {
+                continue;
+            }
+            all_opaque &= pixel[3] == max;
+            bounds = Some(match bounds {
+                Some((x0, y0, x1, y1)) => (x0.min(x), y0.min(y), x1.max(x), y1.max(y)),
+                None => (x, y, x, y),
+            });
+        }
+    }
+
+    let (x0, y0, x1, y1) = match bounds {
+        Some(bounds) => bounds,
+        None => return (current.copy_region(0, 0, 1, 1), BlendOp::Source, 0, 0),
+    };
+    let mut region = current.copy_region(x0, y0, x1 - x0 + 1, y1 - y0 + 1);
+    if !all_opaque {
+        return (region, BlendOp::Source, x0, y0);
+    }
+    // Blending over the previous picture, pixels that didn't change can be left transparent.
+    for y in 0..region.height() {
+        for x in 0..region.width() {
+            if current.pixel(x0 + x, y0 + y) == previous.pixel(x0 + x, y0 + y) {
+                region.set_pixel(x, y, &[0; 4]);
+            }
+        }
+    }
+    (region, BlendOp::Over, x0, y0)
+}
@@ -0,0 +1,88 @@
+// Async front-ends for the "async" feature, so services running on tokio can read uploads and
+// write responses without blocking a worker thread. They drive the same PNGParser as the
+// blocking loaders, so parsing behaves exactly the same way.
+use std::error::Error;
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::parser::{PNGParseEvent, PNGParser, ParseOptions, ParseReport};
+use super::{PNGChunk, PNGFile, TruncatedPNGFile, PNG_HEADER, READ_BUFFER_SIZE};
+
+impl PNGFile {
+    // Like from_reader, for an async byte stream. Reading stops at IEND, so anything after it is
+    // left in the reader.
+    pub async fn from_async_reader<R: AsyncRead + Unpin>(
+        reader: R,
+    ) -> Result<PNGFile, Box<dyn Error>> {
+        let (chunks, parser) = read_chunks(reader, &ParseOptions::default()).await?;
+        if !parser.is_done() {
+            return Err(TruncatedPNGFile::new(&parser.awaiting()).into());
+        }
+        PNGFile::from_chunks(chunks)
+    }
+
+    // Like from_reader_with_options, for an async byte stream.
+    pub async fn from_async_reader_with_options<R: AsyncRead + Unpin>(
+        reader: R,
+        options: &ParseOptions,
+    ) -> Result<(PNGFile, ParseReport), Box<dyn Error>> {
+        let (chunks, mut parser) = read_chunks(reader, options).await?;
+        parser.finish()?;
+        let png_file = PNGFile::from_chunks(chunks)?;
+        Ok((png_file, parser.into_report()))
+    }
+
+    // Like write_to, for an async writer. The writer is flushed at the end.
+    pub async fn write_to_async<W: AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), Box<dyn Error>> {
+        writer.write_all(&PNG_HEADER).await?;
+        write_chunk(writer, &self.ihdr_chunk).await?;
+        if let Some(time_chunk) = &self.time_chunk {
+            write_chunk(writer, time_chunk).await?;
+        }
+        for chunk in &self.chunks {
+            write_chunk(writer, chunk).await?;
+        }
+        writer.flush().await?;
+        Ok(())
+    }
+}
+
+// The async counterpart of read_chunks in png.rs.
+async fn read_chunks<R: AsyncRead + Unpin>(
+    mut reader: R,
+    options: &ParseOptions,
+) -> Result<(Vec<PNGChunk>, PNGParser), Box<dyn Error>> {
+    let mut parser = PNGParser::with_options(*options);
+    let mut chunks = Vec::new();
+    let mut buffer = vec![0u8; READ_BUFFER_SIZE];
+    loop {
+        // Never ask for more than the parser needs, so nothing past IEND is consumed.
+        let wanted = parser.bytes_needed().min(buffer.len());
+        if wanted == 0 {
+            break;
+        }
+        let read = match reader.read(&mut buffer[..wanted]).await {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        for event in parser.feed(&buffer[..read])? {
+            if let PNGParseEvent::ChunkParsed(chunk) = event {
+                chunks.push(chunk);
+            }
+        }
+    }
+    Ok((chunks, parser))
+}
+
+async fn write_chunk<W: AsyncWrite + Unpin>(writer: &mut W, chunk: &PNGChunk) -> io::Result<()> {
+    writer.write_all(&chunk.length.to_be_bytes()).await?;
+    writer.write_all(&chunk.chunk_type).await?;
+    writer.write_all(&chunk.data).await?;
+    writer.write_all(&chunk.crc).await
+}
@@ -0,0 +1,395 @@
+use std::error::Error;
+
+use super::apng::{frame_chunks, AnimationControl, BlendOp, DisposeOp, FrameControl};
+use super::encode::{compress_image, transparency_data, FilterStrategy, DEFAULT_COMPRESSION_LEVEL};
+use super::parser::{ChunkValidator, ParseOptions, RawChunk};
+use super::rules::{ParseMode, AFTER_PLTE};
+use super::{IHDRData, PNGChunk, PNGFile, TextData, TimeData};
+use crate::image::{ColorType, Image, Transparency};
+
+// Assembles a new file from its parts. Chunks are put in an order the spec allows no matter
+// which order they were given in, and build checks the result the way strict parsing would, so
+// a file that builds can be read back by any decoder.
+//
+//     let png_file = PNGFileBuilder::new(2, 1, ColorType::Indexed)
+//         .with_bit_depth(1)
+//         .with_palette(vec![[0, 0, 0], [255, 255, 255]])
+//         .with_text("Title", "Two pixels")
+//         .with_idat(compressed)
+//         .build()?;
+//
+// The image data comes either from with_image, which encodes an Image, or from with_idat, which
+// takes zlib data the caller has filtered and compressed already.
+pub struct PNGFileBuilder {
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: u8,
+    interlace: bool,
+    palette: Option<Vec<[u8; 3]>>,
+    transparency: Option<Transparency>,
+    time: Option<TimeData>,
+    text: Vec<(String, String)>,
+    chunks: Vec<PNGChunk>,
+    image: Option<Image>,
+    idat: Vec<PNGChunk>,
+}
+
+impl PNGFileBuilder {
+    // Starts a file with a bit depth of 8 and no interlacing.
+    pub fn new(width: u32, height: u32, color_type: ColorType) -> PNGFileBuilder {
+        PNGFileBuilder {
+            width,
+            height,
+            color_type,
+            bit_depth: 8,
+            interlace: false,
+            palette: None,
+            transparency: None,
+            time: None,
+            text: Vec::new(),
+            chunks: Vec::new(),
+            image: None,
+            idat: Vec::new(),
+        }
+    }
+
+    pub fn with_bit_depth(mut self, bit_depth: u8) -> PNGFileBuilder {
+        self.bit_depth = bit_depth;
+        self
+    }
+
+    // Only affects images given to with_image. Data given to with_idat must already be laid out
+    // the way this says.
+    pub fn with_interlace(mut self, interlace: bool) -> PNGFileBuilder {
+        self.interlace = interlace;
+        self
+    }
+
+    pub fn with_palette(mut self, palette: Vec<[u8; 3]>) -> PNGFileBuilder {
+        self.palette = Some(palette);
+        self
+    }
+
+    pub fn with_transparency(mut self, transparency: Transparency) -> PNGFileBuilder {
+        self.transparency = Some(transparency);
+        self
+    }
+
+    pub fn with_time(mut self, time: TimeData) -> PNGFileBuilder {
+        self.time = Some(time);
+        self
+    }
+
+    // Adds a tEXt chunk, or an iTXt chunk for text outside Latin-1.
+    pub fn with_text(mut self, keyword: &str, text: &str) -> PNGFileBuilder {
+        self.text.push((keyword.to_string(), text.to_string()));
+        self
+    }
+
+    // Adds any other ancillary chunk, such as pHYs or gAMA.
+    pub fn with_chunk(mut self, chunk: PNGChunk) -> PNGFileBuilder {
+        self.chunks.push(chunk);
+        self
+    }
+
+    // Encodes the image's pixels as the image data. The image's palette and transparency are
+    // used unless others were given.
+    pub fn with_image(mut self, image: &Image) -> PNGFileBuilder {
+        self.image = Some(image.clone());
+        self
+    }
+
+    // Adds an IDAT chunk holding part of the compressed image data. Call it once per chunk, in
+    // order.
+    pub fn with_idat(mut self, data: Vec<u8>) -> PNGFileBuilder {
+        self.idat.push(PNGChunk::new(*b"IDAT", data));
+        self
+    }
+
+    pub fn build(self) -> Result<PNGFile, Box<dyn Error>> {
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&self.width.to_be_bytes());
+        ihdr.extend_from_slice(&self.height.to_be_bytes());
+        ihdr.push(self.bit_depth);
+        ihdr.push(self.color_type.to_u8());
+        // Compression and filter methods, then interlacing.
+        ihdr.extend_from_slice(&[0, 0, self.interlace as u8]);
+        let ihdr_chunk = PNGChunk::new(*b"IHDR", ihdr);
+        IHDRData::parse(&ihdr_chunk)?;
+
+        let idat = match (&self.image, self.idat.is_empty()) {
+            (Some(_), false) => return Err("Give either an image or IDAT data, not both.".into()),
+            (None, true) => return Err("The file has no image data.".into()),
+            (Some(image), true) => {
+                if image.width() != self.width
+                    || image.height() != self.height
+                    || image.color_type() != self.color_type
+                    || image.bit_depth() != self.bit_depth
+                {
+                    return Err(
+                        "The image's size, color type or bit depth doesn't match the header."
+                            .into(),
+                    );
+                }
+                let compressed = compress_image(
+                    image,
+                    DEFAULT_COMPRESSION_LEVEL,
+                    FilterStrategy::default(),
+                    self.interlace,
+                );
+                vec![PNGChunk::new(*b"IDAT", compressed)]
+            }
+            (None, false) => self.idat,
+        };
+
+        let image = self.image.as_ref();
+        let palette = self
+            .palette
+            .or_else(|| image.and_then(|i| i.palette().cloned()));
+        let transparency = self
+            .transparency
+            .or_else(|| image.and_then(|i| i.transparency().cloned()));
+
+        // Chunks that must follow PLTE go after it, and everything else before it, which is
+        // allowed for every ancillary chunk that has to come before IDAT.
+        let (after_plte, before_plte): (Vec<PNGChunk>, Vec<PNGChunk>) = self
+            .chunks
+            .into_iter()
+            .partition(|c| AFTER_PLTE.contains(&&c.chunk_type));
+
+        let mut chunks = vec![ihdr_chunk];
+        chunks.extend(self.time.map(|t| t.to_chunk()));
+        chunks.extend(before_plte);
+        if let Some(palette) = palette {
+            let max = 1usize << self.bit_depth.min(8);
+            if palette.is_empty() || palette.len() > max {
+                return Err(format!("The palette must have 1 to {} entries.", max).into());
+            }
+            let data = palette.iter().flat_map(|e| e.iter().copied()).collect();
+            chunks.push(PNGChunk::new(*b"PLTE", data));
+        }
+        if let Some(transparency) = &transparency {
+            let matches = matches!(
+                (transparency, self.color_type),
+                (Transparency::Grayscale(_), ColorType::Grayscale)
+                    | (Transparency::Truecolor(_), ColorType::Truecolor)
+                    | (Transparency::Indexed(_), ColorType::Indexed)
+            );
+            if !matches {
+                return Err("The transparency doesn't match the color type.".into());
+            }
+            chunks.push(PNGChunk::new(*b"tRNS", transparency_data(transparency)));
+        }
+        chunks.extend(after_plte);
+        for (keyword, text) in &self.text {
+            chunks.push(TextData::new(keyword, text)?.to_chunk());
+        }
+        chunks.extend(idat);
+        chunks.push(PNGChunk::new(*b"IEND", Vec::new()));
+
+        validate(&chunks)?;
+        PNGFile::from_chunks(chunks)
+    }
+}
+
+// Assembles an APNG from still frames of the same size.
+//
+//     let apng = ApngBuilder::new()
+//         .with_frame(&first, 1, 10)
+//         .with_frame(&second, 1, 10)
+//         .with_loop_count(0)
+//         .build()?;
+//
+// Frames cover the whole canvas unless they are added with with_frame_region. When every frame
+// has the same color type, bit depth, interlacing, palette and transparency, their compressed
+// data is copied as it is. Otherwise every frame is converted to RGBA and
+// encoded again. The first frame doubles as the image decoders without APNG support show,
+// unless a separate one is given with with_default_image.
+pub struct ApngBuilder {
+    // Each frame with its offsets, delay and ops. The control's size and sequence number are
+    // filled in when building.
+    frames: Vec<(PNGFile, FrameControl)>,
+    num_plays: u32,
+    default_image: Option<PNGFile>,
+}
+
+impl ApngBuilder {
+    // Starts an animation that loops forever.
+    pub fn new() -> ApngBuilder {
+        ApngBuilder {
+            frames: Vec::new(),
+            num_plays: 0,
+            default_image: None,
+        }
+    }
+
+    // Adds a frame shown for delay_num / delay_den seconds. A denominator of 0 stands for 100.
+    pub fn with_frame(self, frame: &PNGFile, delay_num: u16, delay_den: u16) -> ApngBuilder {
+        self.with_frame_region(
+            frame,
+            0,
+            0,
+            delay_num,
+            delay_den,
+            DisposeOp::None,
+            BlendOp::Source,
+        )
+    }
+
+    // Adds a frame that only covers part of the canvas, drawn at x_offset, y_offset with
+    // blend_op and disposed of with dispose_op. The canvas is the size of the default image, or
+    // of the first frame without one, so the first frame of such an animation covers it all.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_frame_region(
+        mut self,
+        frame: &PNGFile,
+        x_offset: u32,
+        y_offset: u32,
+        delay_num: u16,
+        delay_den: u16,
+        dispose_op: DisposeOp,
+        blend_op: BlendOp,
+    ) -> ApngBuilder {
+        let (width, height) = frame.dimensions();
+        let control = FrameControl {
+            sequence_number: 0,
+            width,
+            height,
+            x_offset,
+            y_offset,
+            delay_num,
+            delay_den,
+            dispose_op,
+            blend_op,
+        };
+        self.frames.push((frame.clone(), control));
+        self
+    }
+
+    // How many times the animation plays, 0 meaning forever.
+    pub fn with_loop_count(mut self, num_plays: u32) -> ApngBuilder {
+        self.num_plays = num_plays;
+        self
+    }
+
+    // An image for decoders without APNG support that isn't part of the animation, such as a
+    // poster frame.
+    pub fn with_default_image(mut self, image: &PNGFile) -> ApngBuilder {
+        self.default_image = Some(image.clone());
+        self
+    }
+
+    pub fn build(self) -> Result<PNGFile, Box<dyn Error>> {
+        if self.frames.is_empty() {
+            return Err("An animation needs at least one frame.".into());
+        }
+        let images: Vec<&PNGFile> = self
+            .default_image
+            .iter()
+            .chain(self.frames.iter().map(|(frame, _)| frame))
+            .collect();
+        // The canvas.
+        let first = images[0];
+        let (width, height) = first.dimensions();
+        let fits = |c: &FrameControl| {
+            c.x_offset as u64 + c.width as u64 <= width as u64
+                && c.y_offset as u64 + c.height as u64 <= height as u64
+        };
+        if !self.frames.iter().all(|(_, control)| fits(control)) {
+            return Err("Every frame must fit on the canvas.".into());
+        }
+
+        // The header, PLTE and tRNS chunks, and the image data of each image as chunk data.
+        let (ihdr_chunk, color_chunks, mut data) = if images.iter().all(|i| same_format(i, first)) {
+            let color_chunks: Vec<PNGChunk> = first
+                .chunks
+                .iter()
+                .filter(|c| &c.chunk_type == b"PLTE" || &c.chunk_type == b"tRNS")
+                .cloned()
+                .collect();
+            let data: Vec<Vec<Vec<u8>>> = images
+                .iter()
+                .map(|i| i.chunks_of_type(b"IDAT").map(|c| c.data.clone()).collect())
+                .collect();
+            (first.ihdr_chunk.clone(), color_chunks, data)
+        } else {
+            let decoded = images
+                .iter()
+                .map(|i| i.decode())
+                .collect::<Result<Vec<_>, _>>()?;
+            let bit_depth = if decoded.iter().any(|i| i.bit_depth() == 16) {
+                16
+            } else {
+                8
+            };
+            let mut data = Vec::with_capacity(decoded.len());
+            for image in &decoded {
+                let image = image
+                    .expand_transparency()
+                    .convert_to(ColorType::TruecolorAlpha, bit_depth)?;
+                data.push(vec![compress_image(
+                    &image,
+                    DEFAULT_COMPRESSION_LEVEL,
+                    FilterStrategy::default(),
+                    false,
+                )]);
+            }
+            let mut ihdr = Vec::with_capacity(13);
+            ihdr.extend_from_slice(&width.to_be_bytes());
+            ihdr.extend_from_slice(&height.to_be_bytes());
+            ihdr.extend_from_slice(&[bit_depth, ColorType::TruecolorAlpha.to_u8(), 0, 0, 0]);
+            (PNGChunk::new(*b"IHDR", ihdr), Vec::new(), data)
+        };
+
+        let mut chunks = vec![
+            ihdr_chunk,
+            AnimationControl::new(self.frames.len() as u32, self.num_plays).to_chunk(),
+        ];
+        chunks.extend(color_chunks);
+        let default_data = self.default_image.as_ref().map(|_| data.remove(0));
+        let frames = self
+            .frames
+            .iter()
+            .map(|(_, control)| *control)
+            .zip(data)
+            .collect();
+        chunks.extend(frame_chunks(default_data, frames));
+        chunks.push(PNGChunk::new(*b"IEND", Vec::new()));
+
+        validate(&chunks)?;
+        let png_file = PNGFile::from_chunks(chunks)?;
+        png_file.frames()?;
+        Ok(png_file)
+    }
+}
+
+impl Default for ApngBuilder {
+    fn default() -> ApngBuilder {
+        ApngBuilder::new()
+    }
+}
+
+// Whether two files' image data can be mixed in one animation as it is.
+fn same_format(a: &PNGFile, b: &PNGFile) -> bool {
+    a.ihdr_chunk.data[8..] == b.ihdr_chunk.data[8..]
+        && a.first_chunk(b"PLTE") == b.first_chunk(b"PLTE")
+        && a.first_chunk(b"tRNS") == b.first_chunk(b"tRNS")
+}
+
+// Checks a finished list of chunks the way strict parsing would.
+fn validate(chunks: &[PNGChunk]) -> Result<(), Box<dyn Error>> {
+    let mut validator = ChunkValidator::new(ParseOptions {
+        mode: ParseMode::Strict,
+        ..ParseOptions::default()
+    });
+    for chunk in chunks {
+        validator.check_chunk(&RawChunk {
+            chunk_type: chunk.chunk_type,
+            data: &chunk.data,
+            crc: chunk.crc,
+        })?;
+    }
+    validator.finish(false)?;
+    Ok(())
+}
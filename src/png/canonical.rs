@@ -0,0 +1,190 @@
+// Re-encoding files so that the same picture with the same metadata always comes out as the same
+// bytes, for reproducible builds that hash their assets. Files differ for many reasons that don't
+// change what they hold: the encoder's filter choices and zlib settings, how the image data is
+// split into IDAT chunks, interlacing, the order of ancillary chunks and the time they were
+// written. Canonical files fix all of these.
+//
+// The output only stays the same for as long as the compressor does, so hashes should be
+// recorded with the version of this library that made them.
+use std::error::Error;
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use miniz_oxide::inflate::{decompress_to_vec_zlib_with_limit, TINFLStatus};
+
+use super::encode::{compress_image, FilterStrategy};
+use super::write::copyable_after_edit;
+use super::{PNGChunk, PNGFile};
+
+const CANONICAL_COMPRESSION_LEVEL: u8 = 9;
+
+// Most a compressed text chunk or ICC profile may inflate to. Real ones are far smaller; the
+// limit is there so a few kilobytes of zlib data can't ask for gigabytes.
+const MAX_INFLATED_SIZE: usize = 1 << 26;
+
+// The order chunks are written in. Unknown chunks go just before IDAT, sorted by type, and chunks
+// of the same type are sorted by their data so the order they came in doesn't matter.
+const CHUNK_ORDER: [&[u8; 4]; 22] = [
+    b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP", b"mDCV", b"cLLI", b"PLTE", b"bKGD",
+    b"hIST", b"tRNS", b"pHYs", b"sPLT", b"oFFs", b"pCAL", b"sCAL", b"sTER", b"eXIf", b"tEXt",
+    b"zTXt", b"iTXt",
+];
+
+// Chunks that are dropped: timestamps, and chunks that no longer apply to re-encoded data.
+const DROPPED_CHUNKS: [&[u8; 4]; 2] = [b"tIME", b"dSIG"];
+
+// Text keyword the spec suggests for the time the picture was made.
+const CREATION_TIME_KEYWORD: &[u8] = b"Creation Time";
+
+impl PNGFile {
+    // Returns the canonical form of the file. The pixels are re-encoded without interlacing, with
+    // adaptive filtering and fixed zlib settings, into a single IDAT chunk. Ancillary chunks are
+    // put in a fixed order, compressed text and ICC profiles are recompressed with the same
+    // settings, and tIME chunks and "Creation Time" text are dropped, as are dSIG signatures and
+    // unknown chunks that aren't safe to copy. The color type, bit depth, palette and all other
+    // metadata are kept as they are.
+    //
+    // Animated files can't be canonicalized yet.
+    pub fn canonicalize(&self) -> Result<PNGFile, Box<dyn Error>> {
+        if self.first_chunk(b"acTL").is_some() {
+            return Err("Animated files can't be canonicalized.".into());
+        }
+        let image = self.decode()?;
+        let mut ihdr = self.ihdr_chunk.data.clone();
+        ihdr[12] = 0;
+        let compressed = compress_image(
+            &image,
+            CANONICAL_COMPRESSION_LEVEL,
+            FilterStrategy::Adaptive,
+            false,
+        );
+
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        for chunk in &self.chunks {
+            if chunk.is_critical() && &chunk.chunk_type != b"PLTE" {
+                continue;
+            }
+            if DROPPED_CHUNKS.contains(&&chunk.chunk_type)
+                || !copyable_after_edit(chunk)
+                || is_creation_time(chunk)
+            {
+                continue;
+            }
+            chunks.push(canonical_chunk(chunk)?);
+        }
+        let rank = |c: &PNGChunk| {
+            CHUNK_ORDER
+                .iter()
+                .position(|t| *t == &c.chunk_type)
+                .unwrap_or(CHUNK_ORDER.len())
+        };
+        chunks.sort_by(|a, b| {
+            (rank(a), a.chunk_type, &a.data).cmp(&(rank(b), b.chunk_type, &b.data))
+        });
+        chunks.push(PNGChunk::new(*b"IDAT", compressed));
+        chunks.push(PNGChunk::new(*b"IEND", Vec::new()));
+
+        let mut all_chunks = vec![PNGChunk::new(*b"IHDR", ihdr)];
+        all_chunks.extend(chunks);
+        PNGFile::from_chunks(all_chunks)
+    }
+}
+
+fn is_creation_time(chunk: &PNGChunk) -> bool {
+    matches!(&chunk.chunk_type, b"tEXt" | b"zTXt" | b"iTXt")
+        && chunk.data.split(|b| *b == 0).next() == Some(CREATION_TIME_KEYWORD)
+}
+
+// A copy of the chunk with its compressed contents, if any, recompressed with fixed settings.
+// The offset and index of the original are dropped, since the chunk moves.
+fn canonical_chunk(chunk: &PNGChunk) -> Result<PNGChunk, Box<dyn Error>> {
+    let keyword_end = chunk.data.iter().position(|b| *b == 0);
+    // Where the zlib data starts: after the keyword or profile name, its null separator and the
+    // compression method byte, and for iTXt the compression flag, language tag and translated
+    // keyword as well.
+    let compressed_start = match (&chunk.chunk_type, keyword_end) {
+        (b"zTXt", Some(end)) | (b"iCCP", Some(end)) => Some(end + 2),
+        (b"iTXt", Some(end)) if chunk.data.get(end + 1) == Some(&1) => {
+            let rest = chunk.data.get(end + 3..).unwrap_or(&[]);
+            let mut fields = rest.splitn(3, |b| *b == 0);
+            match (fields.next(), fields.next(), fields.next()) {
+                (Some(language), Some(translated), Some(_)) => {
+                    Some(end + 3 + language.len() + 1 + translated.len() + 1)
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    let mut data = chunk.data.clone();
+    if let Some(start) = compressed_start.filter(|s| *s <= data.len()) {
+        let chunk_type = String::from_utf8_lossy(&chunk.chunk_type);
+        let contents = decompress_to_vec_zlib_with_limit(&data[start..], MAX_INFLATED_SIZE)
+            .map_err(|err| match err.status {
+                TINFLStatus::HasMoreOutput => format!(
+                    "The compressed data in a {} chunk inflates to more than {} bytes.",
+                    chunk_type, MAX_INFLATED_SIZE
+                ),
+                _ => format!(
+                    "The compressed data in a {} chunk is not valid zlib data.",
+                    chunk_type
+                ),
+            })?;
+        data.truncate(start);
+        data.extend(compress_to_vec_zlib(&contents, CANONICAL_COMPRESSION_LEVEL));
+    }
+    Ok(PNGChunk::new(chunk.chunk_type, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{ColorType, Image};
+
+    // A small gray file with the given ancillary chunks before its image data.
+    fn with_chunks(extra: Vec<PNGChunk>) -> PNGFile {
+        let image = Image::from_samples(2, 2, ColorType::Grayscale, 8, vec![0, 80, 160, 240]);
+        let png_file = PNGFile::from_image(&image.unwrap()).unwrap();
+        let mut chunks: Vec<PNGChunk> = png_file.iter_chunks().cloned().collect();
+        let idat = chunks
+            .iter()
+            .position(|c| &c.chunk_type == b"IDAT")
+            .unwrap();
+        chunks.splice(idat..idat, extra);
+        PNGFile::from_chunks(chunks).unwrap()
+    }
+
+    fn ztxt(keyword: &[u8], text: &[u8]) -> PNGChunk {
+        let mut data = keyword.to_vec();
+        data.extend_from_slice(&[0, 0]);
+        data.extend(compress_to_vec_zlib(text, 1));
+        PNGChunk::new(*b"zTXt", data)
+    }
+
+    fn text(keyword: &[u8], text: &[u8]) -> PNGChunk {
+        let mut data = keyword.to_vec();
+        data.push(0);
+        data.extend_from_slice(text);
+        PNGChunk::new(*b"tEXt", data)
+    }
+
+    #[test]
+    fn order_of_same_type_chunks() {
+        let one = with_chunks(vec![text(b"Title", b"Dunes"), text(b"Author", b"Ana")]);
+        let other = with_chunks(vec![text(b"Author", b"Ana"), text(b"Title", b"Dunes")]);
+        assert_eq!(
+            one.canonicalize().unwrap().to_bytes().unwrap(),
+            other.canonicalize().unwrap().to_bytes().unwrap()
+        );
+    }
+
+    #[test]
+    fn compressed_text_bomb() {
+        // A byte more than the limit, which zlib squeezes into well under a megabyte.
+        let bomb = with_chunks(vec![ztxt(b"Comment", &vec![b' '; MAX_INFLATED_SIZE + 1])]);
+        let err = bomb.canonicalize().unwrap_err();
+        assert!(err.to_string().contains("inflates to more than"));
+
+        let text = with_chunks(vec![ztxt(b"Comment", b"fine")]);
+        assert!(text.canonicalize().is_ok());
+    }
+}
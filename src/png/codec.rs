@@ -0,0 +1,120 @@
+use std::error::Error;
+
+use super::builder::PNGFileBuilder;
+use super::{PNGChunk, PNGFile};
+
+// Reads and writes the data of an application's own chunk type, so the rest of the application
+// can work with a typed value instead of bytes. Implementing it for a type is all it takes to
+// register the chunk type with PNGFile and PNGFileBuilder:
+//
+//     struct GameChunk;
+//
+//     impl ChunkCodec for GameChunk {
+//         type Value = SaveState;
+//         const CHUNK_TYPE: [u8; 4] = *b"gaME";
+//
+//         fn decode(data: &[u8]) -> Result<SaveState, Box<dyn Error>> {
+//             SaveState::from_bytes(data)
+//         }
+//
+//         fn encode(value: &SaveState) -> Vec<u8> {
+//             value.to_bytes()
+//         }
+//     }
+//
+//     let state = png_file.get_custom_chunk::<GameChunk>().transpose()?;
+//     png_file.set_custom_chunk::<GameChunk>(&new_state)?;
+//
+// Private chunk types have a lowercase second letter. They should also be ancillary, with a
+// lowercase first letter, or decoders that don't know them will refuse the file. The chunk is
+// placed before the image data, so anything that has to come after it can't use this.
+pub trait ChunkCodec {
+    type Value;
+
+    const CHUNK_TYPE: [u8; 4];
+
+    fn decode(data: &[u8]) -> Result<Self::Value, Box<dyn Error>>;
+
+    fn encode(value: &Self::Value) -> Vec<u8>;
+
+    fn to_chunk(value: &Self::Value) -> PNGChunk {
+        PNGChunk::new(Self::CHUNK_TYPE, Self::encode(value))
+    }
+}
+
+impl PNGChunk {
+    // Decodes the chunk's data with the given codec, which must be for this chunk type.
+    pub fn decode_as<C: ChunkCodec>(&self) -> Result<C::Value, Box<dyn Error>> {
+        if self.chunk_type != C::CHUNK_TYPE {
+            return Err(format!(
+                "Can't decode a {} chunk as {}.",
+                String::from_utf8_lossy(&self.chunk_type),
+                String::from_utf8_lossy(&C::CHUNK_TYPE)
+            )
+            .into());
+        }
+        C::decode(&self.data)
+    }
+}
+
+impl PNGFile {
+    // The value of the first chunk of the codec's type, or None when there is no such chunk.
+    pub fn get_custom_chunk<C: ChunkCodec>(&self) -> Option<Result<C::Value, Box<dyn Error>>> {
+        self.first_chunk(&C::CHUNK_TYPE).map(|c| c.decode_as::<C>())
+    }
+
+    // The values of every chunk of the codec's type, in file order.
+    pub fn get_custom_chunks<C: ChunkCodec>(&self) -> Result<Vec<C::Value>, Box<dyn Error>> {
+        self.chunks_of_type(&C::CHUNK_TYPE)
+            .map(|c| c.decode_as::<C>())
+            .collect()
+    }
+
+    // Replaces every chunk of the codec's type with one holding value, or adds it before the
+    // image data.
+    pub fn set_custom_chunk<C: ChunkCodec>(
+        &mut self,
+        value: &C::Value,
+    ) -> Result<(), Box<dyn Error>> {
+        check_chunk_type(&C::CHUNK_TYPE)?;
+        let chunk = C::to_chunk(value);
+        let at = self
+            .chunks
+            .iter()
+            .position(|c| c.chunk_type == C::CHUNK_TYPE || &c.chunk_type == b"IDAT")
+            .unwrap_or(0);
+        self.chunks.retain(|c| c.chunk_type != C::CHUNK_TYPE);
+        self.chunks.insert(at, chunk);
+        Ok(())
+    }
+
+    // Removes every chunk of the codec's type and returns how many there were.
+    pub fn remove_custom_chunks<C: ChunkCodec>(&mut self) -> usize {
+        let before = self.chunks.len();
+        self.chunks.retain(|c| c.chunk_type != C::CHUNK_TYPE);
+        before - self.chunks.len()
+    }
+}
+
+impl PNGFileBuilder {
+    // Adds a chunk holding value, encoded with the given codec.
+    pub fn with_custom_chunk<C: ChunkCodec>(self, value: &C::Value) -> PNGFileBuilder {
+        self.with_chunk(C::to_chunk(value))
+    }
+}
+
+// Critical chunk types would make other decoders refuse the file, and types that aren't four
+// letters aren't chunk types at all.
+fn check_chunk_type(chunk_type: &[u8; 4]) -> Result<(), Box<dyn Error>> {
+    if !chunk_type.iter().all(|b| b.is_ascii_alphabetic()) {
+        return Err(format!("{:?} is not a valid chunk type.", chunk_type).into());
+    }
+    if chunk_type[0].is_ascii_uppercase() {
+        return Err(format!(
+            "{} is a critical chunk type; custom chunks must be ancillary.",
+            String::from_utf8_lossy(chunk_type)
+        )
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,41 @@
+// CRC-32 as described in Annex D of the PNG spec. The table is built at compile time.
+const CRC_TABLE: [u32; 256] = make_table();
+
+const fn make_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            if c & 1 == 1 {
+                c = 0xEDB8_8320 ^ (c >> 1);
+            } else {
+                c >>= 1;
+            }
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+// The CRC of a chunk covers the chunk type and data fields, but not the length.
+pub fn chunk_crc(chunk_type: &[u8; 4], data: &[u8]) -> u32 {
+    let c = update(0xFFFF_FFFF, chunk_type);
+    update(c, data) ^ 0xFFFF_FFFF
+}
+
+#[cfg(feature = "std")]
+pub fn crc32(bytes: &[u8]) -> u32 {
+    update(0xFFFF_FFFF, bytes) ^ 0xFFFF_FFFF
+}
+
+fn update(crc: u32, bytes: &[u8]) -> u32 {
+    let mut c = crc;
+    for b in bytes {
+        c = CRC_TABLE[((c ^ *b as u32) & 0xFF) as usize] ^ (c >> 8);
+    }
+    c
+}
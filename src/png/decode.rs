@@ -0,0 +1,743 @@
+use std::error;
+use std::error::Error;
+use std::fmt;
+
+use miniz_oxide::inflate::{decompress_to_vec_zlib_with_limit, TINFLStatus};
+
+use super::icc::IccProfile;
+use super::simd;
+use super::warnings::{Warning, Warnings};
+use super::{IHDRData, PNGFile};
+use crate::image::{ColorType, Image, Transparency};
+
+// Starting column, starting row, column step and row step for each Adam7 pass.
+pub(crate) const ADAM7_PASSES: [(u32, u32, u32, u32); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+// Largest image decode will allocate samples for, 16384 x 16384 pixels. The header alone decides
+// the size, so without a cap a few hundred bytes could ask for tens of gigabytes.
+const MAX_IMAGE_PIXELS: u64 = 1 << 28;
+
+#[derive(Debug, Clone)]
+pub struct PNGDecodeError {
+    message: String,
+}
+
+impl PNGDecodeError {
+    pub(crate) fn new(message: &str) -> PNGDecodeError {
+        PNGDecodeError {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for PNGDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not decode image data: {}", self.message)
+    }
+}
+
+impl error::Error for PNGDecodeError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeOptions {
+    // Number of threads to unfilter and unpack with. 1 decodes on the calling thread and 0 uses
+    // one thread per core. Anything other than 1 needs the "parallel" feature and is otherwise
+    // ignored.
+    pub threads: usize,
+    pub gamma: GammaCorrection,
+    // Converts the pixels from the iCCP profile to sRGB before any gamma correction. Only
+    // matrix/TRC profiles are supported; other profiles are left unapplied with a warning.
+    pub to_srgb: bool,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> DecodeOptions {
+        DecodeOptions {
+            threads: 1,
+            gamma: GammaCorrection::None,
+            to_srgb: false,
+        }
+    }
+}
+
+// What decoding does with the transfer curve the samples were stored with. The file's curve
+// comes from its sRGB chunk, or else its gAMA chunk, and is taken to be sRGB when it has
+// neither. Samples keep their bit depth, so linear output loses shadow detail below 16 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum GammaCorrection {
+    // Samples as stored in the file.
+    #[default]
+    None,
+    // Samples proportional to light intensity, for resizing, blending and other pixel math.
+    Linear,
+    // Samples encoded for a display with the given gamma, such as 2.2.
+    Display(f32),
+}
+
+// The curve a file's samples were stored with.
+#[derive(Debug, Clone, Copy)]
+enum TransferCurve {
+    Srgb,
+    // Stored values are linear values raised to this power, the gAMA value over 100000.
+    Power(f32),
+}
+
+impl TransferCurve {
+    fn of(png_file: &PNGFile) -> TransferCurve {
+        if png_file.chunks.iter().any(|c| &c.chunk_type == b"sRGB") {
+            return TransferCurve::Srgb;
+        }
+        match png_file.chunks.iter().find(|c| &c.chunk_type == b"gAMA") {
+            Some(chunk) if chunk.data.len() == 4 => {
+                let gamma = u32::from_be_bytes([
+                    chunk.data[0],
+                    chunk.data[1],
+                    chunk.data[2],
+                    chunk.data[3],
+                ]);
+                if gamma == 0 {
+                    TransferCurve::Srgb
+                } else {
+                    TransferCurve::Power(gamma as f32 / 100_000.0)
+                }
+            }
+            _ => TransferCurve::Srgb,
+        }
+    }
+
+    // Turns a stored value from 0.0 to 1.0 into linear light.
+    fn to_linear(self, v: f32) -> f32 {
+        match self {
+            TransferCurve::Srgb if v <= 0.04045 => v / 12.92,
+            TransferCurve::Srgb => ((v + 0.055) / 1.055).powf(2.4),
+            TransferCurve::Power(gamma) => v.powf(1.0 / gamma),
+        }
+    }
+}
+
+impl PNGFile {
+    // Inflates and unfilters the IDAT stream into an Image.
+    pub fn decode(&self) -> Result<Image, Box<dyn Error>> {
+        self.decode_with_options(&DecodeOptions::default())
+    }
+
+    pub fn decode_with_options(&self, options: &DecodeOptions) -> Result<Image, Box<dyn Error>> {
+        Ok(self.decode_with_warnings(options)?.0)
+    }
+
+    // Whether every pixel is fully opaque, in which case the image can be flattened to gray or
+    // RGB without changing how it looks. Only files where has_alpha is true get decoded.
+    pub fn is_fully_opaque(&self) -> Result<bool, Box<dyn Error>> {
+        if !self.has_alpha() {
+            return Ok(true);
+        }
+        Ok(self.decode()?.is_fully_opaque())
+    }
+
+    // Image::pixel_digest of the decoded image, which is the same for files holding the same
+    // picture whatever their chunks, compression or metadata.
+    pub fn pixel_digest(&self) -> Result<[u8; 32], Box<dyn Error>> {
+        self.decode()?.pixel_digest()
+    }
+
+    // Inflates the IDAT stream and checks that it holds exactly as much data as the header
+    // implies, without unfiltering it. This finds damaged image data in files whose chunks are
+    // all well formed, more cheaply than decoding them.
+    pub fn check_idat(&self) -> Result<(), PNGDecodeError> {
+        let color_type = ColorType::from_u8(self.ihdr.color_type)
+            .ok_or_else(|| PNGDecodeError::new("Unknown color type."))?;
+        if !self.chunks.iter().any(|c| &c.chunk_type == b"IDAT") {
+            return Err(PNGDecodeError::new("There are no IDAT chunks."));
+        }
+
+        let expected = image_data_len(&self.ihdr, color_type)?;
+        let (inflated, extra) = self.inflate_image_data(expected)?;
+        if extra {
+            return Err(PNGDecodeError::new(&format!(
+                "The IDAT stream holds more than the {} bytes the header implies.",
+                expected
+            )));
+        }
+        if inflated.len() != expected {
+            return Err(PNGDecodeError::new(&format!(
+                "The IDAT stream holds {} bytes but the header implies {}.",
+                inflated.len(),
+                expected
+            )));
+        }
+        Ok(())
+    }
+
+    // Inflates the IDAT stream, stopping once it has produced more than expected bytes, so a
+    // small stream that inflates to gigabytes can't exhaust memory. Returns the data, cut to
+    // expected bytes, and whether the stream went on past that.
+    fn inflate_image_data(&self, expected: usize) -> Result<(Vec<u8>, bool), PNGDecodeError> {
+        let mut compressed: Vec<u8> = Vec::new();
+        for chunk in self.chunks.iter().filter(|c| &c.chunk_type == b"IDAT") {
+            compressed.extend_from_slice(&chunk.data);
+        }
+        let mut raw = match decompress_to_vec_zlib_with_limit(&compressed, expected + 1) {
+            Ok(raw) => raw,
+            Err(err) if err.status == TINFLStatus::HasMoreOutput => err.output,
+            Err(err) => {
+                return Err(PNGDecodeError::new(&format!(
+                    "The IDAT stream is not valid zlib data: {}.",
+                    err
+                )))
+            }
+        };
+        let extra = raw.len() > expected;
+        raw.truncate(expected);
+        Ok((raw, extra))
+    }
+
+    // Same as decode_with_options, but also reports problems that decoding worked around, such
+    // as palette indices with no palette entry (drawn black) or tRNS data that doesn't fit the
+    // color type (ignored).
+    pub fn decode_with_warnings(
+        &self,
+        options: &DecodeOptions,
+    ) -> Result<(Image, Warnings), Box<dyn Error>> {
+        let mut warnings = Warnings::new();
+        let ihdr = &self.ihdr;
+        let color_type = ColorType::from_u8(ihdr.color_type)
+            .ok_or_else(|| PNGDecodeError::new("Unknown color type."))?;
+
+        let expected = image_data_len(ihdr, color_type)?;
+        let (raw, extra) = self.inflate_image_data(expected)?;
+        // Checked before any samples are allocated, which for interlaced images happens up front.
+        if raw.len() < expected {
+            return Err(PNGDecodeError::new("The IDAT stream is shorter than the image.").into());
+        }
+        if extra {
+            warnings.push(Warning::new(
+                "extra_image_data",
+                "The IDAT stream holds more data than the image needs.".to_string(),
+                Some(*b"IDAT"),
+            ));
+        }
+        let samples = decode_samples(&raw, ihdr, color_type, options.threads)?;
+
+        let mut image =
+            Image::from_samples(ihdr.width, ihdr.height, color_type, ihdr.bit_depth, samples)?;
+
+        if let Some(plte) = self.chunks.iter().find(|c| &c.chunk_type == b"PLTE") {
+            let palette = parse_palette(&plte.data);
+            let mut warn = |code, message: &str| {
+                warnings.push(Warning::new(code, message.to_string(), Some(*b"PLTE")))
+            };
+            if plte.data.len() % 3 != 0 {
+                warn(
+                    "invalid_palette_length",
+                    "The PLTE chunk length is not a multiple of 3.",
+                );
+            }
+            if color_type == ColorType::Indexed
+                && image.samples().iter().any(|i| *i as usize >= palette.len())
+            {
+                warn(
+                    "index_out_of_palette",
+                    "Some pixels use palette entries that don't exist and were drawn black.",
+                );
+            }
+            image.set_palette(Some(palette));
+        } else if color_type == ColorType::Indexed {
+            return Err(PNGDecodeError::new("Indexed image has no PLTE chunk.").into());
+        }
+
+        if let Some(trns) = self.chunks.iter().find(|c| &c.chunk_type == b"tRNS") {
+            let transparency = parse_transparency(&trns.data, color_type);
+            let palette_len = image.palette().map_or(0, |p| p.len());
+            let message = match &transparency {
+                None => Some("The tRNS chunk does not fit the color type and was ignored."),
+                Some(Transparency::Indexed(alphas)) if alphas.len() > palette_len => {
+                    Some("The tRNS chunk has more entries than the palette.")
+                }
+                _ => None,
+            };
+            if let Some(message) = message {
+                warnings.push(Warning::new(
+                    "invalid_transparency",
+                    message.to_string(),
+                    Some(*b"tRNS"),
+                ));
+            }
+            image.set_transparency(transparency);
+        }
+
+        let mut curve = TransferCurve::of(self);
+        let iccp = self.chunks.iter().find(|c| &c.chunk_type == b"iCCP");
+        // An sRGB chunk overrides iCCP, so the pixels are sRGB already.
+        let has_srgb = self.chunks.iter().any(|c| &c.chunk_type == b"sRGB");
+        if let (true, Some(iccp), false) = (options.to_srgb, iccp, has_srgb) {
+            match IccProfile::from_iccp(&iccp.data).and_then(|p| p.convert_to_srgb(&image)) {
+                Ok(converted) => {
+                    image = converted;
+                    curve = TransferCurve::Srgb;
+                }
+                Err(err) => warnings.push(Warning::new(
+                    "unsupported_icc_profile",
+                    format!("The ICC profile was not applied: {}", err),
+                    Some(*b"iCCP"),
+                )),
+            }
+        }
+        match options.gamma {
+            GammaCorrection::None => {}
+            GammaCorrection::Linear => image = image.map_colors(|v| curve.to_linear(v)),
+            GammaCorrection::Display(gamma) if gamma > 0.0 => {
+                image = image.map_colors(|v| curve.to_linear(v).powf(1.0 / gamma))
+            }
+            GammaCorrection::Display(_) => {}
+        }
+
+        Ok((image, warnings))
+    }
+}
+
+// Length of the inflated image data, filter type bytes included. Images over MAX_IMAGE_PIXELS
+// are refused here, which also keeps the length from overflowing.
+pub(crate) fn image_data_len(ihdr: &IHDRData, color_type: ColorType) -> Result<usize, PNGDecodeError> {
+    if ihdr.width as u64 * ihdr.height as u64 > MAX_IMAGE_PIXELS {
+        return Err(PNGDecodeError::new("The image is too large to decode."));
+    }
+    let pass_len = |width: u32, height: u32| {
+        (row_bytes(width, color_type, ihdr.bit_depth) + 1) * height as usize
+    };
+    if ihdr.interlace_method != 1 {
+        return Ok(pass_len(ihdr.width, ihdr.height));
+    }
+    Ok(ADAM7_PASSES
+        .iter()
+        .filter(|(x0, y0, _, _)| *x0 < ihdr.width && *y0 < ihdr.height)
+        .map(|(x0, y0, dx, dy)| {
+            pass_len(
+                (ihdr.width - x0).div_ceil(*dx),
+                (ihdr.height - y0).div_ceil(*dy),
+            )
+        })
+        .sum())
+}
+
+#[cfg(feature = "parallel")]
+fn decode_samples(
+    raw: &[u8],
+    ihdr: &IHDRData,
+    color_type: ColorType,
+    threads: usize,
+) -> Result<Vec<u16>, Box<dyn Error>> {
+    if threads != 1 {
+        return super::parallel::decode_samples(raw, ihdr, color_type, threads);
+    }
+    decode_samples_serial(raw, ihdr, color_type)
+}
+
+#[cfg(not(feature = "parallel"))]
+fn decode_samples(
+    raw: &[u8],
+    ihdr: &IHDRData,
+    color_type: ColorType,
+    _threads: usize,
+) -> Result<Vec<u16>, Box<dyn Error>> {
+    decode_samples_serial(raw, ihdr, color_type)
+}
+
+pub(crate) fn decode_samples_serial(
+    raw: &[u8],
+    ihdr: &IHDRData,
+    color_type: ColorType,
+) -> Result<Vec<u16>, Box<dyn Error>> {
+    if ihdr.interlace_method == 1 {
+        deinterlace(raw, ihdr, color_type)
+    } else {
+        let rows = unfilter(raw, ihdr.width, ihdr.height, color_type, ihdr.bit_depth)?;
+        Ok(unpack(
+            &rows,
+            ihdr.width,
+            ihdr.height,
+            color_type,
+            ihdr.bit_depth,
+        ))
+    }
+}
+
+pub(crate) fn parse_palette(data: &[u8]) -> Vec<[u8; 3]> {
+    data.chunks_exact(3).map(|e| [e[0], e[1], e[2]]).collect()
+}
+
+pub(crate) fn parse_transparency(data: &[u8], color_type: ColorType) -> Option<Transparency> {
+    let sample = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+    match color_type {
+        ColorType::Grayscale if data.len() >= 2 => Some(Transparency::Grayscale(sample(0))),
+        ColorType::Truecolor if data.len() >= 6 => {
+            Some(Transparency::Truecolor([sample(0), sample(2), sample(4)]))
+        }
+        ColorType::Indexed => Some(Transparency::Indexed(data.to_vec())),
+        _ => None,
+    }
+}
+
+pub(crate) fn bits_per_pixel(color_type: ColorType, bit_depth: u8) -> usize {
+    color_type.channels() * bit_depth as usize
+}
+
+pub(crate) fn row_bytes(width: u32, color_type: ColorType, bit_depth: u8) -> usize {
+    (width as usize * bits_per_pixel(color_type, bit_depth)).div_ceil(8)
+}
+
+// Reverses the per-scanline filters, returning the rows back to back without filter type bytes.
+pub(crate) fn unfilter(
+    raw: &[u8],
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: u8,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    let stride = row_bytes(width, color_type, bit_depth);
+    let bpp = (bits_per_pixel(color_type, bit_depth) / 8).max(1);
+    if raw.len() < (stride + 1) * height as usize {
+        return Err(PNGDecodeError::new("The IDAT stream is shorter than the image.").into());
+    }
+
+    let mut rows = vec![0u8; stride * height as usize];
+    let mut previous = vec![0u8; stride];
+    for y in 0..height as usize {
+        let line = &raw[y * (stride + 1)..(y + 1) * (stride + 1)];
+        let current = &mut rows[y * stride..(y + 1) * stride];
+        current.copy_from_slice(&line[1..]);
+        unfilter_row(line[0], current, &previous, bpp)?;
+        previous.copy_from_slice(current);
+    }
+    Ok(rows)
+}
+
+pub(crate) fn unfilter_row(
+    filter_type: u8,
+    current: &mut [u8],
+    previous: &[u8],
+    bpp: usize,
+) -> Result<(), Box<dyn Error>> {
+    if simd::unfilter_row(filter_type, current, previous, bpp) {
+        return Ok(());
+    }
+    unfilter_row_scalar(filter_type, current, previous, bpp)
+}
+
+pub(crate) fn unfilter_row_scalar(
+    filter_type: u8,
+    current: &mut [u8],
+    previous: &[u8],
+    bpp: usize,
+) -> Result<(), Box<dyn Error>> {
+    match filter_type {
+        0 => {}
+        // Sub
+        1 => {
+            for i in bpp..current.len() {
+                current[i] = current[i].wrapping_add(current[i - bpp]);
+            }
+        }
+        // Up
+        2 => {
+            for i in 0..current.len() {
+                current[i] = current[i].wrapping_add(previous[i]);
+            }
+        }
+        // Average
+        3 => {
+            for i in 0..current.len() {
+                let left = if i >= bpp { current[i - bpp] as u16 } else { 0 };
+                let avg = ((left + previous[i] as u16) / 2) as u8;
+                current[i] = current[i].wrapping_add(avg);
+            }
+        }
+        // Paeth
+        4 => {
+            for i in 0..current.len() {
+                let (left, upper_left) = if i >= bpp {
+                    (current[i - bpp], previous[i - bpp])
+                } else {
+                    (0, 0)
+                };
+                current[i] = current[i].wrapping_add(paeth(left, previous[i], upper_left));
+            }
+        }
+        _ => return Err(PNGDecodeError::new("Unknown scanline filter type.").into()),
+    }
+    Ok(())
+}
+
+pub(crate) fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// Splits unfiltered rows into one u16 per sample.
+pub(crate) fn unpack(
+    rows: &[u8],
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: u8,
+) -> Vec<u16> {
+    let stride = row_bytes(width, color_type, bit_depth);
+    let per_row = width as usize * color_type.channels();
+    let mut samples = Vec::with_capacity(per_row * height as usize);
+    for row in rows.chunks_exact(stride).take(height as usize) {
+        unpack_row(row, per_row, bit_depth, &mut samples);
+    }
+    samples
+}
+
+pub(crate) fn unpack_row(row: &[u8], count: usize, bit_depth: u8, out: &mut Vec<u16>) {
+    match bit_depth {
+        16 => out.extend(
+            row.chunks_exact(2)
+                .take(count)
+                .map(|b| u16::from_be_bytes([b[0], b[1]])),
+        ),
+        8 => out.extend(row.iter().take(count).map(|b| *b as u16)),
+        _ => {
+            let per_byte = 8 / bit_depth as usize;
+            let mask = (1u8 << bit_depth) - 1;
+            for i in 0..count {
+                let byte = row[i / per_byte];
+                let shift = 8 - bit_depth as usize * (i % per_byte + 1);
+                out.push(((byte >> shift) & mask) as u16);
+            }
+        }
+    }
+}
+
+// Where one Adam7 pass sits in the image and in the inflated data.
+pub(crate) struct Adam7Pass {
+    x0: u32,
+    y0: u32,
+    dx: u32,
+    dy: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) offset: usize,
+    pub(crate) len: usize,
+}
+
+// Lays out the non-empty passes of an interlaced image, checking that raw holds all of them.
+pub(crate) fn adam7_passes(
+    raw: &[u8],
+    ihdr: &IHDRData,
+    color_type: ColorType,
+) -> Result<Vec<Adam7Pass>, PNGDecodeError> {
+    let mut passes = Vec::new();
+    let mut offset = 0;
+
+    for (x0, y0, dx, dy) in ADAM7_PASSES.iter() {
+        if *x0 >= ihdr.width || *y0 >= ihdr.height {
+            continue;
+        }
+        let width = (ihdr.width - x0).div_ceil(*dx);
+        let height = (ihdr.height - y0).div_ceil(*dy);
+        let len = (row_bytes(width, color_type, ihdr.bit_depth) + 1) * height as usize;
+        if raw.len() < offset + len {
+            return Err(PNGDecodeError::new(
+                "The IDAT stream is shorter than the image.",
+            ));
+        }
+
+        passes.push(Adam7Pass {
+            x0: *x0,
+            y0: *y0,
+            dx: *dx,
+            dy: *dy,
+            width,
+            height,
+            offset,
+            len,
+        });
+        offset += len;
+    }
+    Ok(passes)
+}
+
+// Copies the unpacked samples of one pass to their places in the full image.
+pub(crate) fn scatter_pass(
+    samples: &mut [u16],
+    image_width: u32,
+    channels: usize,
+    pass: &Adam7Pass,
+    pass_samples: &[u16],
+) {
+    for py in 0..pass.height {
+        for px in 0..pass.width {
+            let x = pass.x0 + px * pass.dx;
+            let y = pass.y0 + py * pass.dy;
+            let src = (py as usize * pass.width as usize + px as usize) * channels;
+            let dst = (y as usize * image_width as usize + x as usize) * channels;
+            samples[dst..dst + channels].copy_from_slice(&pass_samples[src..src + channels]);
+        }
+    }
+}
+
+fn deinterlace(
+    raw: &[u8],
+    ihdr: &IHDRData,
+    color_type: ColorType,
+) -> Result<Vec<u16>, Box<dyn Error>> {
+    let channels = color_type.channels();
+    let mut samples = vec![0u16; ihdr.width as usize * ihdr.height as usize * channels];
+
+    for pass in adam7_passes(raw, ihdr, color_type)? {
+        let rows = unfilter(
+            &raw[pass.offset..pass.offset + pass.len],
+            pass.width,
+            pass.height,
+            color_type,
+            ihdr.bit_depth,
+        )?;
+        let pass_samples = unpack(&rows, pass.width, pass.height, color_type, ihdr.bit_depth);
+        scatter_pass(&mut samples, ihdr.width, channels, &pass, &pass_samples);
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use miniz_oxide::deflate::compress_to_vec_zlib;
+
+    use super::*;
+    use crate::png::PNGChunk;
+
+    // A non-interlaced file holding the given filtered scanlines.
+    fn png(width: u32, height: u32, color_type: u8, bit_depth: u8, scanlines: &[u8]) -> PNGFile {
+        let idat = compress_to_vec_zlib(scanlines, 6);
+        file(width, height, color_type, bit_depth, false, idat)
+    }
+
+    fn file(
+        width: u32,
+        height: u32,
+        color_type: u8,
+        bit_depth: u8,
+        interlace: bool,
+        idat: Vec<u8>,
+    ) -> PNGFile {
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&width.to_be_bytes());
+        ihdr.extend_from_slice(&height.to_be_bytes());
+        ihdr.extend_from_slice(&[bit_depth, color_type, 0, 0, interlace as u8]);
+        PNGFile::from_chunks(vec![
+            PNGChunk::new(*b"IHDR", ihdr),
+            PNGChunk::new(*b"IDAT", idat),
+            PNGChunk::new(*b"IEND", Vec::new()),
+        ])
+        .unwrap()
+    }
+
+    fn message(err: &(dyn Error + 'static)) -> String {
+        err.downcast_ref::<PNGDecodeError>().unwrap().to_string()
+    }
+
+    #[test]
+    fn gray_alpha_8() {
+        // The second row uses the Sub filter, which steps back a whole gray and alpha pair.
+        let scanlines = [0, 10, 255, 200, 0, 1, 10, 20, 20, 30];
+        let image = png(2, 2, 4, 8, &scanlines).decode().unwrap();
+        assert_eq!(image.color_type(), ColorType::GrayscaleAlpha);
+        assert_eq!(image.bit_depth(), 8);
+        assert_eq!(image.samples(), [10, 255, 200, 0, 10, 20, 30, 50]);
+    }
+
+    #[test]
+    fn gray_alpha_16() {
+        let scanlines = [
+            0, 0x12, 0x34, 0xFF, 0xFF, 0xAB, 0xCD, 0x00, 0x01, //
+            1, 0x00, 0x10, 0x80, 0x00, 0x00, 0x10, 0x00, 0x01,
+        ];
+        let image = png(2, 2, 4, 16, &scanlines).decode().unwrap();
+        assert_eq!(image.color_type(), ColorType::GrayscaleAlpha);
+        assert_eq!(image.bit_depth(), 16);
+        assert_eq!(
+            image.samples(),
+            [0x1234, 0xFFFF, 0xABCD, 0x0001, 0x0010, 0x8000, 0x0020, 0x8001]
+        );
+    }
+
+    #[test]
+    fn gray_alpha_opacity() {
+        let opaque = png(2, 1, 4, 8, &[0, 10, 255, 90, 255]);
+        assert!(opaque.has_alpha());
+        assert!(opaque.is_fully_opaque().unwrap());
+
+        let translucent = png(2, 1, 4, 16, &[0, 0, 10, 0xFF, 0xFF, 0, 90, 0xFF, 0xFE]);
+        assert!(!translucent.is_fully_opaque().unwrap());
+    }
+
+    #[test]
+    fn gray_alpha_short_data() {
+        // One byte of the last pixel is missing.
+        let err = png(2, 1, 4, 8, &[0, 10, 255, 90]).decode().unwrap_err();
+        assert!(err.is::<PNGDecodeError>());
+    }
+
+    #[test]
+    fn huge_interlaced_header() {
+        // 60000 x 60000 RGBA at 16 bits, with a few bytes of image data.
+        let huge = file(60000, 60000, 6, 16, true, compress_to_vec_zlib(&[0; 64], 6));
+        let err = huge.decode().unwrap_err();
+        assert!(message(&*err).contains("too large"));
+        assert!(huge.check_idat().is_err());
+    }
+
+    #[test]
+    fn short_interlaced_data() {
+        // Within the size cap, but nowhere near enough data. This fails before the 200 MB sample
+        // buffer is allocated.
+        let short = file(10000, 10000, 0, 8, true, compress_to_vec_zlib(&[0; 64], 6));
+        let err = short.decode().unwrap_err();
+        assert!(message(&*err).contains("shorter"));
+        assert!(short.check_idat().is_err());
+    }
+
+    #[test]
+    fn zlib_bomb() {
+        // 16 MB of zeros compresses to a few kilobytes, far more than a 100 x 100 image needs.
+        let zeros = compress_to_vec_zlib(&vec![0; 1 << 24], 9);
+        let bomb = file(100, 100, 0, 8, false, zeros);
+        assert!(bomb.chunks[0].data.len() < 1 << 16);
+
+        let (raw, extra) = bomb.inflate_image_data(101 * 100).unwrap();
+        assert_eq!(raw.len(), 101 * 100);
+        assert!(extra);
+
+        let (image, warnings) = bomb
+            .decode_with_warnings(&DecodeOptions::default())
+            .unwrap();
+        assert_eq!(image.samples(), [0; 100 * 100]);
+        assert!(warnings.iter().any(|w| w.code == "extra_image_data"));
+        assert!(message(&bomb.check_idat().unwrap_err()).contains("more than"));
+    }
+}
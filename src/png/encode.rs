@@ -0,0 +1,274 @@
+use std::error::Error;
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+
+use super::decode::{bits_per_pixel, paeth, row_bytes, ADAM7_PASSES};
+use super::{IHDRData, PNGChunk, PNGFile};
+use crate::image::{ColorType, Image, Transparency};
+
+pub(crate) const DEFAULT_COMPRESSION_LEVEL: u8 = 6;
+
+// How scanlines are filtered before compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterStrategy {
+    None,
+    Sub,
+    Up,
+    Average,
+    Paeth,
+    // Following the recommendation in the spec, indexed and sub-byte images are left unfiltered
+    // and everything else picks, per row, the filter whose output has the smallest sum of
+    // absolute values.
+    #[default]
+    Adaptive,
+}
+
+impl PNGFile {
+    // Encodes an Image as a new PNG file with IHDR, PLTE and tRNS (when the image has them),
+    // a single IDAT chunk and IEND.
+    pub fn from_image(image: &Image) -> Result<PNGFile, Box<dyn Error>> {
+        let mut ihdr = Vec::with_capacity(13);
+        ihdr.extend_from_slice(&image.width().to_be_bytes());
+        ihdr.extend_from_slice(&image.height().to_be_bytes());
+        ihdr.push(image.bit_depth());
+        ihdr.push(image.color_type().to_u8());
+        // Compression, filter and interlace methods.
+        ihdr.extend_from_slice(&[0, 0, 0]);
+
+        let mut chunks = Vec::new();
+        if let Some(palette) = image.palette() {
+            let data = palette.iter().flat_map(|e| e.iter().copied()).collect();
+            chunks.push(PNGChunk::new(*b"PLTE", data));
+        }
+        if let Some(transparency) = image.transparency() {
+            chunks.push(PNGChunk::new(*b"tRNS", transparency_data(transparency)));
+        }
+
+        let compressed = compress_image(
+            image,
+            DEFAULT_COMPRESSION_LEVEL,
+            FilterStrategy::Adaptive,
+            false,
+        );
+        chunks.push(PNGChunk::new(*b"IDAT", compressed));
+        chunks.push(PNGChunk::new(*b"IEND", Vec::new()));
+
+        let ihdr_chunk = PNGChunk::new(*b"IHDR", ihdr);
+        Ok(PNGFile {
+            ihdr: IHDRData::parse(&ihdr_chunk)?,
+            ihdr_chunk,
+            time_chunk: None,
+            chunks,
+        })
+    }
+}
+
+pub(crate) fn transparency_data(transparency: &Transparency) -> Vec<u8> {
+    match transparency {
+        Transparency::Grayscale(v) => v.to_be_bytes().to_vec(),
+        Transparency::Truecolor(rgb) => rgb.iter().flat_map(|v| v.to_be_bytes()).collect(),
+        Transparency::Indexed(alphas) => alphas.clone(),
+    }
+}
+
+pub(crate) fn pack_row(samples: &[u16], bit_depth: u8, out: &mut Vec<u8>) {
+    match bit_depth {
+        16 => {
+            for s in samples {
+                out.extend_from_slice(&s.to_be_bytes());
+            }
+        }
+        8 => out.extend(samples.iter().map(|s| *s as u8)),
+        _ => {
+            let per_byte = 8 / bit_depth as usize;
+            for group in samples.chunks(per_byte) {
+                let mut byte = 0u8;
+                for (i, s) in group.iter().enumerate() {
+                    byte |= (*s as u8) << (8 - bit_depth as usize * (i + 1));
+                }
+                out.push(byte);
+            }
+        }
+    }
+}
+
+// Filters and compresses the image data, producing the contents of the IDAT stream. Interlaced
+// output stores the seven Adam7 passes one after another, each filtered as its own image.
+pub(crate) fn compress_image(
+    image: &Image,
+    level: u8,
+    strategy: FilterStrategy,
+    interlace: bool,
+) -> Vec<u8> {
+    let raw = if interlace {
+        let mut raw = Vec::new();
+        for (x0, y0, dx, dy) in ADAM7_PASSES.iter() {
+            if *x0 >= image.width() || *y0 >= image.height() {
+                continue;
+            }
+            let width = (image.width() - x0).div_ceil(*dx);
+            let height = (image.height() - y0).div_ceil(*dy);
+            let mut samples = Vec::with_capacity(width as usize * height as usize);
+            for y in (*y0..image.height()).step_by(*dy as usize) {
+                for x in (*x0..image.width()).step_by(*dx as usize) {
+                    samples.extend_from_slice(image.pixel(x, y));
+                }
+            }
+            raw.extend(filter(&samples, width, image, strategy));
+        }
+        raw
+    } else {
+        filter(image.samples(), image.width(), image, strategy)
+    };
+    compress_to_vec_zlib(&raw, level)
+}
+
+// Packs and filters every scanline of samples, which are width pixels wide and otherwise laid out
+// like the image's.
+fn filter(samples: &[u16], width: u32, image: &Image, strategy: FilterStrategy) -> Vec<u8> {
+    let color_type = image.color_type();
+    let bit_depth = image.bit_depth();
+    let stride = row_bytes(width, color_type, bit_depth);
+    let bpp = (bits_per_pixel(color_type, bit_depth) / 8).max(1);
+    let per_row = width as usize * image.channels();
+    let filter_types = match strategy {
+        FilterStrategy::None => 0..1,
+        FilterStrategy::Sub => 1..2,
+        FilterStrategy::Up => 2..3,
+        FilterStrategy::Average => 3..4,
+        FilterStrategy::Paeth => 4..5,
+        FilterStrategy::Adaptive if color_type == ColorType::Indexed || bit_depth < 8 => 0..1,
+        FilterStrategy::Adaptive => 0..5,
+    };
+
+    let mut out = Vec::with_capacity((stride + 1) * (samples.len() / per_row));
+    let mut previous = vec![0u8; stride];
+    let mut current = Vec::with_capacity(stride);
+    for row in samples.chunks_exact(per_row) {
+        current.clear();
+        pack_row(row, bit_depth, &mut current);
+
+        let (filter_type, filtered) = filter_types
+            .clone()
+            .map(|f| (f, filter_row(f, &current, &previous, bpp)))
+            .min_by_key(|(_, filtered)| {
+                filtered
+                    .iter()
+                    .map(|b| (*b as i8).unsigned_abs() as u64)
+                    .sum::<u64>()
+            })
+            .unwrap();
+        out.push(filter_type);
+        out.extend_from_slice(&filtered);
+        previous.copy_from_slice(&current);
+    }
+    out
+}
+
+pub(crate) fn filter_row(filter_type: u8, current: &[u8], previous: &[u8], bpp: usize) -> Vec<u8> {
+    (0..current.len())
+        .map(|i| {
+            let left = if i >= bpp { current[i - bpp] } else { 0 };
+            let upper_left = if i >= bpp { previous[i - bpp] } else { 0 };
+            let predictor = match filter_type {
+                1 => left,
+                2 => previous[i],
+                3 => ((left as u16 + previous[i] as u16) / 2) as u8,
+                4 => paeth(left, previous[i], upper_left),
+                _ => 0,
+            };
+            current[i].wrapping_sub(predictor)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STRATEGIES: [FilterStrategy; 6] = [
+        FilterStrategy::None,
+        FilterStrategy::Sub,
+        FilterStrategy::Up,
+        FilterStrategy::Average,
+        FilterStrategy::Paeth,
+        FilterStrategy::Adaptive,
+    ];
+
+    // A 5x3 gray+alpha image whose samples vary in both directions and cover the whole range.
+    fn gray_alpha(bit_depth: u8) -> Image {
+        let max = ((1u32 << bit_depth) - 1) as u16;
+        let mut samples = Vec::new();
+        for y in 0..3u16 {
+            for x in 0..5u16 {
+                samples.push(max / 4 * x);
+                samples.push(max - max / 2 * y);
+            }
+        }
+        Image::from_samples(5, 3, ColorType::GrayscaleAlpha, bit_depth, samples).unwrap()
+    }
+
+    // A file holding the image data compress_image produces for the given settings.
+    fn encoded(image: &Image, strategy: FilterStrategy, interlace: bool) -> PNGFile {
+        let mut ihdr = Vec::new();
+        ihdr.extend_from_slice(&image.width().to_be_bytes());
+        ihdr.extend_from_slice(&image.height().to_be_bytes());
+        ihdr.extend_from_slice(&[image.bit_depth(), 4, 0, 0, interlace as u8]);
+        PNGFile::from_chunks(vec![
+            PNGChunk::new(*b"IHDR", ihdr),
+            PNGChunk::new(*b"IDAT", compress_image(image, 6, strategy, interlace)),
+            PNGChunk::new(*b"IEND", Vec::new()),
+        ])
+        .unwrap()
+    }
+
+    // Image isn't PartialEq, so its fields are compared one by one.
+    fn assert_same(decoded: &Image, image: &Image) {
+        assert_eq!(
+            (decoded.width(), decoded.height()),
+            (image.width(), image.height())
+        );
+        assert_eq!(decoded.color_type(), image.color_type());
+        assert_eq!(decoded.bit_depth(), image.bit_depth());
+        assert_eq!(decoded.samples(), image.samples());
+    }
+
+    #[test]
+    fn gray_alpha_round_trip() {
+        for bit_depth in [8, 16] {
+            let image = gray_alpha(bit_depth);
+            let png = PNGFile::from_image(&image).unwrap();
+            let ihdr = png.get_ihdr_data();
+            assert_eq!(
+                (ihdr.get_color_type(), ihdr.get_bit_depth()),
+                (4, bit_depth)
+            );
+            assert!(png.first_chunk(b"tRNS").is_none());
+            assert_same(&png.decode().unwrap(), &image);
+        }
+    }
+
+    #[test]
+    fn gray_alpha_filters_and_interlacing() {
+        for bit_depth in [8, 16] {
+            let image = gray_alpha(bit_depth);
+            for strategy in STRATEGIES {
+                for interlace in [false, true] {
+                    assert_same(
+                        &encoded(&image, strategy, interlace).decode().unwrap(),
+                        &image,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gray_alpha_16_is_big_endian() {
+        let image =
+            Image::from_samples(1, 1, ColorType::GrayscaleAlpha, 16, vec![0x1234, 0xABCD]).unwrap();
+        let mut row = Vec::new();
+        pack_row(image.samples(), 16, &mut row);
+        assert_eq!(row, [0x12, 0x34, 0xAB, 0xCD]);
+    }
+}
@@ -0,0 +1,299 @@
+// Color management for ICC profiles built from a matrix and tone curves, which covers the RGB and
+// gray profiles cameras and image editors usually embed. Profiles built from lookup tables, such
+// as most printer and CMYK profiles, aren't supported.
+use std::convert::TryInto;
+use std::error::Error;
+
+use miniz_oxide::inflate::decompress_to_vec_zlib;
+
+use crate::image::{ColorType, Image, Transparency};
+
+// The sRGB primaries adapted to the D50 white of the profile connection space, as in the sRGB
+// profile, with red, green and blue as columns.
+const SRGB_D50: [[f32; 3]; 3] = [
+    [0.4361, 0.3851, 0.1431],
+    [0.2225, 0.7169, 0.0606],
+    [0.0139, 0.0971, 0.7141],
+];
+
+// A tone curve, turning stored values from 0.0 to 1.0 into linear ones.
+#[derive(Debug, Clone)]
+pub(crate) enum Curve {
+    Gamma(f32),
+    // Evenly spaced samples, interpolated between.
+    Table(Vec<f32>),
+    // One of the five parametric curve types, with parameters g, a, b, c, d, e and f.
+    Parametric(u16, [f32; 7]),
+}
+
+impl Curve {
+    fn eval(&self, v: f32) -> f32 {
+        let v = v.clamp(0.0, 1.0);
+        match self {
+            Curve::Gamma(gamma) => v.powf(*gamma),
+            Curve::Table(table) => {
+                let at = v * (table.len() - 1) as f32;
+                let i = (at.floor() as usize).min(table.len() - 2);
+                let t = at - i as f32;
+                table[i] * (1.0 - t) + table[i + 1] * t
+            }
+            Curve::Parametric(kind, [g, a, b, c, d, e, f]) => {
+                let power = |x: f32| (a * x + b).max(0.0).powf(*g);
+                match kind {
+                    0 => v.powf(*g),
+                    1 if v >= -b / a => power(v),
+                    1 => 0.0,
+                    2 if v >= -b / a => power(v) + c,
+                    2 => *c,
+                    3 if v >= *d => power(v),
+                    3 => c * v,
+                    _ if v >= *d => power(v) + e,
+                    _ => c * v + f,
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum IccProfile {
+    // Tone curves for red, green and blue, and the matrix from the linear values to linear sRGB.
+    Rgb([Curve; 3], [[f32; 3]; 3]),
+    Gray(Curve),
+}
+
+impl IccProfile {
+    // Reads the profile in the data of an iCCP chunk.
+    pub(crate) fn from_iccp(data: &[u8]) -> Result<IccProfile, Box<dyn Error>> {
+        let end = data
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or("The iCCP chunk has no profile name.")?;
+        if data.get(end + 1) != Some(&0) {
+            return Err("The iCCP chunk uses an unknown compression method.".into());
+        }
+        let profile = decompress_to_vec_zlib(&data[end + 2..])
+            .map_err(|_| "The ICC profile is not valid zlib data.")?;
+        IccProfile::parse(&profile)
+    }
+
+    pub(crate) fn parse(profile: &[u8]) -> Result<IccProfile, Box<dyn Error>> {
+        if profile.len() < 132 {
+            return Err("The ICC profile is too short.".into());
+        }
+        let tag_count = read_u32(profile, 128)? as usize;
+        let tag = |signature: &[u8; 4]| -> Result<&[u8], Box<dyn Error>> {
+            for i in 0..tag_count {
+                let entry = 132 + i * 12;
+                if profile.get(entry..entry + 4) == Some(&signature[..]) {
+                    let offset = read_u32(profile, entry + 4)? as usize;
+                    let size = read_u32(profile, entry + 8)? as usize;
+                    return profile
+                        .get(offset..offset.saturating_add(size))
+                        .ok_or_else(|| "An ICC profile tag runs past the end.".into());
+                }
+            }
+            Err(format!(
+                "The ICC profile has no {} tag, so it is not a matrix/TRC profile.",
+                String::from_utf8_lossy(signature)
+            )
+            .into())
+        };
+
+        match &profile[16..20] {
+            b"RGB " => {
+                let curves = [
+                    read_curve(tag(b"rTRC")?)?,
+                    read_curve(tag(b"gTRC")?)?,
+                    read_curve(tag(b"bTRC")?)?,
+                ];
+                let columns = [
+                    read_xyz(tag(b"rXYZ")?)?,
+                    read_xyz(tag(b"gXYZ")?)?,
+                    read_xyz(tag(b"bXYZ")?)?,
+                ];
+                let to_xyz = [
+                    [columns[0][0], columns[1][0], columns[2][0]],
+                    [columns[0][1], columns[1][1], columns[2][1]],
+                    [columns[0][2], columns[1][2], columns[2][2]],
+                ];
+                let to_srgb = multiply(&invert(&SRGB_D50), &to_xyz);
+                Ok(IccProfile::Rgb(curves, to_srgb))
+            }
+            b"GRAY" => Ok(IccProfile::Gray(read_curve(tag(b"kTRC")?)?)),
+            _ => Err("Only RGB and gray ICC profiles are supported.".into()),
+        }
+    }
+
+    // Converts the image's colors from the profile to sRGB, keeping its color type and bit
+    // depth. Colors outside of sRGB are clipped.
+    pub(crate) fn convert_to_srgb(&self, image: &Image) -> Result<Image, Box<dyn Error>> {
+        match self {
+            IccProfile::Gray(curve) => {
+                if !image.is_gray() {
+                    return Err("The ICC profile is for gray images but the image is not.".into());
+                }
+                Ok(image.map_colors(|v| srgb_encode(curve.eval(v))))
+            }
+            IccProfile::Rgb(curves, matrix) => {
+                if image.is_gray() {
+                    return Err("The ICC profile is for color images but the image is gray.".into());
+                }
+                let convert = |rgb: [f32; 3]| {
+                    let linear = [
+                        curves[0].eval(rgb[0]),
+                        curves[1].eval(rgb[1]),
+                        curves[2].eval(rgb[2]),
+                    ];
+                    let mut out = [0.0; 3];
+                    for (value, row) in out.iter_mut().zip(matrix) {
+                        *value = srgb_encode(
+                            row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2],
+                        );
+                    }
+                    out
+                };
+                let mut converted = image.clone();
+                if image.color_type() == ColorType::Indexed {
+                    if let Some(palette) = image.palette() {
+                        let palette = palette
+                            .iter()
+                            .map(|entry| {
+                                let rgb = convert(entry.map(|v| v as f32 / 255.0));
+                                rgb.map(|v| (v * 255.0).round() as u8)
+                            })
+                            .collect();
+                        converted.set_palette(Some(palette));
+                    }
+                    return Ok(converted);
+                }
+
+                let max = image.max_value() as f32;
+                let convert_samples = |samples: &mut [u16]| {
+                    let rgb = convert([
+                        samples[0] as f32 / max,
+                        samples[1] as f32 / max,
+                        samples[2] as f32 / max,
+                    ]);
+                    for (sample, value) in samples.iter_mut().zip(rgb.iter()) {
+                        *sample = (value * max).round() as u16;
+                    }
+                };
+                let channels = image.channels();
+                for pixel in converted.samples_mut().chunks_exact_mut(channels) {
+                    convert_samples(&mut pixel[..3]);
+                }
+                if let Some(Transparency::Truecolor(key)) = image.transparency() {
+                    let mut key = *key;
+                    convert_samples(&mut key);
+                    converted.set_transparency(Some(Transparency::Truecolor(key)));
+                }
+                Ok(converted)
+            }
+        }
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32, Box<dyn Error>> {
+    let bytes = data
+        .get(at..at + 4)
+        .ok_or("The ICC profile is truncated.")?;
+    Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_s15_fixed16(data: &[u8], at: usize) -> Result<f32, Box<dyn Error>> {
+    Ok(read_u32(data, at)? as i32 as f32 / 65536.0)
+}
+
+fn read_xyz(tag: &[u8]) -> Result<[f32; 3], Box<dyn Error>> {
+    if tag.get(..4) != Some(b"XYZ ") {
+        return Err("An ICC profile colorant tag is not an XYZ value.".into());
+    }
+    Ok([
+        read_s15_fixed16(tag, 8)?,
+        read_s15_fixed16(tag, 12)?,
+        read_s15_fixed16(tag, 16)?,
+    ])
+}
+
+fn read_curve(tag: &[u8]) -> Result<Curve, Box<dyn Error>> {
+    match tag.get(..4) {
+        Some(b"curv") => {
+            let count = read_u32(tag, 8)? as usize;
+            let entries = tag
+                .get(12..12 + count.saturating_mul(2))
+                .ok_or("An ICC profile curve is truncated.")?;
+            let entries: Vec<u16> = entries
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect();
+            Ok(match entries.len() {
+                0 => Curve::Gamma(1.0),
+                1 => Curve::Gamma(entries[0] as f32 / 256.0),
+                _ => Curve::Table(entries.iter().map(|e| *e as f32 / 65535.0).collect()),
+            })
+        }
+        Some(b"para") => {
+            let kind = (read_u32(tag, 8)? >> 16) as u16;
+            let count = match kind {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => return Err("An ICC profile curve has an unknown type.".into()),
+            };
+            let mut params = [0.0; 7];
+            for (i, param) in params.iter_mut().take(count).enumerate() {
+                *param = read_s15_fixed16(tag, 12 + i * 4)?;
+            }
+            Ok(Curve::Parametric(kind, params))
+        }
+        _ => Err("An ICC profile tone curve is not a curve.".into()),
+    }
+}
+
+// Turns a linear value into an sRGB-encoded one, clipping it to 0.0 - 1.0.
+fn srgb_encode(v: f32) -> f32 {
+    let v = v.clamp(0.0, 1.0);
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+fn multiply(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let mut out = [[0.0; 3]; 3];
+    for (i, row) in out.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    out
+}
+
+fn invert(m: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    let cofactor =
+        |r0: usize, r1: usize, c0: usize, c1: usize| m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0];
+    let adjugate = [
+        [
+            cofactor(1, 2, 1, 2),
+            -cofactor(0, 2, 1, 2),
+            cofactor(0, 1, 1, 2),
+        ],
+        [
+            -cofactor(1, 2, 0, 2),
+            cofactor(0, 2, 0, 2),
+            -cofactor(0, 1, 0, 2),
+        ],
+        [
+            cofactor(1, 2, 0, 1),
+            -cofactor(0, 2, 0, 1),
+            cofactor(0, 1, 0, 1),
+        ],
+    ];
+    let determinant =
+        m[0][0] * adjugate[0][0] + m[0][1] * adjugate[1][0] + m[0][2] * adjugate[2][0];
+    adjugate.map(|row| row.map(|v| v / determinant))
+}
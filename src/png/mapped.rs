@@ -0,0 +1,214 @@
+use std::convert::TryInto;
+use std::error::Error;
+use std::fs::File;
+
+use memmap2::Mmap;
+
+use super::decode::DecodeOptions;
+use super::parser::{split_chunk, ChunkValidator, PNGParser, ParseOptions, ParseReport};
+use super::{IHDRData, InvalidPNGFormat, PNGChunk, PNGFile, TruncatedPNGFile, PNG_HEADER};
+use crate::image::Image;
+
+// Where a chunk's data lives in the mapping.
+struct ChunkSpan {
+    chunk_type: [u8; 4],
+    start: usize,
+    length: usize,
+    crc: u32,
+}
+
+// A PNG file read through a memory mapping. Parsing only records where each chunk is, and chunk
+// data is handed out as slices of the mapping, so nothing is copied until it's needed. This
+// keeps memory use and load time down for very large files.
+//
+// The file must not be changed by another process while it's mapped. If it is, the data seen
+// through the mapping changes too, or the process may be killed if the file shrinks.
+pub struct MappedPNGFile {
+    map: Mmap,
+    chunks: Vec<ChunkSpan>,
+}
+
+// A chunk borrowed from a MappedPNGFile.
+#[derive(Clone, Copy)]
+pub struct MappedChunk<'a> {
+    chunk_type: [u8; 4],
+    data: &'a [u8],
+    crc: u32,
+    offset: u64,
+    index: usize,
+}
+
+impl MappedPNGFile {
+    pub fn open(filename: &str) -> Result<MappedPNGFile, Box<dyn Error>> {
+        let file = File::open(filename)?;
+        // Safety: see the note on MappedPNGFile. The mapping is read-only, and a file changing
+        // underneath it can't break memory safety beyond what that note describes.
+        let map = unsafe { Mmap::map(&file)? };
+        let chunks = index_chunks(&map, None)?;
+        Ok(MappedPNGFile { map, chunks })
+    }
+
+    // Like open, but also checks every chunk against the spec and the limits in options, the
+    // same way PNGFile::from_file_with_options does. This reads all of the data to check the
+    // CRCs, where open only reads the chunk headers.
+    pub fn open_with_options(
+        filename: &str,
+        options: &ParseOptions,
+    ) -> Result<(MappedPNGFile, ParseReport), Box<dyn Error>> {
+        let file = File::open(filename)?;
+        // Safety: as in open.
+        let map = unsafe { Mmap::map(&file)? };
+        let mut validator = ChunkValidator::new(*options);
+        let chunks = index_chunks(&map, Some(&mut validator))?;
+        Ok((MappedPNGFile { map, chunks }, validator.into_report()))
+    }
+
+    // Every chunk in file order, starting with IHDR and ending with IEND.
+    pub fn chunks(&self) -> impl Iterator<Item = MappedChunk<'_>> {
+        self.chunks
+            .iter()
+            .enumerate()
+            .map(move |(index, span)| MappedChunk {
+                chunk_type: span.chunk_type,
+                data: &self.map[span.start..span.start + span.length],
+                crc: span.crc,
+                offset: span.start as u64 - 8,
+                index,
+            })
+    }
+
+    // The first chunk of the given type.
+    pub fn get_chunk(&self, chunk_type: &[u8; 4]) -> Option<MappedChunk<'_>> {
+        self.chunks().find(|c| &c.chunk_type == chunk_type)
+    }
+
+    pub fn get_ihdr_chunk(&self) -> MappedChunk<'_> {
+        self.get_chunk(b"IHDR")
+            .expect("Mapped files are only created with an IHDR chunk")
+    }
+
+    // Copies every chunk into an ordinary PNGFile.
+    pub fn to_png_file(&self) -> Result<PNGFile, Box<dyn Error>> {
+        PNGFile::from_chunks(self.chunks().map(|c| c.to_chunk()).collect())
+    }
+
+    // Decodes the pixels, copying only the chunks the decoder reads.
+    pub fn decode(&self) -> Result<Image, Box<dyn Error>> {
+        self.decode_with_options(&DecodeOptions::default())
+    }
+
+    pub fn decode_with_options(&self, options: &DecodeOptions) -> Result<Image, Box<dyn Error>> {
+        let chunks = self
+            .chunks()
+            .filter(|c| {
+                matches!(
+                    &c.chunk_type,
+                    b"IHDR" | b"PLTE" | b"tRNS" | b"gAMA" | b"sRGB" | b"iCCP" | b"IDAT" | b"IEND"
+                )
+            })
+            .map(|c| c.to_chunk())
+            .collect();
+        PNGFile::from_chunks(chunks)?.decode_with_options(options)
+    }
+}
+
+impl<'a> MappedChunk<'a> {
+    pub fn get_chunk_type(&self) -> &[u8; 4] {
+        &self.chunk_type
+    }
+
+    pub fn get_data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn get_crc(&self) -> u32 {
+        self.crc
+    }
+
+    // Byte offset of the chunk's length field from the start of the file.
+    pub fn get_offset(&self) -> u64 {
+        self.offset
+    }
+
+    // Position of the chunk among all the chunks of the file, IHDR being 0.
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    // Copies the chunk, keeping its stored CRC and position.
+    pub fn to_chunk(&self) -> PNGChunk {
+        PNGChunk {
+            length: self.data.len() as u32,
+            chunk_type: self.chunk_type,
+            data: self.data.to_vec(),
+            crc: self.crc.to_be_bytes(),
+            offset: Some(self.offset),
+            index: Some(self.index),
+        }
+    }
+}
+
+// Walks the chunk headers with the same framing as PNGParser, recording where each chunk's data
+// is instead of copying it. Without a validator only the checks PNGParser treats as fatal in
+// every mode are made, and data that ends early is an error.
+fn index_chunks(
+    bytes: &[u8],
+    mut validator: Option<&mut ChunkValidator>,
+) -> Result<Vec<ChunkSpan>, Box<dyn Error>> {
+    if bytes.len() < PNG_HEADER.len() {
+        return Err(TruncatedPNGFile::new("PNG signature").into());
+    }
+    if bytes[..PNG_HEADER.len()] != PNG_HEADER {
+        return Err(InvalidPNGFormat.into());
+    }
+
+    let mut chunks = Vec::new();
+    let mut at = PNG_HEADER.len();
+    loop {
+        if let (Some(validator), Some(length)) = (validator.as_deref_mut(), bytes.get(at..at + 4)) {
+            validator.check_header(u32::from_be_bytes(length.try_into().unwrap()))?;
+        }
+        let raw = match split_chunk(&bytes[at..])? {
+            Some(raw) => raw,
+            None => {
+                if let Some(validator) = validator {
+                    validator.finish(at < bytes.len())?;
+                    return Ok(chunks);
+                }
+                // Let a parser fed the leftover bytes say which part is missing.
+                let mut parser = PNGParser::new();
+                let _ = parser.feed(&PNG_HEADER)?;
+                let _ = parser.feed(&bytes[at..])?;
+                return Err(TruncatedPNGFile::new(&parser.awaiting()).into());
+            }
+        };
+        match validator.as_deref_mut() {
+            Some(validator) => {
+                validator.check_chunk(&raw)?;
+            }
+            None if chunks.is_empty() => {
+                if &raw.chunk_type != b"IHDR" {
+                    return Err(InvalidPNGFormat.into());
+                }
+                IHDRData::parse(&PNGChunk::new(raw.chunk_type, raw.data.to_vec()))?;
+            }
+            None => {}
+        }
+
+        chunks.push(ChunkSpan {
+            chunk_type: raw.chunk_type,
+            start: at + 8,
+            length: raw.data.len(),
+            crc: u32::from_be_bytes(raw.crc),
+        });
+        at += raw.len();
+        if &raw.chunk_type == b"IEND" {
+            break;
+        }
+    }
+
+    if let Some(validator) = validator {
+        validator.finish(false)?;
+    }
+    Ok(chunks)
+}
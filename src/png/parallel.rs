@@ -0,0 +1,223 @@
+// Multithreaded unfiltering and unpacking for the "parallel" feature.
+//
+// Inflating has to happen in one pass over the zlib stream, and most filters depend on the row
+// above, so rows can't simply be handed out to threads. Rows filtered with None or Sub only look
+// at themselves though, so the image is cut into runs of rows starting at each of those and the
+// runs are unfiltered independently. Unpacking rows into samples has no dependencies at all, and
+// the seven passes of an interlaced image are separate images.
+use std::error::Error;
+
+use rayon::prelude::*;
+
+use super::decode::{
+    adam7_passes, bits_per_pixel, row_bytes, scatter_pass, unfilter_row, unpack, PNGDecodeError,
+};
+use super::IHDRData;
+use crate::image::ColorType;
+
+// Runs shorter than this are merged with the next one so threads get a worthwhile amount of work.
+const MIN_RUN_ROWS: usize = 32;
+
+pub(crate) fn decode_samples(
+    raw: &[u8],
+    ihdr: &IHDRData,
+    color_type: ColorType,
+    threads: usize,
+) -> Result<Vec<u16>, Box<dyn Error>> {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()?;
+    let samples = pool.install(|| {
+        if ihdr.interlace_method == 1 {
+            deinterlace(raw, ihdr, color_type)
+        } else {
+            let rows = unfilter(raw, ihdr.width, ihdr.height, color_type, ihdr.bit_depth)?;
+            Ok(unpack_rows(
+                &rows,
+                ihdr.width,
+                ihdr.height,
+                color_type,
+                ihdr.bit_depth,
+            ))
+        }
+    })?;
+    Ok(samples)
+}
+
+fn unfilter(
+    raw: &[u8],
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: u8,
+) -> Result<Vec<u8>, PNGDecodeError> {
+    let stride = row_bytes(width, color_type, bit_depth);
+    let bpp = (bits_per_pixel(color_type, bit_depth) / 8).max(1);
+    let height = height as usize;
+    if raw.len() < (stride + 1) * height {
+        return Err(PNGDecodeError::new(
+            "The IDAT stream is shorter than the image.",
+        ));
+    }
+    let filter_type = |y: usize| raw[y * (stride + 1)];
+    if (0..height).any(|y| filter_type(y) > 4) {
+        return Err(PNGDecodeError::new("Unknown scanline filter type."));
+    }
+
+    let mut starts = vec![0];
+    for y in 1..height {
+        if filter_type(y) <= 1 && y - starts[starts.len() - 1] >= MIN_RUN_ROWS {
+            starts.push(y);
+        }
+    }
+
+    let mut rows = vec![0u8; stride * height];
+    let mut runs = Vec::with_capacity(starts.len());
+    let mut rest = rows.as_mut_slice();
+    for (i, start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(height);
+        let (run, remaining) = rest.split_at_mut((end - start) * stride);
+        runs.push((*start, run));
+        rest = remaining;
+    }
+
+    runs.into_par_iter().for_each(|(start, run)| {
+        // The first row of a run is None or Sub filtered, or the first row of the image, so it
+        // never needs anything from the previous run.
+        let mut previous = vec![0u8; stride];
+        for (i, current) in run.chunks_exact_mut(stride).enumerate() {
+            let line = &raw[(start + i) * (stride + 1)..(start + i + 1) * (stride + 1)];
+            current.copy_from_slice(&line[1..]);
+            unfilter_row(line[0], current, &previous, bpp)
+                .expect("Filter types were checked up front");
+            previous.copy_from_slice(current);
+        }
+    });
+    Ok(rows)
+}
+
+// Unpacks bands of rows on separate threads and joins them back together.
+fn unpack_rows(
+    rows: &[u8],
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: u8,
+) -> Vec<u16> {
+    let stride = row_bytes(width, color_type, bit_depth);
+    let band_rows = (height as usize)
+        .div_ceil(rayon::current_num_threads())
+        .max(1);
+    rows[..stride * height as usize]
+        .par_chunks(stride * band_rows)
+        .map(|band| {
+            let band_height = (band.len() / stride) as u32;
+            unpack(band, width, band_height, color_type, bit_depth)
+        })
+        .collect::<Vec<Vec<u16>>>()
+        .concat()
+}
+
+fn deinterlace(
+    raw: &[u8],
+    ihdr: &IHDRData,
+    color_type: ColorType,
+) -> Result<Vec<u16>, PNGDecodeError> {
+    let passes = adam7_passes(raw, ihdr, color_type)?;
+    let decoded = passes
+        .par_iter()
+        .map(|pass| {
+            let pass_raw = &raw[pass.offset..pass.offset + pass.len];
+            unfilter(
+                pass_raw,
+                pass.width,
+                pass.height,
+                color_type,
+                ihdr.bit_depth,
+            )
+            .map(|rows| unpack(&rows, pass.width, pass.height, color_type, ihdr.bit_depth))
+            .map_err(|err| err.to_string())
+        })
+        .collect::<Result<Vec<Vec<u16>>, String>>()
+        .map_err(|message| PNGDecodeError::new(&message))?;
+
+    let channels = color_type.channels();
+    let mut samples = vec![0u16; ihdr.width as usize * ihdr.height as usize * channels];
+    for (pass, pass_samples) in passes.iter().zip(decoded) {
+        scatter_pass(&mut samples, ihdr.width, channels, pass, &pass_samples);
+    }
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::decode::{decode_samples_serial, image_data_len};
+
+    fn ihdr(color_type: ColorType, bit_depth: u8, interlace_method: u8) -> IHDRData {
+        IHDRData {
+            width: 61,
+            height: 150,
+            bit_depth,
+            color_type: color_type as u8,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method,
+        }
+    }
+
+    // Filtered image data made of noise, with a filter type byte picked from the noise at the
+    // start of every row so that runs are split at a few None and Sub rows.
+    fn raw_data(ihdr: &IHDRData, color_type: ColorType) -> Vec<u8> {
+        let mut state = 7u32;
+        let len = image_data_len(ihdr, color_type).unwrap();
+        let mut raw: Vec<u8> = (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect();
+        let passes = if ihdr.interlace_method == 1 {
+            adam7_passes(&raw, ihdr, color_type).unwrap()
+        } else {
+            Vec::new()
+        };
+        let mut set_filter_types = |offset: usize, width: u32, height: u32| {
+            let stride = row_bytes(width, color_type, ihdr.bit_depth) + 1;
+            for y in 0..height as usize {
+                raw[offset + y * stride] %= 5;
+            }
+        };
+        if passes.is_empty() {
+            set_filter_types(0, ihdr.width, ihdr.height);
+        }
+        for pass in &passes {
+            set_filter_types(pass.offset, pass.width, pass.height);
+        }
+        raw
+    }
+
+    #[test]
+    fn matches_serial() {
+        let formats = [
+            (ColorType::Grayscale, 1),
+            (ColorType::Truecolor, 8),
+            (ColorType::TruecolorAlpha, 16),
+        ];
+        for (color_type, bit_depth) in formats {
+            for interlace_method in [0, 1] {
+                let ihdr = ihdr(color_type, bit_depth, interlace_method);
+                let raw = raw_data(&ihdr, color_type);
+                let serial = decode_samples_serial(&raw, &ihdr, color_type).unwrap();
+                let parallel = decode_samples(&raw, &ihdr, color_type, 4).unwrap();
+                assert!(
+                    serial == parallel,
+                    "{:?} at {} bits, interlace method {}",
+                    color_type,
+                    bit_depth,
+                    interlace_method
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,750 @@
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::error;
+use core::error::Error;
+use core::fmt;
+
+use super::rules::{lint_chunk, ChunkRules, ParseMode, SpecViolation};
+use super::warnings::Warnings;
+use super::{IHDRData, InvalidPNGFormat, PNGChunk, PNG_HEADER};
+
+// The spec caps chunk lengths at 2^31 - 1 bytes.
+pub(crate) const MAX_CHUNK_LENGTH: u32 = 0x7fff_ffff;
+
+// Limits for parsing untrusted files. The parser stops with LimitExceeded as soon as a chunk
+// header shows a limit would be crossed, before any of that chunk is buffered. None means no
+// limit beyond the spec's own cap of 2^31 - 1 bytes per chunk.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParseOptions {
+    pub mode: ParseMode,
+    // Largest data field allowed in a single chunk, in bytes.
+    pub max_chunk_size: Option<u32>,
+    // Largest file allowed, counting the signature and every chunk in full.
+    pub max_total_size: Option<u64>,
+    // Most chunks allowed, IHDR and IEND included.
+    pub max_chunk_count: Option<usize>,
+}
+
+impl ParseOptions {
+    // No limits, in strict mode or permissive mode. This is what the language bindings' strict
+    // flag means.
+    pub fn from_strict(strict: bool) -> ParseOptions {
+        ParseOptions {
+            mode: if strict {
+                ParseMode::Strict
+            } else {
+                ParseMode::Permissive
+            },
+            ..ParseOptions::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LimitExceeded {
+    // Name of the ParseOptions field that was exceeded, e.g. "max_chunk_size".
+    pub limit: &'static str,
+    message: String,
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Parse limit exceeded: {}", self.message)
+    }
+}
+
+impl error::Error for LimitExceeded {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+// What happened while a file was parsed, returned alongside the PNGFile so that linting tools
+// can list everything that looks wrong without the parse stopping.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseReport {
+    pub mode: ParseMode,
+    // Suspicious values and odd chunk orderings in both modes, plus the spec violations let
+    // through in permissive mode.
+    pub warnings: Warnings,
+    // Chunks parsed, IHDR and IEND included.
+    pub chunk_count: usize,
+    // Bytes parsed, counting the signature and every complete chunk.
+    pub bytes_read: u64,
+    // False when the data ended before IEND, which only permissive mode allows.
+    pub complete: bool,
+}
+
+impl ParseReport {
+    // True if nothing at all was found.
+    pub fn is_clean(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    pub fn has_spec_violations(&self) -> bool {
+        self.warnings.has_spec_violations()
+    }
+}
+
+// Something the parser found in the bytes fed so far.
+pub enum PNGParseEvent {
+    // The IHDR chunk was read and its values are valid. It is also reported as a ChunkParsed
+    // event right after this one.
+    HeaderParsed(IHDRData),
+    // A complete chunk, in file order.
+    ChunkParsed(PNGChunk),
+    // IEND was reached. Anything fed after this is ignored.
+    Done,
+}
+
+// One chunk's fields, borrowed from the bytes it was found in.
+pub(crate) struct RawChunk<'a> {
+    pub(crate) chunk_type: [u8; 4],
+    pub(crate) data: &'a [u8],
+    pub(crate) crc: [u8; 4],
+}
+
+impl<'a> RawChunk<'a> {
+    // Bytes the chunk takes up in the file, including length, type and CRC.
+    pub(crate) fn len(&self) -> usize {
+        12 + self.data.len()
+    }
+}
+
+// Splits the chunk at the start of bytes off without copying it. Returns None when bytes ends
+// before the chunk does. This framing is shared by PNGParser and the memory-mapped loader.
+pub(crate) fn split_chunk(bytes: &[u8]) -> Result<Option<RawChunk<'_>>, InvalidPNGFormat> {
+    if bytes.len() < 8 {
+        return Ok(None);
+    }
+    let length = u32::from_be_bytes(bytes[0..4].try_into().unwrap());
+    if length > MAX_CHUNK_LENGTH {
+        return Err(InvalidPNGFormat);
+    }
+    let total = 12 + length as usize;
+    if bytes.len() < total {
+        return Ok(None);
+    }
+    Ok(Some(RawChunk {
+        chunk_type: bytes[4..8].try_into().unwrap(),
+        data: &bytes[8..total - 4],
+        crc: bytes[total - 4..total].try_into().unwrap(),
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Signature,
+    Chunks,
+    Done,
+}
+
+// The checks every chunk goes through, whichever loader read it: limits, spec rules, lints and
+// the requirement that IHDR comes first. Violations are returned as errors in strict mode and
+// collected in warnings in permissive mode.
+pub(crate) struct ChunkValidator {
+    options: ParseOptions,
+    rules: ChunkRules,
+    // Bytes checked so far and the number of chunks among them.
+    consumed: u64,
+    chunk_count: usize,
+    seen_ihdr: bool,
+    seen_iend: bool,
+    warnings: Warnings,
+}
+
+impl ChunkValidator {
+    pub(crate) fn new(options: ParseOptions) -> ChunkValidator {
+        ChunkValidator {
+            options,
+            rules: ChunkRules::new(),
+            consumed: PNG_HEADER.len() as u64,
+            chunk_count: 0,
+            seen_ihdr: false,
+            seen_iend: false,
+            warnings: Warnings::new(),
+        }
+    }
+
+    // Checks a chunk header against the limits before its data is read.
+    pub(crate) fn check_header(&self, length: u32) -> Result<(), LimitExceeded> {
+        let options = &self.options;
+        if let Some(max) = options.max_chunk_size {
+            if length > max {
+                return Err(LimitExceeded {
+                    limit: "max_chunk_size",
+                    message: format!("A {} byte chunk is over the {} byte limit.", length, max),
+                });
+            }
+        }
+        if let Some(max) = options.max_total_size {
+            if self.consumed + 12 + length as u64 > max {
+                return Err(LimitExceeded {
+                    limit: "max_total_size",
+                    message: format!("The file is over the {} byte limit.", max),
+                });
+            }
+        }
+        if let Some(max) = options.max_chunk_count {
+            if self.chunk_count >= max {
+                return Err(LimitExceeded {
+                    limit: "max_chunk_count",
+                    message: format!("The file has more than {} chunks.", max),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Checks a complete chunk. Returns the header values when this is the IHDR chunk.
+    pub(crate) fn check_chunk(
+        &mut self,
+        chunk: &RawChunk,
+    ) -> Result<Option<IHDRData>, Box<dyn Error>> {
+        self.check_header(chunk.data.len() as u32)?;
+        // Nothing can be done without a header, so this is an error in either mode.
+        if !self.seen_ihdr && &chunk.chunk_type != b"IHDR" {
+            return Err(InvalidPNGFormat.into());
+        }
+        let offset = self.consumed;
+        self.consumed += chunk.len() as u64;
+        self.chunk_count += 1;
+        self.seen_iend |= &chunk.chunk_type == b"IEND";
+
+        // Ordering lints look at the chunks before this one, so they run before the rules
+        // record it.
+        let order_warnings = self.rules.lint_order(chunk);
+        let violations = self.rules.check(chunk);
+        self.record(violations)?;
+        self.warnings.extend(order_warnings);
+        self.warnings.extend(lint_chunk(chunk, offset));
+
+        if self.seen_ihdr {
+            return Ok(None);
+        }
+        self.seen_ihdr = true;
+        let ihdr = PNGChunk::new(chunk.chunk_type, chunk.data.to_vec());
+        Ok(Some(IHDRData::parse(&ihdr)?))
+    }
+
+    // Rules that need the whole file, plus a note if it stopped part way through a chunk.
+    pub(crate) fn finish(&mut self, truncated: bool) -> Result<(), Box<dyn Error>> {
+        let mut violations = self.rules.finish();
+        if truncated {
+            violations.insert(
+                0,
+                SpecViolation::new(
+                    "truncated",
+                    format!(
+                        "The data ends in the middle of the chunk at offset {:#X}.",
+                        self.consumed
+                    ),
+                    None,
+                ),
+            );
+        }
+        if !self.seen_ihdr {
+            return Err(InvalidPNGFormat.into());
+        }
+        self.record(violations)
+    }
+
+    // Offset and index the next chunk checked will have.
+    pub(crate) fn position(&self) -> (u64, usize) {
+        (self.consumed, self.chunk_count)
+    }
+
+    pub(crate) fn warnings(&self) -> &Warnings {
+        &self.warnings
+    }
+
+    pub(crate) fn into_warnings(self) -> Warnings {
+        self.warnings
+    }
+
+    pub(crate) fn into_report(self) -> ParseReport {
+        ParseReport {
+            mode: self.options.mode,
+            warnings: self.warnings,
+            chunk_count: self.chunk_count,
+            bytes_read: self.consumed,
+            complete: self.seen_iend,
+        }
+    }
+
+    fn record(&mut self, violations: Vec<SpecViolation>) -> Result<(), Box<dyn Error>> {
+        if self.options.mode == ParseMode::Strict {
+            return match violations.into_iter().next() {
+                Some(violation) => Err(violation.into()),
+                None => Ok(()),
+            };
+        }
+        self.warnings
+            .extend(violations.into_iter().map(|v| v.into()));
+        Ok(())
+    }
+}
+
+// Incremental parser for PNG data arriving in pieces, e.g. from a socket. Feed it byte slices of
+// any size as they come in and it returns the events that became complete; bytes_needed says
+// how much more it wants. Only the chunk currently being received is buffered. The parser does
+// no IO of its own. PNGFile::from_reader, from_file and the other loaders all drive one of these,
+// so every way of reading a file behaves the same.
+//
+// Every chunk is checked against the spec as it arrives. In strict mode the first violation is
+// returned as an error; in permissive mode violations are collected in warnings() instead.
+// Either way, data without a valid IHDR up front can't be parsed at all. Values that are legal
+// but suspicious end up in warnings() in both modes.
+pub struct PNGParser {
+    buffer: Vec<u8>,
+    state: State,
+    options: ParseOptions,
+    validator: ChunkValidator,
+}
+
+impl Default for PNGParser {
+    fn default() -> PNGParser {
+        PNGParser::new()
+    }
+}
+
+impl PNGParser {
+    pub fn new() -> PNGParser {
+        PNGParser::with_mode(ParseMode::default())
+    }
+
+    pub fn with_mode(mode: ParseMode) -> PNGParser {
+        PNGParser::with_options(ParseOptions {
+            mode,
+            ..ParseOptions::default()
+        })
+    }
+
+    pub fn with_options(options: ParseOptions) -> PNGParser {
+        PNGParser {
+            buffer: Vec::new(),
+            state: State::Signature,
+            options,
+            validator: ChunkValidator::new(options),
+        }
+    }
+
+    pub fn mode(&self) -> ParseMode {
+        self.options.mode
+    }
+
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.state == State::Done
+    }
+
+    // Suspicious values found so far, plus the violations let through in permissive mode.
+    pub fn warnings(&self) -> &Warnings {
+        self.validator.warnings()
+    }
+
+    pub fn into_warnings(self) -> Warnings {
+        self.validator.into_warnings()
+    }
+
+    // Everything found while parsing. Call after finish for the complete picture.
+    pub fn into_report(self) -> ParseReport {
+        self.validator.into_report()
+    }
+
+    // How many more bytes have to be fed before the next event can complete, or 0 once IEND has
+    // been reached. Front-ends reading from a stream can ask for exactly this much, so they
+    // never read past the end of the PNG.
+    pub fn bytes_needed(&self) -> usize {
+        let buffered = self.buffer.len();
+        match self.state {
+            State::Signature => PNG_HEADER.len() - buffered,
+            State::Chunks if buffered < 8 => 8 - buffered,
+            State::Chunks => {
+                let length = u32::from_be_bytes(self.buffer[0..4].try_into().unwrap());
+                (12 + length as usize).saturating_sub(buffered)
+            }
+            State::Done => 0,
+        }
+    }
+
+    // The part of the file the parser is waiting for, for truncation messages.
+    pub(crate) fn awaiting(&self) -> String {
+        let buffered = self.buffer.len();
+        match self.state {
+            State::Signature => "PNG signature".to_string(),
+            State::Chunks if buffered < 4 => "chunk length".to_string(),
+            State::Chunks if buffered < 8 => "chunk type".to_string(),
+            State::Chunks => {
+                let length = u32::from_be_bytes(self.buffer[0..4].try_into().unwrap()) as usize;
+                if buffered < 8 + length {
+                    format!(
+                        "data of the {} chunk",
+                        String::from_utf8_lossy(&self.buffer[4..8])
+                    )
+                } else {
+                    "chunk CRC".to_string()
+                }
+            }
+            State::Done => "end of the file".to_string(),
+        }
+    }
+
+    // Call once the input has run out. Reports a missing IEND, a chunk cut off part way, and
+    // other problems that only show up at the end.
+    pub fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        let truncated = !self.buffer.is_empty();
+        self.buffer = Vec::new();
+        self.validator.finish(truncated)?;
+        self.state = State::Done;
+        Ok(())
+    }
+
+    pub fn feed(&mut self, bytes: &[u8]) -> Result<Vec<PNGParseEvent>, Box<dyn Error>> {
+        let mut events = Vec::new();
+        if self.state == State::Done {
+            return Ok(events);
+        }
+        self.buffer.extend_from_slice(bytes);
+
+        let mut pos = 0;
+        loop {
+            let available = &self.buffer[pos..];
+            match self.state {
+                State::Signature => {
+                    if available.len() < PNG_HEADER.len() {
+                        break;
+                    }
+                    if available[..PNG_HEADER.len()] != PNG_HEADER {
+                        return Err(InvalidPNGFormat.into());
+                    }
+                    pos += PNG_HEADER.len();
+                    self.state = State::Chunks;
+                }
+                State::Chunks => {
+                    if available.len() >= 8 {
+                        let length = u32::from_be_bytes(available[0..4].try_into().unwrap());
+                        self.validator.check_header(length)?;
+                    }
+                    let raw = match split_chunk(available)? {
+                        Some(raw) => raw,
+                        None => break,
+                    };
+                    pos += raw.len();
+                    let (offset, index) = self.validator.position();
+                    if let Some(ihdr) = self.validator.check_chunk(&raw)? {
+                        events.push(PNGParseEvent::HeaderParsed(ihdr));
+                    }
+
+                    let done = &raw.chunk_type == b"IEND";
+                    events.push(PNGParseEvent::ChunkParsed(PNGChunk {
+                        length: raw.data.len() as u32,
+                        chunk_type: raw.chunk_type,
+                        data: raw.data.to_vec(),
+                        crc: raw.crc,
+                        offset: Some(offset),
+                        index: Some(index),
+                    }));
+                    if done {
+                        self.state = State::Done;
+                        events.push(PNGParseEvent::Done);
+                    }
+                }
+                State::Done => break,
+            }
+        }
+
+        self.buffer.drain(..pos);
+        if self.state == State::Done {
+            self.buffer = Vec::new();
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 1x1 8-bit grayscale image: signature, IHDR, one IDAT holding a stored zlib block, IEND.
+    fn tiny_png() -> Vec<u8> {
+        let mut bytes = PNG_HEADER.to_vec();
+        let ihdr = [0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0];
+        PNGChunk::new(*b"IHDR", ihdr.to_vec()).append_to(&mut bytes);
+        let idat = [
+            0x78, 0x01, 0x01, 0x02, 0x00, 0xFD, 0xFF, 0, 0, 0x00, 0x02, 0x00, 0x01,
+        ];
+        PNGChunk::new(*b"IDAT", idat.to_vec()).append_to(&mut bytes);
+        PNGChunk::new(*b"IEND", Vec::new()).append_to(&mut bytes);
+        bytes
+    }
+
+    // The chunk types in a list of events, with "HDR!" for HeaderParsed and "END!" for Done.
+    fn kinds(events: &[PNGParseEvent]) -> Vec<[u8; 4]> {
+        events
+            .iter()
+            .map(|event| match event {
+                PNGParseEvent::HeaderParsed(_) => *b"HDR!",
+                PNGParseEvent::ChunkParsed(chunk) => chunk.chunk_type,
+                PNGParseEvent::Done => *b"END!",
+            })
+            .collect()
+    }
+
+    const EXPECTED: [[u8; 4]; 5] = [*b"HDR!", *b"IHDR", *b"IDAT", *b"IEND", *b"END!"];
+
+    fn strict() -> PNGParser {
+        PNGParser::with_mode(ParseMode::Strict)
+    }
+
+    // PNGParseEvent isn't Debug, so unwrap_err can't be used on the result of feed.
+    fn error<T>(result: Result<T, Box<dyn Error>>) -> Box<dyn Error> {
+        match result {
+            Ok(_) => panic!("expected an error"),
+            Err(err) => err,
+        }
+    }
+
+    fn violation_code(err: &(dyn Error + 'static)) -> &'static str {
+        err.downcast_ref::<SpecViolation>().unwrap().code
+    }
+
+    fn limit_name(err: &(dyn Error + 'static)) -> &'static str {
+        err.downcast_ref::<LimitExceeded>().unwrap().limit
+    }
+
+    #[test]
+    fn whole_file() {
+        let mut parser = strict();
+        let events = parser.feed(&tiny_png()).unwrap();
+        assert_eq!(kinds(&events), EXPECTED);
+        match &events[0] {
+            PNGParseEvent::HeaderParsed(ihdr) => {
+                assert_eq!((ihdr.get_width(), ihdr.get_height()), (1, 1));
+                assert_eq!((ihdr.get_bit_depth(), ihdr.get_color_type()), (8, 0));
+            }
+            _ => panic!("IHDR wasn't reported first"),
+        }
+        assert!(parser.is_done());
+        assert_eq!(parser.bytes_needed(), 0);
+        parser.finish().unwrap();
+        let report = parser.into_report();
+        assert!(report.complete);
+        assert_eq!(report.chunk_count, 3);
+        assert_eq!(report.bytes_read, tiny_png().len() as u64);
+    }
+
+    #[test]
+    fn byte_at_a_time() {
+        let bytes = tiny_png();
+        let mut parser = strict();
+        let mut events = Vec::new();
+        for (i, byte) in bytes.iter().enumerate() {
+            assert!(!parser.is_done(), "done before byte {}", i);
+            assert!(parser.bytes_needed() > 0);
+            events.extend(parser.feed(&[*byte]).unwrap());
+        }
+        assert_eq!(kinds(&events), EXPECTED);
+        parser.finish().unwrap();
+        assert!(parser.into_report().complete);
+    }
+
+    #[test]
+    fn split_chunks() {
+        let bytes = tiny_png();
+        // Cuts inside the signature, the IHDR length, the IHDR data, the IDAT CRC and IEND's type.
+        let cuts = [3, 10, 20, 53, 62, bytes.len()];
+        let mut parser = strict();
+        let mut events = Vec::new();
+        let mut start = 0;
+        for cut in cuts.iter() {
+            events.extend(parser.feed(&bytes[start..*cut]).unwrap());
+            start = *cut;
+        }
+        assert_eq!(kinds(&events), EXPECTED);
+
+        // Offsets and indexes match those of a single feed.
+        let offsets: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                PNGParseEvent::ChunkParsed(chunk) => Some((chunk.offset, chunk.index)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            offsets,
+            [(Some(8), Some(0)), (Some(33), Some(1)), (Some(58), Some(2))]
+        );
+    }
+
+    #[test]
+    fn bytes_needed_stops_at_chunk_boundaries() {
+        let bytes = tiny_png();
+        let mut parser = strict();
+        assert_eq!(parser.bytes_needed(), 8);
+        parser.feed(&bytes[..8]).unwrap();
+        assert_eq!(parser.bytes_needed(), 8);
+        parser.feed(&bytes[8..16]).unwrap();
+        // The rest of IHDR: 13 bytes of data and the CRC.
+        assert_eq!(parser.bytes_needed(), 17);
+    }
+
+    #[test]
+    fn nothing_after_iend_is_parsed() {
+        let mut bytes = tiny_png();
+        bytes.extend_from_slice(b"trailing garbage");
+        let mut parser = strict();
+        assert_eq!(kinds(&parser.feed(&bytes).unwrap()), EXPECTED);
+        assert!(parser.feed(b"more").unwrap().is_empty());
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = tiny_png();
+        let cut = &bytes[..bytes.len() - 5];
+
+        let mut parser = strict();
+        let events = parser.feed(cut).unwrap();
+        assert_eq!(kinds(&events), [*b"HDR!", *b"IHDR", *b"IDAT"]);
+        assert!(!parser.is_done());
+        // Seven bytes of IEND are in; the parser wants the rest of its header before anything else.
+        assert_eq!(parser.bytes_needed(), 1);
+        assert_eq!(violation_code(&*parser.finish().unwrap_err()), "truncated");
+
+        let mut parser = PNGParser::with_mode(ParseMode::Permissive);
+        parser.feed(cut).unwrap();
+        parser.finish().unwrap();
+        let report = parser.into_report();
+        assert!(!report.complete);
+        assert_eq!(report.chunk_count, 2);
+        assert!(report.warnings.iter().any(|w| w.code == "truncated"));
+    }
+
+    #[test]
+    fn truncated_signature() {
+        let mut parser = strict();
+        assert!(parser.feed(&PNG_HEADER[..5]).unwrap().is_empty());
+        assert!(parser.finish().unwrap_err().is::<InvalidPNGFormat>());
+    }
+
+    #[test]
+    fn bad_signature() {
+        let mut bytes = tiny_png();
+        bytes[1] = b'J';
+        let err = error(strict().feed(&bytes));
+        assert!(err.is::<InvalidPNGFormat>());
+    }
+
+    #[test]
+    fn bad_crc() {
+        let mut bytes = tiny_png();
+        // The last byte of the IDAT CRC.
+        bytes[57] ^= 0xFF;
+
+        let mut parser = strict();
+        let err = error(parser.feed(&bytes));
+        assert_eq!(violation_code(&*err), "bad_crc");
+
+        // Permissive mode passes the chunk on and notes the violation.
+        let mut parser = PNGParser::with_mode(ParseMode::Permissive);
+        let events = parser.feed(&bytes).unwrap();
+        assert_eq!(kinds(&events), EXPECTED);
+        parser.finish().unwrap();
+        let warnings = parser.into_warnings();
+        assert!(warnings.has_spec_violations());
+        assert!(warnings.iter().any(|w| w.code == "bad_crc"));
+    }
+
+    #[test]
+    fn bad_crc_split_across_feeds() {
+        let mut bytes = tiny_png();
+        bytes[57] ^= 0xFF;
+        let mut parser = strict();
+        assert_eq!(
+            kinds(&parser.feed(&bytes[..55]).unwrap()),
+            [*b"HDR!", *b"IHDR"]
+        );
+        let err = error(parser.feed(&bytes[55..]));
+        assert_eq!(violation_code(&*err), "bad_crc");
+    }
+
+    #[test]
+    fn max_chunk_size() {
+        let mut parser = PNGParser::with_options(ParseOptions {
+            max_chunk_size: Some(12),
+            ..ParseOptions::default()
+        });
+        // The limit is checked as soon as the IHDR length and type are in, before its data.
+        let err = error(parser.feed(&tiny_png()[..16]));
+        assert_eq!(limit_name(&*err), "max_chunk_size");
+
+        let mut parser = PNGParser::with_options(ParseOptions {
+            max_chunk_size: Some(13),
+            ..ParseOptions::default()
+        });
+        assert_eq!(kinds(&parser.feed(&tiny_png()).unwrap()), EXPECTED);
+    }
+
+    #[test]
+    fn max_total_size() {
+        let bytes = tiny_png();
+        let mut parser = PNGParser::with_options(ParseOptions {
+            max_total_size: Some(bytes.len() as u64 - 1),
+            ..ParseOptions::default()
+        });
+        let mut events = Vec::new();
+        let mut result = Ok(());
+        for byte in bytes.iter() {
+            match parser.feed(&[*byte]) {
+                Ok(more) => events.extend(more),
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
+        }
+        // IHDR and IDAT fit, IEND would cross the limit.
+        assert_eq!(kinds(&events), [*b"HDR!", *b"IHDR", *b"IDAT"]);
+        assert_eq!(limit_name(&*result.unwrap_err()), "max_total_size");
+
+        let mut parser = PNGParser::with_options(ParseOptions {
+            max_total_size: Some(bytes.len() as u64),
+            ..ParseOptions::default()
+        });
+        assert_eq!(kinds(&parser.feed(&bytes).unwrap()), EXPECTED);
+    }
+
+    #[test]
+    fn max_chunk_count() {
+        let mut parser = PNGParser::with_options(ParseOptions {
+            max_chunk_count: Some(2),
+            ..ParseOptions::default()
+        });
+        let err = error(parser.feed(&tiny_png()));
+        assert_eq!(limit_name(&*err), "max_chunk_count");
+
+        let mut parser = PNGParser::with_options(ParseOptions {
+            max_chunk_count: Some(3),
+            ..ParseOptions::default()
+        });
+        assert_eq!(kinds(&parser.feed(&tiny_png()).unwrap()), EXPECTED);
+    }
+
+    #[test]
+    fn chunk_before_ihdr() {
+        let mut bytes = PNG_HEADER.to_vec();
+        PNGChunk::new(*b"IEND", Vec::new()).append_to(&mut bytes);
+        let err = error(PNGParser::new().feed(&bytes));
+        assert!(err.is::<InvalidPNGFormat>());
+    }
+}
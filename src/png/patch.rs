@@ -0,0 +1,139 @@
+// Patching chunks in place. Rewriting a file means streaming all of its image data out again,
+// which is slow for very large files when only a timestamp or a line of text changes. As long as
+// the new chunk has exactly the same type and length as the old one, only its data and CRC need
+// to be overwritten and nothing else in the file moves.
+//
+// The file is changed directly rather than through a temporary file, so a crash part way through
+// can leave the chunk half written. Its CRC will then be wrong, which parsers report.
+use std::error::Error;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+
+use super::{InvalidPNGFormat, PNGChunk, TextData, TimeData, TruncatedPNGFile, PNG_HEADER};
+
+// The length and type of a chunk, and where it starts.
+pub(crate) struct ChunkHeader {
+    pub(crate) offset: u64,
+    pub(crate) length: u32,
+    pub(crate) chunk_type: [u8; 4],
+}
+
+// Overwrites the chunk starting at offset, as reported by PNGChunk::get_offset, with chunk. The
+// chunk there must have the same type and length.
+pub fn patch_chunk(filename: &str, offset: u64, chunk: &PNGChunk) -> Result<(), Box<dyn Error>> {
+    let mut file = OpenOptions::new().read(true).write(true).open(filename)?;
+    file.seek(SeekFrom::Start(offset))?;
+    let header = read_header(&mut file, offset)?.ok_or("There is no chunk at that offset.")?;
+    write_chunk(&mut file, &header, chunk)
+}
+
+// Sets the tIME chunk, which must already be in the file.
+pub fn patch_time(filename: &str, time: &TimeData) -> Result<(), Box<dyn Error>> {
+    let mut file = OpenOptions::new().read(true).write(true).open(filename)?;
+    let header = find_chunk(&mut file, |header, _| Ok(&header.chunk_type == b"tIME"))?
+        .ok_or("The file has no tIME chunk to patch.")?;
+    write_chunk(&mut file, &header, &time.to_chunk())
+}
+
+// Replaces the text of the first text chunk with the given keyword. The new text has to take up
+// as many bytes as the old text, so pad it with spaces if need be. Compressed text chunks can't
+// be patched.
+pub fn patch_text(filename: &str, keyword: &str, text: &str) -> Result<(), Box<dyn Error>> {
+    let chunk = TextData::new(keyword, text)?.to_chunk();
+    let mut file = OpenOptions::new().read(true).write(true).open(filename)?;
+    let header = find_chunk(&mut file, |header, file| {
+        if !matches!(&header.chunk_type, b"tEXt" | b"zTXt" | b"iTXt") {
+            return Ok(false);
+        }
+        // The keyword and its null separator.
+        let mut prefix = vec![0u8; (keyword.chars().count() + 1).min(header.length as usize)];
+        file.read_exact(&mut prefix)?;
+        let (last, chars) = prefix.split_last().unwrap_or((&1, &[]));
+        Ok(*last == 0 && chars.iter().map(|b| *b as char).eq(keyword.chars()))
+    })?
+    .ok_or_else(|| format!("The file has no text chunk with the keyword {}.", keyword))?;
+    write_chunk(&mut file, &header, &chunk)
+}
+
+// Walks the chunks from the start of the file, seeking over their data, and returns the first
+// one `wanted` accepts. `wanted` is called with the file positioned at the start of the chunk's
+// data and may read from it.
+pub(crate) fn find_chunk<F>(
+    file: &mut File,
+    mut wanted: F,
+) -> Result<Option<ChunkHeader>, Box<dyn Error>>
+where
+    F: FnMut(&ChunkHeader, &mut File) -> Result<bool, Box<dyn Error>>,
+{
+    let mut signature = [0u8; 8];
+    file.read_exact(&mut signature)
+        .map_err(|_| InvalidPNGFormat)?;
+    if signature != PNG_HEADER {
+        return Err(InvalidPNGFormat.into());
+    }
+
+    let mut offset = PNG_HEADER.len() as u64;
+    while let Some(header) = read_header(file, offset)? {
+        if wanted(&header, file)? {
+            return Ok(Some(header));
+        }
+        if &header.chunk_type == b"IEND" {
+            break;
+        }
+        offset += 12 + header.length as u64;
+        file.seek(SeekFrom::Start(offset))?;
+    }
+    Ok(None)
+}
+
+// Reads the length and type of the chunk at offset, where the file must be positioned. Returns
+// None at the end of the file.
+fn read_header(file: &mut File, offset: u64) -> Result<Option<ChunkHeader>, Box<dyn Error>> {
+    let mut bytes = [0u8; 8];
+    match file.read_exact(&mut bytes) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err.into()),
+    }
+    let length = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let chunk_type = [bytes[4], bytes[5], bytes[6], bytes[7]];
+    // Make sure the whole chunk is there, so a patch never writes past the end of the file.
+    if offset + 12 + length as u64 > file.metadata()?.len() {
+        return Err(TruncatedPNGFile::new("chunk data").into());
+    }
+    Ok(Some(ChunkHeader {
+        offset,
+        length,
+        chunk_type,
+    }))
+}
+
+fn write_chunk(
+    file: &mut File,
+    header: &ChunkHeader,
+    chunk: &PNGChunk,
+) -> Result<(), Box<dyn Error>> {
+    if header.chunk_type != chunk.chunk_type {
+        return Err(format!(
+            "Can't patch a {} chunk with a {} chunk.",
+            String::from_utf8_lossy(&header.chunk_type),
+            String::from_utf8_lossy(&chunk.chunk_type)
+        )
+        .into());
+    }
+    if header.length != chunk.length {
+        return Err(format!(
+            "The new {} chunk is {} bytes long but the one in the file is {}. Chunks can only \
+             be patched in place with data of the same length.",
+            String::from_utf8_lossy(&chunk.chunk_type),
+            chunk.length,
+            header.length
+        )
+        .into());
+    }
+    file.seek(SeekFrom::Start(header.offset + 8))?;
+    file.write_all(&chunk.data)?;
+    file.write_all(&chunk.crc)?;
+    file.sync_all()?;
+    Ok(())
+}
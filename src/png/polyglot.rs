@@ -0,0 +1,209 @@
+// Polyglot detection. A polyglot is a file that is a valid PNG and, read by another program, also
+// a valid file of another format. Zip readers look for their directory at the end of a file, PDF
+// readers for a header anywhere in its first kilobyte and browsers that sniff content for markup,
+// so a PNG can carry an archive, a document or a script that image checks never look at. Such
+// files are used to smuggle data past upload filters and to serve scripts from image hosts.
+//
+// These are heuristics. The signatures are looked for in the raw bytes of the file, outside the
+// compressed image data where they would turn up by chance in large files.
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::PNG_HEADER;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolyglotKind {
+    Zip,
+    // A zip archive with a manifest, which Java runs as a program.
+    Jar,
+    Pdf,
+    Html,
+}
+
+impl fmt::Display for PolyglotKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            PolyglotKind::Zip => "ZIP",
+            PolyglotKind::Jar => "JAR",
+            PolyglotKind::Pdf => "PDF",
+            PolyglotKind::Html => "HTML",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A signature of another format found in a PNG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PolyglotFinding {
+    pub kind: PolyglotKind,
+    // Where the signature starts in the file.
+    pub offset: u64,
+    // The chunk whose data holds the signature, or None when it is after IEND.
+    pub chunk_type: Option<[u8; 4]>,
+    pub message: String,
+}
+
+// Chunks holding compressed image data, which is never looked at by other formats' readers.
+const IMAGE_DATA_CHUNKS: [&[u8; 4]; 2] = [b"IDAT", b"fdAT"];
+
+// Markup that makes a browser treat a file as a page, matched without regard to case.
+const HTML_MARKERS: [&[u8]; 8] = [
+    b"<html",
+    b"<!doctype html",
+    b"<script",
+    b"<iframe",
+    b"<svg",
+    b"<body",
+    b"<object",
+    b"<embed",
+];
+
+// Readers accept a PDF header anywhere in this many bytes from the start.
+const PDF_HEADER_RANGE: usize = 1024;
+
+// Browsers that sniff content only look this far into a file.
+const SNIFF_RANGE: usize = 512;
+
+// Looks for signs that a PNG held in memory is also a zip archive, Java archive, PDF or HTML
+// page. Files that aren't PNGs at all get no findings.
+pub fn find_polyglots(bytes: &[u8]) -> Vec<PolyglotFinding> {
+    let mut findings = Vec::new();
+    if !bytes.starts_with(&PNG_HEADER) {
+        return findings;
+    }
+    let regions = regions(bytes);
+    let region = |offset: usize| regions.iter().find(|r| r.start <= offset && offset < r.end);
+    let in_image_data = |offset: usize| {
+        region(offset)
+            .and_then(|r| r.chunk_type)
+            .is_some_and(|t| IMAGE_DATA_CHUNKS.contains(&&t))
+    };
+    let mut add = |kind, offset: usize, what: &str| {
+        let chunk_type = region(offset).and_then(|r| r.chunk_type);
+        let place = match (region(offset), chunk_type) {
+            (Some(_), Some(chunk_type)) => {
+                format!("the {} chunk", String::from_utf8_lossy(&chunk_type))
+            }
+            (Some(_), None) => String::from("the data after IEND"),
+            (None, _) => String::from("a chunk header"),
+        };
+        findings.push(PolyglotFinding {
+            kind,
+            offset: offset as u64,
+            chunk_type,
+            message: format!("{} at offset {:#X} in {}.", what, offset, place),
+        });
+    };
+
+    // Zip readers start from the end of central directory record, which ends the file but for a
+    // comment whose length it gives.
+    let zip_kind = if contains(bytes, b"META-INF/") {
+        PolyglotKind::Jar
+    } else {
+        PolyglotKind::Zip
+    };
+    let zip_end = find_all(bytes, b"PK\x05\x06").into_iter().rev().find(|o| {
+        bytes.get(o + 20..o + 22).is_some_and(|comment| {
+            o + 22 + u16::from_le_bytes([comment[0], comment[1]]) as usize == bytes.len()
+        })
+    });
+    if let Some(offset) = zip_end {
+        add(
+            zip_kind,
+            offset,
+            &format!("A {} end of central directory record", zip_kind),
+        );
+    }
+    for offset in find_all(bytes, b"PK\x03\x04") {
+        // A chunk's data can only start with one on purpose.
+        let at_chunk_start = regions.iter().any(|r| r.start == offset);
+        let after_iend = region(offset).is_some_and(|r| r.chunk_type.is_none());
+        if at_chunk_start || after_iend {
+            add(zip_kind, offset, &format!("A {} file entry", zip_kind));
+        }
+    }
+
+    for offset in find_all(bytes, b"%PDF-") {
+        if !in_image_data(offset) {
+            let what = if offset < PDF_HEADER_RANGE {
+                "A PDF header close enough to the start for PDF readers to open the file"
+            } else {
+                "A PDF header"
+            };
+            add(PolyglotKind::Pdf, offset, what);
+        }
+    }
+
+    let lowercase = bytes.to_ascii_lowercase();
+    for marker in HTML_MARKERS.iter() {
+        for offset in find_all(&lowercase, marker) {
+            if in_image_data(offset) {
+                continue;
+            }
+            let tag = String::from_utf8_lossy(&bytes[offset..offset + marker.len()]);
+            let what = if offset < SNIFF_RANGE {
+                format!("{} markup where browsers sniffing the content see it", tag)
+            } else {
+                format!("{} markup", tag)
+            };
+            add(PolyglotKind::Html, offset, &what);
+        }
+    }
+
+    findings.sort_by_key(|f| f.offset);
+    findings
+}
+
+// A chunk's data, or the data after IEND when chunk_type is None.
+struct Region {
+    start: usize,
+    end: usize,
+    chunk_type: Option<[u8; 4]>,
+}
+
+// Splits a PNG into the data of its chunks, walking them by their lengths. Anything after IEND,
+// or after the point where the chunks stop making sense, is one last region without a type.
+fn regions(bytes: &[u8]) -> Vec<Region> {
+    let mut regions = Vec::new();
+    let mut offset = PNG_HEADER.len();
+    while let Some(header) = bytes.get(offset..offset + 8) {
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let chunk_type = [header[4], header[5], header[6], header[7]];
+        let end = match offset.checked_add(12 + length) {
+            Some(end) if end <= bytes.len() => end,
+            _ => break,
+        };
+        regions.push(Region {
+            start: offset + 8,
+            end: offset + 8 + length,
+            chunk_type: Some(chunk_type),
+        });
+        offset = end;
+        if &chunk_type == b"IEND" {
+            break;
+        }
+    }
+    regions.push(Region {
+        start: offset,
+        end: bytes.len(),
+        chunk_type: None,
+    });
+    regions
+}
+
+fn find_all(haystack: &[u8], needle: &[u8]) -> Vec<usize> {
+    haystack
+        .windows(needle.len())
+        .enumerate()
+        .filter(|(_, window)| *window == needle)
+        .map(|(offset, _)| offset)
+        .collect()
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
@@ -0,0 +1,98 @@
+// Recovering files that were cut off, such as interrupted downloads. Every chunk that was read in
+// full is kept, whatever rules it breaks, and the chunk the data ended in is dropped. An IEND
+// chunk can be added so that the result is a well formed file other tools will open.
+//
+// Image data is often split over many IDAT chunks, so a cut-off file can still hold most of its
+// rows, which browsers and many viewers show once the file ends properly.
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::error::Error;
+#[cfg(feature = "std")]
+use std::fs;
+
+use super::{parse_chunks, PNGChunk, PNGFile, ParseMode, ParseOptions, ParseReport};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecoveryOptions {
+    // Whether to end a cut-off file with an IEND chunk.
+    pub add_iend: bool,
+}
+
+impl Default for RecoveryOptions {
+    fn default() -> RecoveryOptions {
+        RecoveryOptions { add_iend: true }
+    }
+}
+
+// What recovery kept and what was lost.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecoveryReport {
+    // The report of the permissive parse, with the spec violations that were let through. Its
+    // bytes_read counts the signature and every chunk that was kept.
+    pub parse_report: ParseReport,
+    // True when the data ended before IEND.
+    pub truncated: bool,
+    // Bytes of the chunk the data ended in, which were dropped.
+    pub bytes_discarded: u64,
+    // The type of that chunk, if its header was there.
+    pub partial_chunk_type: Option<[u8; 4]>,
+    // How many bytes that chunk was short of its stated length. Whatever came after it in the
+    // original file is lost as well and can't be counted.
+    pub bytes_missing: Option<u64>,
+    pub iend_added: bool,
+}
+
+impl PNGFile {
+    // Parses as much of a PNG held in memory as there is. Only data that isn't a PNG at all, or
+    // a missing or invalid IHDR, is an error.
+    pub fn recover(
+        bytes: &[u8],
+        options: &RecoveryOptions,
+    ) -> Result<(PNGFile, RecoveryReport), Box<dyn Error>> {
+        let parse_options = ParseOptions {
+            mode: ParseMode::Permissive,
+            ..ParseOptions::default()
+        };
+        let (mut chunks, mut parser) = parse_chunks(bytes, &parse_options)?;
+        parser.finish()?;
+        let parse_report = parser.into_report();
+
+        let truncated = !parse_report.complete;
+        let rest = bytes.get(parse_report.bytes_read as usize..).unwrap_or(&[]);
+        let (bytes_discarded, partial_chunk_type, bytes_missing) = if truncated {
+            let partial_chunk_type = rest.get(4..8).map(|t| [t[0], t[1], t[2], t[3]]);
+            let bytes_missing = rest.get(0..4).map(|length| {
+                let length = u32::from_be_bytes([length[0], length[1], length[2], length[3]]);
+                (12 + length as u64).saturating_sub(rest.len() as u64)
+            });
+            (rest.len() as u64, partial_chunk_type, bytes_missing)
+        } else {
+            (0, None, None)
+        };
+
+        let iend_added = truncated && options.add_iend;
+        if iend_added {
+            chunks.push(PNGChunk::new(*b"IEND", Vec::new()));
+        }
+        let png_file = PNGFile::from_chunks(chunks)?;
+        Ok((
+            png_file,
+            RecoveryReport {
+                parse_report,
+                truncated,
+                bytes_discarded,
+                partial_chunk_type,
+                bytes_missing,
+                iend_added,
+            },
+        ))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn recover_file(
+        filename: &str,
+        options: &RecoveryOptions,
+    ) -> Result<(PNGFile, RecoveryReport), Box<dyn Error>> {
+        PNGFile::recover(&fs::read(filename)?, options)
+    }
+}
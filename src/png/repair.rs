@@ -0,0 +1,41 @@
+use alloc::vec::Vec;
+use core::iter;
+
+use super::{crc, PNGFile};
+
+// A chunk whose CRC repair_crcs replaced.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrcRepair {
+    pub chunk_type: [u8; 4],
+    // Where the chunk starts in the file it was read from.
+    pub offset: Option<u64>,
+    // The CRC the file had and the one it has now.
+    pub stored: u32,
+    pub computed: u32,
+}
+
+impl PNGFile {
+    // Recomputes the CRC of every chunk whose stored CRC is wrong and returns the chunks that
+    // were fixed. Read the file in permissive mode first, since strict mode rejects bad CRCs.
+    //
+    // A bad CRC means the chunk changed after it was written. When only the CRC itself was
+    // damaged, or an editor changed the data without updating it, this makes the file readable
+    // again. When the data was damaged, it only hides that, so check the pixels afterwards.
+    pub fn repair_crcs(&mut self) -> Vec<CrcRepair> {
+        let mut repairs = Vec::new();
+        let chunks = iter::once(&mut self.ihdr_chunk)
+            .chain(self.time_chunk.iter_mut())
+            .chain(self.chunks.iter_mut());
+        for chunk in chunks.filter(|c| !c.has_valid_crc()) {
+            let computed = crc::chunk_crc(&chunk.chunk_type, &chunk.data);
+            repairs.push(CrcRepair {
+                chunk_type: chunk.chunk_type,
+                offset: chunk.offset,
+                stored: chunk.get_crc(),
+                computed,
+            });
+            chunk.crc = computed.to_be_bytes();
+        }
+        repairs
+    }
+}
@@ -0,0 +1,360 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::error;
+use core::fmt;
+use core::str;
+
+use super::warnings::Warning;
+use super::crc;
+use super::parser::RawChunk;
+use super::{PNGFile, PNG_HEADER};
+
+// How the parser treats data that breaks the rules of the PNG spec. Strict stops at the first
+// violation, which is what validators and archives want. Permissive records the violation and
+// keeps going with whatever it can use, which is what recovery tools want.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    Strict,
+    #[default]
+    Permissive,
+}
+
+// Chunks that may appear at most once.
+const SINGLE_CHUNKS: [&[u8; 4]; 16] = [
+    b"IHDR", b"PLTE", b"IEND", b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP", b"bKGD",
+    b"hIST", b"tRNS", b"pHYs", b"tIME", b"eXIf", b"acTL",
+];
+
+// Chunks that must come before PLTE and IDAT.
+const BEFORE_PLTE: [&[u8; 4]; 6] = [b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP"];
+
+// Chunks that must come after PLTE, when there is one, and before IDAT.
+pub(crate) const AFTER_PLTE: [&[u8; 4]; 3] = [b"bKGD", b"hIST", b"tRNS"];
+
+// Chunks that must come before IDAT.
+const BEFORE_IDAT: [&[u8; 4]; 4] = [b"PLTE", b"pHYs", b"sPLT", b"acTL"];
+
+const KNOWN_CRITICAL: [&[u8; 4]; 4] = [b"IHDR", b"PLTE", b"IDAT", b"IEND"];
+
+// Ancillary chunks defined by the spec, the registered extensions and APNG.
+const KNOWN_ANCILLARY: [&[u8; 4]; 29] = [
+    b"tRNS", b"cHRM", b"gAMA", b"iCCP", b"sBIT", b"sRGB", b"cICP", b"mDCV", b"cLLI", b"tEXt",
+    b"zTXt", b"iTXt", b"bKGD", b"hIST", b"pHYs", b"sPLT", b"eXIf", b"tIME", b"acTL", b"fcTL",
+    b"fdAT", b"oFFs", b"pCAL", b"sCAL", b"sTER", b"gIFg", b"gIFx", b"gIFt", b"dSIG",
+];
+
+#[cfg(feature = "std")]
+pub(crate) fn is_known_chunk(chunk_type: &[u8; 4]) -> bool {
+    KNOWN_CRITICAL.contains(&chunk_type) || KNOWN_ANCILLARY.contains(&chunk_type)
+}
+
+// A way in which a file departs from the spec.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecViolation {
+    // Stable identifier such as "bad_crc".
+    pub code: &'static str,
+    pub message: String,
+    pub chunk_type: Option<[u8; 4]>,
+    // Where in the file the offending chunk starts, for violations that belong to one chunk.
+    pub offset: Option<u64>,
+}
+
+impl SpecViolation {
+    pub(crate) fn new(
+        code: &'static str,
+        message: String,
+        chunk_type: Option<[u8; 4]>,
+    ) -> SpecViolation {
+        SpecViolation {
+            code,
+            message,
+            chunk_type,
+            offset: None,
+        }
+    }
+
+    fn at(self, offset: u64) -> SpecViolation {
+        SpecViolation {
+            offset: Some(offset),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for SpecViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl error::Error for SpecViolation {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+// Checks chunks against the spec's rules as they arrive in file order.
+pub(crate) struct ChunkRules {
+    color_type: Option<u8>,
+    seen: Vec<[u8; 4]>,
+    idat_finished: bool,
+    // Offset of the next chunk in the file.
+    offset: u64,
+}
+
+impl ChunkRules {
+    pub(crate) fn new() -> ChunkRules {
+        ChunkRules {
+            color_type: None,
+            seen: Vec::new(),
+            idat_finished: false,
+            offset: PNG_HEADER.len() as u64,
+        }
+    }
+
+    fn has_seen(&self, chunk_type: &[u8; 4]) -> bool {
+        self.seen.contains(chunk_type)
+    }
+
+    pub(crate) fn check(&mut self, chunk: &RawChunk) -> Vec<SpecViolation> {
+        let chunk_type = chunk.chunk_type;
+        let name = chunk_type_name(&chunk_type);
+        let offset = self.offset;
+        let mut violations = Vec::new();
+        let mut violation = |code, message: String| {
+            violations.push(SpecViolation::new(code, message, Some(chunk_type)).at(offset))
+        };
+
+        if crc::chunk_crc(&chunk_type, chunk.data) != u32::from_be_bytes(chunk.crc) {
+            violation(
+                "bad_crc",
+                format!("The {} chunk at offset {:#X} has a bad CRC.", name, offset),
+            );
+        }
+        if !chunk_type.iter().all(|b| b.is_ascii_alphabetic()) {
+            violation(
+                "invalid_chunk_type",
+                format!(
+                    "{:?} at offset {:#X} is not a valid chunk type.",
+                    chunk_type, offset
+                ),
+            );
+        } else if chunk_type[2].is_ascii_lowercase() {
+            violation(
+                "reserved_bit_set",
+                format!(
+                    "The {} chunk at offset {:#X} has its reserved bit set.",
+                    name, offset
+                ),
+            );
+        }
+        if chunk_type[0].is_ascii_uppercase() && !KNOWN_CRITICAL.contains(&&chunk_type) {
+            violation(
+                "unknown_critical_chunk",
+                format!(
+                    "The {} chunk at offset {:#X} is an unknown critical chunk.",
+                    name, offset
+                ),
+            );
+        }
+
+        if self.seen.is_empty() && &chunk_type != b"IHDR" {
+            violation(
+                "ihdr_not_first",
+                format!("The first chunk is {} instead of IHDR.", name),
+            );
+        }
+        if SINGLE_CHUNKS.contains(&&chunk_type) && self.has_seen(&chunk_type) {
+            violation(
+                "duplicate_chunk",
+                format!("There is more than one {} chunk.", name),
+            );
+        }
+        if self.has_seen(b"IEND") {
+            violation("chunk_after_iend", format!("{} comes after IEND.", name));
+        }
+
+        let idat_seen = self.has_seen(b"IDAT");
+        let misplaced = |after: &str| format!("{} must not come after {}.", name, after);
+        if BEFORE_PLTE.contains(&&chunk_type) && self.has_seen(b"PLTE") {
+            violation("misplaced_chunk", misplaced("PLTE"));
+        } else if (BEFORE_PLTE.contains(&&chunk_type)
+            || AFTER_PLTE.contains(&&chunk_type)
+            || BEFORE_IDAT.contains(&&chunk_type))
+            && idat_seen
+        {
+            violation("misplaced_chunk", misplaced("IDAT"));
+        } else if AFTER_PLTE.contains(&&chunk_type)
+            && self.color_type == Some(3)
+            && !self.has_seen(b"PLTE")
+        {
+            violation("misplaced_chunk", format!("{} must come after PLTE.", name));
+        }
+
+        match &chunk_type {
+            b"IHDR" => self.color_type = chunk.data.get(9).copied(),
+            b"PLTE" if self.color_type == Some(0) || self.color_type == Some(4) => violation(
+                "unexpected_palette",
+                "Grayscale images must not have a PLTE chunk.".to_string(),
+            ),
+            b"IDAT" if self.idat_finished => violation(
+                "idat_not_consecutive",
+                "IDAT chunks must follow each other with nothing in between.".to_string(),
+            ),
+            b"IEND" if !chunk.data.is_empty() => {
+                violation("invalid_iend", "The IEND chunk must be empty.".to_string())
+            }
+            _ => {}
+        }
+        if idat_seen && &chunk_type != b"IDAT" {
+            self.idat_finished = true;
+        }
+
+        self.seen.push(chunk_type);
+        self.offset += chunk.len() as u64;
+        violations
+    }
+
+    // Orderings the spec allows but that some readers don't expect. Call before check, so that
+    // the chunk isn't counted among the ones before it.
+    pub(crate) fn lint_order(&self, chunk: &RawChunk) -> Vec<Warning> {
+        let mut warnings = Vec::new();
+        if !self.has_seen(b"IDAT") || self.has_seen(b"IEND") {
+            return warnings;
+        }
+        let code = match &chunk.chunk_type {
+            b"tIME" => "late_time",
+            b"eXIf" => "late_exif",
+            _ => return warnings,
+        };
+        let message = format!(
+            "The {} chunk comes after IDAT, where some readers don't look for it.",
+            chunk_type_name(&chunk.chunk_type)
+        );
+        warnings.push(Warning::new(code, message, Some(chunk.chunk_type)).at(self.offset));
+        warnings
+    }
+
+    // Rules that can only be checked once every chunk has been seen.
+    pub(crate) fn finish(&self) -> Vec<SpecViolation> {
+        let mut violations = Vec::new();
+        if self.color_type == Some(3) && !self.has_seen(b"PLTE") {
+            violations.push(SpecViolation::new(
+                "missing_palette",
+                "Indexed images must have a PLTE chunk.".to_string(),
+                Some(*b"PLTE"),
+            ));
+        }
+        if !self.has_seen(b"IDAT") {
+            violations.push(SpecViolation::new(
+                "missing_idat",
+                "There is no IDAT chunk.".to_string(),
+                Some(*b"IDAT"),
+            ));
+        }
+        if !self.has_seen(b"IEND") {
+            violations.push(SpecViolation::new(
+                "missing_iend",
+                "The file ends without an IEND chunk.".to_string(),
+                Some(*b"IEND"),
+            ));
+        }
+        violations
+    }
+}
+
+impl PNGFile {
+    // Checks the chunks, in the order to_bytes writes them, against the spec's rules on which
+    // chunks must be present, how often they may appear and where. Parsing in permissive mode
+    // keeps files that break them, and editing can add chunks in the wrong place, so this is the
+    // way to find out whether a file is fit to be written. An empty list means it is.
+    pub fn validate(&self) -> Vec<SpecViolation> {
+        let mut rules = ChunkRules::new();
+        let mut violations = Vec::new();
+        for chunk in self.iter_chunks() {
+            violations.extend(rules.check(&RawChunk {
+                chunk_type: chunk.chunk_type,
+                data: &chunk.data,
+                crc: chunk.crc,
+            }));
+        }
+        violations.extend(rules.finish());
+        violations
+    }
+}
+
+// Images with more pixels than this are flagged, since decoding them takes gigabytes of memory.
+const LARGE_PIXEL_COUNT: u64 = 1 << 28;
+
+// Looks for values that are allowed, or at least harmless to parse, but suggest something is
+// wrong with the file.
+pub(crate) fn lint_chunk(chunk: &RawChunk, offset: u64) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let data = chunk.data;
+    let mut warn = |code, message: &str| {
+        warnings.push(Warning::new(code, message.to_string(), Some(chunk.chunk_type)).at(offset))
+    };
+
+    match &chunk.chunk_type {
+        b"IHDR" if data.len() >= 8 => {
+            let width = u32::from_be_bytes(data[0..4].try_into().unwrap()) as u64;
+            let height = u32::from_be_bytes(data[4..8].try_into().unwrap()) as u64;
+            if width * height > LARGE_PIXEL_COUNT {
+                warn(
+                    "large_dimensions",
+                    "The image is unusually large and will need a lot of memory to decode.",
+                );
+            }
+        }
+        b"IDAT" if data.is_empty() => warn("empty_idat", "An IDAT chunk is empty."),
+        b"tIME" => {
+            let valid = data.len() == 7
+                && (1..=12).contains(&data[2])
+                && (1..=31).contains(&data[3])
+                && data[4] <= 23
+                && data[5] <= 59
+                && data[6] <= 60;
+            if !valid {
+                warn(
+                    "invalid_time",
+                    "The tIME chunk does not hold a valid date and time.",
+                );
+            }
+        }
+        b"gAMA" if data.len() != 4 || data == [0, 0, 0, 0] => warn(
+            "invalid_gamma",
+            "The gAMA chunk does not hold a usable gamma.",
+        ),
+        b"pHYs" if data.len() != 9 || data[8] > 1 => warn(
+            "invalid_physical_size",
+            "The pHYs chunk has the wrong length or an unknown unit.",
+        ),
+        chunk_type
+            if chunk_type[0].is_ascii_lowercase() && !KNOWN_ANCILLARY.contains(&chunk_type) =>
+        {
+            warn(
+                "unknown_chunk",
+                &format!(
+                    "{} is not a chunk type this library knows. It was kept as is.",
+                    chunk_type_name(chunk_type)
+                ),
+            )
+        }
+        _ => {}
+    }
+    warnings
+}
+
+// Printable form of a chunk type for messages and reports.
+pub(crate) fn chunk_type_name(chunk_type: &[u8; 4]) -> String {
+    match str::from_utf8(chunk_type) {
+        Ok(name) => name.to_string(),
+        Err(_) => format!("{:?}", chunk_type),
+    }
+}
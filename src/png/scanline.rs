@@ -0,0 +1,273 @@
+use std::error::Error;
+
+use miniz_oxide::inflate::stream::{inflate, InflateState};
+use miniz_oxide::{DataFormat, MZError, MZFlush, MZStatus};
+
+use super::decode::{
+    bits_per_pixel, parse_palette, parse_transparency, row_bytes, unfilter_row, unpack_row,
+    PNGDecodeError,
+};
+use super::PNGFile;
+use crate::image::{ColorType, Image, Transparency};
+
+// Decodes an image one row at a time. The IDAT stream is inflated only as far as the next row
+// needs, so memory use stays at a couple of rows no matter how large the image is. Rows come out
+// as one u16 per sample, the same layout Image uses.
+//
+// Interlaced images can't be produced row by row because the last pass fills in every other
+// row, so those are decoded in full and then handed out a row at a time.
+pub struct ScanlineDecoder<'a> {
+    width: u32,
+    height: u32,
+    color_type: ColorType,
+    bit_depth: u8,
+    palette: Option<Vec<[u8; 3]>>,
+    transparency: Option<Transparency>,
+    idat: Vec<&'a [u8]>,
+    chunk_index: usize,
+    chunk_offset: usize,
+    state: Box<InflateState>,
+    line: Vec<u8>,
+    previous: Vec<u8>,
+    row: u32,
+    interlaced: Option<Image>,
+}
+
+impl PNGFile {
+    pub fn scanlines(&self) -> Result<ScanlineDecoder<'_>, Box<dyn Error>> {
+        ScanlineDecoder::new(self)
+    }
+
+    // Decodes only the given rectangle. Rows above it still have to be inflated and unfiltered,
+    // since each row depends on the one before, but they are never unpacked, and nothing below
+    // the rectangle is touched.
+    pub fn decode_region(
+        &self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<Image, Box<dyn Error>> {
+        let mut decoder = self.scanlines()?;
+        if width == 0
+            || height == 0
+            || x as u64 + width as u64 > decoder.width() as u64
+            || y as u64 + height as u64 > decoder.height() as u64
+        {
+            return Err(PNGDecodeError::new("The region lies outside the image.").into());
+        }
+
+        for _ in 0..y {
+            decoder.skip_row()?;
+        }
+        let channels = decoder.color_type().channels();
+        let start = x as usize * channels;
+        let end = start + width as usize * channels;
+        let mut samples = Vec::with_capacity(width as usize * height as usize * channels);
+        let mut row = Vec::new();
+        for _ in 0..height {
+            decoder.next_row(&mut row)?;
+            samples.extend_from_slice(&row[start..end]);
+        }
+
+        let mut image = Image::from_samples(
+            width,
+            height,
+            decoder.color_type(),
+            decoder.bit_depth(),
+            samples,
+        )?;
+        image.set_palette(decoder.palette().cloned());
+        image.set_transparency(decoder.transparency().cloned());
+        Ok(image)
+    }
+}
+
+impl<'a> ScanlineDecoder<'a> {
+    pub fn new(png_file: &'a PNGFile) -> Result<ScanlineDecoder<'a>, Box<dyn Error>> {
+        let ihdr = &png_file.ihdr;
+        let color_type = ColorType::from_u8(ihdr.color_type)
+            .ok_or_else(|| PNGDecodeError::new("Unknown color type."))?;
+        if !color_type.allows_bit_depth(ihdr.bit_depth) {
+            return Err(PNGDecodeError::new("Invalid bit depth for the color type.").into());
+        }
+
+        let palette = png_file
+            .chunks
+            .iter()
+            .find(|c| &c.chunk_type == b"PLTE")
+            .map(|c| parse_palette(&c.data));
+        if palette.is_none() && color_type == ColorType::Indexed {
+            return Err(PNGDecodeError::new("Indexed image has no PLTE chunk.").into());
+        }
+        let transparency = png_file
+            .chunks
+            .iter()
+            .find(|c| &c.chunk_type == b"tRNS")
+            .and_then(|c| parse_transparency(&c.data, color_type));
+
+        let interlaced = if ihdr.interlace_method == 1 {
+            Some(png_file.decode()?)
+        } else {
+            None
+        };
+        let stride = row_bytes(ihdr.width, color_type, ihdr.bit_depth);
+
+        Ok(ScanlineDecoder {
+            width: ihdr.width,
+            height: ihdr.height,
+            color_type,
+            bit_depth: ihdr.bit_depth,
+            palette,
+            transparency,
+            idat: png_file
+                .chunks
+                .iter()
+                .filter(|c| &c.chunk_type == b"IDAT")
+                .map(|c| c.data.as_slice())
+                .collect(),
+            chunk_index: 0,
+            chunk_offset: 0,
+            state: InflateState::new_boxed(DataFormat::Zlib),
+            line: Vec::with_capacity(stride + 1),
+            previous: vec![0; stride],
+            row: 0,
+            interlaced,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn color_type(&self) -> ColorType {
+        self.color_type
+    }
+
+    pub fn bit_depth(&self) -> u8 {
+        self.bit_depth
+    }
+
+    pub fn palette(&self) -> Option<&Vec<[u8; 3]>> {
+        self.palette.as_ref()
+    }
+
+    pub fn transparency(&self) -> Option<&Transparency> {
+        self.transparency.as_ref()
+    }
+
+    // Index of the row the next call to next_row returns.
+    pub fn current_row(&self) -> u32 {
+        self.row
+    }
+
+    // Decodes the next row into out, replacing its contents. Returns false once every row has
+    // been read.
+    pub fn next_row(&mut self, out: &mut Vec<u16>) -> Result<bool, Box<dyn Error>> {
+        if !self.advance()? {
+            return Ok(false);
+        }
+        out.clear();
+        let count = self.width as usize * self.color_type.channels();
+        match &self.interlaced {
+            Some(image) => {
+                let start = (self.row - 1) as usize * count;
+                out.extend_from_slice(&image.samples()[start..start + count]);
+            }
+            None => unpack_row(&self.previous, count, self.bit_depth, out),
+        }
+        Ok(true)
+    }
+
+    // Moves past the next row without unpacking it. Returns false once every row has been read.
+    pub fn skip_row(&mut self) -> Result<bool, Box<dyn Error>> {
+        self.advance()
+    }
+
+    // Inflates and unfilters the next row into previous, which the following row needs anyway.
+    fn advance(&mut self) -> Result<bool, Box<dyn Error>> {
+        if self.row >= self.height {
+            return Ok(false);
+        }
+        if self.interlaced.is_none() {
+            let stride = self.previous.len();
+            self.inflate_line(stride + 1)?;
+            let bpp = (bits_per_pixel(self.color_type, self.bit_depth) / 8).max(1);
+            let filter_type = self.line[0];
+            unfilter_row(filter_type, &mut self.line[1..], &self.previous, bpp)?;
+            self.previous.copy_from_slice(&self.line[1..]);
+            self.line.clear();
+        }
+        self.row += 1;
+        Ok(true)
+    }
+
+    // Inflates IDAT data until line holds len bytes.
+    fn inflate_line(&mut self, len: usize) -> Result<(), Box<dyn Error>> {
+        while self.line.len() < len {
+            while self.chunk_index < self.idat.len()
+                && self.chunk_offset == self.idat[self.chunk_index].len()
+            {
+                self.chunk_index += 1;
+                self.chunk_offset = 0;
+            }
+            let input = match self.idat.get(self.chunk_index) {
+                Some(chunk) => &chunk[self.chunk_offset..],
+                None => &[][..],
+            };
+
+            let start = self.line.len();
+            self.line.resize(len, 0);
+            let result = inflate(
+                &mut self.state,
+                input,
+                &mut self.line[start..],
+                MZFlush::None,
+            );
+            self.chunk_offset += result.bytes_consumed;
+            self.line.truncate(start + result.bytes_written);
+
+            let stalled = result.bytes_consumed == 0 && result.bytes_written == 0;
+            match result.status {
+                Ok(MZStatus::StreamEnd) if self.line.len() < len => {
+                    return Err(
+                        PNGDecodeError::new("The IDAT stream is shorter than the image.").into(),
+                    );
+                }
+                Ok(_) | Err(MZError::Buf) if !(stalled && input.is_empty()) => {}
+                Ok(_) | Err(MZError::Buf) => {
+                    return Err(
+                        PNGDecodeError::new("The IDAT stream is shorter than the image.").into(),
+                    );
+                }
+                Err(_) => {
+                    return Err(
+                        PNGDecodeError::new("The IDAT stream is not valid zlib data.").into(),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Iterator for ScanlineDecoder<'a> {
+    type Item = Result<Vec<u16>, Box<dyn Error>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut row = Vec::new();
+        match self.next_row(&mut row) {
+            Ok(true) => Some(Ok(row)),
+            Ok(false) => None,
+            Err(err) => {
+                // Stop after the first error rather than returning garbage rows.
+                self.row = self.height;
+                Some(Err(err))
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+// Serialize implementations for the "serde" feature, so a parsed file can be dumped as JSON,
+// YAML or anything else serde supports. IHDRData, TimeData and TextData derive theirs. Chunks
+// are described rather than dumped: their data is left out, since for IDAT it's most of the file.
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+use super::{PNGChunk, PNGFile};
+
+// {"chunk_type": "IDAT", "length": 8192, "crc": "1A2B3C4D", "offset": 33, "index": 2}, leaving
+// out the position of chunks that weren't read from a file. The CRC is written the way the
+// info report writes it.
+impl Serialize for PNGChunk {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("PNGChunk", 5)?;
+        state.serialize_field("chunk_type", &String::from_utf8_lossy(&self.chunk_type))?;
+        state.serialize_field("length", &self.length)?;
+        state.serialize_field("crc", &format!("{:08X}", self.get_crc()))?;
+        match self.offset {
+            Some(offset) => state.serialize_field("offset", &offset)?,
+            None => state.skip_field("offset")?,
+        }
+        match self.index {
+            Some(index) => state.serialize_field("index", &index)?,
+            None => state.skip_field("index")?,
+        }
+        state.end()
+    }
+}
+
+// The header values, tIME, the text chunks and every chunk in the order write_to writes them.
+impl Serialize for PNGFile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("PNGFile", 4)?;
+        state.serialize_field("ihdr", &self.ihdr)?;
+        match self.get_last_modified() {
+            Some(time) => state.serialize_field("last_modified", &time)?,
+            None => state.skip_field("last_modified")?,
+        }
+        state.serialize_field("text", &self.get_text())?;
+        state.serialize_field("chunks", &self.to_chunks())?;
+        state.end()
+    }
+}
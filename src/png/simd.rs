@@ -0,0 +1,279 @@
+// Vectorised versions of the scanline unfilter loops. Up works on 16 bytes at a
+// time for any pixel size. Sub, Average and Paeth carry a dependency from one
+// pixel to the next, so those work a whole pixel at a time and are only used
+// for 3 and 4 byte pixels (8-bit RGB and RGBA), which is where most of the
+// decode time goes.
+//
+// Returns false if there's no vector path for this filter, pixel size and CPU,
+// in which case the caller falls back to the scalar loops.
+pub(crate) fn unfilter_row(
+    filter_type: u8,
+    current: &mut [u8],
+    previous: &[u8],
+    bpp: usize,
+) -> bool {
+    if previous.len() < current.len() {
+        return false;
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    return x86::unfilter_row(filter_type, current, previous, bpp);
+
+    #[cfg(target_arch = "aarch64")]
+    return neon::unfilter_row(filter_type, current, previous, bpp);
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        let _ = (filter_type, current, previous, bpp);
+        false
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use std::arch::x86_64::*;
+
+    pub(super) fn unfilter_row(
+        filter_type: u8,
+        current: &mut [u8],
+        previous: &[u8],
+        bpp: usize,
+    ) -> bool {
+        match (filter_type, bpp) {
+            // SSE2 is part of the x86_64 baseline so Up needs no check
+            (2, _) => unsafe { up(current, previous) },
+            (1, 3 | 4) => unsafe { sub(current, bpp) },
+            (3, 3 | 4) => unsafe { average(current, previous, bpp) },
+            // Paeth needs the SSSE3 absolute value instruction
+            (4, 3 | 4) if is_x86_feature_detected!("ssse3") => unsafe {
+                paeth(current, previous, bpp)
+            },
+            _ => return false,
+        }
+        true
+    }
+
+    // Pixels are moved through a small buffer so a 3 byte pixel at the end of
+    // the row never reads or writes past the slice.
+    #[inline(always)]
+    unsafe fn load(pixel: &[u8]) -> __m128i {
+        let mut buffer = [0u8; 4];
+        buffer[..pixel.len()].copy_from_slice(pixel);
+        _mm_cvtsi32_si128(i32::from_le_bytes(buffer))
+    }
+
+    #[inline(always)]
+    unsafe fn store(pixel: &mut [u8], value: __m128i) {
+        let buffer = _mm_cvtsi128_si32(value).to_le_bytes();
+        let len = pixel.len();
+        pixel.copy_from_slice(&buffer[..len]);
+    }
+
+    #[inline(always)]
+    unsafe fn select(mask: __m128i, a: __m128i, b: __m128i) -> __m128i {
+        _mm_or_si128(_mm_and_si128(mask, a), _mm_andnot_si128(mask, b))
+    }
+
+    unsafe fn up(current: &mut [u8], previous: &[u8]) {
+        let len = current.len();
+        let mut i = 0;
+        while i + 16 <= len {
+            let x = _mm_loadu_si128(current.as_ptr().add(i) as *const __m128i);
+            let b = _mm_loadu_si128(previous.as_ptr().add(i) as *const __m128i);
+            _mm_storeu_si128(
+                current.as_mut_ptr().add(i) as *mut __m128i,
+                _mm_add_epi8(x, b),
+            );
+            i += 16;
+        }
+        for j in i..len {
+            current[j] = current[j].wrapping_add(previous[j]);
+        }
+    }
+
+    unsafe fn sub(current: &mut [u8], bpp: usize) {
+        let mut a = _mm_setzero_si128();
+        for pixel in current.chunks_exact_mut(bpp) {
+            a = _mm_add_epi8(load(pixel), a);
+            store(pixel, a);
+        }
+    }
+
+    unsafe fn average(current: &mut [u8], previous: &[u8], bpp: usize) {
+        let ones = _mm_set1_epi8(1);
+        let mut a = _mm_setzero_si128();
+        for (pixel, upper) in current
+            .chunks_exact_mut(bpp)
+            .zip(previous.chunks_exact(bpp))
+        {
+            let b = load(upper);
+            // _mm_avg_epu8 rounds up, the filter rounds down
+            let avg = _mm_sub_epi8(_mm_avg_epu8(a, b), _mm_and_si128(_mm_xor_si128(a, b), ones));
+            a = _mm_add_epi8(load(pixel), avg);
+            store(pixel, a);
+        }
+    }
+
+    // a, b and c are kept as 16-bit lanes so a + b - 2c can't overflow.
+    #[target_feature(enable = "ssse3")]
+    unsafe fn paeth(current: &mut [u8], previous: &[u8], bpp: usize) {
+        let zero = _mm_setzero_si128();
+        let mut a = zero;
+        let mut c = zero;
+        for (pixel, upper) in current
+            .chunks_exact_mut(bpp)
+            .zip(previous.chunks_exact(bpp))
+        {
+            let b = _mm_unpacklo_epi8(load(upper), zero);
+            let pa = _mm_abs_epi16(_mm_sub_epi16(b, c));
+            let pb = _mm_abs_epi16(_mm_sub_epi16(a, c));
+            let pc = _mm_abs_epi16(_mm_sub_epi16(_mm_add_epi16(a, b), _mm_add_epi16(c, c)));
+            let smallest = _mm_min_epi16(pc, _mm_min_epi16(pa, pb));
+            // Ties go to a, then b, then c
+            let nearest = select(_mm_cmpeq_epi16(smallest, pb), b, c);
+            let nearest = select(_mm_cmpeq_epi16(smallest, pa), a, nearest);
+            let x = _mm_add_epi8(load(pixel), _mm_packus_epi16(nearest, nearest));
+            store(pixel, x);
+            a = _mm_unpacklo_epi8(x, zero);
+            c = b;
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+mod neon {
+    use std::arch::aarch64::*;
+    use std::arch::is_aarch64_feature_detected;
+
+    pub(super) fn unfilter_row(
+        filter_type: u8,
+        current: &mut [u8],
+        previous: &[u8],
+        bpp: usize,
+    ) -> bool {
+        if !is_aarch64_feature_detected!("neon") {
+            return false;
+        }
+        match (filter_type, bpp) {
+            (2, _) => unsafe { up(current, previous) },
+            (1, 3 | 4) => unsafe { sub(current, bpp) },
+            (3, 3 | 4) => unsafe { average(current, previous, bpp) },
+            (4, 3 | 4) => unsafe { paeth(current, previous, bpp) },
+            _ => return false,
+        }
+        true
+    }
+
+    #[inline(always)]
+    unsafe fn load(pixel: &[u8]) -> uint8x8_t {
+        let mut buffer = [0u8; 8];
+        buffer[..pixel.len()].copy_from_slice(pixel);
+        vld1_u8(buffer.as_ptr())
+    }
+
+    #[inline(always)]
+    unsafe fn store(pixel: &mut [u8], value: uint8x8_t) {
+        let mut buffer = [0u8; 8];
+        vst1_u8(buffer.as_mut_ptr(), value);
+        let len = pixel.len();
+        pixel.copy_from_slice(&buffer[..len]);
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn up(current: &mut [u8], previous: &[u8]) {
+        let len = current.len();
+        let mut i = 0;
+        while i + 16 <= len {
+            let x = vld1q_u8(current.as_ptr().add(i));
+            let b = vld1q_u8(previous.as_ptr().add(i));
+            vst1q_u8(current.as_mut_ptr().add(i), vaddq_u8(x, b));
+            i += 16;
+        }
+        for j in i..len {
+            current[j] = current[j].wrapping_add(previous[j]);
+        }
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn sub(current: &mut [u8], bpp: usize) {
+        let mut a = vdup_n_u8(0);
+        for pixel in current.chunks_exact_mut(bpp) {
+            a = vadd_u8(load(pixel), a);
+            store(pixel, a);
+        }
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn average(current: &mut [u8], previous: &[u8], bpp: usize) {
+        let mut a = vdup_n_u8(0);
+        for (pixel, upper) in current
+            .chunks_exact_mut(bpp)
+            .zip(previous.chunks_exact(bpp))
+        {
+            // The halving add rounds down, like the filter
+            a = vadd_u8(load(pixel), vhadd_u8(a, load(upper)));
+            store(pixel, a);
+        }
+    }
+
+    #[target_feature(enable = "neon")]
+    unsafe fn paeth(current: &mut [u8], previous: &[u8], bpp: usize) {
+        let mut a = vdup_n_u8(0);
+        let mut c = vdup_n_u8(0);
+        for (pixel, upper) in current
+            .chunks_exact_mut(bpp)
+            .zip(previous.chunks_exact(bpp))
+        {
+            let b = load(upper);
+            let pa = vmovl_u8(vabd_u8(b, c));
+            let pb = vmovl_u8(vabd_u8(a, c));
+            let pc = vabdq_u16(vaddl_u8(a, b), vaddl_u8(c, c));
+            let smallest = vminq_u16(pc, vminq_u16(pa, pb));
+            // Ties go to a, then b, then c
+            let nearest = vbsl_u8(vmovn_u16(vceqq_u16(smallest, pb)), b, c);
+            let nearest = vbsl_u8(vmovn_u16(vceqq_u16(smallest, pa)), a, nearest);
+            a = vadd_u8(load(pixel), nearest);
+            store(pixel, a);
+            c = b;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::decode::unfilter_row_scalar;
+
+    // Bytes from a small linear congruential generator, so every run sees the same rows.
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_scalar() {
+        for bpp in [1, 2, 3, 4, 6, 8] {
+            // Long enough for several 16 byte blocks plus a tail.
+            let len = bpp * 37;
+            for filter_type in 0..5 {
+                let previous = noise(len, 1);
+                let filtered = noise(len, 2);
+
+                let mut vector = filtered.clone();
+                let used = unfilter_row(filter_type, &mut vector, &previous, bpp);
+                let mut scalar = filtered.clone();
+                unfilter_row_scalar(filter_type, &mut scalar, &previous, bpp).unwrap();
+                if used {
+                    assert_eq!(vector, scalar, "filter {} at {} bytes", filter_type, bpp);
+                } else {
+                    assert_eq!(vector, filtered, "filter {} at {} bytes", filter_type, bpp);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+// Scoring how likely a PNG is to hold hidden data, for forensics and CTF work. The pixel tests
+// look for payloads in the lowest bits of the samples; the chunk test looks for payloads stored
+// as metadata, where encrypted or compressed data stands out from ordinary text by its entropy.
+// Data after IEND is found by trailing_data, and files that double as archives by
+// find_polyglots.
+use std::error::Error;
+
+use super::rules::is_known_chunk;
+use super::{PNGChunk, PNGFile};
+use crate::image::ColorType;
+
+// Below this many bytes the entropy of a chunk says little.
+const MIN_ENTROPY_LENGTH: usize = 64;
+
+// Bits per byte above which text is unusual. English text is around 4 to 4.5, and base64, the
+// usual way to put binary data in text, close to 6.
+const TEXT_ENTROPY_LIMIT: f64 = 5.5;
+
+// Bits per byte above which data looks encrypted or compressed; 8 is the most there can be.
+const DATA_ENTROPY_LIMIT: f64 = 7.0;
+
+// An ancillary chunk whose contents look like a payload.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuspiciousChunk {
+    pub chunk_type: [u8; 4],
+    pub offset: Option<u64>,
+    pub length: usize,
+    // Shannon entropy of the contents, in bits per byte.
+    pub entropy: f64,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SteganalysisReport {
+    // Image::lsb_chi_square, or None for indexed and low bit depth images.
+    pub chi_square: Option<f64>,
+    // Image::sample_pair_rate, or None for indexed and low bit depth images.
+    pub sample_pair_rate: Option<f64>,
+    pub suspicious_chunks: Vec<SuspiciousChunk>,
+    // A rough likelihood, from 0 to 1, that the file holds hidden data, from the strongest of the
+    // signs above.
+    pub score: f64,
+}
+
+impl PNGFile {
+    // Runs the steganalysis tests on the file, decoding its pixels.
+    pub fn analyze_steganography(&self) -> Result<SteganalysisReport, Box<dyn Error>> {
+        let image = self.decode()?;
+        let (chi_square, sample_pair_rate) =
+            if image.color_type() == ColorType::Indexed || image.bit_depth() < 8 {
+                (None, None)
+            } else {
+                (Some(image.lsb_chi_square()), Some(image.sample_pair_rate()))
+            };
+        let suspicious_chunks: Vec<_> = self.iter_chunks().filter_map(suspicious_chunk).collect();
+
+        // Ordinary pictures have sample pair rates of a few percent; a fifth of the samples
+        // carrying payload is about as certain as the test gets.
+        let pair_score = sample_pair_rate.map_or(0.0, |r| ((r - 0.03) / 0.17).clamp(0.0, 1.0));
+        let chunk_score = if suspicious_chunks.is_empty() {
+            0.0
+        } else {
+            0.8
+        };
+        let score = chi_square.unwrap_or(0.0).max(pair_score).max(chunk_score);
+        Ok(SteganalysisReport {
+            chi_square,
+            sample_pair_rate,
+            suspicious_chunks,
+            score,
+        })
+    }
+}
+
+// Text chunks whose text has the entropy of binary data, and unknown chunks whose data looks
+// encrypted or compressed.
+fn suspicious_chunk(chunk: &PNGChunk) -> Option<SuspiciousChunk> {
+    let (contents, limit, reason) = match &chunk.chunk_type {
+        // The text follows the keyword and its null separator.
+        b"tEXt" => {
+            let text = chunk.data.splitn(2, |b| *b == 0).nth(1).unwrap_or(&[]);
+            (
+                text,
+                TEXT_ENTROPY_LIMIT,
+                "Its text is as varied as encoded binary data.",
+            )
+        }
+        chunk_type if !is_known_chunk(chunk_type) && chunk.is_ancillary() => (
+            &chunk.data[..],
+            DATA_ENTROPY_LIMIT,
+            "It is an unknown chunk whose data looks encrypted or compressed.",
+        ),
+        _ => return None,
+    };
+    if contents.len() < MIN_ENTROPY_LENGTH {
+        return None;
+    }
+    let entropy = entropy(contents);
+    if entropy <= limit {
+        return None;
+    }
+    Some(SuspiciousChunk {
+        chunk_type: chunk.chunk_type,
+        offset: chunk.offset,
+        length: chunk.data.len(),
+        entropy,
+        reason: reason.to_string(),
+    })
+}
+
+// Shannon entropy in bits per byte.
+fn entropy(bytes: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for b in bytes {
+        counts[*b as usize] += 1;
+    }
+    let total = bytes.len() as f64;
+    counts
+        .iter()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
@@ -0,0 +1,52 @@
+use super::{PNGChunk, PNGFile};
+
+// Ancillary chunks that change how the pixels look.
+pub(crate) const COLOR_CHUNKS: [&[u8; 4]; 7] = [
+    b"tRNS", b"gAMA", b"cHRM", b"sRGB", b"iCCP", b"sBIT", b"cICP",
+];
+
+// Ancillary chunks that say something about who made the file, when or where.
+const IDENTIFYING_CHUNKS: [&[u8; 4]; 5] = [b"tEXt", b"zTXt", b"iTXt", b"tIME", b"eXIf"];
+
+const ANIMATION_CHUNKS: [&[u8; 4]; 3] = [b"acTL", b"fcTL", b"fdAT"];
+
+// How much metadata strip removes. Each level removes everything the one before it does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StripLevel {
+    // Text, timestamps and Exif data, which can hold names, places, software versions and
+    // camera details. Nothing that affects how the image looks is touched.
+    #[default]
+    Safe,
+    // Every ancillary chunk except transparency, color management and animation.
+    KeepColor,
+    // Every ancillary chunk except tRNS and the APNG chunks, which are needed to draw the
+    // pixels at all. Colors may shift slightly in viewers that honored gAMA, cHRM or iCCP.
+    AllAncillary,
+}
+
+impl StripLevel {
+    fn removes(self, chunk: &PNGChunk) -> bool {
+        let chunk_type = &chunk.chunk_type;
+        if chunk.is_critical() || ANIMATION_CHUNKS.contains(&chunk_type) {
+            return false;
+        }
+        match self {
+            StripLevel::Safe => IDENTIFYING_CHUNKS.contains(&chunk_type),
+            StripLevel::KeepColor => !COLOR_CHUNKS.contains(&chunk_type),
+            StripLevel::AllAncillary => chunk_type != b"tRNS",
+        }
+    }
+}
+
+impl PNGFile {
+    // Removes metadata, for scrubbing files before they are published. Returns how many chunks
+    // were removed.
+    pub fn strip(&mut self, level: StripLevel) -> usize {
+        let before = self.chunks.len() + self.time_chunk.is_some() as usize;
+        self.chunks.retain(|c| !level.removes(c));
+        if self.time_chunk.as_ref().is_some_and(|c| level.removes(c)) {
+            self.time_chunk = None;
+        }
+        before - self.chunks.len() - self.time_chunk.is_some() as usize
+    }
+}
@@ -0,0 +1,199 @@
+use std::error;
+use std::error::Error;
+use std::fmt;
+
+use miniz_oxide::inflate::decompress_to_vec_zlib;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use super::{PNGChunk, PNGFile};
+
+// Keywords are 1 to 79 Latin-1 characters.
+const MAX_KEYWORD_LENGTH: usize = 79;
+
+#[derive(Debug, Clone)]
+pub struct InvalidTextChunk {
+    message: String,
+}
+
+impl InvalidTextChunk {
+    fn new(message: &str) -> InvalidTextChunk {
+        InvalidTextChunk {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for InvalidTextChunk {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid text chunk: {}", self.message)
+    }
+}
+
+impl error::Error for InvalidTextChunk {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+// tEXt, zTXt and iTXt chunks: a keyword such as "Title" or "Author" and its text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct TextData {
+    keyword: String,
+    text: String,
+}
+
+impl TextData {
+    pub fn new(keyword: &str, text: &str) -> Result<TextData, InvalidTextChunk> {
+        let length = keyword.chars().count();
+        if length == 0 || length > MAX_KEYWORD_LENGTH {
+            return Err(InvalidTextChunk::new(
+                "Keywords must be 1 to 79 characters long.",
+            ));
+        }
+        if keyword.starts_with(' ') || keyword.ends_with(' ') || keyword.contains("  ") {
+            return Err(InvalidTextChunk::new(
+                "Keywords must not start or end with a space or have two spaces in a row.",
+            ));
+        }
+        if !keyword
+            .chars()
+            .all(|c| matches!(c as u32, 32..=126 | 161..=255))
+        {
+            return Err(InvalidTextChunk::new(
+                "Keywords must only hold printable Latin-1 characters.",
+            ));
+        }
+        Ok(TextData {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    // Reads any of the three text chunk types. tEXt and zTXt hold Latin-1, iTXt holds UTF-8.
+    pub fn parse(chunk: &PNGChunk) -> Result<TextData, InvalidTextChunk> {
+        let data = &chunk.data;
+        let null = data
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| InvalidTextChunk::new("The keyword is not terminated."))?;
+        let keyword = latin1(&data[..null]);
+        let rest = &data[null + 1..];
+
+        let text = match &chunk.chunk_type {
+            b"tEXt" => latin1(rest),
+            b"zTXt" => match rest.split_first() {
+                Some((0, compressed)) => latin1(&inflate(compressed)?),
+                _ => return Err(InvalidTextChunk::new("Unknown compression method.")),
+            },
+            b"iTXt" => {
+                if rest.len() < 2 {
+                    return Err(InvalidTextChunk::new("The iTXt chunk is too short."));
+                }
+                let (compressed, method) = (rest[0], rest[1]);
+                // Skip the language tag and translated keyword.
+                let mut fields = rest[2..].splitn(3, |b| *b == 0);
+                let text = match (fields.next(), fields.next(), fields.next()) {
+                    (Some(_), Some(_), Some(text)) => text,
+                    _ => return Err(InvalidTextChunk::new("The iTXt chunk is too short.")),
+                };
+                let text = match (compressed, method) {
+                    (0, _) => text.to_vec(),
+                    (1, 0) => inflate(text)?,
+                    _ => return Err(InvalidTextChunk::new("Unknown compression method.")),
+                };
+                String::from_utf8(text)
+                    .map_err(|_| InvalidTextChunk::new("iTXt text must be UTF-8."))?
+            }
+            _ => return Err(InvalidTextChunk::new("Not a text chunk.")),
+        };
+        Ok(TextData { keyword, text })
+    }
+
+    // A tEXt chunk when the text fits in Latin-1, an uncompressed iTXt chunk otherwise.
+    pub fn to_chunk(&self) -> PNGChunk {
+        let mut data: Vec<u8> = self.keyword.chars().map(|c| c as u8).collect();
+        data.push(0);
+        if self.text.chars().all(|c| (c as u32) < 256) {
+            data.extend(self.text.chars().map(|c| c as u8));
+            PNGChunk::new(*b"tEXt", data)
+        } else {
+            // Not compressed, no language tag and no translated keyword.
+            data.extend_from_slice(&[0, 0, 0, 0]);
+            data.extend_from_slice(self.text.as_bytes());
+            PNGChunk::new(*b"iTXt", data)
+        }
+    }
+
+    pub fn get_keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+}
+
+impl fmt::Display for TextData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.keyword, self.text)
+    }
+}
+
+impl PNGFile {
+    // Every readable text chunk in file order. Chunks that can't be read are skipped.
+    pub fn get_text(&self) -> Vec<TextData> {
+        self.chunks
+            .iter()
+            .filter(|c| is_text_chunk(c))
+            .filter_map(|c| TextData::parse(c).ok())
+            .collect()
+    }
+
+    // Replaces every text chunk with the same keyword, or adds one before the image data.
+    pub fn set_text(&mut self, keyword: &str, text: &str) -> Result<(), Box<dyn Error>> {
+        let chunk = TextData::new(keyword, text)?.to_chunk();
+        let at = self.remove_text(keyword).unwrap_or_else(|| {
+            self.chunks
+                .iter()
+                .position(|c| &c.chunk_type == b"IDAT")
+                .unwrap_or(0)
+        });
+        self.chunks.insert(at, chunk);
+        Ok(())
+    }
+
+    // Removes every text chunk with the given keyword, returning where the first one was.
+    pub fn remove_text(&mut self, keyword: &str) -> Option<usize> {
+        let first = self.chunks.iter().position(|c| has_keyword(c, keyword));
+        self.chunks.retain(|c| !has_keyword(c, keyword));
+        first
+    }
+}
+
+fn is_text_chunk(chunk: &PNGChunk) -> bool {
+    matches!(&chunk.chunk_type, b"tEXt" | b"zTXt" | b"iTXt")
+}
+
+fn has_keyword(chunk: &PNGChunk, keyword: &str) -> bool {
+    is_text_chunk(chunk)
+        && chunk
+            .data
+            .iter()
+            .position(|b| *b == 0)
+            .is_some_and(|null| latin1(&chunk.data[..null]) == keyword)
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+fn inflate(compressed: &[u8]) -> Result<Vec<u8>, InvalidTextChunk> {
+    decompress_to_vec_zlib(compressed)
+        .map_err(|_| InvalidTextChunk::new("The compressed text is not valid zlib data."))
+}
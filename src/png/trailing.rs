@@ -0,0 +1,133 @@
+// Data after IEND. Decoders stop reading at IEND, so anything appended to a PNG is invisible
+// when the image is viewed. Some tools append thumbnails or checksums there, but it is also
+// where archives and other hidden payloads are attached, so uploads are worth checking for it.
+//
+// The chunks are walked by their lengths only. Their CRCs and contents aren't checked, since
+// the point is to find where the PNG ends, not whether it is valid.
+use alloc::boxed::Box;
+use alloc::format;
+use core::error::Error;
+#[cfg(feature = "std")]
+use std::fs::{File, OpenOptions};
+#[cfg(feature = "std")]
+use std::io::{Read, Seek, SeekFrom};
+
+#[cfg(feature = "std")]
+use super::patch::find_chunk;
+use super::warnings::Warning;
+use super::{InvalidPNGFormat, TruncatedPNGFile, PNG_HEADER};
+
+// Offset of the first byte after the IEND chunk of a PNG held in memory.
+pub fn png_end(bytes: &[u8]) -> Result<usize, Box<dyn Error>> {
+    if !bytes.starts_with(&PNG_HEADER) {
+        return Err(InvalidPNGFormat.into());
+    }
+    // Offsets are worked out in u64 so a chunk length near 4 GB can't overflow them, even where
+    // usize is 32 bits.
+    let size = bytes.len() as u64;
+    let mut offset = PNG_HEADER.len() as u64;
+    loop {
+        let header = offset
+            .checked_add(8)
+            .filter(|end| *end <= size)
+            .map(|end| &bytes[offset as usize..end as usize])
+            .ok_or_else(|| TruncatedPNGFile::new("chunk length"))?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+        let end = u64::from(length)
+            .checked_add(12)
+            .and_then(|chunk_size| offset.checked_add(chunk_size))
+            .filter(|end| *end <= size)
+            .ok_or_else(|| TruncatedPNGFile::new("chunk data"))?;
+        if &header[4..8] == b"IEND" {
+            return Ok(end as usize);
+        }
+        offset = end;
+    }
+}
+
+// The bytes after IEND, which are empty for most files.
+pub fn trailing_data(bytes: &[u8]) -> Result<&[u8], Box<dyn Error>> {
+    Ok(&bytes[png_end(bytes)?..])
+}
+
+// Where the PNG in a file ends and how many bytes follow it, without reading the image data.
+#[cfg(feature = "std")]
+pub fn find_trailing_data(filename: &str) -> Result<(u64, u64), Box<dyn Error>> {
+    let mut file = File::open(filename)?;
+    let end = file_png_end(&mut file)?;
+    Ok((end, file.metadata()?.len() - end))
+}
+
+// Reads the bytes after IEND out of a file.
+#[cfg(feature = "std")]
+pub fn extract_trailing_data(filename: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut file = File::open(filename)?;
+    let end = file_png_end(&mut file)?;
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(end))?;
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+// Cuts a file off at the end of its IEND chunk and returns how many bytes were removed.
+#[cfg(feature = "std")]
+pub fn remove_trailing_data(filename: &str) -> Result<u64, Box<dyn Error>> {
+    let mut file = OpenOptions::new().read(true).write(true).open(filename)?;
+    let end = file_png_end(&mut file)?;
+    let removed = file.metadata()?.len() - end;
+    if removed > 0 {
+        file.set_len(end)?;
+        file.sync_all()?;
+    }
+    Ok(removed)
+}
+
+#[cfg(feature = "std")]
+fn file_png_end(file: &mut File) -> Result<u64, Box<dyn Error>> {
+    let iend = find_chunk(file, |header, _| Ok(&header.chunk_type == b"IEND"))?
+        .ok_or_else(|| TruncatedPNGFile::new("IEND chunk"))?;
+    let end = u64::from(iend.length)
+        .checked_add(12)
+        .and_then(|chunk_size| iend.offset.checked_add(chunk_size))
+        .ok_or_else(|| TruncatedPNGFile::new("IEND chunk"))?;
+    Ok(end)
+}
+
+// The warning the loaders that see the whole file give when something follows IEND.
+pub(crate) fn trailing_data_warning(offset: u64, length: u64) -> Warning {
+    let message = format!("There are {} bytes after the IEND chunk.", length);
+    Warning::new("trailing_data", message, None).at(offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    fn chunk(bytes: &mut Vec<u8>, length: u32, chunk_type: &[u8; 4]) {
+        bytes.extend_from_slice(&length.to_be_bytes());
+        bytes.extend_from_slice(chunk_type);
+        bytes.resize(bytes.len() + length as usize + 4, 0);
+    }
+
+    #[test]
+    fn data_after_iend() {
+        let mut bytes = PNG_HEADER.to_vec();
+        chunk(&mut bytes, 13, b"IHDR");
+        chunk(&mut bytes, 0, b"IEND");
+        let end = bytes.len();
+        bytes.extend_from_slice(b"hidden");
+        assert_eq!(png_end(&bytes).unwrap(), end);
+        assert_eq!(trailing_data(&bytes).unwrap(), b"hidden");
+    }
+
+    #[test]
+    fn chunk_length_past_end() {
+        let mut bytes = PNG_HEADER.to_vec();
+        chunk(&mut bytes, 13, b"IHDR");
+        bytes.extend_from_slice(&u32::MAX.to_be_bytes());
+        bytes.extend_from_slice(b"IDAT");
+        let err = png_end(&bytes).unwrap_err();
+        assert!(err.to_string().contains("chunk data"));
+    }
+}
@@ -0,0 +1,21 @@
+// Edits to the pixels of a file. They decode the image, change it and store the result, either
+// back into the file with with_image, which writes the new size into IHDR and drops the chunks
+// that no longer apply, or into a new file.
+use std::error::Error;
+
+use super::PNGFile;
+
+impl PNGFile {
+    // Crops the image to the rectangle with its top left corner at x, y. Fails if the rectangle
+    // doesn't lie inside the image.
+    pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Result<PNGFile, Box<dyn Error>> {
+        self.with_image(&self.decode()?.crop(x, y, width, height)?)
+    }
+
+    // A new file holding the image shrunk to fit within max_width by max_height, as
+    // Image::thumbnail does. Only the chunks needed to draw it are written; metadata such as text
+    // and color profiles is left out, as thumbnails usually want.
+    pub fn thumbnail(&self, max_width: u32, max_height: u32) -> Result<PNGFile, Box<dyn Error>> {
+        PNGFile::from_image(&self.decode()?.thumbnail(max_width, max_height)?)
+    }
+}
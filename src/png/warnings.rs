@@ -0,0 +1,118 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::slice;
+
+use super::rules::SpecViolation;
+
+// A problem that didn't stop parsing or decoding but that a user may want to know about.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    // Stable identifier such as "empty_idat".
+    pub code: &'static str,
+    pub message: String,
+    pub chunk_type: Option<[u8; 4]>,
+    // Where in the file the chunk the warning is about starts, when it's about one chunk.
+    pub offset: Option<u64>,
+    // True when the file breaks a rule of the spec, false when something merely looks odd or
+    // had to be worked around.
+    pub spec_violation: bool,
+}
+
+impl Warning {
+    pub(crate) fn new(code: &'static str, message: String, chunk_type: Option<[u8; 4]>) -> Warning {
+        Warning {
+            code,
+            message,
+            chunk_type,
+            offset: None,
+            spec_violation: false,
+        }
+    }
+
+    pub(crate) fn at(self, offset: u64) -> Warning {
+        Warning {
+            offset: Some(offset),
+            ..self
+        }
+    }
+}
+
+impl From<SpecViolation> for Warning {
+    fn from(violation: SpecViolation) -> Warning {
+        Warning {
+            code: violation.code,
+            message: violation.message,
+            chunk_type: violation.chunk_type,
+            offset: violation.offset,
+            spec_violation: true,
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+// Warnings collected while parsing or decoding, in the order they were found.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Warnings {
+    items: Vec<Warning>,
+}
+
+impl Warnings {
+    pub fn new() -> Warnings {
+        Warnings { items: Vec::new() }
+    }
+
+    pub fn push(&mut self, warning: Warning) {
+        self.items.push(warning);
+    }
+
+    pub fn append(&mut self, other: Warnings) {
+        self.items.extend(other.items);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Warning> {
+        self.items.iter()
+    }
+
+    // True if any warning is a spec violation that strict parsing would have rejected.
+    pub fn has_spec_violations(&self) -> bool {
+        self.items.iter().any(|w| w.spec_violation)
+    }
+}
+
+impl Extend<Warning> for Warnings {
+    fn extend<I: IntoIterator<Item = Warning>>(&mut self, iter: I) {
+        self.items.extend(iter);
+    }
+}
+
+impl IntoIterator for Warnings {
+    type Item = Warning;
+    type IntoIter = alloc::vec::IntoIter<Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Warnings {
+    type Item = &'a Warning;
+    type IntoIter = slice::Iter<'a, Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
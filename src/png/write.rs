@@ -0,0 +1,309 @@
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::process;
+
+use super::encode::{compress_image, FilterStrategy, DEFAULT_COMPRESSION_LEVEL};
+use super::rules::is_known_chunk;
+use super::strip::COLOR_CHUNKS;
+use super::{IHDRData, PNGChunk, PNGFile, TimeData};
+use crate::image::Image;
+
+// Which ancillary chunks to carry over when writing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MetadataPolicy {
+    #[default]
+    Keep,
+    // Keep transparency and color management chunks, drop text, timestamps and the rest.
+    KeepColor,
+    // Drop every ancillary chunk except tRNS, which is needed to draw the pixels.
+    Strip,
+}
+
+// Settings for writing a file. The defaults copy every chunk verbatim. Setting a compression
+// level, filter strategy or interlacing re-encodes the image data; the other options work on
+// the chunk level and leave the compressed data alone.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteOptions {
+    // zlib level from 0 to 10.
+    pub compression_level: Option<u8>,
+    pub filter: Option<FilterStrategy>,
+    pub interlace: Option<bool>,
+    // Splits the image data into IDAT chunks of at most this many bytes.
+    pub idat_chunk_size: Option<usize>,
+    pub metadata: MetadataPolicy,
+    // Sets tIME to the current time, even when the metadata policy would drop it.
+    pub refresh_time: bool,
+    // Keeps the file being replaced as filename.bak.
+    pub backup: bool,
+}
+
+impl WriteOptions {
+    pub fn new() -> WriteOptions {
+        WriteOptions::default()
+    }
+
+    pub fn compression_level(mut self, level: u8) -> WriteOptions {
+        self.compression_level = Some(level);
+        self
+    }
+
+    pub fn filter(mut self, filter: FilterStrategy) -> WriteOptions {
+        self.filter = Some(filter);
+        self
+    }
+
+    pub fn interlace(mut self, interlace: bool) -> WriteOptions {
+        self.interlace = Some(interlace);
+        self
+    }
+
+    pub fn idat_chunk_size(mut self, size: usize) -> WriteOptions {
+        self.idat_chunk_size = Some(size);
+        self
+    }
+
+    pub fn metadata(mut self, metadata: MetadataPolicy) -> WriteOptions {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn refresh_time(mut self, refresh_time: bool) -> WriteOptions {
+        self.refresh_time = refresh_time;
+        self
+    }
+
+    pub fn backup(mut self, backup: bool) -> WriteOptions {
+        self.backup = backup;
+        self
+    }
+
+    fn reencodes(&self) -> bool {
+        self.compression_level.is_some() || self.filter.is_some() || self.interlace.is_some()
+    }
+}
+
+impl PNGFile {
+    pub fn write_with_options(
+        &self,
+        filename: &str,
+        options: &WriteOptions,
+    ) -> Result<(), Box<dyn Error>> {
+        let png_file = self.rewrite(options)?;
+        write_atomically(filename, options.backup, |out| png_file.write_to(out))
+    }
+
+    pub fn write_to_with_options<W: Write>(
+        &self,
+        buffer: &mut W,
+        options: &WriteOptions,
+    ) -> Result<(), Box<dyn Error>> {
+        self.rewrite(options)?.write_to(buffer)
+    }
+
+    // Builds the file write_with_options would write, without writing it.
+    pub fn rewrite(&self, options: &WriteOptions) -> Result<PNGFile, Box<dyn Error>> {
+        let mut ihdr_chunk = self.ihdr_chunk.clone();
+        let idat = if options.reencodes() {
+            let image = self.decode()?;
+            let interlace = options.interlace.unwrap_or(self.ihdr_chunk.data[12] == 1);
+            let mut ihdr = self.ihdr_chunk.data.clone();
+            ihdr[12] = interlace as u8;
+            ihdr_chunk = PNGChunk::new(*b"IHDR", ihdr);
+
+            let compressed = compress_image(
+                &image,
+                options
+                    .compression_level
+                    .unwrap_or(DEFAULT_COMPRESSION_LEVEL),
+                options.filter.unwrap_or_default(),
+                interlace,
+            );
+            split_idat(&compressed, options.idat_chunk_size)
+        } else if options.idat_chunk_size.is_some() {
+            let compressed: Vec<u8> = self
+                .chunks
+                .iter()
+                .filter(|c| &c.chunk_type == b"IDAT")
+                .flat_map(|c| c.data.iter().copied())
+                .collect();
+            split_idat(&compressed, options.idat_chunk_size)
+        } else {
+            self.chunks
+                .iter()
+                .filter(|c| &c.chunk_type == b"IDAT")
+                .cloned()
+                .collect()
+        };
+
+        // Splitting or re-encoding the image data changes critical chunks.
+        let idat_changed = options.reencodes() || options.idat_chunk_size.is_some();
+        let keep = |chunk: &PNGChunk| {
+            if idat_changed && !copyable_after_edit(chunk) {
+                return false;
+            }
+            chunk.is_critical()
+                || match options.metadata {
+                    MetadataPolicy::Keep => true,
+                    MetadataPolicy::KeepColor => COLOR_CHUNKS.contains(&&chunk.chunk_type),
+                    MetadataPolicy::Strip => &chunk.chunk_type == b"tRNS",
+                }
+        };
+
+        // The new IDAT chunks go where the first old one was.
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        let mut idat = Some(idat);
+        for chunk in self.chunks.iter().filter(|c| keep(c)) {
+            if &chunk.chunk_type == b"IDAT" {
+                if let Some(idat) = idat.take() {
+                    chunks.extend(idat);
+                }
+            } else {
+                chunks.push(chunk.clone());
+            }
+        }
+
+        let time_chunk = if options.refresh_time {
+            Some(TimeData::now().to_chunk())
+        } else {
+            self.time_chunk.clone().filter(|c| keep(c))
+        };
+
+        Ok(PNGFile {
+            ihdr: IHDRData::parse(&ihdr_chunk)?,
+            ihdr_chunk,
+            time_chunk,
+            chunks,
+        })
+    }
+}
+
+impl PNGFile {
+    // Replaces the pixel data with a new image of the same color type and bit depth, keeping
+    // the other chunks that still apply. The dimensions may change. PLTE and tRNS are kept as
+    // they are, so an indexed image must still use the same palette. Unknown chunks are only
+    // kept when they are safe to copy, hIST is dropped because it counts pixels, and so are the
+    // APNG chunks when the size changes, because their frames no longer fit.
+    pub fn with_image(&self, image: &Image) -> Result<PNGFile, Box<dyn Error>> {
+        let ihdr = &self.ihdr;
+        if image.color_type().to_u8() != ihdr.get_color_type()
+            || image.bit_depth() != ihdr.get_bit_depth()
+        {
+            return Err("The new image must have the same color type and bit depth.".into());
+        }
+
+        let interlace = ihdr.get_interlace_method() == 1;
+        let mut ihdr_data = self.ihdr_chunk.data.clone();
+        ihdr_data[0..4].copy_from_slice(&image.width().to_be_bytes());
+        ihdr_data[4..8].copy_from_slice(&image.height().to_be_bytes());
+        let compressed = compress_image(
+            image,
+            DEFAULT_COMPRESSION_LEVEL,
+            FilterStrategy::default(),
+            interlace,
+        );
+
+        let resized = (image.width(), image.height()) != (ihdr.get_width(), ihdr.get_height());
+        let keep = |chunk: &PNGChunk| {
+            copyable_after_edit(chunk)
+                && &chunk.chunk_type != b"hIST"
+                && !(resized && matches!(&chunk.chunk_type, b"acTL" | b"fcTL" | b"fdAT"))
+        };
+
+        let mut chunks = Vec::with_capacity(self.chunks.len());
+        let mut idat = Some(split_idat(&compressed, None));
+        for chunk in self.chunks.iter().filter(|c| keep(c)) {
+            if &chunk.chunk_type == b"IDAT" {
+                if let Some(idat) = idat.take() {
+                    chunks.extend(idat);
+                }
+            } else {
+                chunks.push(chunk.clone());
+            }
+        }
+
+        let ihdr_chunk = PNGChunk::new(*b"IHDR", ihdr_data);
+        Ok(PNGFile {
+            ihdr: IHDRData::parse(&ihdr_chunk)?,
+            ihdr_chunk,
+            time_chunk: self.time_chunk.clone(),
+            chunks,
+        })
+    }
+}
+
+// Whether a chunk may be kept once critical chunks have changed: anything this library knows
+// about, and unknown chunks marked safe to copy.
+pub(crate) fn copyable_after_edit(chunk: &PNGChunk) -> bool {
+    chunk.is_safe_to_copy() || is_known_chunk(&chunk.chunk_type)
+}
+
+fn split_idat(compressed: &[u8], chunk_size: Option<usize>) -> Vec<PNGChunk> {
+    let chunk_size = chunk_size.unwrap_or(compressed.len()).max(1);
+    if compressed.is_empty() {
+        return vec![PNGChunk::new(*b"IDAT", Vec::new())];
+    }
+    compressed
+        .chunks(chunk_size)
+        .map(|data| PNGChunk::new(*b"IDAT", data.to_vec()))
+        .collect()
+}
+
+// Writes a file by writing a temporary file next to it and renaming that over the target once
+// everything is written, so a crash or error part way through leaves the old file as it was.
+// This matters most when a file is rewritten in place. The temporary file has to be in the same
+// directory for the rename to be atomic. With backup set, the file being replaced is first
+// copied to filename.bak.
+pub(crate) fn write_atomically<F>(
+    filename: &str,
+    backup: bool,
+    write: F,
+) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), Box<dyn Error>>,
+{
+    let path = Path::new(filename);
+    let name = path
+        .file_name()
+        .ok_or("The output path has no file name.")?;
+    let mut temp_name = OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(format!(".{}.tmp", process::id()));
+    let temp_path = path.with_file_name(temp_name);
+
+    let result = write_temp_file(&temp_path, path, write).and_then(|()| {
+        if backup && path.exists() {
+            let mut backup_path = path.as_os_str().to_owned();
+            backup_path.push(".bak");
+            fs::copy(path, backup_path)?;
+        }
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn write_temp_file<F>(temp_path: &Path, target: &Path, write: F) -> Result<(), Box<dyn Error>>
+where
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), Box<dyn Error>>,
+{
+    let file = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(temp_path)?;
+    // The new file takes over the permissions of the one it replaces.
+    if let Ok(metadata) = fs::metadata(target) {
+        file.set_permissions(metadata.permissions())?;
+    }
+    let mut out = BufWriter::new(file);
+    write(&mut out)?;
+    let file = out.into_inner().map_err(|err| err.into_error())?;
+    file.sync_all()?;
+    Ok(())
+}
@@ -0,0 +1,278 @@
+// Python bindings for the "python" feature. Build the extension module with
+// `cargo rustc --release --lib --features python --crate-type cdylib`, then copy the library to
+// imagetools.so (imagetools.pyd on Windows) somewhere on the Python path. It covers reading
+// files, walking their chunks, editing metadata and writing the result back, which is what bulk
+// metadata fixes need:
+//
+//     import imagetools
+//     png = imagetools.PNGFile.open("photo.png")
+//     png.set_text("Author", "Jane Doe")
+//     png.last_modified = "2024-05-01T12:00:00"
+//     png.write("photo.png", backup=True)
+use std::error::Error;
+use std::io;
+
+use pyo3::exceptions::{PyOSError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use crate::png::{IHDRData, PNGChunk, PNGFile, ParseOptions, TimeData, WriteOptions};
+
+#[pyclass(name = "PNGFile")]
+pub struct PyPNGFile {
+    png_file: PNGFile,
+}
+
+// A copy of one chunk. Changing the file doesn't change chunks already handed out.
+#[pyclass(name = "Chunk", frozen)]
+pub struct PyChunk {
+    chunk: PNGChunk,
+}
+
+#[pymethods]
+impl PyPNGFile {
+    // In strict mode the first spec violation raises ValueError; otherwise only files that
+    // can't be parsed at all do.
+    #[staticmethod]
+    #[pyo3(signature = (path, strict = false))]
+    fn open(path: &str, strict: bool) -> PyResult<PyPNGFile> {
+        let (png_file, _) =
+            PNGFile::from_file_with_options(path, &ParseOptions::from_strict(strict))
+                .map_err(py_error)?;
+        Ok(PyPNGFile { png_file })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (data, strict = false))]
+    fn from_bytes(data: &[u8], strict: bool) -> PyResult<PyPNGFile> {
+        let (png_file, _) =
+            PNGFile::from_bytes_with_options(data, &ParseOptions::from_strict(strict))
+                .map_err(py_error)?;
+        Ok(PyPNGFile { png_file })
+    }
+
+    #[getter]
+    fn width(&self) -> u32 {
+        self.png_file.width()
+    }
+
+    #[getter]
+    fn height(&self) -> u32 {
+        self.png_file.height()
+    }
+
+    #[getter]
+    fn bit_depth(&self) -> u8 {
+        self.ihdr().get_bit_depth()
+    }
+
+    #[getter]
+    fn color_type(&self) -> u8 {
+        self.ihdr().get_color_type()
+    }
+
+    #[getter]
+    fn interlaced(&self) -> bool {
+        self.ihdr().get_interlace_method() == 1
+    }
+
+    // Every chunk in the order write() writes them, starting with IHDR.
+    fn chunks(&self) -> Vec<PyChunk> {
+        self.png_file
+            .to_chunks()
+            .into_iter()
+            .map(|chunk| PyChunk { chunk })
+            .collect()
+    }
+
+    fn __iter__(&self, py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let chunks = self.chunks().into_pyobject(py)?;
+        Ok(chunks.try_iter()?.into_any().unbind())
+    }
+
+    // The tIME value as "YYYY-MM-DDTHH:MM:SS", or None. Assigning None removes it.
+    #[getter]
+    fn get_last_modified(&self) -> Option<String> {
+        self.png_file.get_last_modified().map(|t| {
+            format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                t.get_year(),
+                t.get_month(),
+                t.get_day(),
+                t.get_hour(),
+                t.get_minute(),
+                t.get_second()
+            )
+        })
+    }
+
+    #[setter]
+    fn set_last_modified(&mut self, value: Option<&str>) -> PyResult<()> {
+        match value {
+            Some(value) => {
+                let time = parse_time(value).ok_or_else(|| {
+                    PyValueError::new_err("Expected a time like 2024-05-01T12:00:00.")
+                })?;
+                self.png_file.set_last_modified(&time);
+            }
+            None => {
+                let mut chunks = self.png_file.to_chunks();
+                chunks.retain(|c| c.get_chunk_type() != b"tIME");
+                self.png_file = PNGFile::from_chunks(chunks).map_err(py_error)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Text chunks as (keyword, text) pairs in file order.
+    fn text(&self) -> Vec<(String, String)> {
+        self.png_file
+            .get_text()
+            .into_iter()
+            .map(|t| (t.get_keyword().to_string(), t.get_text().to_string()))
+            .collect()
+    }
+
+    fn set_text(&mut self, keyword: &str, text: &str) -> PyResult<()> {
+        self.png_file.set_text(keyword, text).map_err(py_error)
+    }
+
+    // Returns whether there was any text with that keyword.
+    fn remove_text(&mut self, keyword: &str) -> bool {
+        self.png_file.remove_text(keyword).is_some()
+    }
+
+    // The first chunk of the given type, or None.
+    fn get_chunk(&self, chunk_type: &str) -> Option<PyChunk> {
+        let chunk_type = parse_chunk_type(chunk_type).ok()?;
+        self.png_file.first_chunk(&chunk_type).map(|chunk| PyChunk {
+            chunk: chunk.clone(),
+        })
+    }
+
+    // Replaces the first chunk of the given type, or adds the chunk straight after IHDR.
+    fn set_chunk(&mut self, chunk_type: &str, data: &[u8]) -> PyResult<()> {
+        let chunk = PNGChunk::new(parse_chunk_type(chunk_type)?, data.to_vec());
+        let mut chunks = self.png_file.to_chunks();
+        match chunks
+            .iter()
+            .position(|c| c.get_chunk_type() == chunk.get_chunk_type())
+        {
+            Some(at) => chunks[at] = chunk,
+            None => chunks.insert(1, chunk),
+        }
+        self.png_file = PNGFile::from_chunks(chunks).map_err(py_error)?;
+        Ok(())
+    }
+
+    // Removes every chunk of the given type and returns how many there were. IHDR, IDAT and
+    // IEND can't be removed.
+    fn remove_chunks(&mut self, chunk_type: &str) -> PyResult<usize> {
+        let chunk_type = parse_chunk_type(chunk_type)?;
+        if matches!(&chunk_type, b"IHDR" | b"IDAT" | b"IEND") {
+            return Err(PyValueError::new_err("Required chunks can't be removed."));
+        }
+        let mut chunks = self.png_file.to_chunks();
+        let before = chunks.len();
+        chunks.retain(|c| c.get_chunk_type() != &chunk_type);
+        let removed = before - chunks.len();
+        self.png_file = PNGFile::from_chunks(chunks).map_err(py_error)?;
+        Ok(removed)
+    }
+
+    // Writes through a temporary file, optionally keeping the old file as path.bak.
+    #[pyo3(signature = (path, backup = false))]
+    fn write(&self, path: &str, backup: bool) -> PyResult<()> {
+        self.png_file
+            .write_with_options(path, &WriteOptions::new().backup(backup))
+            .map_err(py_error)
+    }
+
+    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let bytes = self.png_file.to_bytes().map_err(py_error)?;
+        Ok(PyBytes::new(py, &bytes))
+    }
+}
+
+impl PyPNGFile {
+    fn ihdr(&self) -> IHDRData {
+        self.png_file.get_ihdr_data().clone()
+    }
+}
+
+#[pymethods]
+impl PyChunk {
+    #[getter]
+    fn chunk_type(&self) -> String {
+        String::from_utf8_lossy(self.chunk.get_chunk_type()).into_owned()
+    }
+
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, self.chunk.get_data())
+    }
+
+    #[getter]
+    fn crc(&self) -> u32 {
+        self.chunk.get_crc()
+    }
+
+    // Where the chunk was in the file it was read from, or None for chunks added since.
+    #[getter]
+    fn offset(&self) -> Option<u64> {
+        self.chunk.get_offset()
+    }
+
+    #[getter]
+    fn index(&self) -> Option<usize> {
+        self.chunk.get_index()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Chunk({}, {} bytes)",
+            self.chunk_type(),
+            self.chunk.get_data().len()
+        )
+    }
+}
+
+#[pymodule]
+fn imagetools(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyPNGFile>()?;
+    module.add_class::<PyChunk>()?;
+    Ok(())
+}
+
+fn parse_chunk_type(chunk_type: &str) -> PyResult<[u8; 4]> {
+    match chunk_type.as_bytes() {
+        [a, b, c, d] if chunk_type.chars().all(|c| c.is_ascii_alphabetic()) => Ok([*a, *b, *c, *d]),
+        _ => Err(PyValueError::new_err(
+            "Chunk types are four ASCII letters, such as \"tEXt\".",
+        )),
+    }
+}
+
+// Reads "YYYY-MM-DDTHH:MM:SS"; a space may stand in for the T.
+fn parse_time(value: &str) -> Option<TimeData> {
+    let fields: Vec<&str> = value.split(['-', ':', 'T', ' ']).collect();
+    if fields.len() != 6 {
+        return None;
+    }
+    Some(TimeData::new(
+        fields[0].parse().ok()?,
+        fields[1].parse().ok()?,
+        fields[2].parse().ok()?,
+        fields[3].parse().ok()?,
+        fields[4].parse().ok()?,
+        fields[5].parse().ok()?,
+    ))
+}
+
+// IO errors become OSError and everything else ValueError.
+fn py_error(err: Box<dyn Error>) -> PyErr {
+    match err.downcast::<io::Error>() {
+        Ok(err) => PyOSError::new_err(err.to_string()),
+        Err(err) => PyValueError::new_err(err.to_string()),
+    }
+}
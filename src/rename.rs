@@ -0,0 +1,259 @@
+use std::collections::HashSet;
+use std::error;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{self, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::exif::ExifData;
+use crate::png::crc::crc32;
+use crate::png::{PNGFile, TimeData};
+
+pub const DEFAULT_TEMPLATE: &str = "{date}_{width}x{height}_{hash8}.png";
+
+#[derive(Debug, Clone)]
+pub struct InvalidTemplate {
+    placeholder: String,
+}
+
+impl fmt::Display for InvalidTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unknown placeholder in template: {{{}}}",
+            self.placeholder
+        )
+    }
+}
+
+impl error::Error for InvalidTemplate {
+    fn description(&self) -> &str {
+        "Unknown placeholder in template."
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+// A single planned rename. Both paths are in the same directory.
+#[derive(Debug, Clone)]
+pub struct RenamePlan {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+struct Timestamp {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+
+// Works out new names for the given files from the template without touching the filesystem.
+// Placeholders:
+//   {date} {time}           capture time as YYYY-MM-DD and HHMMSS, taken from eXIf, then tIME,
+//                           then the file's modification time
+//   {width} {height}        image dimensions
+//   {bit_depth} {color_type} IHDR values
+//   {hash8}                 CRC-32 of the file contents in hex
+//   {name}                  original file name without its extension
+// A name that is already taken, on disk or earlier in the plan, gets a _1, _2, ... suffix.
+// Path separators in substituted values become underscores, and a template that still renders to
+// something other than a plain file name, such as "..", is an error, so files never move out of
+// their directory.
+pub fn plan_renames(files: &[PathBuf], template: &str) -> Result<Vec<RenamePlan>, Box<dyn Error>> {
+    let mut claimed: HashSet<PathBuf> = HashSet::new();
+    let mut plans = Vec::new();
+
+    for file in files {
+        let name = render_template(file, template)?;
+        let target = unique_target(file, &file.with_file_name(name), &claimed);
+        claimed.insert(target.clone());
+        plans.push(RenamePlan {
+            from: file.clone(),
+            to: target,
+        });
+    }
+    Ok(plans)
+}
+
+pub fn apply_renames(plans: &[RenamePlan]) -> Result<(), Box<dyn Error>> {
+    for plan in plans.iter().filter(|p| p.from != p.to) {
+        fs::rename(&plan.from, &plan.to)?;
+    }
+    Ok(())
+}
+
+fn render_template(file: &Path, template: &str) -> Result<String, Box<dyn Error>> {
+    let filename = file.to_str().ok_or("File name is not valid UTF-8.")?;
+    let png_file = PNGFile::from_file(filename)?;
+    let ihdr = png_file.get_ihdr_data();
+    let timestamp = timestamp(&png_file, file)?;
+    let hash = crc32(&fs::read(file)?);
+
+    let mut rendered = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        rendered.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or("Unclosed placeholder in template.")?
+            + start;
+        let placeholder = &rest[start + 1..end];
+        let value = match placeholder {
+            "date" => format!(
+                "{:04}-{:02}-{:02}",
+                timestamp.year, timestamp.month, timestamp.day
+            ),
+            "time" => format!(
+                "{:02}{:02}{:02}",
+                timestamp.hour, timestamp.minute, timestamp.second
+            ),
+            "width" => ihdr.get_width().to_string(),
+            "height" => ihdr.get_height().to_string(),
+            "bit_depth" => ihdr.get_bit_depth().to_string(),
+            "color_type" => ihdr.get_color_type().to_string(),
+            "hash8" => format!("{:08x}", hash),
+            "name" => file
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            _ => {
+                return Err(InvalidTemplate {
+                    placeholder: placeholder.to_string(),
+                }
+                .into())
+            }
+        };
+        rendered.push_str(&file_name_part(&value));
+        rest = &rest[end + 1..];
+    }
+    rendered.push_str(rest);
+    check_file_name(&rendered)?;
+    Ok(rendered)
+}
+
+// A substituted value with anything that would split it into path components replaced.
+fn file_name_part(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if path::is_separator(c) || c == '\0' {
+                '_'
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+fn check_file_name(name: &str) -> Result<(), Box<dyn Error>> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(path::is_separator) {
+        return Err(format!("The template gives \"{}\", which is not a file name.", name).into());
+    }
+    Ok(())
+}
+
+fn timestamp(png_file: &PNGFile, file: &Path) -> Result<Timestamp, Box<dyn Error>> {
+    if let Some(chunk) = png_file.first_chunk(b"eXIf") {
+        if let Ok(exif) = ExifData::parse(chunk.get_data()) {
+            if let Some(timestamp) = exif.date_time().and_then(parse_exif_date_time) {
+                return Ok(timestamp);
+            }
+        }
+    }
+
+    if let Some(time) = png_file.get_last_modified() {
+        return Ok(Timestamp {
+            year: time.get_year(),
+            month: time.get_month(),
+            day: time.get_day(),
+            hour: time.get_hour(),
+            minute: time.get_minute(),
+            second: time.get_second(),
+        });
+    }
+
+    let modified = fs::metadata(file)?.modified()?;
+    let seconds = modified.duration_since(UNIX_EPOCH)?.as_secs();
+    Ok(timestamp_from_unix(seconds))
+}
+
+// EXIF dates look like "2021:07:04 18:30:00".
+fn parse_exif_date_time(value: &str) -> Option<Timestamp> {
+    let fields: Vec<u16> = value
+        .split(&[':', ' '][..])
+        .map(|f| f.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    if fields.len() != 6 || fields[0] == 0 {
+        return None;
+    }
+    Some(Timestamp {
+        year: fields[0],
+        month: fields[1] as u8,
+        day: fields[2] as u8,
+        hour: fields[3] as u8,
+        minute: fields[4] as u8,
+        second: fields[5] as u8,
+    })
+}
+
+// Converts seconds since the Unix epoch to a UTC calendar date.
+fn timestamp_from_unix(seconds: u64) -> Timestamp {
+    let time = TimeData::from_unix_seconds(seconds);
+    Timestamp {
+        year: time.get_year(),
+        month: time.get_month(),
+        day: time.get_day(),
+        hour: time.get_hour(),
+        minute: time.get_minute(),
+        second: time.get_second(),
+    }
+}
+
+fn unique_target(source: &Path, target: &Path, claimed: &HashSet<PathBuf>) -> PathBuf {
+    let taken = |p: &Path| claimed.contains(p) || (p.exists() && p != source);
+    if !taken(target) {
+        return target.to_path_buf();
+    }
+
+    let stem = target
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let extension = target
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+    let mut n = 1;
+    loop {
+        let candidate = target.with_file_name(format!("{}_{}{}", stem, n, extension));
+        if !taken(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separators_in_values() {
+        assert_eq!(file_name_part("../../etc/passwd"), ".._.._etc_passwd");
+        assert_eq!(file_name_part("2021-07-04"), "2021-07-04");
+    }
+
+    #[test]
+    fn names_outside_directory() {
+        for name in ["", ".", "..", "../a.png", "a/b.png"] {
+            assert!(check_file_name(name).is_err(), "{:?}", name);
+        }
+        assert!(check_file_name("..a.png").is_ok());
+    }
+}
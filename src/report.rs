@@ -0,0 +1,744 @@
+// Machine-readable reports produced by the info, validate, conformance, analyze, diff, audit and
+// batch tools.
+//
+// These types are the schema. With the "serde" feature they serialize to JSON with the field
+// names below. Stability rules:
+//   * Within a schema version, fields are only ever added. Existing fields keep their name,
+//     type and meaning, so consumers should ignore fields they don't recognize.
+//   * Optional fields are omitted rather than written as null.
+//   * Anything that would break a consumer bumps REPORT_SCHEMA_VERSION, which every report
+//     carries in its schema_version field. That only happens in a major release.
+use std::error::Error;
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::image::Image;
+use crate::png::{self, DecodeOptions, PNGFile, ParseMode, ParseOptions};
+
+pub const REPORT_SCHEMA_VERSION: u32 = 1;
+
+// One chunk in file order.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChunkSummary {
+    // Four character chunk type, e.g. "IDAT".
+    pub chunk_type: String,
+    // Length of the data field in bytes.
+    pub length: u32,
+    // Stored CRC as 8 uppercase hex digits.
+    pub crc: String,
+    // Byte offset of the chunk from the start of the file.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub offset: Option<u64>,
+}
+
+// Output of `info`: header values and the chunk layout of one file.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct InfoReport {
+    pub schema_version: u32,
+    pub file: String,
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    // PNG color type number (0, 2, 3, 4 or 6).
+    pub color_type: u8,
+    pub interlaced: bool,
+    // tIME value formatted as "YYYY-MM-DDTHH:MM:SS".
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub last_modified: Option<String>,
+    pub chunks: Vec<ChunkSummary>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Severity {
+    Error,
+    Warning,
+    Info,
+}
+
+// A single finding in a validate report.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReportIssue {
+    pub severity: Severity,
+    // Stable identifier such as "decode_failed", meant for matching in scripts.
+    pub code: String,
+    // Human readable description. The wording may change between releases.
+    pub message: String,
+    // Chunk type the issue refers to, if any.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub chunk_type: Option<String>,
+    // Byte offset of that chunk from the start of the file, if the issue is about one chunk.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub offset: Option<u64>,
+}
+
+// Output of `validate`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ValidateReport {
+    pub schema_version: u32,
+    pub file: String,
+    // True when there are no issues of severity "error".
+    pub valid: bool,
+    pub issues: Vec<ReportIssue>,
+}
+
+// A QA verdict on one file, covering its header, chunk CRCs, chunk ordering, whether the image
+// data decodes and whether anything follows IEND. Errors are spec violations and image data
+// that can't be decoded, warnings are things that are allowed but suspicious, and info records
+// facts about the file worth seeing next to them.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConformanceReport {
+    pub schema_version: u32,
+    pub file: String,
+    // True when there are no errors.
+    pub conformant: bool,
+    pub errors: Vec<ReportIssue>,
+    pub warnings: Vec<ReportIssue>,
+    pub info: Vec<ReportIssue>,
+}
+
+// Output of `analyze`: statistics computed from the decoded pixels.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AnalyzeReport {
+    pub schema_version: u32,
+    pub file: String,
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    // Whether the color type has an alpha channel or the file has a tRNS chunk.
+    pub has_alpha: bool,
+    // Number of distinct pixel values.
+    pub distinct_colors: u64,
+    // Mean Rec. 601 luminance on a 0 - 255 scale.
+    pub mean_luma: f64,
+    // Otsu threshold of the luminance histogram, 0 - 255.
+    pub otsu_level: u8,
+}
+
+// Output of `diff`: how two decoded images differ, compared as 8-bit RGBA.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DiffReport {
+    pub schema_version: u32,
+    pub left: String,
+    pub right: String,
+    pub dimensions_match: bool,
+    // The remaining fields are only present when the dimensions match.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub changed_pixels: Option<u64>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub total_pixels: Option<u64>,
+    // Largest difference in any channel, 0 - 255.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub max_difference: Option<u8>,
+    // Mean absolute difference over all channels, 0 - 255.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub mean_difference: Option<f64>,
+}
+
+// Output of `audit`: a file's display metadata and how it compares with the policy.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AuditReport {
+    pub schema_version: u32,
+    pub file: String,
+    // EXIF orientation, 1 when the file has none.
+    pub orientation: u16,
+    // Horizontal and vertical resolution from pHYs in dots per inch.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub dpi: Option<(f64, f64)>,
+    // "sRGB", "ICC profile \"NAME\"", "gAMA/cHRM" or "unspecified".
+    pub color_space: String,
+    // True when there are no issues of severity "error". Issues fixed by --fix are "info".
+    pub compliant: bool,
+    pub issues: Vec<ReportIssue>,
+}
+
+// The report a batch run produced for one file.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "kind", rename_all = "snake_case"))]
+pub enum BatchItem {
+    Info(InfoReport),
+    Validate(ValidateReport),
+    Conformance(ConformanceReport),
+    Analyze(AnalyzeReport),
+    Diff(DiffReport),
+    Audit(AuditReport),
+    // The file could not be processed at all.
+    Failed { file: String, error: String },
+}
+
+// Output of a batch run over many files.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BatchReport {
+    pub schema_version: u32,
+    // The command that was run for every file, e.g. "info".
+    pub command: String,
+    pub items: Vec<BatchItem>,
+}
+
+impl InfoReport {
+    pub fn from_png(file: &str, png_file: &PNGFile) -> InfoReport {
+        let ihdr = png_file.get_ihdr_data();
+        let chunks = png_file
+            .iter_chunks()
+            .map(|chunk| ChunkSummary {
+                chunk_type: String::from_utf8_lossy(chunk.get_chunk_type()).into_owned(),
+                length: chunk.get_data().len() as u32,
+                crc: format!("{:08X}", chunk.get_crc()),
+                offset: chunk.get_offset(),
+            })
+            .collect();
+        let last_modified = png_file.get_last_modified().map(|t| {
+            format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                t.get_year(),
+                t.get_month(),
+                t.get_day(),
+                t.get_hour(),
+                t.get_minute(),
+                t.get_second()
+            )
+        });
+
+        InfoReport {
+            schema_version: REPORT_SCHEMA_VERSION,
+            file: file.to_string(),
+            width: ihdr.get_width(),
+            height: ihdr.get_height(),
+            bit_depth: ihdr.get_bit_depth(),
+            color_type: ihdr.get_color_type(),
+            interlaced: ihdr.get_interlace_method() == 1,
+            last_modified,
+            chunks,
+        }
+    }
+}
+
+impl ValidateReport {
+    pub fn new(file: &str, issues: Vec<ReportIssue>) -> ValidateReport {
+        ValidateReport {
+            schema_version: REPORT_SCHEMA_VERSION,
+            file: file.to_string(),
+            valid: !issues.iter().any(|i| i.severity == Severity::Error),
+            issues,
+        }
+    }
+
+    // Checks that the pixel data decodes.
+    pub fn from_png(file: &str, png_file: &PNGFile) -> ValidateReport {
+        let mut issues = Vec::new();
+        if let Err(err) = png_file.decode() {
+            issues.push(ReportIssue {
+                severity: Severity::Error,
+                code: "decode_failed".to_string(),
+                message: err.to_string(),
+                chunk_type: Some("IDAT".to_string()),
+                offset: first_idat_offset(png_file),
+            });
+        }
+        ValidateReport::new(file, issues)
+    }
+
+    // Parses the file permissively so that every spec violation is found, then checks that the
+    // pixel data decodes. Violations count as errors in strict mode and warnings otherwise.
+    // Suspicious values and problems decoding worked around are always warnings.
+    pub fn from_file(file: &str, mode: ParseMode) -> Result<ValidateReport, Box<dyn Error>> {
+        let (png_file, parse_report) = PNGFile::from_file_with_mode(file, ParseMode::Permissive)?;
+        let mut warnings = parse_report.warnings;
+        let mut issues = Vec::new();
+        match png_file.decode_with_warnings(&DecodeOptions::default()) {
+            Ok((_, decode_warnings)) => warnings.append(decode_warnings),
+            Err(err) => issues.push(ReportIssue {
+                severity: Severity::Error,
+                code: "decode_failed".to_string(),
+                message: err.to_string(),
+                chunk_type: Some("IDAT".to_string()),
+                offset: first_idat_offset(&png_file),
+            }),
+        }
+
+        let warning_issues = warnings.into_iter().map(|w| ReportIssue {
+            severity: if w.spec_violation && mode == ParseMode::Strict {
+                Severity::Error
+            } else {
+                Severity::Warning
+            },
+            code: w.code.to_string(),
+            message: w.message,
+            chunk_type: w
+                .chunk_type
+                .map(|t| String::from_utf8_lossy(&t).into_owned()),
+            offset: w.offset,
+        });
+        issues.splice(0..0, warning_issues);
+        Ok(ValidateReport::new(file, issues))
+    }
+}
+
+impl ConformanceReport {
+    pub fn from_file(file: &str) -> Result<ConformanceReport, Box<dyn Error>> {
+        Ok(ConformanceReport::from_bytes(file, &std::fs::read(file)?))
+    }
+
+    // Checks a file held in memory. A file that can't be parsed at all, because it isn't a PNG
+    // or its header is invalid, gets a report with that one error.
+    pub fn from_bytes(file: &str, bytes: &[u8]) -> ConformanceReport {
+        let mut issues = Vec::new();
+        let options = ParseOptions {
+            mode: ParseMode::Permissive,
+            ..ParseOptions::default()
+        };
+        let (png_file, parse_report) = match PNGFile::from_bytes_with_options(bytes, &options) {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                issues.push(issue(
+                    Severity::Error,
+                    "invalid_header",
+                    err.to_string(),
+                    None,
+                ));
+                return ConformanceReport::new(file, issues);
+            }
+        };
+
+        let ihdr = png_file.get_ihdr_data();
+        issues.push(issue(
+            Severity::Info,
+            "header",
+            format!(
+                "{}x{} pixels, color type {} at {} bits per sample, {}.",
+                ihdr.get_width(),
+                ihdr.get_height(),
+                ihdr.get_color_type(),
+                ihdr.get_bit_depth(),
+                if ihdr.get_interlace_method() == 1 {
+                    "interlaced"
+                } else {
+                    "not interlaced"
+                }
+            ),
+            Some((*b"IHDR", png_file.get_ihdr_chunk().get_offset())),
+        ));
+        let idat: Vec<_> = png_file.chunks_of_type(b"IDAT").collect();
+        issues.push(issue(
+            Severity::Info,
+            "chunks",
+            format!(
+                "{} chunks, with {} bytes of image data in {} IDAT chunks.",
+                png_file.iter_chunks().count(),
+                idat.iter().map(|c| c.get_data().len()).sum::<usize>(),
+                idat.len()
+            ),
+            None,
+        ));
+        if let Ok(Some(animation_control)) = png_file.get_animation_control() {
+            issues.push(issue(
+                Severity::Info,
+                "animated",
+                format!(
+                    "An animation with {} frames.",
+                    animation_control.get_num_frames()
+                ),
+                Some((
+                    *b"acTL",
+                    png_file.first_chunk(b"acTL").and_then(|c| c.get_offset()),
+                )),
+            ));
+        }
+
+        // Spec violations, bad CRCs and misplaced chunks among them, are errors here whatever
+        // mode the file would be read in. Data after IEND is among the warnings.
+        let mut warnings = parse_report.warnings;
+        match png_file.decode_with_warnings(&DecodeOptions::default()) {
+            Ok((_, decode_warnings)) => warnings.append(decode_warnings),
+            Err(err) => issues.push(issue(
+                Severity::Error,
+                "decode_failed",
+                err.to_string(),
+                Some((*b"IDAT", first_idat_offset(&png_file))),
+            )),
+        }
+        issues.extend(warnings.into_iter().map(|w| {
+            ReportIssue {
+                severity: if w.spec_violation {
+                    Severity::Error
+                } else {
+                    Severity::Warning
+                },
+                code: w.code.to_string(),
+                message: w.message,
+                chunk_type: w
+                    .chunk_type
+                    .map(|t| String::from_utf8_lossy(&t).into_owned()),
+                offset: w.offset,
+            }
+        }));
+
+        // Files that other programs also read as something else are how uploads smuggle
+        // archives and scripts past image checks.
+        issues.extend(png::find_polyglots(bytes).into_iter().map(|finding| {
+            issue(
+                Severity::Warning,
+                "polyglot",
+                finding.message,
+                finding.chunk_type.map(|t| (t, None)),
+            )
+        }));
+
+        ConformanceReport::new(file, issues)
+    }
+
+    fn new(file: &str, issues: Vec<ReportIssue>) -> ConformanceReport {
+        let (errors, rest): (Vec<_>, Vec<_>) = issues
+            .into_iter()
+            .partition(|i| i.severity == Severity::Error);
+        let (warnings, info) = rest
+            .into_iter()
+            .partition(|i| i.severity == Severity::Warning);
+        ConformanceReport {
+            schema_version: REPORT_SCHEMA_VERSION,
+            file: file.to_string(),
+            conformant: errors.is_empty(),
+            errors,
+            warnings,
+            info,
+        }
+    }
+}
+
+fn issue(
+    severity: Severity,
+    code: &str,
+    message: String,
+    chunk: Option<([u8; 4], Option<u64>)>,
+) -> ReportIssue {
+    ReportIssue {
+        severity,
+        code: code.to_string(),
+        message,
+        chunk_type: chunk.map(|(t, _)| String::from_utf8_lossy(&t).into_owned()),
+        offset: chunk.and_then(|(_, offset)| offset),
+    }
+}
+
+// Where the image data starts, for issues about the IDAT stream as a whole.
+fn first_idat_offset(png_file: &PNGFile) -> Option<u64> {
+    png_file.first_chunk(b"IDAT").and_then(|c| c.get_offset())
+}
+
+impl AuditReport {
+    pub fn new(
+        file: &str,
+        orientation: u16,
+        dpi: Option<(f64, f64)>,
+        color_space: &str,
+        issues: Vec<ReportIssue>,
+    ) -> AuditReport {
+        AuditReport {
+            schema_version: REPORT_SCHEMA_VERSION,
+            file: file.to_string(),
+            orientation,
+            dpi,
+            color_space: color_space.to_string(),
+            compliant: !issues.iter().any(|i| i.severity == Severity::Error),
+            issues,
+        }
+    }
+}
+
+impl AnalyzeReport {
+    pub fn from_image(file: &str, image: &Image) -> AnalyzeReport {
+        let luma = image.luma_plane();
+        let mean_luma = luma.iter().map(|l| *l as f64).sum::<f64>() / luma.len() as f64;
+
+        AnalyzeReport {
+            schema_version: REPORT_SCHEMA_VERSION,
+            file: file.to_string(),
+            width: image.width(),
+            height: image.height(),
+            bit_depth: image.bit_depth(),
+            color_type: image.color_type().to_u8(),
+            has_alpha: image.color_type().has_alpha_channel() || image.transparency().is_some(),
+            distinct_colors: image.count_colors(usize::MAX - 1) as u64,
+            mean_luma,
+            otsu_level: image.otsu_level(),
+        }
+    }
+}
+
+impl DiffReport {
+    pub fn compare(left_name: &str, left: &Image, right_name: &str, right: &Image) -> DiffReport {
+        let mut report = DiffReport {
+            schema_version: REPORT_SCHEMA_VERSION,
+            left: left_name.to_string(),
+            right: right_name.to_string(),
+            dimensions_match: left.width() == right.width() && left.height() == right.height(),
+            changed_pixels: None,
+            total_pixels: None,
+            max_difference: None,
+            mean_difference: None,
+        };
+        if !report.dimensions_match {
+            return report;
+        }
+
+        let left_pixels = left.rgba8_pixels();
+        let right_pixels = right.rgba8_pixels();
+        let mut changed = 0u64;
+        let mut max = 0u8;
+        let mut sum = 0u64;
+        for (a, b) in left_pixels.iter().zip(right_pixels.iter()) {
+            if a != b {
+                changed += 1;
+            }
+            for c in 0..4 {
+                let d = (a[c] as i16 - b[c] as i16).unsigned_abs() as u8;
+                max = max.max(d);
+                sum += d as u64;
+            }
+        }
+        report.changed_pixels = Some(changed);
+        report.total_pixels = Some(left_pixels.len() as u64);
+        report.max_difference = Some(max);
+        report.mean_difference = Some(sum as f64 / (left_pixels.len() * 4) as f64);
+        report
+    }
+}
+
+impl BatchReport {
+    pub fn new(command: &str, items: Vec<BatchItem>) -> BatchReport {
+        BatchReport {
+            schema_version: REPORT_SCHEMA_VERSION,
+            command: command.to_string(),
+            items,
+        }
+    }
+}
+
+impl fmt::Display for InfoReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "File: {}", self.file)?;
+        writeln!(f, "Width (pixels): {}", self.width)?;
+        writeln!(f, "Height (pixels): {}", self.height)?;
+        writeln!(f, "Bit depth: {}", self.bit_depth)?;
+        writeln!(f, "Color Type: {}", self.color_type)?;
+        writeln!(f, "Interlaced: {}", self.interlaced)?;
+        if let Some(last_modified) = &self.last_modified {
+            writeln!(f, "Last modified: {}", last_modified)?;
+        }
+        write!(f, "Chunks:")?;
+        for chunk in &self.chunks {
+            write!(f, "\n\t{}", chunk.chunk_type)?;
+            if let Some(offset) = chunk.offset {
+                write!(f, " at {:#X}", offset)?;
+            }
+            write!(f, " ({} bytes, CRC {})", chunk.length, chunk.crc)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ValidateReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}",
+            self.file,
+            if self.valid { "valid" } else { "invalid" }
+        )?;
+        for issue in &self.issues {
+            write!(
+                f,
+                "\n\t{:?} [{}] {}",
+                issue.severity, issue.code, issue.message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ConformanceReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}",
+            self.file,
+            if self.conformant {
+                "conformant"
+            } else {
+                "not conformant"
+            }
+        )?;
+        for issue in self.errors.iter().chain(&self.warnings).chain(&self.info) {
+            write!(
+                f,
+                "\n\t{:?} [{}] {}",
+                issue.severity, issue.code, issue.message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AnalyzeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "File: {}\n\
+             Dimensions: {}x{}\n\
+             Bit depth: {}\n\
+             Color Type: {}\n\
+             Has alpha: {}\n\
+             Distinct colors: {}\n\
+             Mean luminance: {:.2}\n\
+             Otsu level: {}",
+            self.file,
+            self.width,
+            self.height,
+            self.bit_depth,
+            self.color_type,
+            self.has_alpha,
+            self.distinct_colors,
+            self.mean_luma,
+            self.otsu_level
+        )
+    }
+}
+
+impl fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} vs {}: ", self.left, self.right)?;
+        match (self.changed_pixels, self.total_pixels) {
+            (Some(changed), Some(total)) => write!(
+                f,
+                "{} of {} pixels differ (max difference {}, mean {:.3})",
+                changed,
+                total,
+                self.max_difference.unwrap_or(0),
+                self.mean_difference.unwrap_or(0.0)
+            ),
+            _ => write!(f, "dimensions differ"),
+        }
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}",
+            self.file,
+            if self.compliant {
+                "compliant"
+            } else {
+                "not compliant"
+            }
+        )?;
+        for issue in &self.issues {
+            write!(
+                f,
+                "\n\t{:?} [{}] {}",
+                issue.severity, issue.code, issue.message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BatchItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BatchItem::Info(report) => write!(f, "{}", report),
+            BatchItem::Validate(report) => write!(f, "{}", report),
+            BatchItem::Conformance(report) => write!(f, "{}", report),
+            BatchItem::Analyze(report) => write!(f, "{}", report),
+            BatchItem::Diff(report) => write!(f, "{}", report),
+            BatchItem::Audit(report) => write!(f, "{}", report),
+            BatchItem::Failed { file, error } => write!(f, "{}: {}", file, error),
+        }
+    }
+}
+
+// Pretty-printed JSON for any report type.
+#[cfg(feature = "serde")]
+pub fn to_json<T: Serialize>(report: &T) -> Result<String, Box<dyn Error>> {
+    Ok(serde_json::to_string_pretty(report)?)
+}
+
+// Runs a per-file report over several files, recording failures instead of stopping.
+pub fn batch<F>(command: &str, files: &[String], mut report: F) -> BatchReport
+where
+    F: FnMut(&str) -> Result<BatchItem, Box<dyn Error>>,
+{
+    let items = files
+        .iter()
+        .map(|file| {
+            report(file).unwrap_or_else(|err| BatchItem::Failed {
+                file: file.clone(),
+                error: err.to_string(),
+            })
+        })
+        .collect();
+    BatchReport::new(command, items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{ColorType, Image};
+    use crate::png::TimeData;
+
+    #[test]
+    fn info_lists_time_chunk() {
+        let image = Image::from_samples(1, 1, ColorType::Grayscale, 8, vec![0]).unwrap();
+        let mut png_file = PNGFile::from_image(&image).unwrap();
+        png_file.set_last_modified(&TimeData::new(2024, 5, 17, 8, 30, 0));
+        let report = InfoReport::from_png("a.png", &png_file);
+        let types: Vec<&str> = report
+            .chunks
+            .iter()
+            .map(|c| c.chunk_type.as_str())
+            .collect();
+        assert_eq!(types, ["IHDR", "tIME", "IDAT", "IEND"]);
+    }
+}
@@ -0,0 +1,206 @@
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tiny_http::{Header, Response, Server};
+
+use crate::png::PNGFile;
+
+const THUMBNAIL_SIZE: u32 = 160;
+
+// Serves a gallery of the PNG files in dir until the process is stopped. Pages:
+//   /              thumbnails of every PNG in the directory
+//   /info/NAME     IHDR values and a chunk report for one file
+//   /thumb/NAME    a thumbnail generated on request
+//   /image/NAME    the original file
+pub fn serve(dir: &Path, address: &str) -> Result<(), Box<dyn Error>> {
+    let server = Server::http(address).map_err(|err| err.to_string())?;
+    for request in server.incoming_requests() {
+        let url = request.url().to_string();
+        let response = match handle(dir, &url) {
+            Ok(Some((content_type, body))) => Response::from_data(body)
+                .with_header(Header::from_bytes("Content-Type", content_type).unwrap()),
+            Ok(None) => Response::from_string("Not found").with_status_code(404),
+            Err(err) => Response::from_string(format!("Error: {}", err)).with_status_code(500),
+        };
+        // A client that hangs up early shouldn't take the server down with it.
+        let _ = request.respond(response);
+    }
+    Ok(())
+}
+
+type Page = Option<(&'static str, Vec<u8>)>;
+
+fn handle(dir: &Path, url: &str) -> Result<Page, Box<dyn Error>> {
+    let path = url.split('?').next().unwrap_or("");
+    if path == "/" {
+        return Ok(Some((
+            "text/html; charset=utf-8",
+            gallery(dir)?.into_bytes(),
+        )));
+    }
+
+    let (route, name) = match path.strip_prefix('/').unwrap_or(path).split_once('/') {
+        Some(parts) => parts,
+        None => return Ok(None),
+    };
+    let name = percent_decode(name);
+    let name = name.as_str();
+    let file = match resolve(dir, name) {
+        Some(file) => file,
+        None => return Ok(None),
+    };
+    match route {
+        "image" => Ok(Some(("image/png", fs::read(file)?))),
+        "thumb" => Ok(Some(("image/png", thumbnail(&file)?))),
+        "info" => Ok(Some((
+            "text/html; charset=utf-8",
+            info(&file, name)?.into_bytes(),
+        ))),
+        _ => Ok(None),
+    }
+}
+
+// Only plain file names inside the served directory are allowed.
+fn resolve(dir: &Path, name: &str) -> Option<PathBuf> {
+    if name.is_empty() || name.contains('/') || name.contains('\\') || name.starts_with('.') {
+        return None;
+    }
+    let file = dir.join(name);
+    if file.is_file() {
+        Some(file)
+    } else {
+        None
+    }
+}
+
+fn png_names(dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut names: Vec<String> = fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.file_name().to_string_lossy().into_owned())
+        .filter(|n| n.to_lowercase().ends_with(".png"))
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+fn gallery(dir: &Path) -> Result<String, Box<dyn Error>> {
+    let mut html = String::from(
+        "<!DOCTYPE html><html><head><title>imagetools</title><style>\
+         body{font-family:sans-serif}figure{display:inline-block;margin:8px;text-align:center}\
+         img{max-width:160px;max-height:160px;background:#ddd}</style></head><body>",
+    );
+    html.push_str(&format!("<h1>{}</h1>", escape(&dir.display().to_string())));
+    for name in png_names(dir)? {
+        html.push_str(&format!(
+            "<figure><a href=\"/info/{0}\"><img src=\"/thumb/{0}\" alt=\"{1}\"></a>\
+             <figcaption>{1}</figcaption></figure>",
+            percent_encode(&name),
+            escape(&name)
+        ));
+    }
+    html.push_str("</body></html>");
+    Ok(html)
+}
+
+fn info(file: &Path, name: &str) -> Result<String, Box<dyn Error>> {
+    let png_file = PNGFile::from_file(&file.to_string_lossy())?;
+    let ihdr = png_file.get_ihdr_data();
+    let mut html = format!(
+        "<!DOCTYPE html><html><head><title>{0}</title><style>\
+         body{{font-family:sans-serif}}td,th{{padding:2px 12px;text-align:left}}</style></head>\
+         <body><p><a href=\"/\">Back</a></p><h1>{0}</h1>\
+         <p><a href=\"/image/{1}\"><img src=\"/thumb/{1}\" alt=\"{0}\"></a></p>\
+         <h2>IHDR</h2><pre>{2}</pre>",
+        escape(name),
+        percent_encode(name),
+        escape(&ihdr.to_string())
+    );
+    if let Some(last_modified) = png_file.get_last_modified() {
+        html.push_str(&format!("<p>Last modified: {}</p>", last_modified));
+    }
+
+    html.push_str("<h2>Chunks</h2><table><tr><th>Type</th><th>Length</th><th>CRC</th></tr>");
+    for chunk in png_file.iter_chunks() {
+        html.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td>{:08X}</td></tr>",
+            escape(&String::from_utf8_lossy(chunk.get_chunk_type())),
+            chunk.get_data().len(),
+            chunk.get_crc()
+        ));
+    }
+    html.push_str("</table></body></html>");
+    Ok(html)
+}
+
+fn thumbnail(file: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    let image = PNGFile::from_file(&file.to_string_lossy())?.decode()?;
+    let thumb = image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)?;
+    PNGFile::from_image(&thumb)?.to_bytes()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok());
+        match (bytes[i], hex.and_then(|h| u8::from_str_radix(h, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// Encodes everything but the characters RFC 3986 leaves unreserved, so a file name can be put
+// in a URL path whatever it contains.
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_path() {
+        let dir = Path::new(".");
+        assert!(handle(dir, "?x").unwrap().is_none());
+        assert!(handle(dir, "").unwrap().is_none());
+    }
+
+    #[test]
+    fn names_in_links() {
+        let name = "a b\"<'>&?#/%.png";
+        let encoded = percent_encode(name);
+        assert_eq!(encoded, "a%20b%22%3C%27%3E%26%3F%23%2F%25.png");
+        assert_eq!(percent_decode(&encoded), name);
+        assert_eq!(escape(name), "a b&quot;&lt;&#39;&gt;&amp;?#/%.png");
+    }
+}
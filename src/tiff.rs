@@ -0,0 +1,167 @@
+// TIFF files as the chain of image file directories they hold, the same structure exif reads.
+// Each directory describes one image, usually a page of a scan, and points at the strips of
+// pixel data elsewhere in the file, so the file's bytes are kept alongside the directories and
+// strips are only read when an image is decoded.
+use std::error;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+
+use crate::exif::{ExifEntry, ExifValue, TiffReader};
+
+mod convert;
+mod decode;
+
+pub use decode::TIFFDecodeError;
+
+// Tags this crate looks at directly.
+pub const IMAGE_WIDTH: u16 = 256;
+pub const IMAGE_LENGTH: u16 = 257;
+pub const BITS_PER_SAMPLE: u16 = 258;
+pub const COMPRESSION: u16 = 259;
+pub const PHOTOMETRIC_INTERPRETATION: u16 = 262;
+pub const STRIP_OFFSETS: u16 = 273;
+pub const SAMPLES_PER_PIXEL: u16 = 277;
+pub const ROWS_PER_STRIP: u16 = 278;
+pub const STRIP_BYTE_COUNTS: u16 = 279;
+pub const X_RESOLUTION: u16 = 282;
+pub const Y_RESOLUTION: u16 = 283;
+pub const PLANAR_CONFIGURATION: u16 = 284;
+pub const RESOLUTION_UNIT: u16 = 296;
+pub const PREDICTOR: u16 = 317;
+pub const COLOR_MAP: u16 = 320;
+pub const EXTRA_SAMPLES: u16 = 338;
+pub const ICC_PROFILE: u16 = 34675;
+
+#[derive(Debug, Clone)]
+pub struct InvalidTIFFFormat {
+    message: String,
+}
+
+impl InvalidTIFFFormat {
+    pub(crate) fn new(message: &str) -> InvalidTIFFFormat {
+        InvalidTIFFFormat {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for InvalidTIFFFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid TIFF file: {}", self.message)
+    }
+}
+
+impl error::Error for InvalidTIFFFormat {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TIFFFile {
+    little_endian: bool,
+    // Every directory in the chain starting at the header, in file order.
+    ifds: Vec<IFD>,
+    data: Vec<u8>,
+}
+
+// One image file directory. Entries with a field type TIFF doesn't define are left out.
+#[derive(Debug, Clone)]
+pub struct IFD {
+    pub entries: Vec<ExifEntry>,
+}
+
+impl TIFFFile {
+    pub fn from_file(filename: &str) -> Result<TIFFFile, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        File::open(filename)?.read_to_end(&mut bytes)?;
+        TIFFFile::from_bytes(&bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<TIFFFile, Box<dyn Error>> {
+        let reader = TiffReader::new(bytes)
+            .ok_or_else(|| InvalidTIFFFormat::new("The file does not start with a TIFF header."))?;
+        let malformed = |_| InvalidTIFFFormat::new("An image file directory is malformed.");
+        let mut offset = reader.read_u32(4).map_err(malformed)? as usize;
+        let mut ifds = Vec::new();
+        let mut visited = Vec::new();
+        // A directory pointing back at an earlier one would loop forever.
+        while offset != 0 && !visited.contains(&offset) {
+            visited.push(offset);
+            let (entries, next) = reader.read_ifd(offset).map_err(malformed)?;
+            ifds.push(IFD { entries });
+            offset = next;
+        }
+        if ifds.is_empty() {
+            return Err(InvalidTIFFFormat::new("The file has no image file directories.").into());
+        }
+        Ok(TIFFFile {
+            little_endian: reader.little_endian,
+            ifds,
+            data: bytes.to_vec(),
+        })
+    }
+
+    pub fn little_endian(&self) -> bool {
+        self.little_endian
+    }
+
+    pub fn ifds(&self) -> &[IFD] {
+        &self.ifds
+    }
+
+    // The size of the first image, which is what decode returns.
+    pub fn width(&self) -> u32 {
+        self.ifds[0].get_u32(IMAGE_WIDTH).unwrap_or(0)
+    }
+
+    pub fn height(&self) -> u32 {
+        self.ifds[0].get_u32(IMAGE_LENGTH).unwrap_or(0)
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        (self.width(), self.height())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl IFD {
+    pub fn get(&self, tag: u16) -> Option<&ExifValue> {
+        self.entries.iter().find(|e| e.tag == tag).map(|e| &e.value)
+    }
+
+    // The first value of an unsigned integer field. Writers are free to pick BYTE, SHORT or LONG
+    // for most of them.
+    pub fn get_u32(&self, tag: u16) -> Option<u32> {
+        self.get_u32s(tag)?.first().copied()
+    }
+
+    pub fn get_u32s(&self, tag: u16) -> Option<Vec<u32>> {
+        match self.get(tag)? {
+            ExifValue::Byte(values) => Some(values.iter().map(|v| *v as u32).collect()),
+            ExifValue::Short(values) => Some(values.iter().map(|v| *v as u32).collect()),
+            ExifValue::Long(values) => Some(values.clone()),
+            _ => None,
+        }
+    }
+
+    // The first value of a RATIONAL field as a number, if its denominator isn't 0.
+    pub fn get_rational(&self, tag: u16) -> Option<f64> {
+        match self.get(tag)? {
+            ExifValue::Rational(values) => values
+                .first()
+                .filter(|(_, d)| *d != 0)
+                .map(|(n, d)| *n as f64 / *d as f64),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,60 @@
+// Converting TIFF files to PNG. The first image is decoded and encoded again without loss, with
+// the resolution scanners record carried over as a pHYs chunk and an embedded ICC profile that
+// fits the pixels as an iCCP chunk.
+use std::error::Error;
+
+use miniz_oxide::deflate::compress_to_vec_zlib;
+
+use super::{TIFFFile, ICC_PROFILE, RESOLUTION_UNIT, X_RESOLUTION, Y_RESOLUTION};
+use crate::exif::ExifValue;
+use crate::image::ColorType;
+use crate::png::{PNGChunk, PNGFile, PNGFileBuilder};
+
+// Name given to the profile in the iCCP chunk. TIFF files don't name their profiles.
+const ICC_PROFILE_NAME: &[u8] = b"ICC Profile";
+
+// Resolution units.
+const INCH: u32 = 2;
+const CENTIMETER: u32 = 3;
+
+impl TIFFFile {
+    pub fn to_png(&self) -> Result<PNGFile, Box<dyn Error>> {
+        let image = self.decode()?;
+        let ifd = &self.ifds[0];
+        let mut builder = PNGFileBuilder::new(image.width(), image.height(), image.color_type())
+            .with_bit_depth(image.bit_depth())
+            .with_image(&image);
+
+        // pHYs counts pixels per meter. TIFF resolutions are per inch unless the unit says
+        // otherwise, and a unit of 1 only gives the aspect ratio, which isn't worth keeping.
+        let per_meter = match ifd.get_u32(RESOLUTION_UNIT).unwrap_or(INCH) {
+            INCH => Some(1.0 / 0.0254),
+            CENTIMETER => Some(100.0),
+            _ => None,
+        };
+        let x = ifd.get_rational(X_RESOLUTION);
+        let y = ifd.get_rational(Y_RESOLUTION);
+        if let (Some(per_meter), Some(x), Some(y)) = (per_meter, x, y) {
+            let mut data = Vec::with_capacity(9);
+            data.extend_from_slice(&((x * per_meter).round() as u32).to_be_bytes());
+            data.extend_from_slice(&((y * per_meter).round() as u32).to_be_bytes());
+            data.push(1);
+            builder = builder.with_chunk(PNGChunk::new(*b"pHYs", data));
+        }
+
+        let color_space: &[u8] = match image.color_type() {
+            ColorType::Grayscale | ColorType::GrayscaleAlpha => b"GRAY",
+            _ => b"RGB ",
+        };
+        if let Some(ExifValue::Undefined(profile)) = ifd.get(ICC_PROFILE) {
+            if profile.get(16..20) == Some(color_space) {
+                let mut data = ICC_PROFILE_NAME.to_vec();
+                // The name's terminator, then the compression method.
+                data.extend_from_slice(&[0, 0]);
+                data.extend(compress_to_vec_zlib(profile, 9));
+                builder = builder.with_chunk(PNGChunk::new(*b"iCCP", data));
+            }
+        }
+        builder.build()
+    }
+}
@@ -0,0 +1,370 @@
+use std::error;
+use std::error::Error;
+use std::fmt;
+
+use super::{
+    TIFFFile, BITS_PER_SAMPLE, COLOR_MAP, COMPRESSION, EXTRA_SAMPLES, IFD, IMAGE_LENGTH,
+    IMAGE_WIDTH, PHOTOMETRIC_INTERPRETATION, PLANAR_CONFIGURATION, PREDICTOR, ROWS_PER_STRIP,
+    SAMPLES_PER_PIXEL, STRIP_BYTE_COUNTS, STRIP_OFFSETS,
+};
+use crate::image::{ColorType, Image};
+
+// Compression methods.
+const UNCOMPRESSED: u32 = 1;
+const LZW: u32 = 5;
+
+// Photometric interpretations.
+const WHITE_IS_ZERO: u32 = 0;
+const BLACK_IS_ZERO: u32 = 1;
+const RGB: u32 = 2;
+const PALETTE: u32 = 3;
+
+// Extra sample meanings.
+const ASSOCIATED_ALPHA: u32 = 1;
+const UNASSOCIATED_ALPHA: u32 = 2;
+
+const HORIZONTAL_DIFFERENCING: u32 = 2;
+
+// LZW codes 256 and 257 clear the table and end the data, and codes are at most 12 bits wide.
+const CLEAR: usize = 256;
+const END: usize = 257;
+const MAX_CODES: usize = 4096;
+
+#[derive(Debug, Clone)]
+pub struct TIFFDecodeError {
+    message: String,
+}
+
+impl TIFFDecodeError {
+    pub(crate) fn new(message: &str) -> TIFFDecodeError {
+        TIFFDecodeError {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for TIFFDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Could not decode TIFF data: {}", self.message)
+    }
+}
+
+impl error::Error for TIFFDecodeError {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+impl TIFFFile {
+    // The first image in the file.
+    pub fn decode(&self) -> Result<Image, Box<dyn Error>> {
+        self.decode_ifd(&self.ifds[0])
+    }
+
+    // Decodes the image a directory describes, limited to baseline TIFF: strips of interleaved
+    // samples, uncompressed or LZW-compressed, with or without a horizontal predictor. Bilevel
+    // and grayscale images of up to 16 bits, palette images of up to 8 bits and RGB images of 8
+    // or 16 bits are supported, along with an alpha channel stored as the first extra sample.
+    // Images keep their bit depth, and WhiteIsZero samples and premultiplied alpha are turned
+    // into the usual form. Strips must hold every row they cover, so the size the directory
+    // claims is never allocated before the file has shown it has the data for it.
+    pub fn decode_ifd(&self, ifd: &IFD) -> Result<Image, Box<dyn Error>> {
+        let missing = |name: &str| TIFFDecodeError::new(&format!("The image has no {}.", name));
+        let width = ifd.get_u32(IMAGE_WIDTH).ok_or_else(|| missing("width"))?;
+        let height = ifd.get_u32(IMAGE_LENGTH).ok_or_else(|| missing("height"))?;
+        let photometric = ifd
+            .get_u32(PHOTOMETRIC_INTERPRETATION)
+            .ok_or_else(|| missing("photometric interpretation"))?;
+        let samples_per_pixel = ifd.get_u32(SAMPLES_PER_PIXEL).unwrap_or(1) as usize;
+        let bits = ifd.get_u32s(BITS_PER_SAMPLE).unwrap_or_else(|| vec![1]);
+        let bit_depth = bits.first().copied().unwrap_or(1);
+        let compression = ifd.get_u32(COMPRESSION).unwrap_or(UNCOMPRESSED);
+        let predictor = ifd.get_u32(PREDICTOR).unwrap_or(1);
+        let extra_sample = ifd.get_u32(EXTRA_SAMPLES);
+
+        let (color_type, color_channels) = match photometric {
+            WHITE_IS_ZERO | BLACK_IS_ZERO => (ColorType::Grayscale, 1),
+            RGB => (ColorType::Truecolor, 3),
+            PALETTE => (ColorType::Indexed, 1),
+            _ => {
+                return Err(TIFFDecodeError::new(
+                    "Only bilevel, grayscale, palette and RGB images are supported.",
+                )
+                .into())
+            }
+        };
+        let alpha = photometric != PALETTE
+            && samples_per_pixel > color_channels
+            && matches!(
+                extra_sample,
+                Some(ASSOCIATED_ALPHA) | Some(UNASSOCIATED_ALPHA)
+            );
+        let color_type = match (color_type, alpha) {
+            (ColorType::Grayscale, true) => ColorType::GrayscaleAlpha,
+            (ColorType::Truecolor, true) => ColorType::TruecolorAlpha,
+            (color_type, _) => color_type,
+        };
+
+        if samples_per_pixel < color_channels {
+            return Err(TIFFDecodeError::new("The image has too few samples per pixel.").into());
+        }
+        if bits.iter().any(|b| *b != bit_depth)
+            || bit_depth > 16
+            || !color_type.allows_bit_depth(bit_depth as u8)
+            || (bit_depth < 8 && samples_per_pixel > 1)
+        {
+            return Err(TIFFDecodeError::new("The bit depth is not supported.").into());
+        }
+        if samples_per_pixel > 1 && ifd.get_u32(PLANAR_CONFIGURATION).unwrap_or(1) != 1 {
+            return Err(TIFFDecodeError::new("Only interleaved samples are supported.").into());
+        }
+        if compression != UNCOMPRESSED && compression != LZW {
+            return Err(TIFFDecodeError::new(
+                "Only uncompressed and LZW-compressed images are supported.",
+            )
+            .into());
+        }
+        let predictor = match predictor {
+            1 => false,
+            HORIZONTAL_DIFFERENCING if bit_depth >= 8 => true,
+            _ => return Err(TIFFDecodeError::new("The predictor is not supported.").into()),
+        };
+
+        let too_large = || TIFFDecodeError::new("The image is too large.");
+        let row_samples = (width as usize)
+            .checked_mul(samples_per_pixel)
+            .ok_or_else(too_large)?;
+        let row_size = row_samples
+            .checked_mul(bit_depth as usize)
+            .ok_or_else(too_large)?
+            .div_ceil(8);
+        row_size
+            .checked_mul(height as usize)
+            .ok_or_else(too_large)?;
+        let data = self.strips(ifd, compression, row_size, height as usize)?;
+
+        let max = ((1u32 << bit_depth) - 1) as u16;
+        let mut samples =
+            Vec::with_capacity(width as usize * height as usize * color_type.channels());
+        let mut row = Vec::with_capacity(row_samples);
+        for stored in data.chunks_exact(row_size.max(1)).take(height as usize) {
+            row.clear();
+            match bit_depth {
+                16 => row.extend(stored.chunks_exact(2).map(|b| {
+                    if self.little_endian {
+                        u16::from_le_bytes([b[0], b[1]])
+                    } else {
+                        u16::from_be_bytes([b[0], b[1]])
+                    }
+                })),
+                8 => row.extend(stored.iter().map(|b| *b as u16)),
+                _ => row.extend((0..row_samples).map(|i| {
+                    let bit = i * bit_depth as usize;
+                    let byte = stored[bit / 8] as u16;
+                    (byte >> (8 - bit_depth as usize - bit % 8)) & max
+                })),
+            }
+            if predictor {
+                for i in samples_per_pixel..row.len() {
+                    row[i] = row[i].wrapping_add(row[i - samples_per_pixel]) & max;
+                }
+            }
+
+            for pixel in row.chunks_exact(samples_per_pixel) {
+                let start = samples.len();
+                samples.extend_from_slice(&pixel[..color_channels]);
+                if photometric == WHITE_IS_ZERO {
+                    samples[start] = max - samples[start];
+                }
+                if alpha {
+                    let a = pixel[color_channels];
+                    if extra_sample == Some(ASSOCIATED_ALPHA) {
+                        for value in &mut samples[start..] {
+                            *value = match a {
+                                0 => 0,
+                                _ => ((*value as u32 * max as u32 + a as u32 / 2) / a as u32)
+                                    .min(max as u32) as u16,
+                            };
+                        }
+                    }
+                    samples.push(a);
+                }
+            }
+        }
+
+        let mut image = Image::from_samples(width, height, color_type, bit_depth as u8, samples)?;
+        if photometric == PALETTE {
+            // Every red value, then every green, then every blue, each 16 bits.
+            let entries = 1usize << bit_depth;
+            let map = ifd
+                .get_u32s(COLOR_MAP)
+                .filter(|map| map.len() >= entries * 3)
+                .ok_or_else(|| missing("color map"))?;
+            let palette = (0..entries)
+                .map(|i| {
+                    [
+                        (map[i] >> 8) as u8,
+                        (map[entries + i] >> 8) as u8,
+                        (map[entries * 2 + i] >> 8) as u8,
+                    ]
+                })
+                .collect();
+            image.set_palette(Some(palette));
+        }
+        Ok(image)
+    }
+
+    // The image's rows one after another, row_size bytes each.
+    fn strips(
+        &self,
+        ifd: &IFD,
+        compression: u32,
+        row_size: usize,
+        height: usize,
+    ) -> Result<Vec<u8>, TIFFDecodeError> {
+        let offsets = ifd
+            .get_u32s(STRIP_OFFSETS)
+            .ok_or_else(|| TIFFDecodeError::new("The image has no strip offsets."))?;
+        let byte_counts = ifd.get_u32s(STRIP_BYTE_COUNTS);
+        let rows_per_strip = (ifd.get_u32(ROWS_PER_STRIP).unwrap_or(u32::MAX) as usize)
+            .min(height)
+            .max(1);
+        let strips = height.div_ceil(rows_per_strip);
+        if offsets.len() < strips {
+            return Err(TIFFDecodeError::new("The image has too few strips."));
+        }
+
+        let short = || TIFFDecodeError::new("A strip is shorter than the rows it covers.");
+        let mut data = Vec::new();
+        for (i, offset) in offsets.iter().take(strips).enumerate() {
+            let rows = rows_per_strip.min(height - i * rows_per_strip);
+            let size = rows * row_size;
+            let stored = self
+                .data
+                .get(*offset as usize..)
+                .ok_or_else(|| TIFFDecodeError::new("A strip lies outside the file."))?;
+            // Uncompressed strips can do without byte counts, since their size is known.
+            let count = byte_counts
+                .as_ref()
+                .and_then(|counts| counts.get(i))
+                .map_or(stored.len(), |count| *count as usize);
+            let stored = &stored[..count.min(stored.len())];
+
+            match compression {
+                LZW => {
+                    let rows = decompress(stored, size)?;
+                    if rows.len() < size {
+                        return Err(short());
+                    }
+                    data.extend(rows);
+                }
+                _ => data.extend_from_slice(stored.get(..size).ok_or_else(short)?),
+            }
+        }
+        Ok(data)
+    }
+}
+
+// Expands LZW data into at most size bytes. Unlike GIF's LZW, codes are packed most significant
+// bit first, always start 9 bits wide, and grow a code early, when the table is one entry short
+// of the next power of two.
+fn decompress(data: &[u8], size: usize) -> Result<Vec<u8>, TIFFDecodeError> {
+    // Writers from before TIFF 6.0 packed codes the other way round, which makes the leading
+    // clear code read as these two bytes.
+    if data.starts_with(&[0x00, 0x01]) {
+        return Err(TIFFDecodeError::new(
+            "Old-style LZW compression is not supported.",
+        ));
+    }
+
+    let mut prefix = [0u16; MAX_CODES];
+    let mut suffix = [0u8; MAX_CODES];
+    let mut first = [0u8; MAX_CODES];
+    for code in 0..CLEAR {
+        suffix[code] = code as u8;
+        first[code] = code as u8;
+    }
+    let mut next = END + 1;
+    let mut width = 9;
+    let mut previous: Option<usize> = None;
+
+    // Each byte of codes expands to a few bytes in typical data. Reserving for the whole strip
+    // up front would let a short strip claiming many rows allocate far more than it holds.
+    let mut out = Vec::with_capacity(size.min(data.len().saturating_mul(4)));
+    let mut string = Vec::new();
+    let (mut bits, mut count, mut at) = (0u32, 0u32, 0usize);
+    while out.len() < size {
+        while count < width && at < data.len() {
+            bits = (bits << 8) | data[at] as u32;
+            count += 8;
+            at += 1;
+        }
+        if count < width {
+            break;
+        }
+        let code = ((bits >> (count - width)) & ((1 << width) - 1)) as usize;
+        count -= width;
+
+        if code == CLEAR {
+            next = END + 1;
+            width = 9;
+            previous = None;
+            continue;
+        }
+        if code == END {
+            break;
+        }
+        let prior = match previous {
+            Some(prior) => prior,
+            None => {
+                if code >= CLEAR {
+                    return Err(TIFFDecodeError::new(
+                        "The strip data holds an invalid code.",
+                    ));
+                }
+                out.push(code as u8);
+                previous = Some(code);
+                continue;
+            }
+        };
+
+        // A code one past the table is the previous string followed by its own first byte.
+        let (entry, tail) = if code < next {
+            (code, None)
+        } else if code == next {
+            (prior, Some(first[prior]))
+        } else {
+            return Err(TIFFDecodeError::new(
+                "The strip data holds an invalid code.",
+            ));
+        };
+        string.clear();
+        let mut link = entry;
+        loop {
+            string.push(suffix[link]);
+            if link < CLEAR {
+                break;
+            }
+            link = prefix[link] as usize;
+        }
+        out.extend(string.iter().rev());
+        out.extend(tail);
+
+        if next < MAX_CODES {
+            prefix[next] = prior as u16;
+            suffix[next] = first[entry];
+            first[next] = first[prior];
+            next += 1;
+            if next + 1 == 1 << width && width < 12 {
+                width += 1;
+            }
+        }
+        previous = Some(code);
+    }
+    out.truncate(size);
+    Ok(out)
+}
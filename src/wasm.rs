@@ -0,0 +1,90 @@
+// JavaScript bindings for the "wasm" feature, so a browser-based inspector can look at PNGs with
+// the same parser the command line tools use. Byte arrays cross the boundary as Uint8Array, and
+// errors become JavaScript Error objects carrying the message the Rust error prints.
+use std::error::Error;
+
+use wasm_bindgen::prelude::*;
+
+use crate::png::{PNGChunk, PNGFile, ParseOptions, ParseReport};
+use crate::report::{self, InfoReport};
+
+// A parsed file, as handed to JavaScript.
+#[wasm_bindgen(js_name = PngFile)]
+pub struct WasmPNGFile {
+    png_file: PNGFile,
+    // Every chunk in the order serialize writes them, for indexed access.
+    chunks: Vec<PNGChunk>,
+    report: ParseReport,
+}
+
+// Parses a PNG. In strict mode the first spec violation is thrown; otherwise violations are
+// listed by warnings() along with anything else suspicious.
+#[wasm_bindgen]
+pub fn parse(bytes: &[u8], strict: bool) -> Result<WasmPNGFile, JsError> {
+    let options = ParseOptions::from_strict(strict);
+    let (png_file, report) = PNGFile::from_bytes_with_options(bytes, &options).map_err(js_error)?;
+    Ok(WasmPNGFile {
+        chunks: png_file.to_chunks(),
+        png_file,
+        report,
+    })
+}
+
+#[wasm_bindgen(js_class = PngFile)]
+impl WasmPNGFile {
+    #[wasm_bindgen(js_name = chunkCount)]
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    // Four character type of the chunk at index, e.g. "IDAT".
+    #[wasm_bindgen(js_name = chunkType)]
+    pub fn chunk_type(&self, index: usize) -> Option<String> {
+        self.chunks
+            .get(index)
+            .map(|c| String::from_utf8_lossy(c.get_chunk_type()).into_owned())
+    }
+
+    #[wasm_bindgen(js_name = chunkData)]
+    pub fn chunk_data(&self, index: usize) -> Option<Vec<u8>> {
+        self.chunks.get(index).map(|c| c.get_data().to_vec())
+    }
+
+    #[wasm_bindgen(js_name = chunkCrc)]
+    pub fn chunk_crc(&self, index: usize) -> Option<u32> {
+        self.chunks.get(index).map(|c| c.get_crc())
+    }
+
+    // Byte offset of the chunk in the parsed data. A number rather than a BigInt, which is exact
+    // for any file a browser can hold.
+    #[wasm_bindgen(js_name = chunkOffset)]
+    pub fn chunk_offset(&self, index: usize) -> Option<f64> {
+        self.chunks
+            .get(index)
+            .and_then(|c| c.get_offset())
+            .map(|offset| offset as f64)
+    }
+
+    // Everything the parser found, one "code: message" string per warning.
+    pub fn warnings(&self) -> Vec<String> {
+        self.report
+            .warnings
+            .iter()
+            .map(|w| format!("{}: {}", w.code, w.message))
+            .collect()
+    }
+
+    // The header values and chunk layout as the JSON that `imagetools info --json` prints.
+    pub fn metadata(&self, file_name: &str) -> Result<String, JsError> {
+        report::to_json(&InfoReport::from_png(file_name, &self.png_file)).map_err(js_error)
+    }
+
+    // The file's bytes, as PNGFile::to_bytes writes them.
+    pub fn serialize(&self) -> Result<Vec<u8>, JsError> {
+        self.png_file.to_bytes().map_err(js_error)
+    }
+}
+
+fn js_error<E: Into<Box<dyn Error>>>(err: E) -> JsError {
+    JsError::new(&err.into().to_string())
+}
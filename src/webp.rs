@@ -0,0 +1,375 @@
+// WebP files as the chunks of their RIFF container, the way jpeg holds a JPEG as a list of
+// segments. Simple files hold a single VP8 (lossy) or VP8L (lossless) chunk. Extended files start
+// with a VP8X chunk of feature flags and the canvas size, and may add ICCP, ANIM, ANMF, ALPH, EXIF
+// and XMP chunks. The bitstreams aren't decoded, only read far enough to give the image size, so
+// files can be inspected without a VP8 decoder.
+use std::error;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+
+// Chunks this crate looks at directly.
+pub const VP8: [u8; 4] = *b"VP8 ";
+pub const VP8L: [u8; 4] = *b"VP8L";
+pub const VP8X: [u8; 4] = *b"VP8X";
+pub const ALPH: [u8; 4] = *b"ALPH";
+pub const ANIM: [u8; 4] = *b"ANIM";
+pub const ANMF: [u8; 4] = *b"ANMF";
+pub const ICCP: [u8; 4] = *b"ICCP";
+pub const EXIF: [u8; 4] = *b"EXIF";
+pub const XMP: [u8; 4] = *b"XMP ";
+
+// Some writers keep the APP1 prefix from JPEG in front of the EXIF data.
+const EXIF_PREFIX: &[u8] = b"Exif\0\0";
+const VP8_START_CODE: [u8; 3] = [0x9D, 0x01, 0x2A];
+const VP8L_SIGNATURE: u8 = 0x2F;
+
+#[derive(Debug, Clone)]
+pub struct InvalidWebPFormat {
+    message: String,
+}
+
+impl InvalidWebPFormat {
+    pub(crate) fn new(message: &str) -> InvalidWebPFormat {
+        InvalidWebPFormat {
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for InvalidWebPFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid WebP file: {}", self.message)
+    }
+}
+
+impl error::Error for InvalidWebPFormat {
+    fn description(&self) -> &str {
+        &self.message
+    }
+
+    fn cause(&self) -> Option<&dyn error::Error> {
+        None
+    }
+}
+
+// Every chunk inside the RIFF container, in file order. Chunks inside ANMF chunks stay in their
+// frame's data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebPFile {
+    chunks: Vec<WebPChunk>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebPChunk {
+    fourcc: [u8; 4],
+    // The payload, without the padding byte that follows payloads of odd length.
+    data: Vec<u8>,
+}
+
+// VP8X chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedHeader {
+    // Which optional features the file says it uses.
+    pub icc_profile: bool,
+    pub alpha: bool,
+    pub exif: bool,
+    pub xmp: bool,
+    pub animation: bool,
+    // The size of the canvas every frame is drawn on.
+    pub canvas_width: u32,
+    pub canvas_height: u32,
+}
+
+// ANIM chunk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnimationParams {
+    // Red, green, blue and alpha. Players are free to ignore it, and browsers do.
+    pub background_color: [u8; 4],
+    // How many times the animation is played, 0 meaning forever.
+    pub loop_count: u16,
+}
+
+// ANMF chunk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnimationFrame {
+    // The frame's position and size on the canvas.
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    // How long the frame is shown, in milliseconds.
+    pub duration: u32,
+    // Whether the frame is alpha-blended onto the canvas rather than replacing what it covers.
+    pub blend: bool,
+    // Whether the frame's region is cleared to the background once it has been shown.
+    pub dispose: bool,
+    // The frame's ALPH chunk, if any, and its VP8 or VP8L chunk.
+    pub chunks: Vec<WebPChunk>,
+}
+
+impl WebPFile {
+    pub fn from_file(filename: &str) -> Result<WebPFile, Box<dyn Error>> {
+        let mut bytes = Vec::new();
+        File::open(filename)?.read_to_end(&mut bytes)?;
+        WebPFile::from_bytes(&bytes)
+    }
+
+    // Parses a WebP held in memory. Anything after the RIFF container is ignored. The VP8X,
+    // ANIM and ANMF chunks are checked here, so the accessors below don't fail on them.
+    pub fn from_bytes(bytes: &[u8]) -> Result<WebPFile, Box<dyn Error>> {
+        if bytes.get(..4) != Some(b"RIFF") || bytes.get(8..12) != Some(b"WEBP") {
+            return Err(
+                InvalidWebPFormat::new("The file does not start with a WebP header.").into(),
+            );
+        }
+        let size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        let riff = bytes
+            .get(12..8 + size.max(4))
+            .ok_or_else(|| InvalidWebPFormat::new("The RIFF container is cut off."))?;
+        let chunks = parse_chunks(riff)?;
+
+        match chunks.first().map(|c| c.fourcc) {
+            Some(VP8X) => {}
+            Some(VP8) | Some(VP8L) if chunks.len() == 1 => {}
+            Some(VP8) | Some(VP8L) => {
+                return Err(InvalidWebPFormat::new(
+                    "A simple file holds other chunks besides its image.",
+                )
+                .into())
+            }
+            _ => {
+                return Err(
+                    InvalidWebPFormat::new("The first chunk is not VP8, VP8L or VP8X.").into(),
+                )
+            }
+        }
+        for chunk in &chunks {
+            match chunk.fourcc {
+                VP8X => {
+                    ExtendedHeader::parse(chunk)?;
+                }
+                ANIM => {
+                    AnimationParams::parse(chunk)?;
+                }
+                ANMF => {
+                    AnimationFrame::parse(chunk)?;
+                }
+                _ => {}
+            }
+        }
+        Ok(WebPFile { chunks })
+    }
+
+    pub fn chunks(&self) -> &[WebPChunk] {
+        &self.chunks
+    }
+
+    pub fn chunks_with_fourcc(&self, fourcc: [u8; 4]) -> impl Iterator<Item = &WebPChunk> {
+        self.chunks.iter().filter(move |c| c.fourcc == fourcc)
+    }
+
+    pub fn first_chunk(&self, fourcc: [u8; 4]) -> Option<&WebPChunk> {
+        self.chunks_with_fourcc(fourcc).next()
+    }
+
+    // The VP8X chunk. Only extended files have one.
+    pub fn extended_header(&self) -> Option<ExtendedHeader> {
+        self.first_chunk(VP8X)
+            .and_then(|c| ExtendedHeader::parse(c).ok())
+    }
+
+    // The canvas size for extended files, otherwise the size in the bitstream's header.
+    pub fn dimensions(&self) -> Result<(u32, u32), Box<dyn Error>> {
+        if let Some(header) = self.extended_header() {
+            return Ok((header.canvas_width, header.canvas_height));
+        }
+        let chunk = self
+            .chunks
+            .iter()
+            .find(|c| c.fourcc == VP8 || c.fourcc == VP8L)
+            .ok_or_else(|| InvalidWebPFormat::new("There is no VP8 or VP8L chunk."))?;
+        bitstream_dimensions(chunk)
+    }
+
+    pub fn width(&self) -> Result<u32, Box<dyn Error>> {
+        Ok(self.dimensions()?.0)
+    }
+
+    pub fn height(&self) -> Result<u32, Box<dyn Error>> {
+        Ok(self.dimensions()?.1)
+    }
+
+    // Whether the image, or the first frame of an animation, is VP8L-coded.
+    pub fn is_lossless(&self) -> bool {
+        let first_frame = self
+            .first_chunk(ANMF)
+            .and_then(|c| AnimationFrame::parse(c).ok())
+            .map(|frame| frame.chunks)
+            .unwrap_or_default();
+        self.chunks
+            .iter()
+            .chain(&first_frame)
+            .find(|c| c.fourcc == VP8 || c.fourcc == VP8L)
+            .is_some_and(|c| c.fourcc == VP8L)
+    }
+
+    // From the VP8X flags for extended files, and from the VP8L header for simple ones, since
+    // VP8 bitstreams carry no alpha of their own.
+    pub fn has_alpha(&self) -> bool {
+        match self.extended_header() {
+            Some(header) => header.alpha,
+            None => self.first_chunk(VP8L).is_some_and(|c| {
+                c.data.len() >= 5 && c.data[0] == VP8L_SIGNATURE && c.data[4] & 0x10 != 0
+            }),
+        }
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.extended_header().is_some_and(|h| h.animation) || self.first_chunk(ANMF).is_some()
+    }
+
+    // The ANIM chunk, which animated files need.
+    pub fn animation(&self) -> Option<AnimationParams> {
+        self.first_chunk(ANIM)
+            .and_then(|c| AnimationParams::parse(c).ok())
+    }
+
+    // Every ANMF chunk, in order.
+    pub fn frames(&self) -> Vec<AnimationFrame> {
+        self.chunks_with_fourcc(ANMF)
+            .filter_map(|c| AnimationFrame::parse(c).ok())
+            .collect()
+    }
+
+    pub fn icc_profile(&self) -> Option<&[u8]> {
+        self.first_chunk(ICCP).map(|c| c.data.as_slice())
+    }
+
+    // The EXIF data, starting with the TIFF header ExifData::parse expects.
+    pub fn exif_data(&self) -> Option<&[u8]> {
+        self.first_chunk(EXIF)
+            .map(|c| c.data.strip_prefix(EXIF_PREFIX).unwrap_or(&c.data))
+    }
+
+    pub fn xmp(&self) -> Option<&[u8]> {
+        self.first_chunk(XMP).map(|c| c.data.as_slice())
+    }
+}
+
+impl WebPChunk {
+    pub fn new(fourcc: [u8; 4], data: Vec<u8>) -> WebPChunk {
+        WebPChunk { fourcc, data }
+    }
+
+    pub fn fourcc(&self) -> [u8; 4] {
+        self.fourcc
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn name(&self) -> String {
+        String::from_utf8_lossy(&self.fourcc).trim_end().to_string()
+    }
+}
+
+impl ExtendedHeader {
+    pub fn parse(chunk: &WebPChunk) -> Result<ExtendedHeader, InvalidWebPFormat> {
+        let data = &chunk.data;
+        if chunk.fourcc != VP8X || data.len() < 10 {
+            return Err(InvalidWebPFormat::new("The VP8X chunk is too short."));
+        }
+        let flags = data[0];
+        Ok(ExtendedHeader {
+            icc_profile: flags & 0x20 != 0,
+            alpha: flags & 0x10 != 0,
+            exif: flags & 0x08 != 0,
+            xmp: flags & 0x04 != 0,
+            animation: flags & 0x02 != 0,
+            canvas_width: u24(&data[4..7]) + 1,
+            canvas_height: u24(&data[7..10]) + 1,
+        })
+    }
+}
+
+impl AnimationParams {
+    pub fn parse(chunk: &WebPChunk) -> Result<AnimationParams, InvalidWebPFormat> {
+        let data = &chunk.data;
+        if chunk.fourcc != ANIM || data.len() < 6 {
+            return Err(InvalidWebPFormat::new("The ANIM chunk is too short."));
+        }
+        Ok(AnimationParams {
+            // Stored blue, green, red, alpha.
+            background_color: [data[2], data[1], data[0], data[3]],
+            loop_count: u16::from_le_bytes([data[4], data[5]]),
+        })
+    }
+}
+
+impl AnimationFrame {
+    pub fn parse(chunk: &WebPChunk) -> Result<AnimationFrame, InvalidWebPFormat> {
+        let data = &chunk.data;
+        if chunk.fourcc != ANMF || data.len() < 16 {
+            return Err(InvalidWebPFormat::new("An ANMF chunk is too short."));
+        }
+        let flags = data[15];
+        Ok(AnimationFrame {
+            // Offsets are stored halved.
+            x: u24(&data[0..3]) * 2,
+            y: u24(&data[3..6]) * 2,
+            width: u24(&data[6..9]) + 1,
+            height: u24(&data[9..12]) + 1,
+            duration: u24(&data[12..15]),
+            blend: flags & 0x02 == 0,
+            dispose: flags & 0x01 != 0,
+            chunks: parse_chunks(&data[16..])?,
+        })
+    }
+}
+
+// Splits data into chunks: a FourCC, a little-endian size and the payload, padded to an even
+// length.
+fn parse_chunks(mut data: &[u8]) -> Result<Vec<WebPChunk>, InvalidWebPFormat> {
+    let mut chunks = Vec::new();
+    while !data.is_empty() {
+        if data.len() < 8 {
+            return Err(InvalidWebPFormat::new("A chunk header is cut off."));
+        }
+        let fourcc = [data[0], data[1], data[2], data[3]];
+        let size = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let payload = data
+            .get(8..8 + size)
+            .ok_or_else(|| InvalidWebPFormat::new("A chunk is cut off."))?;
+        chunks.push(WebPChunk::new(fourcc, payload.to_vec()));
+        // The padding byte of the last chunk is sometimes left out.
+        data = data.get(8 + size + size % 2..).unwrap_or(&[]);
+    }
+    Ok(chunks)
+}
+
+// Width and height from the header of a VP8 key frame or a VP8L bitstream.
+fn bitstream_dimensions(chunk: &WebPChunk) -> Result<(u32, u32), Box<dyn Error>> {
+    let data = &chunk.data;
+    if chunk.fourcc == VP8L {
+        if data.len() < 5 || data[0] != VP8L_SIGNATURE {
+            return Err(InvalidWebPFormat::new("The VP8L header is invalid.").into());
+        }
+        let bits = u32::from_le_bytes([data[1], data[2], data[3], data[4]]);
+        return Ok(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1));
+    }
+    // A 3-byte frame tag, whose lowest bit is 0 for key frames, then the start code.
+    if data.len() < 10 || data[0] & 1 != 0 || data[3..6] != VP8_START_CODE {
+        return Err(InvalidWebPFormat::new("The VP8 header is invalid.").into());
+    }
+    // The top two bits of each are an upscaling hint.
+    let width = u16::from_le_bytes([data[6], data[7]]) & 0x3FFF;
+    let height = u16::from_le_bytes([data[8], data[9]]) & 0x3FFF;
+    Ok((width as u32, height as u32))
+}
+
+fn u24(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0])
+}